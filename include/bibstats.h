@@ -0,0 +1,38 @@
+/* The C ABI of bibstats, built with
+ *
+ *   cargo build --release --lib --features ffi
+ *
+ * which gives libbibstats.so (or .dylib, .dll) in target/release.
+ * Bib and LaTeX are passed as bytes with their length; results are
+ * JSON text with the records of the JSON output of the command or,
+ * on errors, an object {"error": message}. Results must be freed
+ * with bibstats_free.
+ */
+#ifndef BIBSTATS_H
+#define BIBSTATS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The entries of the bib text. */
+char *bibstats_parse_bib(const uint8_t *bib, size_t len);
+
+/* The citations of the LaTeX text. */
+char *bibstats_scan_tex(const uint8_t *tex, size_t len);
+
+/* The citations of the LaTeX text per author and work. */
+char *bibstats_stats(const uint8_t *bib, size_t bib_len,
+                     const uint8_t *tex, size_t tex_len);
+
+/* Frees a result. */
+void bibstats_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
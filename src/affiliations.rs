@@ -0,0 +1,272 @@
+use std::ffi::OsStr;
+use std::fs;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde_json::json;
+
+use crate::diagnostics;
+use crate::docs::Citation;
+use crate::names;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::BibMap;
+
+// The affiliations of authors, for reports on where the works
+// cited come from. The affiliations file is CSV with the author,
+// the institution and the country on every line, e.g.
+//
+//   author,institution,country
+//   "Knuth, Donald",Stanford University,US
+//   Edsger W. Dijkstra,Eindhoven University of Technology,NL
+//
+// The header line is optional. Authors are written as in bib files;
+// "Last, First" and "First Last" are the same author.
+// Lines starting with '#' are comments.
+#[derive(Debug, Default)]
+pub struct Affiliations {
+    authors: FxHashMap<String, Affiliation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Affiliation {
+    pub institution: String,
+    pub country: String,
+}
+
+impl Affiliations {
+    pub fn load(path: &OsStr) -> Result<Affiliations, String> {
+        let name = path.to_string_lossy();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read affiliations {}: {}", name, e))?;
+        Affiliations::parse(&text).map_err(|(i, e)| format!("{}:{}: {}", name, i, e))
+    }
+
+    // Fails with the line and the reason.
+    fn parse(text: &str) -> Result<Affiliations, (usize, String)> {
+        let mut a = Affiliations::default();
+        for (i, l) in text.lines().enumerate() {
+            let l = l.trim();
+            if l.is_empty() || l.starts_with('#') {
+                continue;
+            }
+            let fields = csv_fields(l).ok_or((i + 1, "unclosed quote".to_string()))?;
+            let [author, institution, country] = fields.as_slice() else {
+                return Err((
+                    i + 1,
+                    "author, institution and country expected".to_string(),
+                ));
+            };
+            if i == 0 && author.eq_ignore_ascii_case("author") {
+                continue;
+            }
+            a.authors.insert(
+                key(author),
+                Affiliation {
+                    institution: institution.to_string(),
+                    country: country.to_string(),
+                },
+            );
+        }
+        Ok(a)
+    }
+
+    // Editors credited with a work are marked "(ed.)", see names::as_editors.
+    pub fn get(&self, author: &str) -> Option<&Affiliation> {
+        let author = author.strip_suffix(" (ed.)").unwrap_or(author);
+        self.authors.get(&key(author))
+    }
+}
+
+fn key(author: &str) -> String {
+    names::split(author)
+        .into_iter()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+// The fields of a CSV line; fields in double quotes may contain
+// commas and doubled quotes. None if a quote is not closed.
+fn csv_fields(l: &str) -> Option<Vec<String>> {
+    let mut v = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut cs = l.chars().peekable();
+    while let Some(c) = cs.next() {
+        match c {
+            '"' if quoted && cs.peek() == Some(&'"') => {
+                cs.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => v.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    v.push(field.trim().to_string());
+    Some(v)
+}
+
+// How many citations go to the works of the authors of a country
+// or an institution and how many distinct works these are.
+// A citation counts once for every country (institution) of the
+// authors of the work; authors without affiliation count as unknown,
+// which has the empty name.
+pub struct Share {
+    pub name: String,
+    pub count: u32,
+    pub works: u32,
+}
+
+// Sorted by count, the most cited first.
+pub fn compute(
+    aff: &Affiliations,
+    bib: &BibMap,
+    cites: &[Citation],
+    per_institution: bool,
+) -> Vec<Share> {
+    let mut m: FxHashMap<String, (u32, FxHashSet<&str>)> = FxHashMap::default();
+    for c in cites {
+        let Some(e) = bib.get(&c.key) else {
+            diagnostics::warn("B002", &format!("Citekey {} not in database", c.key));
+            continue;
+        };
+        let mut authors = names::split(&e.author);
+        if authors.is_empty() {
+            authors.push(e.author.clone());
+        }
+        let mut groups: Vec<&str> = authors
+            .iter()
+            .map(|a| match aff.get(a) {
+                Some(a) if per_institution => a.institution.as_str(),
+                Some(a) => a.country.as_str(),
+                None => "",
+            })
+            .collect();
+        groups.sort_unstable();
+        groups.dedup();
+        for g in groups {
+            let (count, works) = m.entry(g.to_string()).or_default();
+            *count += 1;
+            works.insert(&e.title);
+        }
+    }
+    let mut v: Vec<Share> = m
+        .into_iter()
+        .map(|(name, (count, works))| Share {
+            name,
+            count,
+            works: works.len() as u32,
+        })
+        .collect();
+    v.sort_unstable_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    v
+}
+
+pub fn print_shares(v: &[Share], f: Format) {
+    match f {
+        Format::Json(j) => shares_as_json(v, j),
+        Format::Tsv => {
+            for s in v {
+                println!("\"{}\"\t{}\t{}", s.name, s.count, s.works);
+            }
+        }
+        Format::Markdown => {
+            let rows = v
+                .iter()
+                .map(|s| vec![s.name.clone(), s.count.to_string(), s.works.to_string()]);
+            output::print_md_table(&["Affiliation", "Count", "Works"], rows);
+        }
+    }
+}
+
+fn shares_as_json(v: &[Share], json: Json) {
+    let records = v.iter().map(|s| {
+        json!({
+            "affiliation": s.name,
+            "count": s.count,
+            "works": s.works
+        })
+    });
+    output::print_json(records, json, "affiliations");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+    use crate::parser::BibEntry;
+
+    #[test]
+    fn test_affiliations() {
+        let aff = Affiliations::parse(
+            "author,institution,country\n\
+             \"Knuth, Donald\",Stanford University,US\n\
+             # a comment\n\
+             Edsger W. Dijkstra,\"University of Texas, Austin\",US\n\
+             Niklaus Wirth,ETH Zurich,CH\n",
+        )
+        .unwrap();
+        assert_eq!(aff.get("Donald Knuth").unwrap().country, "US");
+        assert_eq!(
+            aff.get("Dijkstra, Edsger W. (ed.)").unwrap().institution,
+            "University of Texas, Austin"
+        );
+        assert!(Affiliations::parse("Knuth,Stanford\n").is_err());
+
+        let entry = |key: &str, author: &str| BibEntry {
+            key: key.to_string(),
+            author: author.to_string(),
+            title: key.to_string(),
+            ..BibEntry::empty()
+        };
+        let bib: BibMap = [
+            entry("a", "Knuth, Donald and Dijkstra, Edsger W."),
+            entry("b", "Niklaus Wirth and Donald Knuth"),
+            entry("c", "Tony Hoare"),
+        ]
+        .into_iter()
+        .map(|e| (e.key.clone(), e))
+        .collect();
+        let cites: Vec<Citation> = ["a", "b", "c", "a"].iter().map(|k| citation(k)).collect();
+        let v = compute(&aff, &bib, &cites, false);
+        let shares: Vec<(&str, u32, u32)> = v
+            .iter()
+            .map(|s| (s.name.as_str(), s.count, s.works))
+            .collect();
+        assert_eq!(shares, vec![("US", 3, 2), ("", 1, 1), ("CH", 1, 1)]);
+        assert_eq!(compute(&aff, &bib, &cites, true).len(), 4);
+    }
+
+    #[test]
+    fn test_one_share_per_work() {
+        let aff = Affiliations::parse(
+            "Ada Lovelace,London,UK\nAlan Turing,Manchester,UK\nKurt Goedel,IAS,US\n",
+        )
+        .unwrap();
+        let entry = |key: &str, author: &str| BibEntry {
+            key: key.to_string(),
+            author: author.to_string(),
+            title: key.to_string(),
+            ..BibEntry::empty()
+        };
+        let bib: BibMap = [
+            entry("a", "Ada Lovelace and Alan Turing"),
+            entry("b", "Kurt Goedel"),
+        ]
+        .into_iter()
+        .map(|e| (e.key.clone(), e))
+        .collect();
+        // a work counts once per country; ties go by name
+        let cites = vec![citation("b"), citation("a")];
+        let shares: Vec<(String, u32)> = compute(&aff, &bib, &cites, false)
+            .into_iter()
+            .map(|s| (s.name, s.count))
+            .collect();
+        assert_eq!(shares, [("UK".to_string(), 1), ("US".to_string(), 1)]);
+        assert_eq!(compute(&aff, &bib, &cites, true).len(), 3);
+    }
+}
@@ -0,0 +1,121 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+
+use rustc_hash::FxHashMap;
+
+use crate::docs::Citation;
+use crate::stats::BibMap;
+
+// Writes the bib files as they are, with a field added to every cited
+// entry that says how often it is cited, so that reference managers
+// can show it. All bib files are written into one, in the order in
+// which they are loaded; include commands are dropped, since the
+// included files are part of the output.
+// The field is inserted right after the citekey, so everything else
+// stays as it is. Entries that already have the field are left alone.
+pub fn annotate_bib(
+    out: &OsStr,
+    files: &[OsString],
+    bib: &BibMap,
+    cites: &[Citation],
+    field: &str,
+    doc: &str,
+) -> Result<(), String> {
+    let mut counts: FxHashMap<&str, u32> = FxHashMap::default();
+    for c in cites {
+        *counts.entry(c.key.as_str()).or_default() += 1;
+    }
+
+    // file -> line -> key
+    let mut lines: FxHashMap<&OsStr, FxHashMap<u64, &str>> = FxHashMap::default();
+    for (key, e) in bib {
+        if !counts.contains_key(key.as_str()) {
+            continue;
+        }
+        if e.fields.contains_key(field) {
+            eprintln!("{} already has a field {}, not annotated", key, field);
+            continue;
+        }
+        lines
+            .entry(e.file.as_os_str())
+            .or_default()
+            .insert(e.line, key);
+    }
+
+    let mut text = String::new();
+    for file in files {
+        let src = fs::read_to_string(file)
+            .map_err(|e| format!("cannot read {}: {}", file.to_string_lossy(), e))?;
+        let keys = lines.get(file.as_os_str());
+        for (i, l) in src.lines().enumerate() {
+            let trimmed = l.trim_start();
+            if trimmed.starts_with("\\input") || trimmed.starts_with("\\include") {
+                continue;
+            }
+            match keys.and_then(|ks| ks.get(&(i as u64 + 1))) {
+                Some(key) => {
+                    let note = annotation(counts[key], doc);
+                    text.push_str(&insert_field(l, key, field, &note));
+                }
+                None => text.push_str(l),
+            }
+            text.push('\n');
+        }
+    }
+    fs::write(out, text).map_err(|e| format!("cannot write {}: {}", out.to_string_lossy(), e))
+}
+
+fn annotation(n: u32, doc: &str) -> String {
+    match n {
+        1 => format!("cited once in {}", doc),
+        n => format!("cited {} times in {}", n, doc),
+    }
+}
+
+// Inserts the field after the comma following the citekey
+// on the line where the entry starts. If the key is not on that line,
+// the field is inserted before the line.
+fn insert_field(line: &str, key: &str, field: &str, value: &str) -> String {
+    let new = format!("  {} = {{{}}},", field, value);
+    for (i, _) in line.match_indices('@') {
+        let Some(open) = line[i..].find('{').map(|o| i + o + 1) else {
+            continue;
+        };
+        let rest = line[open..].trim_start();
+        let Some(after) = rest.strip_prefix(key) else {
+            continue;
+        };
+        let after = after.trim_start();
+        if !after.starts_with(',') {
+            continue;
+        }
+        let comma = line.len() - after.len() + 1;
+        let tail = line[comma..].trim_start();
+        if tail.is_empty() {
+            return format!("{}\n{}", &line[..comma], new);
+        }
+        return format!("{}\n{}\n  {}", &line[..comma], new, tail);
+    }
+    format!("{}\n{}", new, line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_insert_field() {
+        assert_eq!(
+            insert_field("@book{capital,", "capital", "annotation", "cited once"),
+            "@book{capital,\n  annotation = {cited once},"
+        );
+        assert_eq!(
+            insert_field("@book{ capital , author = {Karl}}", "capital", "x", "y"),
+            "@book{ capital ,\n  x = {y},\n  author = {Karl}}"
+        );
+        assert_eq!(
+            insert_field("} @book{cap,", "cap", "x", "y"),
+            "} @book{cap,\n  x = {y},"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use serde_json::{json, Value};
+
+use crate::bib;
+use crate::docs;
+use crate::docs::Citation;
+use crate::parser;
+use crate::parser::{BibEntry, TexConfig};
+use crate::stats;
+
+// The in-memory API behind the bindings for the browser and for C:
+// bib and LaTeX are given as text, results are the records of the
+// JSON output and errors the messages the command prints.
+
+// The entries of the bib text.
+pub fn entries(bib: &str) -> Result<Value, String> {
+    let v: Vec<Value> = load(bib)?.iter().map(entry).collect();
+    Ok(v.into())
+}
+
+// The citations of the LaTeX text.
+pub fn citations(tex: &str) -> Result<Value, String> {
+    let v: Vec<Value> = scan(tex)?.iter().map(citation).collect();
+    Ok(v.into())
+}
+
+// The citations of the LaTeX text per author and work,
+// like the default report.
+pub fn stats(bib: &str, tex: &str) -> Result<Value, String> {
+    let (mut bibmap, _) = stats::bib_to_map(load(bib)?);
+    stats::editor_fallback(&mut bibmap);
+    let (cites, _) = docs::nocite_all(scan(tex)?, &[], false);
+    let m = stats::compute(&bibmap, &cites, None, false);
+    Ok(stats::records(m, None).into())
+}
+
+fn load(bib: &str) -> Result<Vec<BibEntry>, String> {
+    bib::load_text("bib", bib, parser::Options::default())
+        .map(|b| b.entries)
+        .map_err(|e| e.to_string())
+}
+
+fn scan(tex: &str) -> Result<Vec<Citation>, String> {
+    docs::collect_text("document", tex, &TexConfig::default()).map_err(|e| e.to_string())
+}
+
+fn entry(e: &BibEntry) -> Value {
+    json!({
+        "key": e.key,
+        "type": e.pubtype.to_string(),
+        "author": e.author,
+        "title": e.title,
+        "date": e.date,
+        "line": e.line,
+        "fields": e.fields
+    })
+}
+
+fn citation(c: &Citation) -> Value {
+    json!({
+        "key": c.key,
+        "line": c.line,
+        "column": c.column,
+        "section": c.section,
+        "chapter": c.chapter,
+        "prenote": c.prenote,
+        "postnote": c.postnote,
+        "quoted": c.quoted,
+        "kind": c.kind.name()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let bib = "@book{knuth84, author={Knuth, Donald E.}, title={Literate Programming}}";
+        let tex = "\\section{Intro}\\cite[p.~5]{knuth84} \\input{ch1}\n\\textcite{knuth84, nobib}";
+        let cites = citations(tex).unwrap();
+        assert_eq!(cites[0]["section"], "1 Intro");
+        assert_eq!(cites[0]["postnote"], "p.~5");
+        assert_eq!(cites[2]["key"], "nobib");
+        assert_eq!(
+            stats(bib, tex).unwrap(),
+            json!([{"author": "Donald E. Knuth", "title": "Literate Programming", "count": 2}])
+        );
+        assert!(entries("@book{a,").unwrap_err().starts_with("bib:"));
+    }
+}
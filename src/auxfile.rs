@@ -0,0 +1,131 @@
+// The aux files LaTeX writes: after a run, they list every citation
+// as \citation{key}, or, with biblatex, as \abx@aux@cite{0}{key},
+// with the macros of the document expanded.
+
+use crate::parser::{Cite, CiteKind, TexItem};
+
+// Reads the citations, the bib files of \bibdata and the aux files
+// of included documents from an aux file. The line of a citation
+// is its line in the aux file; aux files do not tell the kind of
+// a citation nor its notes, and there are no sections.
+pub fn collect_cites(text: &str) -> Vec<TexItem> {
+    let mut items = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let Some((name, args)) = command(line) else {
+            continue;
+        };
+        match (name, args.as_slice()) {
+            ("citation", [keys, ..]) | ("abx@aux@cite", [_, keys] | [keys]) => {
+                items.extend(split(keys).map(|key| {
+                    TexItem::Cite(Cite {
+                        key,
+                        line: n as u64 + 1,
+                        column: 1,
+                        prenote: None,
+                        postnote: None,
+                        quoted: false,
+                        kind: CiteKind::Plain,
+                        span: None,
+                    })
+                }))
+            }
+            ("bibdata", [files, ..]) => items.extend(split(files).map(TexItem::Bib)),
+            ("@input", [file, ..]) => items.push(TexItem::Input(file.to_string())),
+            _ => (),
+        }
+    }
+    items
+}
+
+// A command starting the line with its arguments in braces.
+fn command(line: &str) -> Option<(&str, Vec<&str>)> {
+    let rest = line.trim_start().strip_prefix('\\')?;
+    let end = rest.find(['{', ' ']).unwrap_or(rest.len());
+    let (name, mut rest) = rest.split_at(end);
+    let mut args = Vec::new();
+    while let Some(group) = rest.trim_start().strip_prefix('{') {
+        let close = closing(group)?;
+        args.push(&group[..close]);
+        rest = &group[close + 1..];
+    }
+    Some((name, args))
+}
+
+// Where the group opened before s closes.
+fn closing(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+fn split(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.split(',')
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::docs::{collect_text, Citation};
+    use crate::fixtures::citation;
+    use crate::parser::TexConfig;
+
+    const AUX: &str = "\\relax\n\
+                       \\citation{knuth84,lamport86}\n\
+                       \\@writefile{toc}{\\contentsline {section}{\\numberline {1}Intro}{1}}\n\
+                       \\abx@aux@cite{0}{doe99}\n\
+                       \\abx@aux@cite{roe05}\n\
+                       \\bibdata{refs, more}\n\
+                       \\@input{chapter.aux}\n";
+
+    #[test]
+    fn test_collect_cites() {
+        let cite = |key: &str, line| Citation {
+            file: "main.aux".into(),
+            line,
+            ..citation(key)
+        };
+        assert!(match collect_text("main.aux", AUX, &TexConfig::default()) {
+            Ok(cites) => {
+                cites
+                    == vec![
+                        cite("knuth84", 2),
+                        cite("lamport86", 2),
+                        cite("doe99", 4),
+                        cite("roe05", 5),
+                    ]
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        });
+    }
+
+    #[test]
+    fn test_bibdata_and_input() {
+        let items: Vec<TexItem> = collect_cites(AUX)
+            .into_iter()
+            .filter(|i| !matches!(i, TexItem::Cite(_)))
+            .collect();
+        assert!(match items.as_slice() {
+            [TexItem::Bib(a), TexItem::Bib(b), TexItem::Input(c)] => {
+                a == "refs" && b == "more" && c == "chapter.aux"
+            }
+            is => {
+                eprintln!("items: {:?}", is);
+                false
+            }
+        });
+        assert!(collect_cites("\\citation{}\n\\citation{a\n").is_empty());
+    }
+}
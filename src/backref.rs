@@ -0,0 +1,204 @@
+use rustc_hash::FxHashMap;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::{author_title, BibMap};
+
+// The places where a citekey is cited, in document order.
+pub struct BackRef<'a> {
+    pub key: &'a str,
+    pub sites: Vec<&'a Citation>,
+}
+
+// Returns the back references of all cited keys
+// in the order of their first citation.
+pub fn compute(cites: &[Citation]) -> Vec<BackRef<'_>> {
+    let mut v: Vec<BackRef> = Vec::new();
+    let mut idx: FxHashMap<&str, usize> = FxHashMap::default();
+    for c in cites {
+        match idx.get(c.key.as_str()) {
+            Some(&k) => v[k].sites.push(c),
+            None => {
+                idx.insert(&c.key, v.len());
+                v.push(BackRef {
+                    key: &c.key,
+                    sites: vec![c],
+                });
+            }
+        }
+    }
+    v
+}
+
+pub fn print_backrefs(refs: Vec<BackRef>, bib: &BibMap, f: Format) {
+    match f {
+        Format::Json(j) => backrefs_as_json(refs, bib, j),
+        Format::Tsv => backrefs_as_tsv(refs, bib),
+        Format::Markdown => backrefs_as_markdown(refs, bib),
+    }
+}
+
+fn locations(r: &BackRef) -> Vec<String> {
+    r.sites.iter().map(|c| c.location()).collect()
+}
+
+fn backrefs_as_tsv(refs: Vec<BackRef>, bib: &BibMap) {
+    for r in refs {
+        let (author, title) = author_title(r.key, bib);
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t{}\t\"{}\"",
+            r.key,
+            author,
+            title,
+            r.sites.len(),
+            locations(&r).join(",")
+        );
+    }
+}
+
+// One list item per citekey, like the back references
+// hyperref adds to the bibliography.
+fn backrefs_as_markdown(refs: Vec<BackRef>, bib: &BibMap) {
+    for r in refs {
+        let (author, title) = author_title(r.key, bib);
+        let work = match (author.is_empty(), title.is_empty()) {
+            (true, true) => String::new(),
+            (true, false) => format!(" *{}*", title),
+            (false, true) => format!(" {}", author),
+            (false, false) => format!(" {}: *{}*", author, title),
+        };
+        println!("- `{}`{}: {}", r.key, work, locations(&r).join(", "));
+    }
+}
+
+fn backrefs_as_json(refs: Vec<BackRef>, bib: &BibMap, json: Json) {
+    let records = refs.into_iter().map(|r| {
+        let (author, title) = author_title(r.key, bib);
+        let sites: Vec<serde_json::Value> = r
+            .sites
+            .iter()
+            .map(|c| {
+                json!({
+                    "file": c.file.to_string_lossy(),
+                    "line": c.line,
+                    "column": c.column,
+                    "section": c.section
+                })
+            })
+            .collect();
+        json!({
+            "key": r.key,
+            "author": author,
+            "title": title,
+            "count": r.sites.len(),
+            "sites": sites
+        })
+    });
+    output::print_json(records, json, "backref");
+}
+
+// Every place a citekey is cited as file:line:column,
+// which editors and IDEs take to jump to it.
+fn position(c: &Citation) -> String {
+    format!("{}:{}:{}", c.file.to_string_lossy(), c.line, c.column)
+}
+
+// The places of the citations of every citekey; in TSV one line
+// per place, in the order of the citekeys and then of the places.
+pub fn print_locations(refs: Vec<BackRef>, bib: &BibMap, f: Format) {
+    match f {
+        Format::Json(j) => locations_as_json(refs, bib, j),
+        Format::Tsv => {
+            for r in refs {
+                for c in &r.sites {
+                    println!(
+                        "\"{}\"\t\"{}\"\t{}\t{}",
+                        r.key,
+                        c.file.to_string_lossy(),
+                        c.line,
+                        c.column
+                    );
+                }
+            }
+        }
+        Format::Markdown => {
+            for r in refs {
+                println!("- `{}`", r.key);
+                for c in &r.sites {
+                    println!("  - {}", position(c));
+                }
+            }
+        }
+    }
+}
+
+fn locations_as_json(refs: Vec<BackRef>, bib: &BibMap, json: Json) {
+    let records = refs.into_iter().map(|r| {
+        let (author, title) = author_title(r.key, bib);
+        let locations: Vec<serde_json::Value> = r
+            .sites
+            .iter()
+            .map(|c| {
+                json!({
+                    "file": c.file.to_string_lossy(),
+                    "line": c.line,
+                    "column": c.column,
+                    "position": position(c)
+                })
+            })
+            .collect();
+        json!({
+            "key": r.key,
+            "author": author,
+            "title": title,
+            "count": r.sites.len(),
+            "locations": locations
+        })
+    });
+    output::print_json(records, json, "where");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+
+    #[test]
+    fn test_sites() {
+        assert!(compute(&[]).is_empty());
+        let cites: Vec<Citation> = [
+            ("b", "a.tex"),
+            ("a", "a.tex"),
+            ("b", "b.tex"),
+            ("b", "a.tex"),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (k, f))| Citation {
+            file: f.into(),
+            line: i as u64 + 1,
+            ..citation(k)
+        })
+        .collect();
+        let refs: Vec<(&str, Vec<String>)> = compute(&cites)
+            .iter()
+            .map(|r| (r.key, r.sites.iter().map(|c| position(c)).collect()))
+            .collect();
+        assert_eq!(
+            refs,
+            [
+                (
+                    "b",
+                    vec![
+                        "a.tex:1:1".to_string(),
+                        "b.tex:3:1".to_string(),
+                        "a.tex:4:1".to_string()
+                    ]
+                ),
+                ("a", vec!["a.tex:2:1".to_string()]),
+            ]
+        );
+    }
+}
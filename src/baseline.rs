@@ -0,0 +1,183 @@
+use std::ffi::OsStr;
+use std::fs;
+
+use rustc_hash::FxHashMap;
+use serde_json::json;
+
+use crate::lint::Finding;
+
+// A baseline records the findings of a run, so that later runs
+// only report new findings. Findings are identified by code,
+// citekey and file, not by line, since lines move as the sources
+// are edited; findings that occur more often than recorded are new.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    counts: FxHashMap<(String, String, String), usize>,
+}
+
+const VERSION: u64 = 1;
+
+fn id(f: &Finding) -> (String, String, String) {
+    (
+        f.code.to_string(),
+        f.key.clone(),
+        f.file.to_string_lossy().to_string(),
+    )
+}
+
+impl Baseline {
+    pub fn from_findings(fs: &[Finding]) -> Baseline {
+        let mut b = Baseline::default();
+        for f in fs {
+            *b.counts.entry(id(f)).or_default() += 1;
+        }
+        b
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    pub fn load(path: &OsStr) -> Result<Baseline, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read baseline {}: {}", path.to_string_lossy(), e))?;
+        let js: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|e| format!("invalid baseline {}: {}", path.to_string_lossy(), e))?;
+        if js["version"].as_u64() != Some(VERSION) {
+            return Err(format!(
+                "baseline {} has an unknown version",
+                path.to_string_lossy()
+            ));
+        }
+        let mut b = Baseline::default();
+        for f in js["findings"].as_array().into_iter().flatten() {
+            let (Some(code), Some(key), Some(file), Some(count)) = (
+                f["code"].as_str(),
+                f["key"].as_str(),
+                f["file"].as_str(),
+                f["count"].as_u64(),
+            ) else {
+                return Err(format!(
+                    "invalid finding in baseline {}: {}",
+                    path.to_string_lossy(),
+                    f
+                ));
+            };
+            *b.counts
+                .entry((code.to_string(), key.to_string(), file.to_string()))
+                .or_default() += count as usize;
+        }
+        Ok(b)
+    }
+
+    // The findings are sorted, so the file changes little between runs
+    // and can be kept under version control.
+    pub fn save(&self, path: &OsStr) -> Result<(), String> {
+        let mut ids: Vec<(&(String, String, String), &usize)> = self.counts.iter().collect();
+        ids.sort_unstable();
+        let findings: Vec<serde_json::Value> = ids
+            .into_iter()
+            .map(|((code, key, file), count)| {
+                json!({
+                    "code": code,
+                    "key": key,
+                    "file": file,
+                    "count": count
+                })
+            })
+            .collect();
+        let js = json!({
+            "version": VERSION,
+            "findings": findings
+        });
+        let text = serde_json::to_string_pretty(&js).unwrap_or_default();
+        fs::write(path, text + "\n")
+            .map_err(|e| format!("cannot write baseline {}: {}", path.to_string_lossy(), e))
+    }
+
+    // Returns the findings not in the baseline, the number of
+    // findings in the baseline and the number of recorded findings
+    // that do not occur any more.
+    pub fn filter(&self, fs: Vec<Finding>) -> (Vec<Finding>, usize, usize) {
+        let mut left = self.counts.clone();
+        let mut known = 0;
+        let mut new = Vec::new();
+        for f in fs {
+            match left.get_mut(&id(&f)) {
+                Some(n) if *n > 0 => {
+                    *n -= 1;
+                    known += 1;
+                }
+                _ => new.push(f),
+            }
+        }
+        (new, known, left.values().sum())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::ffi::OsString;
+
+    fn finding(code: &'static str, key: &str, line: u64) -> Finding {
+        Finding {
+            code,
+            key: key.to_string(),
+            file: OsString::from("refs.bib"),
+            line,
+            message: String::new(),
+        }
+    }
+
+    fn keys(fs: &[Finding]) -> Vec<(&str, &str)> {
+        fs.iter().map(|f| (f.code, f.key.as_str())).collect()
+    }
+
+    #[test]
+    fn test_filter() {
+        let b = Baseline::from_findings(&[
+            finding("L001", "a", 1),
+            finding("L002", "b", 5),
+            finding("L002", "c", 9),
+        ]);
+        assert_eq!(b.len(), 3);
+
+        // lines may move, the same code and key in another place is new
+        let (new, known, fixed) = b.filter(vec![
+            finding("L001", "a", 3),
+            finding("L001", "a", 7),
+            finding("L002", "b", 12),
+            finding("L003", "b", 12),
+        ]);
+        assert_eq!(keys(&new), vec![("L001", "a"), ("L003", "b")]);
+        assert_eq!(known, 2);
+        assert_eq!(fixed, 1);
+    }
+
+    #[test]
+    fn test_load_save() {
+        let dir = env::temp_dir().join(format!("bibstats-baseline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("baseline.json");
+
+        let b = Baseline::from_findings(&[
+            finding("L001", "a", 1),
+            finding("L001", "a", 4),
+            finding("L002", "b", 5),
+        ]);
+        b.save(path.as_os_str()).unwrap();
+        let loaded = Baseline::load(path.as_os_str()).unwrap();
+        assert_eq!(loaded.counts, b.counts);
+
+        // no baseline file
+        let missing = dir.join("missing.json");
+        assert!(match Baseline::load(missing.as_os_str()) {
+            Err(e) => e.starts_with("cannot read baseline"),
+            Ok(_) => false,
+        });
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+// The control files biblatex writes for biber: an XML document that
+// lists the cited keys of every refsection as
+// <bcf:citekey order="1">key</bcf:citekey> and the bib files as
+// <bcf:datasource type="file">refs.bib</bcf:datasource>.
+
+use crate::parser::{Cite, CiteKind, TexItem};
+
+// Reads the citekeys and the bib files from a control file, which
+// are what biber sees. Keys marked with nocite="1" come from \nocite;
+// the line of a citation is its line in the control file. Like aux
+// files, control files know nothing of notes, quotations and sections.
+pub fn collect_cites(text: &str) -> Vec<TexItem> {
+    let mut items = Vec::new();
+    let mut line = 1;
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find("<bcf:").map(|n| pos + n) {
+        line += text[pos..start].matches('\n').count() as u64;
+        pos = start + 1;
+        let Some((name, attrs, content)) = element(&text[start..]) else {
+            continue;
+        };
+        match name {
+            "citekey" => items.push(TexItem::Cite(Cite {
+                key: unescaped(content.trim()),
+                line,
+                column: 1,
+                prenote: None,
+                postnote: None,
+                quoted: false,
+                kind: if attribute(attrs, "nocite") == Some("1") {
+                    CiteKind::Nocite
+                } else {
+                    CiteKind::Plain
+                },
+                span: None,
+            })),
+            "datasource" if attribute(attrs, "type").is_none_or(|t| t == "file") => {
+                items.push(TexItem::Bib(unescaped(content.trim())))
+            }
+            _ => (),
+        }
+    }
+    items
+}
+
+// The name, the attributes and the text of an element like
+// <bcf:citekey order="1">key</bcf:citekey>, without nested elements.
+fn element(s: &str) -> Option<(&str, &str, &str)> {
+    let tag = &s["<bcf:".len()..s.find('>')?];
+    if tag.ends_with('/') {
+        return None;
+    }
+    let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    let body = &s["<bcf:".len() + tag.len() + 1..];
+    let content = &body[..body.find('<')?];
+    body[content.len()..]
+        .starts_with(&format!("</bcf:{}>", name))
+        .then_some((name, attrs, content))
+}
+
+// The value of an attribute, like "1" for nocite in nocite="1".
+fn attribute<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    attrs.split_whitespace().find_map(|a| {
+        let (n, v) = a.split_once('=')?;
+        (n == name).then(|| v.trim_matches(['"', '\'']))
+    })
+}
+
+fn unescaped(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::docs::{collect_text, Citation};
+    use crate::fixtures::citation;
+    use crate::parser::TexConfig;
+
+    const BCF: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                       <bcf:controlfile version=\"3.10\" bltxversion=\"3.19\">\n\
+                       <bcf:bibdata section=\"0\">\n\
+                       \x20 <bcf:datasource type=\"file\" datatype=\"bibtex\" glob=\"false\">refs.bib</bcf:datasource>\n\
+                       \x20 <bcf:datasource type=\"url\">https://example.org/refs.bib</bcf:datasource>\n\
+                       </bcf:bibdata>\n\
+                       <bcf:section number=\"0\">\n\
+                       \x20 <bcf:citekey order=\"1\" intorder=\"1\">knuth84</bcf:citekey>\n\
+                       \x20 <bcf:citekey order=\"2\" intorder=\"1\" nocite=\"1\">doe&amp;roe</bcf:citekey>\n\
+                       \x20 <bcf:citekey order=\"3\"/>\n\
+                       </bcf:section>\n\
+                       </bcf:controlfile>\n";
+
+    #[test]
+    fn test_collect_cites() {
+        let cite = |key: &str, line, kind| Citation {
+            file: "main.bcf".into(),
+            line,
+            kind,
+            ..citation(key)
+        };
+        assert!(match collect_text("main.bcf", BCF, &TexConfig::default()) {
+            Ok(cites) => {
+                cites
+                    == vec![
+                        cite("knuth84", 8, CiteKind::Plain),
+                        cite("doe&roe", 9, CiteKind::Nocite),
+                    ]
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        });
+    }
+
+    #[test]
+    fn test_datasource() {
+        // only files are bib files
+        assert!(match collect_cites(BCF).first() {
+            Some(TexItem::Bib(b)) => b == "refs.bib",
+            i => {
+                eprintln!("item: {:?}", i);
+                false
+            }
+        });
+        let bibs = collect_cites(BCF)
+            .iter()
+            .filter(|i| matches!(i, TexItem::Bib(_)))
+            .count();
+        assert_eq!(bibs, 1);
+    }
+}
@@ -0,0 +1,281 @@
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::diagnostics;
+use crate::error;
+use crate::error::SourceError;
+use crate::hayagriva;
+use crate::parser;
+use crate::parser::{BibEntry, BibFile, PubType};
+use crate::resolve::{FileKind, Resolver, SearchConfig};
+
+// Loads the master bib file together with all files it includes
+// and the additional files given on the command line.
+// The additional files are handled like further master files.
+// Includes are resolved relative to the directory of the master file
+// they belong to, also when they are nested, and then in the search paths.
+// The result has the entries and comments of all files;
+// its includes are resolved and therefore empty.
+// Entries skipped in lenient mode are reported as warnings.
+pub fn load(
+    master: &OsString,
+    extra: &[OsString],
+    search: &SearchConfig,
+    opts: parser::Options,
+) -> Result<BibFile, SourceError> {
+    let mut all = BibFile::default();
+    let mut seen = FxHashSet::default();
+    for root in std::iter::once(master).chain(extra.iter()) {
+        let path = PathBuf::from(root);
+        let resolver = Resolver::for_file(&path, search);
+        load_file(&path, &resolver, opts, &mut seen, &mut all)?;
+    }
+    inherit(&mut all.entries);
+    Ok(all)
+}
+
+// Parses bib entries given as text, like content pasted into
+// a web page; 'name' stands for the file in locations and, ending
+// in .yml or .yaml, makes the text a Hayagriva file. Without
+// a filesystem, includes are not followed.
+pub fn load_text(name: &str, text: &str, opts: parser::Options) -> Result<BibFile, SourceError> {
+    let name = OsStr::new(name);
+    let mut bib = if is_hayagriva(name) {
+        parse_hayagriva(name, text.as_bytes())?
+    } else {
+        error::parse_source(name, text.as_bytes(), |s| parser::parse_with(s, opts))?
+    };
+    for e in bib.entries.iter_mut() {
+        e.file = name.to_os_string();
+    }
+    inherit(&mut bib.entries);
+    Ok(bib)
+}
+
+// Entries with a crossref field inherit the fields they lack from
+// the entry it names, as in BibTeX; the title of the parent also
+// serves as booktitle. Keys are compared case-insensitively
+// and, like BibTeX, we resolve only one level.
+fn inherit(entries: &mut [BibEntry]) {
+    let mut index: FxHashMap<String, usize> = FxHashMap::default();
+    for (i, e) in entries.iter().enumerate() {
+        index.entry(e.key.to_lowercase()).or_insert(i);
+    }
+
+    let mut inherited = Vec::new();
+    for (i, e) in entries.iter().enumerate() {
+        let Some(parent) = e.field("crossref") else {
+            continue;
+        };
+        let p = match index.get(&parent.to_lowercase()) {
+            Some(&p) if p != i => &entries[p],
+            Some(_) => continue,
+            None => {
+                diagnostics::warn(
+                    "B005",
+                    &format!(
+                        "crossref {} of {} at {} not in database",
+                        parent,
+                        e.key,
+                        e.location()
+                    ),
+                );
+                continue;
+            }
+        };
+        let mut fields: Vec<(String, String)> = p
+            .fields
+            .iter()
+            .filter(|(k, _)| *k != "crossref" && e.field(k).is_none())
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        if e.field("booktitle").is_none() && p.field("booktitle").is_none() && !p.title.is_empty() {
+            fields.push(("booktitle".to_string(), p.title.clone()));
+        }
+        let author = (e.author.is_empty()).then(|| p.author.clone());
+        let title = (e.title.is_empty()).then(|| p.title.clone());
+        let date = (e.date.is_empty()).then(|| p.date.clone());
+        inherited.push((i, author, title, date, fields));
+    }
+
+    for (i, author, title, date, fields) in inherited {
+        let e = &mut entries[i];
+        if let Some(a) = author {
+            e.author = a;
+        }
+        if let Some(t) = title {
+            e.title = t;
+        }
+        if let Some(d) = date {
+            e.date = d;
+        }
+        e.fields.extend(fields);
+    }
+}
+
+fn load_file(
+    path: &Path,
+    resolver: &Resolver,
+    opts: parser::Options,
+    seen: &mut FxHashSet<PathBuf>,
+    all: &mut BibFile,
+) -> Result<(), SourceError> {
+    // canonicalize fails for files that do not exist;
+    // in that case parsing reports the error
+    let canon = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if !seen.insert(canon) {
+        diagnostics::warn(
+            "B003",
+            &format!("bib file {} is included twice, ignoring", path.display()),
+        );
+        return Ok(());
+    }
+
+    let bib = parse_bib_file(path.as_os_str(), opts)?;
+    for e in &bib.skipped {
+        diagnostics::warn(
+            "B007",
+            &format!(
+                "skipped malformed entry at {}:{}: {}",
+                path.display(),
+                e.line,
+                e.msg
+            ),
+        );
+    }
+    for d in &bib.duplicate_fields {
+        diagnostics::warn(
+            "B008",
+            &format!(
+                "field {} given more than once in {} at {}:{}",
+                d.field,
+                d.key,
+                path.display(),
+                d.line
+            ),
+        );
+    }
+    warn_unknown_types(path, &bib.entries);
+    all.skipped.extend(bib.skipped);
+    all.comments.extend(bib.comments);
+    all.entries.extend(bib.entries.into_iter().map(|mut e| {
+        e.file = path.as_os_str().to_os_string();
+        e
+    }));
+
+    // bib files may be included without extension
+    for include in bib.includes {
+        let p = resolver
+            .resolve(&include, FileKind::Bib)
+            .unwrap_or_else(|| resolver.fallback(&include, FileKind::Bib));
+        load_file(&p, resolver, opts, seen, all)?;
+    }
+    Ok(())
+}
+
+// Once per type and file, since a file often has many entries
+// of a type, like @online.
+fn warn_unknown_types(path: &Path, entries: &[BibEntry]) {
+    let mut types: Vec<(&str, u64, usize)> = Vec::new();
+    for e in entries {
+        let PubType::Other(t) = &e.pubtype else {
+            continue;
+        };
+        match types.iter_mut().find(|(name, _, _)| name == t) {
+            Some((_, _, n)) => *n += 1,
+            None => types.push((t, e.line, 1)),
+        }
+    }
+    for (t, line, n) in types {
+        let entries = if n == 1 { "entry" } else { "entries" };
+        diagnostics::warn(
+            "B009",
+            &format!(
+                "{} {} of unknown type @{}, first at {}:{}",
+                n,
+                entries,
+                t,
+                path.display(),
+                line
+            ),
+        );
+    }
+}
+
+// Files ending in .yml or .yaml are Hayagriva files, the others bib files.
+fn parse_bib_file(path: &OsStr, opts: parser::Options) -> Result<BibFile, SourceError> {
+    if !is_hayagriva(path) {
+        return error::parse_file(path, |s| parser::parse_with(s, opts));
+    }
+    let source = std::fs::read(path).map_err(|e| SourceError::io(path, e))?;
+    parse_hayagriva(path, &source)
+}
+
+fn is_hayagriva(path: &OsStr) -> bool {
+    matches!(
+        Path::new(path).extension().and_then(OsStr::to_str),
+        Some("yml" | "yaml")
+    )
+}
+
+fn parse_hayagriva(name: &OsStr, source: &[u8]) -> Result<BibFile, SourceError> {
+    hayagriva::parse(&String::from_utf8_lossy(source))
+        .map_err(|(offset, reason)| SourceError::at(name, source, Some(offset), reason))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(pubtype: PubType, key: &str, title: &str, fields: &[(&str, &str)]) -> BibEntry {
+        BibEntry {
+            pubtype,
+            key: key.to_string(),
+            title: title.to_string(),
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..BibEntry::empty()
+        }
+    }
+
+    #[test]
+    fn test_inherit() {
+        let mut entries = vec![
+            entry(
+                PubType::Inproc,
+                "paper",
+                "A Paper",
+                &[("crossref", "Conf20"), ("pages", "1--10")],
+            ),
+            entry(
+                PubType::Proceedings,
+                "conf20",
+                "Proceedings of Conf",
+                &[("year", "2020"), ("pages", "300")],
+            ),
+        ];
+        inherit(&mut entries);
+        let e = &entries[0];
+        assert_eq!(e.title, "A Paper");
+        assert_eq!(e.field("booktitle"), Some("Proceedings of Conf"));
+        assert_eq!(e.year(), Some("2020"));
+        assert_eq!(e.field("pages"), Some("1--10"));
+        assert_eq!(entries[1].field("booktitle"), None);
+    }
+
+    #[test]
+    fn test_load_text() {
+        let text = "@inproceedings{paper, title={A Paper}, crossref={conf20}}\n\
+                    @proceedings{conf20, title={Proceedings of Conf}, year={2020}}";
+        let bib = load_text("pasted", text, parser::Options::default()).unwrap();
+        let e = &bib.entries[0];
+        assert_eq!(e.location(), "pasted:1");
+        assert_eq!(e.field("booktitle"), Some("Proceedings of Conf"));
+        let err = load_text("pasted", "@book{a, title={A}", parser::Options::default());
+        assert!(err.unwrap_err().to_string().starts_with("pasted:"));
+    }
+}
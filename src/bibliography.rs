@@ -0,0 +1,315 @@
+use std::str::FromStr;
+
+use rustc_hash::FxHashMap;
+
+use crate::docs::Citation;
+use crate::names;
+use crate::parser::{BibEntry, PubType};
+use crate::stats::BibMap;
+
+// The markup of the formatted bibliography.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Markup {
+    Markdown,
+    Html,
+}
+
+impl FromStr for Markup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Markup, String> {
+        match s {
+            "markdown" | "md" => Ok(Markup::Markdown),
+            "html" => Ok(Markup::Html),
+            _ => Err(format!(
+                "unknown markup '{}', 'markdown' or 'html' expected",
+                s
+            )),
+        }
+    }
+}
+
+// A cited work and how often it is cited.
+pub struct Item<'a> {
+    pub entry: &'a BibEntry,
+    pub count: u32,
+}
+
+// The works cited that are in the database, sorted like
+// author-year styles do: by the last name of the first author,
+// then by year and title.
+pub fn compute<'a>(bib: &'a BibMap, cites: &[Citation]) -> Vec<Item<'a>> {
+    let mut counts: FxHashMap<&str, u32> = FxHashMap::default();
+    for c in cites {
+        if let Some((k, _)) = bib.get_key_value(&c.key) {
+            *counts.entry(k).or_default() += 1;
+        }
+    }
+    let mut v: Vec<Item> = counts
+        .into_iter()
+        .map(|(k, count)| Item {
+            entry: &bib[k],
+            count,
+        })
+        .collect();
+    v.sort_by_cached_key(|i| {
+        let e = i.entry;
+        (
+            sort_name(&e.author),
+            e.year().unwrap_or("").to_string(),
+            e.title.to_lowercase(),
+            e.key.clone(),
+        )
+    });
+    v
+}
+
+fn sort_name(author: &str) -> String {
+    names::split(author)
+        .first()
+        .and_then(|n| n.trim_end_matches(" (ed.)").split_whitespace().last())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+// The authors as written in running text, with et al.
+// for more than three.
+fn authors(author: &str) -> String {
+    let names = names::split(author);
+    match names.as_slice() {
+        [] => String::new(),
+        [a] => a.clone(),
+        [a, b] => format!("{} and {}", a, b),
+        [a, b, c] => format!("{}, {} and {}", a, b, c),
+        [a, ..] => format!("{} et al.", a),
+    }
+}
+
+// Where the work is published: the journal with volume, number
+// and pages, the book or proceedings it is in or its publisher.
+fn venue(e: &BibEntry) -> (Option<&str>, String) {
+    let journal = e.field("journal").or_else(|| e.field("journaltitle"));
+    let container = match e.pubtype {
+        PubType::Article => journal,
+        PubType::Incol | PubType::Inproc | PubType::Inbook => e.field("booktitle"),
+        _ => None,
+    };
+    let mut rest = Vec::new();
+    if let Some(v) = e.field("volume") {
+        rest.push(match e.field("number") {
+            Some(n) => format!("{}({})", v, n),
+            None => v.to_string(),
+        });
+    }
+    if let Some(p) = e.field("pages") {
+        rest.push(p.replace("--", "–"));
+    }
+    let publisher = e
+        .field("publisher")
+        .or_else(|| e.field("school"))
+        .or_else(|| e.field("institution"))
+        .or_else(|| e.field("howpublished"));
+    if container.is_none() || e.pubtype != PubType::Article {
+        rest.extend(publisher.map(String::from));
+    }
+    (container, rest.join(", "))
+}
+
+// An entry in the built-in author-year style, followed
+// by the number of citations, e.g.
+//
+//   Donald E. Knuth (1984). Literate Programming. *The Computer Journal*, 27(2), 97–111.
+//   https://doi.org/10.1093/comjnl/27.2.97 (cited 3 times)
+pub fn format(i: &Item, m: Markup) -> String {
+    let e = i.entry;
+    let text = |s: &str| match m {
+        Markup::Markdown => escape_md(s),
+        Markup::Html => escape_html(s),
+    };
+    let mut s = String::new();
+    let authors = authors(&e.author);
+    if !authors.is_empty() {
+        s.push_str(&text(&authors));
+        s.push(' ');
+    }
+    s.push_str(&format!("({}).", e.year().unwrap_or("n.d.")));
+    if !e.title.is_empty() {
+        s.push(' ');
+        s.push_str(&text(e.title.trim_end_matches('.')));
+        s.push('.');
+    }
+    let (container, rest) = venue(e);
+    if let Some(c) = container {
+        let c = text(c);
+        s.push(' ');
+        if e.pubtype != PubType::Article {
+            s.push_str("In ");
+        }
+        match m {
+            Markup::Markdown => s.push_str(&format!("*{}*", c)),
+            Markup::Html => s.push_str(&format!("<em>{}</em>", c)),
+        }
+        s.push(if rest.is_empty() { '.' } else { ',' });
+    }
+    if !rest.is_empty() {
+        s.push(' ');
+        s.push_str(&text(&rest));
+        s.push('.');
+    }
+    let link = e
+        .doi()
+        .map(|d| format!("https://doi.org/{}", d))
+        .or_else(|| e.field("url").map(String::from));
+    if let Some(l) = link {
+        match m {
+            Markup::Markdown => s.push_str(&format!(" <{}>", l)),
+            Markup::Html => s.push_str(&format!(
+                " <a href=\"{}\">{}</a>",
+                escape_html(&l),
+                escape_html(&l)
+            )),
+        }
+    }
+    match i.count {
+        1 => s.push_str(" (cited once)"),
+        n => s.push_str(&format!(" (cited {} times)", n)),
+    }
+    s
+}
+
+fn escape_md(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '*' | '_' | '[' | ']' | '<' | '>' | '`') {
+            r.push('\\');
+        }
+        r.push(c);
+    }
+    r
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn print_bibliography(v: &[Item], m: Markup) {
+    match m {
+        Markup::Markdown => {
+            for i in v {
+                println!("- {}", format(i, m));
+            }
+        }
+        Markup::Html => {
+            println!("<ul class=\"bibliography\">");
+            for i in v {
+                println!(
+                    "<li id=\"{}\">{}</li>",
+                    escape_html(&i.entry.key),
+                    format(i, m)
+                );
+            }
+            println!("</ul>");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_bibliography() {
+        let fields = |fs: &[(&str, &str)]| {
+            fs.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        let bib: BibMap = [
+            BibEntry {
+                pubtype: PubType::Article,
+                key: "knuth84".to_string(),
+                author: "Knuth, Donald E.".to_string(),
+                title: "Literate Programming".to_string(),
+                date: "1984".to_string(),
+                fields: fields(&[
+                    ("journal", "The Computer Journal"),
+                    ("volume", "27"),
+                    ("number", "2"),
+                    ("pages", "97--111"),
+                    ("doi", "10.1093/comjnl/27.2.97"),
+                ]),
+                ..BibEntry::empty()
+            },
+            BibEntry {
+                pubtype: PubType::Book,
+                key: "dijkstra".to_string(),
+                author: "Dahl, O.-J. and Dijkstra, E. W. and Hoare, C. A. R.".to_string(),
+                title: "Structured Programming".to_string(),
+                date: "1972".to_string(),
+                fields: fields(&[("publisher", "Academic Press")]),
+                ..BibEntry::empty()
+            },
+        ]
+        .into_iter()
+        .map(|e| (e.key.clone(), e))
+        .collect();
+        let cites: Vec<Citation> = ["knuth84", "dijkstra", "knuth84", "nobib"]
+            .iter()
+            .map(|k| citation(k))
+            .collect();
+        let v = compute(&bib, &cites);
+        let lines: Vec<String> = v.iter().map(|i| format(i, Markup::Markdown)).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "O.-J. Dahl, E. W. Dijkstra and C. A. R. Hoare (1972). \
+                 Structured Programming. Academic Press. (cited once)",
+                "Donald E. Knuth (1984). Literate Programming. *The Computer Journal*, \
+                 27(2), 97–111. <https://doi.org/10.1093/comjnl/27.2.97> (cited 2 times)",
+            ]
+        );
+        assert_eq!(
+            format(&v[0], Markup::Html),
+            "O.-J. Dahl, E. W. Dijkstra and C. A. R. Hoare (1972). \
+             Structured Programming. Academic Press. (cited once)"
+        );
+        assert!(format(&v[1], Markup::Html).contains("<em>The Computer Journal</em>"));
+    }
+
+    #[test]
+    fn test_bibliography_order() {
+        let entry = |key: &str, author: &str, date: &str, title: &str| BibEntry {
+            key: key.to_string(),
+            author: author.to_string(),
+            date: date.to_string(),
+            title: title.to_string(),
+            ..BibEntry::empty()
+        };
+        let bib: BibMap = [
+            entry("k2", "Knuth, Donald", "1984", "Literate Programming"),
+            entry("k1", "Donald Knuth", "1984", "Computers and Typesetting"),
+            entry("k0", "Knuth, D.", "1968", "Fundamental Algorithms"),
+            entry("anon", "", "", "Anonymous"),
+        ]
+        .into_iter()
+        .map(|e| (e.key.clone(), e))
+        .collect();
+        // of the same author, by year, then title
+        let cites: Vec<Citation> = ["k2", "k1", "anon", "k0"]
+            .iter()
+            .map(|k| citation(k))
+            .collect();
+        let v = compute(&bib, &cites);
+        let keys: Vec<&str> = v.iter().map(|i| i.entry.key.as_str()).collect();
+        assert_eq!(keys, ["anon", "k0", "k1", "k2"]);
+        assert_eq!(
+            format(&v[0], Markup::Markdown),
+            "(n.d.). Anonymous. (cited once)"
+        );
+    }
+}
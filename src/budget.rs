@@ -0,0 +1,134 @@
+use rustc_hash::FxHashMap;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::{author_title, BibMap};
+
+// The number of distinct works cited against the maximum
+// number of references a venue allows, and the works that
+// are the candidates for trimming, as many as are over budget.
+pub struct Budget {
+    pub works: usize,
+    pub max: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+pub struct Candidate {
+    pub key: String,
+    pub count: u32,
+}
+
+impl Budget {
+    pub fn over(&self) -> usize {
+        self.works.saturating_sub(self.max)
+    }
+}
+
+// The least cited works are the candidates; of works cited equally
+// often, those cited first later in the document come first.
+pub fn compute(cites: &[Citation], max: usize) -> Budget {
+    let mut works: Vec<Candidate> = Vec::new();
+    let mut idx: FxHashMap<&str, usize> = FxHashMap::default();
+    for c in cites {
+        match idx.get(c.key.as_str()) {
+            Some(&i) => works[i].count += 1,
+            None => {
+                idx.insert(&c.key, works.len());
+                works.push(Candidate {
+                    key: c.key.clone(),
+                    count: 1,
+                });
+            }
+        }
+    }
+    let n = works.len();
+    works.reverse();
+    // stable, so ties keep the reversed order of first citation
+    works.sort_by_key(|w| w.count);
+    works.truncate(n.saturating_sub(max));
+    Budget {
+        works: n,
+        max,
+        candidates: works,
+    }
+}
+
+pub fn print_budget(b: &Budget, bib: &BibMap, f: Format) {
+    eprintln!(
+        "{} works cited, {} allowed, {} over budget",
+        b.works,
+        b.max,
+        b.over()
+    );
+    match f {
+        Format::Json(j) => budget_as_json(b, bib, j),
+        Format::Tsv => budget_as_tsv(b, bib),
+        Format::Markdown => budget_as_markdown(b, bib),
+    }
+}
+
+fn budget_as_tsv(b: &Budget, bib: &BibMap) {
+    for c in &b.candidates {
+        let (author, title) = author_title(&c.key, bib);
+        println!("\"{}\"\t\"{}\"\t\"{}\"\t{}", c.key, author, title, c.count);
+    }
+}
+
+fn budget_as_markdown(b: &Budget, bib: &BibMap) {
+    let rows = b.candidates.iter().map(|c| {
+        let (author, title) = author_title(&c.key, bib);
+        vec![
+            c.key.clone(),
+            author.to_string(),
+            title.to_string(),
+            c.count.to_string(),
+        ]
+    });
+    output::print_md_table(&["Key", "Author", "Title", "Count"], rows);
+}
+
+fn budget_as_json(b: &Budget, bib: &BibMap, json: Json) {
+    let records = b.candidates.iter().map(|c| {
+        let (author, title) = author_title(&c.key, bib);
+        json!({
+            "key": c.key,
+            "author": author,
+            "title": title,
+            "count": c.count
+        })
+    });
+    output::print_json(records, json, "budget");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+
+    #[test]
+    fn test_budget() {
+        let cites: Vec<Citation> = ["a", "b", "a", "c", "d", "c", "a"]
+            .iter()
+            .map(|k| citation(k))
+            .collect();
+        let b = compute(&cites, 2);
+        assert_eq!((b.works, b.over()), (4, 2));
+        let keys: Vec<&str> = b.candidates.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, vec!["d", "b"]);
+        assert!(compute(&cites, 4).candidates.is_empty());
+    }
+
+    #[test]
+    fn test_budget_ties() {
+        let b = compute(&[], 10);
+        assert_eq!((b.works, b.over()), (0, 0));
+        assert!(b.candidates.is_empty());
+        // of works cited equally often, the one cited first last goes first
+        let cites: Vec<Citation> = ["a", "b", "c"].iter().map(|k| citation(k)).collect();
+        let b = compute(&cites, 0);
+        let keys: Vec<&str> = b.candidates.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!((b.over(), keys), (3, vec!["c", "b", "a"]));
+    }
+}
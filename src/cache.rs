@@ -0,0 +1,267 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+use rustc_hash::FxHashSet;
+use serde_json::{json, Value};
+
+use crate::parser::{Cite, CiteKind, Section, TexItem};
+
+// The caches live in $XDG_CACHE_HOME/bibstats or ~/.cache/bibstats
+// or, without home directory, in the temporary directory.
+pub fn default_dir() -> PathBuf {
+    match env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        Some(d) => PathBuf::from(d).join("bibstats"),
+        None => match env::var_os("HOME") {
+            Some(h) => PathBuf::from(h).join(".cache").join("bibstats"),
+            None => env::temp_dir().join("bibstats"),
+        },
+    }
+}
+
+// FNV-1a, which, unlike the hashers of std, is stable
+// across versions, so the cache survives updates.
+pub fn fnv1a(s: &str) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in s.bytes() {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+// The version of the scanner; items cached by another version
+// may lack what this one finds, so the documents are scanned again.
+const SCANNER: u64 = 7;
+
+// The items found in documents, kept from earlier runs.
+// Documents that git reports as changed are always scanned again;
+// so are documents whose size or modification time differ from
+// what was cached, for instance after switching branches,
+// and documents scanned with other aliases of citation commands
+// defined before them, which the scanner resolves.
+// The cache only saves time, so failures to write it are ignored.
+pub struct ScanCache {
+    dir: PathBuf,
+    changed: FxHashSet<PathBuf>,
+}
+
+impl ScanCache {
+    // The paths of changed files must be canonical.
+    pub fn new(dir: PathBuf, changed: FxHashSet<PathBuf>) -> ScanCache {
+        ScanCache { dir, changed }
+    }
+
+    pub fn get(&self, canon: &Path, aliases: &[(String, String)]) -> Option<Vec<TexItem>> {
+        if self.changed.contains(canon) {
+            return None;
+        }
+        let name = canon.to_string_lossy();
+        let text = fs::read_to_string(self.file(&name)).ok()?;
+        let js: Value = serde_json::from_str(&text).ok()?;
+        if js["scanner"].as_u64() != Some(SCANNER)
+            || js["path"].as_str()? != name
+            || js["stamp"] != stamp(canon)?
+            || js["aliases"] != json!(aliases)
+        {
+            return None;
+        }
+        js["items"].as_array()?.iter().map(item_from_json).collect()
+    }
+
+    pub fn put(&self, canon: &Path, aliases: &[(String, String)], items: &[TexItem]) {
+        let Some(stamp) = stamp(canon) else {
+            return;
+        };
+        let name = canon.to_string_lossy();
+        let items: Vec<Value> = items.iter().map(item_to_json).collect();
+        let js = json!({
+            "scanner": SCANNER,
+            "path": name,
+            "stamp": stamp,
+            "aliases": aliases,
+            "items": items
+        });
+        let _ =
+            fs::create_dir_all(&self.dir).and_then(|_| fs::write(self.file(&name), js.to_string()));
+    }
+
+    fn file(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(name)))
+    }
+}
+
+// size and modification time in nanoseconds
+fn stamp(path: &Path) -> Option<Value> {
+    let meta = fs::metadata(path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(json!([meta.len(), mtime.as_nanos() as u64]))
+}
+
+fn item_to_json(item: &TexItem) -> Value {
+    match item {
+        TexItem::Cite(c) => json!({
+            "cite": c.key,
+            "line": c.line,
+            "column": c.column,
+            "prenote": c.prenote,
+            "postnote": c.postnote,
+            "quoted": c.quoted,
+            "kind": c.kind.name()
+        }),
+        TexItem::Input(f) => json!({ "input": f }),
+        TexItem::Bib(f) => json!({ "bib": f }),
+        TexItem::Alias(name, target) => json!({ "alias": name, "target": target }),
+        TexItem::Malformed(name, line, column) => json!({
+            "malformed": name,
+            "line": line,
+            "column": column
+        }),
+        TexItem::Section(s) => json!({
+            "section": s.title,
+            "level": s.level,
+            "starred": s.starred
+        }),
+    }
+}
+
+fn item_from_json(js: &Value) -> Option<TexItem> {
+    if let Some(key) = js["cite"].as_str() {
+        return Some(TexItem::Cite(Cite {
+            key: key.to_string(),
+            line: js["line"].as_u64()?,
+            column: js["column"].as_u64()?,
+            prenote: js["prenote"].as_str().map(String::from),
+            postnote: js["postnote"].as_str().map(String::from),
+            quoted: js["quoted"].as_bool()?,
+            kind: CiteKind::from_name(js["kind"].as_str()?)?,
+            span: None,
+        }));
+    }
+    if let Some(f) = js["input"].as_str() {
+        return Some(TexItem::Input(f.to_string()));
+    }
+    if let Some(name) = js["alias"].as_str() {
+        return Some(TexItem::Alias(
+            name.to_string(),
+            js["target"].as_str()?.to_string(),
+        ));
+    }
+    if let Some(name) = js["malformed"].as_str() {
+        return Some(TexItem::Malformed(
+            name.to_string(),
+            js["line"].as_u64()?,
+            js["column"].as_u64()?,
+        ));
+    }
+    if let Some(f) = js["bib"].as_str() {
+        return Some(TexItem::Bib(f.to_string()));
+    }
+    Some(TexItem::Section(Section {
+        level: js["level"].as_u64()? as usize,
+        starred: js["starred"].as_bool()?,
+        title: js["section"].as_str()?.to_string(),
+    }))
+}
+
+// The files changed in the working tree of the git repository
+// of the current directory, including untracked files, and,
+// with since, the files changed since that revision.
+// The paths are canonical; deleted files are left out.
+pub fn changed_files(since: Option<&str>) -> Result<FxHashSet<PathBuf>, String> {
+    let top = git(&["rev-parse", "--show-toplevel"])?;
+    let top = PathBuf::from(top.trim_end_matches('\n'));
+
+    let mut names = Vec::new();
+    let status = git(&["status", "--porcelain=v1", "-z", "--untracked-files=all"])?;
+    let mut records = status.split('\0');
+    while let Some(r) = records.next() {
+        if r.len() < 4 {
+            continue;
+        }
+        names.push(r[3..].to_string());
+        // renames and copies are followed by the old name
+        if r.starts_with(['R', 'C']) {
+            records.next();
+        }
+    }
+    if let Some(rev) = since {
+        let diff = git(&["diff", "--name-only", "-z", rev])?;
+        names.extend(diff.split('\0').filter(|n| !n.is_empty()).map(String::from));
+    }
+
+    Ok(names
+        .iter()
+        .filter_map(|n| top.join(n).canonicalize().ok())
+        .collect())
+}
+
+fn git(args: &[&str]) -> Result<String, String> {
+    let out = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| format!("cannot run git: {}", e))?;
+    if !out.status.success() {
+        return Err(format!(
+            "git {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scan_cache() {
+        let dir = env::temp_dir().join(format!("bibstats-scan-{}", std::process::id()));
+        let doc = dir.join("doc.tex");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&doc, "\\cite{a}").unwrap();
+        let doc = doc.canonicalize().unwrap();
+        let items = vec![
+            TexItem::Section(Section {
+                level: 2,
+                starred: false,
+                title: "Intro".to_string(),
+            }),
+            TexItem::Cite(Cite {
+                key: "a".to_string(),
+                line: 1,
+                column: 1,
+                prenote: Some("see".to_string()),
+                postnote: Some("p. 5".to_string()),
+                quoted: true,
+                kind: CiteKind::Footnote,
+                span: None,
+            }),
+            TexItem::Input("ch1".to_string()),
+            TexItem::Bib("refs".to_string()),
+            TexItem::Alias("mycite".to_string(), "autocite".to_string()),
+            TexItem::Malformed("cite".to_string(), 3, 7),
+        ];
+        let aliases = vec![("tc".to_string(), "textcite".to_string())];
+
+        let cache = ScanCache::new(dir.join("scan"), FxHashSet::default());
+        assert!(cache.get(&doc, &aliases).is_none());
+        cache.put(&doc, &aliases, &items);
+        assert_eq!(cache.get(&doc, &aliases), Some(items));
+
+        // other aliases may change what the scanner finds
+        assert!(cache.get(&doc, &[]).is_none());
+
+        // changed files are scanned again
+        let changed = ScanCache::new(dir.join("scan"), [doc.clone()].into_iter().collect());
+        assert!(changed.get(&doc, &aliases).is_none());
+
+        // and so are files modified since
+        fs::write(&doc, "\\cite{a,b}").unwrap();
+        assert!(cache.get(&doc, &aliases).is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
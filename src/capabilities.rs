@@ -0,0 +1,82 @@
+use serde_json::{json, Value};
+
+use crate::diagnostics;
+use crate::lint;
+
+// Describes what this build can do, for tools that wrap bibstats.
+// The schema is versioned like the JSON output; fields are only added.
+pub fn describe() -> Value {
+    let rules: Vec<Value> = diagnostics::CATALOG
+        .iter()
+        .map(|d| {
+            let rule = lint::RULES.iter().find(|r| r.code == d.code);
+            let styles: Vec<&str> = rule.map_or(Vec::new(), |r| {
+                r.styles
+                    .iter()
+                    .map(|s| match s {
+                        lint::Style::Numeric => "numeric",
+                        lint::Style::AuthorYear => "author-year",
+                    })
+                    .collect()
+            });
+            json!({
+                "code": d.code,
+                "name": d.name,
+                "summary": d.summary,
+                "lint": rule.is_some(),
+                "styles": styles
+            })
+        })
+        .collect();
+    json!({
+        "version": 1,
+        "bibstats": env!("CARGO_PKG_VERSION"),
+        "inputs": {
+            "bib": ["bibtex", "jabref-groups", "hayagriva"],
+            "documents": ["latex", "markdown", "typst", "aux", "bcf", "stdin"],
+            "other": ["weights", "corpus", "baseline", "affiliations"]
+        },
+        "scanners": [
+            "cite", "input", "include", "section", "quote", "newcommand", "markdown",
+            "typst"
+        ],
+        "outputs": {
+            "formats": ["json", "jsonarray", "tsv", "markdown"],
+            "json_versions": [1, 2],
+            "reports": [
+                "stats",
+                "timeline",
+                "backref",
+                "where",
+                "drafts",
+                "heatmap",
+                "budget",
+                "page-estimate",
+                "quotes",
+                "quotations",
+                "cited-pages",
+                "cite-kinds",
+                "completeness",
+                "lint",
+                "corpus",
+                "compare",
+                "overlap",
+                "bibliography",
+                "preprints",
+                "affiliations",
+                "jabref-groups"
+            ],
+            "files": ["annotate-bib", "baseline"],
+            "events": ["file-started", "citation-found", "warning", "file-done"]
+        },
+        "features": {
+            "net": cfg!(feature = "net")
+        },
+        "network": if cfg!(feature = "net") {
+            vec!["crossref", "unpaywall"]
+        } else {
+            Vec::new()
+        },
+        "diagnostics": rules
+    })
+}
@@ -2,18 +2,78 @@ use std::ffi::OsString;
 
 use once_cell::sync::Lazy;
 
-pub static PARSED_COMMANDS: Lazy<Args> = Lazy::new(argh::from_env);
+use crate::bibliography::Markup;
+use crate::docs::Syntax;
+use crate::keymatch::KeyMatch;
+use crate::lint::{Recency, Style};
+use crate::merge::Policy;
+use crate::output::Version;
+use crate::parser::FieldPolicy;
+
+pub static PARSED_COMMANDS: Lazy<Args> = Lazy::new(|| {
+    let mut args: Args = argh::from_env();
+    if !args.no_config {
+        if let Err(e) = args.apply_env(|var| std::env::var_os(var)) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    args
+});
 
 /// The program generates quotation stats for a tex project,
 /// with one bib file and a set of input files. If no input files
 /// are given, the input is read from stdin.
+/// Options not given on the command line may be set in the environment:
+/// BIBSTATS_BIB, BIBSTATS_BIB_INCLUDE, BIBSTATS_DIRS and
+/// BIBSTATS_SEARCH_PATH (lists separated like PATH), BIBSTATS_EXT and
+/// BIBSTATS_CITE_CMD (separated by commas), BIBSTATS_FORMAT (json,
+/// jsonarray, tsv or markdown), BIBSTATS_OUTPUT_VERSION, BIBSTATS_STYLE and
+/// BIBSTATS_CACHE_DIR. The command line wins over the environment,
+/// which wins over the defaults.
 #[derive(argh::FromArgs, PartialEq, Debug)]
 pub struct Args {
     /// indicate the bib file used for all files to process.
-    /// If bib is not given, the program proceeds with the first bib file if finds
-    /// in the current directory. If there is none the program exits with error
+    /// If bib is not given, the program proceeds with the bib files named
+    /// in the documents with \bibliography or \addbibresource or, if there
+    /// are none, with the first bib file it finds in the current directory.
+    /// If there is none the program exits with error
     #[argh(option, short = 'b')]
     pub bib: Option<OsString>,
+    /// additional bib files that are loaded together with the bib file.
+    /// A bib file may also include other bib files with \input{file}
+    /// between entries; such paths are resolved relative to the directory
+    /// of the including master file
+    #[argh(option)]
+    pub bib_include: Vec<OsString>,
+    /// which author to use for a citekey defined more than once
+    /// with different authors: prefer-first, prefer-longest or
+    /// prefer-newest-file (of the bib file). Default: prefer-first
+    #[argh(option, default = "Policy::First")]
+    pub merge_author: Policy,
+    /// which title to use for a citekey defined more than once
+    /// with different titles, like --merge-author. Default: prefer-first
+    #[argh(option, default = "Policy::First")]
+    pub merge_title: Policy,
+    /// write the conflicting authors and titles of citekeys
+    /// defined more than once into this file instead of
+    /// reporting them as warnings
+    #[argh(option)]
+    pub merge_log: Option<OsString>,
+    /// skip malformed entries in the bib files, reporting
+    /// each with its position, instead of failing;
+    /// parsing goes on with the next line starting with '@'
+    #[argh(switch)]
+    pub lenient: bool,
+    /// what to do with a field given more than once in an entry:
+    /// error, first-wins (like BibTeX) or last-wins (like biber);
+    /// the last two report a warning. Default: error
+    #[argh(option, default = "FieldPolicy::Error")]
+    pub duplicate_fields: FieldPolicy,
+    /// keep the braces in the values of the bib files, like those
+    /// protecting the case of {DNA} in titles, instead of dropping them
+    #[argh(switch)]
+    pub keep_braces: bool,
     /// a list of directories that are searched for tex files to examine.
     /// All files with extensions given in 'ext' will be considered.
     /// If no files and no directories are given,
@@ -22,42 +82,684 @@ pub struct Args {
     pub dirs: Vec<OsString>,
     /// a list of extensions to be considered together with the dir option.
     /// If no dir option is given, ext is ignored.
-    /// Default: tex
+    /// Default: tex or, with --syntax markdown, md and markdown
+    /// and, with --syntax typst, typ and, with --from-aux or --from-bcf,
+    /// aux or bcf
     #[argh(option, short = 'e')]
     pub ext: Vec<OsString>,
+    /// the syntax of the documents: tex, markdown, which is Pandoc's
+    /// Markdown with citations like [see @key, p. 3] and @key, or typst,
+    /// with citations like @key[p. 3] and #cite(<key>).
+    /// Default: markdown for files ending in .md or .markdown,
+    /// typst for files ending in .typ, tex for all others and for stdin
+    #[argh(option)]
+    pub syntax: Option<Syntax>,
+    /// read the citations from the aux files a LaTeX run writes next to
+    /// the root documents, like paper.aux for paper.tex, instead of
+    /// scanning the sources: LaTeX has expanded all macros, but the
+    /// aux files know nothing of notes, quotations and sections
+    #[argh(switch)]
+    pub from_aux: bool,
+    /// read the citations and the bib files from the control files
+    /// biblatex writes for biber next to the root documents, like
+    /// paper.bcf for paper.tex, instead of scanning the sources; these
+    /// are exactly the keys biber sees, but without notes, quotations
+    /// and sections
+    #[argh(switch)]
+    pub from_bcf: bool,
+    /// the name, without backslash, of a command to count as citation
+    /// command in addition to \cite and its friends, like wrappers
+    /// defined in packages. Commands a document defines as nothing but
+    /// a citation command, like \newcommand{\mycite}[1]{\autocite{#1}},
+    /// are found without it; like in LaTeX, the case matters; may be
+    /// repeated
+    #[argh(option)]
+    pub cite_cmd: Vec<String>,
+    /// the name of an environment whose content is not scanned for
+    /// citations, like the comment environment, which is always skipped,
+    /// and the verbatim environments; may be repeated
+    #[argh(option)]
+    pub skip_env: Vec<String>,
+    /// how cited keys are matched to the keys of the bib files:
+    /// exact (like BibTeX), case-insensitive, nfc or nfkc (the same
+    /// after Unicode normalization) or prefix (the cited key is
+    /// the start of one key). Keys matched inexactly are reported.
+    /// Default: exact
+    #[argh(option, default = "KeyMatch::Exact")]
+    pub key_match: KeyMatch,
     /// a list of files to be examined. It can be combined with dirs,
     /// in that case, all files found in the directories plus these files
     /// are considered. If no files and no directories are given,
     /// input is expected from stdin
     #[argh(option, short = 'f')]
     pub files: Vec<OsString>,
+    /// directories searched for files included with \input or \include
+    /// and for bib files, after the directory of the root document.
+    /// A directory ending in '//' is searched recursively.
+    /// The directories in TEXINPUTS (for tex files) and BIBINPUTS
+    /// (for bib files) are searched after these
+    #[argh(option)]
+    pub search_path: Vec<OsString>,
+    /// ask kpsewhich for files that are not found otherwise,
+    /// so files are found exactly where LaTeX would find them
+    #[argh(switch)]
+    pub use_kpsewhich: bool,
+    /// only scan the documents git reports as changed in the working
+    /// tree and take the citations of the other documents from the
+    /// cache of earlier runs with --changed; documents that are
+    /// not cached or were modified since are scanned anyway
+    #[argh(switch)]
+    pub changed: bool,
+    /// like --changed, but also scan the documents changed
+    /// since the given git revision
+    #[argh(option)]
+    pub since: Option<String>,
     /// produce output as JSON, this is the default
     #[argh(switch, short = 'j')]
     pub json: bool,
     /// produce output as tab-separated values, default is JSON
     #[argh(switch, short = 't')]
     pub tsv: bool,
+    /// produce output as Markdown, default is JSON
+    #[argh(switch, short = 'm')]
+    pub markdown: bool,
     /// if the output is produced as JSON,
     /// create a JSON array, instead of a stream of single JSON objects.
     /// Default is to create a stream of JSON objects
     #[argh(switch, short = 'a')]
     pub jsonarray: bool,
+    /// the version of the JSON output: 1, a stream or an array of
+    /// flat records, or 2, one object with the version, the report
+    /// and the records. Version 1 stays the default
+    /// until the next major release. Default: 1
+    #[argh(option)]
+    pub output_version: Option<Version>,
+    /// add the bib entries of every work to the output:
+    /// their citekey, type, date and the bib file and line
+    /// where they are defined and, in JSON, all other fields
+    #[argh(switch)]
+    pub full: bool,
+    /// count \nocite{*}, which adds all entries to the bibliography,
+    /// as a citation of every entry in the bib files; without it,
+    /// \nocite{*} is ignored. \nocite with keys is always counted
+    #[argh(switch)]
+    pub nocite_all: bool,
+    /// count citations for the author field as a whole
+    /// instead of for each of the authors it lists
+    #[argh(switch)]
+    pub whole_authors: bool,
+    /// count works without author under their author field, which is
+    /// empty, instead of under their editors marked with "(ed.)"
+    #[argh(switch)]
+    pub no_editor_fallback: bool,
+    /// count only citations of entries with this keyword
+    /// in their keywords field, in any case; may be repeated
+    /// to count entries with any of the keywords
+    #[argh(option)]
+    pub keyword: Vec<String>,
+    /// write the bib files into this file, with a field added
+    /// to every cited entry that says how often it is cited
+    #[argh(option)]
+    pub annotate_bib: Option<OsString>,
+    /// the field added by annotate-bib. Default: annotation
+    #[argh(option, default = "String::from(\"annotation\")")]
+    pub annotate_field: String,
+    /// a file assigning weights to documents, one document or
+    /// directory and its weight per line, e.g. 'appendix/ 0.3'.
+    /// Citations count with the weight of their document;
+    /// the weighted counts are added to the stats
+    #[argh(option)]
+    pub weights: Option<OsString>,
+    /// a corpus file listing dated projects, one per line with
+    /// the date and the root document, e.g. '2019 thesis/main.tex'.
+    /// Instead of the stats, every cited work is listed with the dates
+    /// of the first and the last project citing it
+    #[argh(option)]
+    pub corpus: Option<OsString>,
+    /// instead of the stats, list the works in the order in which
+    /// they are first cited: with the file, line and section of the
+    /// first citation, the number of citations and the position
+    /// of the first citation among all citations in percent
+    #[argh(switch)]
+    pub timeline: bool,
+    /// instead of the stats, list for every cited work
+    /// all places where it is cited, in document order
+    #[argh(switch)]
+    pub backref: bool,
+    /// instead of the stats, list for every citekey where it is
+    /// cited as file:line:column, so editors can jump to it; with
+    /// --tsv one line per place
+    #[argh(switch, long = "where")]
+    pub where_cited: bool,
+    /// instead of the stats, list the cited works that are not
+    /// published yet: @unpublished entries and entries with
+    /// pubstate inpreparation or submitted, with the places
+    /// where they are cited, to check them against the venue's policy
+    #[argh(switch)]
+    pub drafts: bool,
+    /// instead of the stats, count the citations per section
+    /// and decade of publication, as data for a heatmap of how old
+    /// the works cited in each section are
+    #[argh(switch)]
+    pub heatmap: bool,
+    /// instead of the stats, compare the number of distinct works
+    /// cited with this maximum number of references, as some venues
+    /// have, and list the least cited works as candidates for trimming.
+    /// The program exits with 1 if there are more works
+    #[argh(option)]
+    pub max_refs: Option<usize>,
+    /// instead of the stats, count the citations per country of the
+    /// authors, who are mapped to their institution and country in this
+    /// CSV file with the columns author, institution and country
+    #[argh(option)]
+    pub affiliations: Option<OsString>,
+    /// with --affiliations, count the citations per institution
+    /// instead of per country
+    #[argh(switch)]
+    pub per_institution: bool,
+    /// instead of the stats, print the works cited as a bibliography
+    /// in a simple author-year style, with the number of citations
+    /// after every work, as 'markdown' or 'html'
+    #[argh(option)]
+    pub bibliography: Option<Markup>,
+    /// instead of the stats, classify the works cited as preprints,
+    /// like those on arXiv or bioRxiv, peer-reviewed articles and papers
+    /// and other works and report the share of preprints; with --crossref,
+    /// preprints with DOI are looked up for their published versions
+    #[argh(switch)]
+    pub preprints: bool,
+    /// instead of the stats, compare the works cited with the entries
+    /// of this reference bib file, like a reading list or the bibliography
+    /// of a survey: how many of the works cited are in it (precision),
+    /// how many of its works are cited (recall) and which are missing.
+    /// Works with the same DOI or normalized title are the same work
+    #[argh(option)]
+    pub reference: Option<OsString>,
+    /// instead of the stats, estimate how many pages the bibliography
+    /// of the cited works takes, from the lines per entry of the style
+    /// given with --style and the lines per page
+    #[argh(switch)]
+    pub page_estimate: bool,
+    /// the average lines per entry for --page-estimate, including
+    /// the space between entries. Default: 2.5 for numeric styles,
+    /// 3 for author-year styles, 2.75 without style
+    #[argh(option)]
+    pub lines_per_entry: Option<f64>,
+    /// the lines per page for --page-estimate. Default: 50
+    #[argh(option)]
+    pub lines_per_page: Option<u32>,
+    /// instead of the stats, list the citations that seem to give
+    /// the source of a direct quote, since they follow closing
+    /// quotation marks or stand in \enquote, but have no page
+    /// in their postnote
+    #[argh(switch)]
+    pub quotes: bool,
+    /// instead of the stats, list for every cited work how often
+    /// it is cited as the source of a quotation, in or just after
+    /// \enquote, quotation marks or a quote environment, and how
+    /// often it is referenced otherwise; most quoted works first
+    #[argh(switch)]
+    pub quotations: bool,
+    /// instead of the stats, list for every cited work the pages
+    /// cited in postnotes, like \cite[p.~12]{key}, how often they are
+    /// cited and how often as the source of a quotation
+    #[argh(switch)]
+    pub cited_pages: bool,
+    /// instead of the stats, count for every chapter (or section,
+    /// in documents without chapters) the narrative citations, like
+    /// \textcite and \citet, the parenthetical ones, like \parencite
+    /// and \citep, the footnotes, like \footcite, plain \cite and \nocite
+    #[argh(switch)]
+    pub cite_kinds: bool,
+    /// instead of the stats, list the groups JabRef keeps in the bib file
+    /// with the number of cited entries in every group and how often
+    /// they are cited. Static groups, keyword groups without regular
+    /// expressions and the hierarchical context of groups are supported
+    #[argh(switch)]
+    pub jabref_groups: bool,
+    /// instead of the stats, check the cited entries against
+    /// the lint rules and list the findings.
+    /// The program exits with 1 if there are findings
+    #[argh(switch)]
+    pub lint: bool,
+    /// the family of the bibliography style, 'numeric' or 'author-year';
+    /// it selects the lint rules that apply in addition to the rules
+    /// for all styles
+    #[argh(option)]
+    pub style: Option<Style>,
+    /// run only this lint rule, given by code or name,
+    /// whatever the style; may be repeated
+    #[argh(option)]
+    pub rule: Vec<String>,
+    /// do not run this lint rule, given by code or name; may be repeated
+    #[argh(option)]
+    pub skip_rule: Vec<String>,
+    /// the minimum share of recent works a chapter must cite, checked
+    /// by lint, as unit:percent:years, like related-work:30:5 for at
+    /// least 30% of the citations in the chapter Related Work from the
+    /// last 5 years; may be repeated
+    #[argh(option)]
+    pub recency: Vec<Recency>,
+    /// instead of the stats, merge entries that are the same work,
+    /// as the duplicate-doi lint finds them: the entry cited first is
+    /// kept, or, if none is cited, the first in the bib files; the
+    /// citations of the others are rewritten to its key and their
+    /// entries are removed from their bib files
+    #[argh(switch)]
+    pub apply_merge: bool,
+    /// instead of the stats, replace cited keys that are not in the
+    /// bib files by the one key close to them, as suggested by the
+    /// missing-key warnings; keys close to more than one key are left
+    #[argh(switch)]
+    pub fix_keys: bool,
+    /// look up cited works with DOI at CrossRef, e.g. to find
+    /// retracted works with lint or published preprints with --preprints
+    #[argh(switch)]
+    pub crossref: bool,
+    /// instead of the stats, look up the cited works with DOI
+    /// at Unpaywall, which asks for this email address, and list
+    /// whether they have an open-access version and where
+    #[argh(option)]
+    pub unpaywall: Option<String>,
+    /// do not access the net, use only answers cached
+    /// by earlier runs, however old they are
+    #[argh(switch)]
+    pub offline: bool,
+    /// the directory for cached answers from the net and, in
+    /// the subdirectory scan, for documents scanned with --changed;
+    /// it may also
+    /// be given with the environment variable BIBSTATS_CACHE_DIR.
+    /// Default: $XDG_CACHE_HOME/bibstats, ~/.cache/bibstats or,
+    /// without home directory, bibstats in the temporary directory
+    #[argh(option)]
+    pub cache_dir: Option<OsString>,
+    /// ignore the BIBSTATS_* environment variables,
+    /// so that only the command line counts
+    #[argh(switch)]
+    pub no_config: bool,
+    /// a PEM file with the CA certificates to trust for HTTPS
+    /// instead of the built-in ones, e.g. the certificate of a
+    /// corporate proxy. The proxy itself is taken from
+    /// HTTPS_PROXY, HTTP_PROXY or ALL_PROXY, except for hosts in NO_PROXY
+    #[argh(option)]
+    pub ca_cert: Option<OsString>,
+    /// the maximum number of requests to the net at the same time,
+    /// for all services. Default: 2
+    #[argh(option, default = "2")]
+    pub net_concurrency: usize,
+    /// the maximum number of requests to the net per second,
+    /// for all services; 0 means no limit. Default: 5
+    #[argh(option, default = "5.0")]
+    pub net_rate: f64,
+    /// a baseline file for lint. If it does not exist, the findings
+    /// are recorded in it and not reported; otherwise the findings
+    /// recorded in it are not reported, so only new findings fail
+    #[argh(option)]
+    pub baseline: Option<OsString>,
+    /// record the current findings in the baseline file,
+    /// even if it exists
+    #[argh(switch)]
+    pub update_baseline: bool,
+    /// instead of the stats, score the metadata of every cited entry:
+    /// author, year, venue, DOI or URL and, for articles and
+    /// contributions to collections and proceedings, pages.
+    /// Entries scoring below min-score are listed, the weakest first
+    #[argh(switch)]
+    pub completeness: bool,
+    /// the minimum completeness score in percent. Default: 100
+    #[argh(option, default = "100")]
+    pub min_score: u32,
     /// prints the current version and exits
     #[argh(switch, short = 'v')]
     pub version: bool,
+    /// prints a JSON description of the inputs, scanners, outputs
+    /// and diagnostics this build supports and exits
+    #[argh(switch)]
+    pub capabilities: bool,
+    /// report progress as events, one JSON object per line on stderr:
+    /// file-started, citation-found, warning and file-done;
+    /// warnings are then only reported as events
+    #[argh(switch)]
+    pub events: bool,
+    /// print the bib files given with -b and --bib-include, or else
+    /// where they come from, the documents that would be read,
+    /// following \input and \include, and the report and format
+    /// that would be printed, but neither read the bib files
+    /// nor count citations; with --apply-merge and --fix-keys, print
+    /// the changes as a diff instead of writing them
+    #[argh(switch)]
+    pub dry_run: bool,
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+pub enum Command {
+    Explain(Explain),
+    Validate(Validate),
+    Hook(Hook),
+    Migrate(Migrate),
+    Compare(Compare),
+    Show(Show),
+}
+
+/// print the description of a diagnostic and how to fix it
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "explain")]
+pub struct Explain {
+    /// the code (e.g. B001) or the name (e.g. duplicate-key)
+    /// of the diagnostic; without it, all diagnostics are listed
+    #[argh(positional)]
+    pub code: Option<String>,
+}
+
+/// parse the bib files and the documents and check that every
+/// citekey is defined exactly once; prints a one-line summary
+/// and exits with 1 if there are problems. Options for the bib files
+/// and documents go before the subcommand: bibstats -b refs.bib -f main.tex validate
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "validate")]
+pub struct Validate {
+    /// tex files to check in addition to the files given
+    /// before the subcommand; bib files are skipped, so that
+    /// the names of all changed files may be passed
+    #[argh(positional)]
+    pub files: Vec<OsString>,
+}
+
+/// manage the git pre-commit hook that validates staged tex and bib files
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "hook")]
+pub struct Hook {
+    #[argh(subcommand)]
+    pub action: HookAction,
+}
+
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+pub enum HookAction {
+    Install(HookInstall),
+}
+
+/// install the pre-commit hook in the git repository of the current
+/// directory. The bib options given before the subcommand are passed
+/// on to validate: bibstats -b refs.bib hook install
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "install")]
+pub struct HookInstall {
+    /// replace an existing pre-commit hook
+    #[argh(switch)]
+    pub force: bool,
+    /// instead of installing the hook, print the configuration
+    /// of a local hook for the pre-commit framework
+    #[argh(switch)]
+    pub yaml: bool,
+}
+
+/// rewrite the natbib citation commands of the documents to their
+/// biblatex equivalents, like \citet to \textcite; commands without
+/// equivalent are reported. The documents are given before the
+/// subcommand: bibstats -f main.tex migrate --dry-run
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "migrate")]
+pub struct Migrate {
+    /// print the changes as a diff instead of writing them
+    #[argh(switch)]
+    pub dry_run: bool,
+}
+
+/// compare the works cited by two projects, like a thesis and one
+/// of its papers: the works cited in both, only in the first and only
+/// in the second. Works with the same DOI or the same normalized title
+/// are the same work. A project is a directory, whose tex and bib files
+/// are read, or a root document, whose directory has the bib files:
+/// bibstats compare thesis/ papers/nlp/
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "compare")]
+pub struct Compare {
+    /// the first project
+    #[argh(positional)]
+    pub a: OsString,
+    /// the second project
+    #[argh(positional)]
+    pub b: OsString,
+}
+
+/// print the bib entry with the citekey as it is written in its
+/// bib file, for scripts and editors. Options for the bib files
+/// go before the subcommand: bibstats -b refs.bib show knuth84
+#[derive(argh::FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "show")]
+pub struct Show {
+    /// the citekey of the entry
+    #[argh(positional)]
+    pub key: String,
+    /// print the entry as bibstats reads it instead, one field
+    /// per line, with the fields inherited with crossref
+    #[argh(switch)]
+    pub formatted: bool,
+}
+
+impl Args {
+    // Fills in the options not given on the command line
+    // from the BIBSTATS_* variables; empty variables are ignored.
+    fn apply_env<F: Fn(&str) -> Option<OsString>>(&mut self, get: F) -> Result<(), String> {
+        let var = |name: &str| get(name).filter(|v| !v.is_empty());
+        let paths = |name: &str| -> Vec<OsString> {
+            var(name).map_or(Vec::new(), |v| {
+                std::env::split_paths(&v)
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .map(|p| p.into_os_string())
+                    .collect()
+            })
+        };
+        let parse = |name: &str| -> Result<Option<String>, String> {
+            match var(name) {
+                Some(v) => match v.into_string() {
+                    Ok(s) => Ok(Some(s)),
+                    Err(_) => Err(format!("{} is not valid UTF-8", name)),
+                },
+                None => Ok(None),
+            }
+        };
+
+        if self.bib.is_none() {
+            self.bib = var("BIBSTATS_BIB");
+        }
+        if self.bib_include.is_empty() {
+            self.bib_include = paths("BIBSTATS_BIB_INCLUDE");
+        }
+        if self.dirs.is_empty() {
+            self.dirs = paths("BIBSTATS_DIRS");
+        }
+        if self.search_path.is_empty() {
+            self.search_path = paths("BIBSTATS_SEARCH_PATH");
+        }
+        if self.ext.is_empty() {
+            if let Some(ext) = parse("BIBSTATS_EXT")? {
+                self.ext = ext
+                    .split(',')
+                    .map(|e| e.trim())
+                    .filter(|e| !e.is_empty())
+                    .map(OsString::from)
+                    .collect();
+            }
+        }
+        if self.cite_cmd.is_empty() {
+            if let Some(cmds) = parse("BIBSTATS_CITE_CMD")? {
+                self.cite_cmd = cmds
+                    .split(',')
+                    .map(|c| c.trim().trim_start_matches('\\'))
+                    .filter(|c| !c.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
+        }
+        if !(self.json || self.jsonarray || self.tsv || self.markdown) {
+            match parse("BIBSTATS_FORMAT")?.as_deref() {
+                None | Some("json") => (),
+                Some("jsonarray") => self.jsonarray = true,
+                Some("tsv") => self.tsv = true,
+                Some("markdown") => self.markdown = true,
+                Some(f) => {
+                    return Err(format!(
+                        "BIBSTATS_FORMAT: unknown format '{}', \
+                         'json', 'jsonarray', 'tsv' or 'markdown' expected",
+                        f
+                    ))
+                }
+            }
+        }
+        if self.output_version.is_none() {
+            if let Some(v) = parse("BIBSTATS_OUTPUT_VERSION")? {
+                self.output_version = Some(
+                    v.parse()
+                        .map_err(|e| format!("BIBSTATS_OUTPUT_VERSION: {}", e))?,
+                );
+            }
+        }
+        if self.style.is_none() {
+            if let Some(s) = parse("BIBSTATS_STYLE")? {
+                self.style = Some(s.parse().map_err(|e| format!("BIBSTATS_STYLE: {}", e))?);
+            }
+        }
+        if self.cache_dir.is_none() {
+            self.cache_dir = var("BIBSTATS_CACHE_DIR");
+        }
+        Ok(())
+    }
 }
 
 impl Default for Args {
     fn default() -> Args {
         Args {
             bib: None,
+            bib_include: Vec::default(),
+            merge_author: Policy::First,
+            merge_title: Policy::First,
+            merge_log: None,
+            lenient: false,
+            duplicate_fields: FieldPolicy::Error,
+            keep_braces: false,
             dirs: Vec::default(),
             ext: vec!["tex".into()],
+            syntax: None,
+            from_aux: false,
+            from_bcf: false,
+            cite_cmd: Vec::default(),
+            skip_env: Vec::default(),
+            key_match: KeyMatch::Exact,
             files: Vec::default(),
+            search_path: Vec::default(),
+            use_kpsewhich: false,
+            changed: false,
+            since: None,
             json: true,
             tsv: false,
+            markdown: false,
             jsonarray: false,
+            output_version: None,
+            full: false,
+            nocite_all: false,
+            whole_authors: false,
+            no_editor_fallback: false,
+            keyword: Vec::default(),
+            annotate_bib: None,
+            annotate_field: String::from("annotation"),
+            weights: None,
+            corpus: None,
+            timeline: false,
+            backref: false,
+            where_cited: false,
+            drafts: false,
+            heatmap: false,
+            max_refs: None,
+            affiliations: None,
+            per_institution: false,
+            bibliography: None,
+            preprints: false,
+            reference: None,
+            page_estimate: false,
+            lines_per_entry: None,
+            lines_per_page: None,
+            quotes: false,
+            quotations: false,
+            cited_pages: false,
+            cite_kinds: false,
+            jabref_groups: false,
+            lint: false,
+            style: None,
+            rule: Vec::default(),
+            skip_rule: Vec::default(),
+            recency: Vec::default(),
+            apply_merge: false,
+            fix_keys: false,
+            crossref: false,
+            unpaywall: None,
+            offline: false,
+            cache_dir: None,
+            no_config: false,
+            ca_cert: None,
+            net_concurrency: 2,
+            net_rate: 5.0,
+            baseline: None,
+            update_baseline: false,
+            completeness: false,
+            min_score: 100,
             version: false,
+            capabilities: false,
+            events: false,
+            dry_run: false,
+            command: None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_env() {
+        let env = |var: &str| match var {
+            "BIBSTATS_BIB" => Some(OsString::from("env.bib")),
+            "BIBSTATS_EXT" => Some(OsString::from("tex, ltx")),
+            "BIBSTATS_FORMAT" => Some(OsString::from("tsv")),
+            "BIBSTATS_STYLE" => Some(OsString::from("numeric")),
+            "BIBSTATS_CACHE_DIR" => Some(OsString::new()),
+            "BIBSTATS_CITE_CMD" => Some(OsString::from("mycite, \\othercite")),
+            _ => None,
+        };
+        let mut args = Args {
+            bib: Some(OsString::from("cli.bib")),
+            ext: Vec::new(),
+            json: false,
+            ..Default::default()
+        };
+        assert!(args.apply_env(env).is_ok());
+        // the command line wins
+        assert_eq!(args.bib, Some(OsString::from("cli.bib")));
+        assert_eq!(args.ext, vec![OsString::from("tex"), OsString::from("ltx")]);
+        assert!(args.tsv);
+        assert_eq!(args.style, Some(Style::Numeric));
+        assert_eq!(args.cite_cmd, vec!["mycite", "othercite"]);
+        // empty variables are ignored
+        assert_eq!(args.cache_dir, None);
+
+        let mut args = Args {
+            json: false,
+            markdown: true,
+            ..Default::default()
+        };
+        assert!(args.apply_env(env).is_ok());
+        assert!(args.markdown && !args.tsv);
+
+        let mut args = Args {
+            json: false,
+            ..Default::default()
+        };
+        let bad = |var: &str| (var == "BIBSTATS_FORMAT").then(|| OsString::from("xml"));
+        assert!(args.apply_env(bad).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde_json::json;
+
+use crate::corpus;
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::parser::BibEntry;
+use crate::stats::{author_title, BibMap};
+
+// A project to compare: the citations of its documents
+// and the entries of its bib files.
+pub struct Project {
+    pub bib: BibMap,
+    pub cites: Vec<Citation>,
+}
+
+// The works cited by two projects, like a thesis and one of
+// the papers it is made of: the works cited in both, with the key
+// in each, and the works cited only in the one or the other.
+// Works are in the order in which they are first cited.
+pub struct Comparison {
+    pub both: Vec<(String, String)>,
+    pub only_a: Vec<String>,
+    pub only_b: Vec<String>,
+}
+
+// Works with the same DOI or the same normalized title are
+// the same work; the projects have their own bib files
+// and hardly use the same keys.
+pub fn compute(a: &Project, b: &Project) -> Comparison {
+    let works_a = works(a);
+    let works_b = works(b);
+    let mut ids: FxHashMap<&str, usize> = FxHashMap::default();
+    for (i, (_, is)) in works_b.iter().enumerate() {
+        for id in is {
+            ids.entry(id.as_str()).or_insert(i);
+        }
+    }
+    let mut matched = FxHashSet::default();
+    let mut c = Comparison {
+        both: Vec::new(),
+        only_a: Vec::new(),
+        only_b: Vec::new(),
+    };
+    for (key, is) in &works_a {
+        match is.iter().find_map(|id| ids.get(id.as_str())) {
+            Some(&i) => {
+                matched.insert(i);
+                c.both.push((key.to_string(), works_b[i].0.to_string()));
+            }
+            None => c.only_a.push(key.to_string()),
+        }
+    }
+    c.only_b = works_b
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !matched.contains(i))
+        .map(|(_, (key, _))| key.to_string())
+        .collect();
+    c
+}
+
+// The cited keys with the identities of their works.
+fn works(p: &Project) -> Vec<(&str, Vec<String>)> {
+    let mut seen = FxHashSet::default();
+    p.cites
+        .iter()
+        .filter(|c| seen.insert(c.key.as_str()))
+        .map(|c| (c.key.as_str(), identities(&c.key, p.bib.get(&c.key))))
+        .collect()
+}
+
+// The identities of a work: the DOI and the normalized title or,
+// for works with neither, the key, which might be shared after all.
+pub fn identities(key: &str, e: Option<&BibEntry>) -> Vec<String> {
+    let mut is = Vec::new();
+    if let Some(e) = e {
+        if let Some(doi) = e.doi() {
+            is.push(format!("doi:{}", doi));
+        }
+        let t = corpus::normalize_title(&e.title);
+        if !t.is_empty() {
+            is.push(format!("title:{}", t));
+        }
+    }
+    if is.is_empty() {
+        is.push(format!("key:{}", key));
+    }
+    is
+}
+
+// The rows of the report: where the work is cited ("both", "a" or "b"),
+// its key in the one and in the other project, author and title.
+fn rows<'a>(
+    c: &'a Comparison,
+    a: &'a Project,
+    b: &'a Project,
+) -> impl Iterator<Item = (&'static str, &'a str, &'a str, &'a str, &'a str)> {
+    let both = c.both.iter().map(move |(ka, kb)| {
+        let (author, title) = author_title(ka, &a.bib);
+        ("both", ka.as_str(), kb.as_str(), author, title)
+    });
+    let only_a = c.only_a.iter().map(move |k| {
+        let (author, title) = author_title(k, &a.bib);
+        ("a", k.as_str(), "", author, title)
+    });
+    let only_b = c.only_b.iter().map(move |k| {
+        let (author, title) = author_title(k, &b.bib);
+        ("b", "", k.as_str(), author, title)
+    });
+    both.chain(only_a).chain(only_b)
+}
+
+pub fn print_comparison(c: &Comparison, a: &Project, b: &Project, f: Format) {
+    eprintln!(
+        "{} works cited in both, {} only in the first, {} only in the second",
+        c.both.len(),
+        c.only_a.len(),
+        c.only_b.len()
+    );
+    match f {
+        Format::Json(j) => comparison_as_json(c, a, b, j),
+        Format::Tsv => comparison_as_tsv(c, a, b),
+        Format::Markdown => comparison_as_markdown(c, a, b),
+    }
+}
+
+fn comparison_as_tsv(c: &Comparison, a: &Project, b: &Project) {
+    for (cited, key_a, key_b, author, title) in rows(c, a, b) {
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"",
+            cited, key_a, key_b, author, title
+        );
+    }
+}
+
+fn comparison_as_markdown(c: &Comparison, a: &Project, b: &Project) {
+    let rows = rows(c, a, b).map(|(cited, key_a, key_b, author, title)| {
+        vec![
+            cited.to_string(),
+            key_a.to_string(),
+            key_b.to_string(),
+            author.to_string(),
+            title.to_string(),
+        ]
+    });
+    output::print_md_table(&["Cited in", "Key A", "Key B", "Author", "Title"], rows);
+}
+
+fn comparison_as_json(c: &Comparison, a: &Project, b: &Project, json: Json) {
+    let records = rows(c, a, b).map(|(cited, key_a, key_b, author, title)| {
+        json!({
+            "cited": cited,
+            "key_a": key_a,
+            "key_b": key_b,
+            "author": author,
+            "title": title
+        })
+    });
+    output::print_json(records, json, "compare");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+    use std::collections::HashMap;
+
+    fn project(entries: &[(&str, &str, &str)], cited: &[&str]) -> Project {
+        let bib = entries
+            .iter()
+            .map(|(key, title, doi)| {
+                let mut fields = HashMap::new();
+                if !doi.is_empty() {
+                    fields.insert("doi".to_string(), doi.to_string());
+                }
+                let e = BibEntry {
+                    key: key.to_string(),
+                    title: title.to_string(),
+                    fields,
+                    ..BibEntry::empty()
+                };
+                (key.to_string(), e)
+            })
+            .collect();
+        let cites = cited.iter().map(|k| citation(k)).collect();
+        Project { bib, cites }
+    }
+
+    #[test]
+    fn test_compare() {
+        let a = project(
+            &[
+                ("knuth84", "Literate Programming", ""),
+                ("lamport", "LaTeX", "10.1000/latex"),
+                ("wirth", "Algorithms", ""),
+            ],
+            &["knuth84", "lamport", "wirth", "knuth84", "nobib"],
+        );
+        let b = project(
+            &[
+                ("Knuth1984", "Literate {Programming}.", ""),
+                (
+                    "lamport94",
+                    "LaTeX: A Document Preparation System",
+                    "10.1000/LATEX",
+                ),
+                ("dijkstra", "Go To Statement", ""),
+            ],
+            &["dijkstra", "lamport94", "Knuth1984", "nobib"],
+        );
+        let c = compute(&a, &b);
+        assert_eq!(
+            c.both,
+            vec![
+                ("knuth84".to_string(), "Knuth1984".to_string()),
+                ("lamport".to_string(), "lamport94".to_string()),
+                ("nobib".to_string(), "nobib".to_string()),
+            ]
+        );
+        assert_eq!(c.only_a, vec!["wirth"]);
+        assert_eq!(c.only_b, vec!["dijkstra"]);
+        assert_eq!(rows(&c, &a, &b).count(), 5);
+    }
+
+    #[test]
+    fn test_same_work_twice() {
+        // of two keys for the same work, the one cited first matches
+        let a = project(&[("knuth84", "Literate Programming", "")], &["knuth84"]);
+        let b = project(
+            &[
+                ("lp", "Literate programming", ""),
+                ("knuth", "Literate Programming.", ""),
+            ],
+            &["knuth", "lp"],
+        );
+        let c = compute(&a, &b);
+        assert_eq!(c.both, vec![("knuth84".to_string(), "knuth".to_string())]);
+        assert_eq!((c.only_a.len(), c.only_b), (0, vec!["lp".to_string()]));
+    }
+}
@@ -0,0 +1,202 @@
+use rustc_hash::FxHashSet;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::parser::{BibEntry, PubType};
+use crate::stats::BibMap;
+
+// The fields that may name the venue of a work.
+const VENUES: [&str; 6] = [
+    "journal",
+    "booktitle",
+    "publisher",
+    "howpublished",
+    "school",
+    "institution",
+];
+
+// The metadata we expect of an entry: author (or editor), year,
+// venue, DOI or URL and, for parts of a bigger work, pages.
+// The score is the share of the expected metadata
+// the entry has, in percent.
+pub struct Score<'a> {
+    pub entry: &'a BibEntry,
+    pub score: u32,
+    pub missing: Vec<&'static str>,
+}
+
+fn score(e: &BibEntry) -> Score<'_> {
+    let mut checks = vec![
+        (
+            "author",
+            !e.author.trim().is_empty() || e.field("editor").is_some(),
+        ),
+        ("year", e.year().is_some()),
+        ("venue", VENUES.iter().any(|v| e.field(v).is_some())),
+        (
+            "doi/url",
+            e.field("doi").is_some() || e.field("url").is_some(),
+        ),
+    ];
+    if matches!(
+        e.pubtype,
+        PubType::Article | PubType::Incol | PubType::Inproc
+    ) {
+        checks.push(("pages", e.field("pages").is_some()));
+    }
+    let have = checks.iter().filter(|(_, ok)| *ok).count();
+    Score {
+        entry: e,
+        score: (have * 100 / checks.len()) as u32,
+        missing: checks
+            .into_iter()
+            .filter(|(_, ok)| !ok)
+            .map(|(name, _)| name)
+            .collect(),
+    }
+}
+
+// Scores every cited entry and returns those below the threshold,
+// the weakest first and otherwise in the order of first citation.
+pub fn compute<'a>(bib: &'a BibMap, cites: &[Citation], threshold: u32) -> Vec<Score<'a>> {
+    let mut seen = FxHashSet::default();
+    let mut v: Vec<Score> = cites
+        .iter()
+        .filter(|c| seen.insert(c.key.as_str()))
+        .filter_map(|c| bib.get(&c.key))
+        .map(score)
+        .filter(|s| s.score < threshold)
+        .collect();
+    v.sort_by_key(|s| s.score);
+    v
+}
+
+pub fn print_scores(v: &[Score], f: Format) {
+    match f {
+        Format::Json(j) => scores_as_json(v, j),
+        Format::Tsv => scores_as_tsv(v),
+        Format::Markdown => scores_as_markdown(v),
+    }
+}
+
+fn scores_as_tsv(v: &[Score]) {
+    for s in v {
+        println!(
+            "\"{}\"\t\"{}\"\t{}\t\"{}\"",
+            s.entry.key,
+            s.entry.location(),
+            s.score,
+            s.missing.join(",")
+        );
+    }
+}
+
+fn scores_as_markdown(v: &[Score]) {
+    let rows = v.iter().map(|s| {
+        vec![
+            s.entry.key.clone(),
+            s.entry.location(),
+            format!("{}%", s.score),
+            s.missing.join(", "),
+        ]
+    });
+    output::print_md_table(&["Key", "Defined at", "Score", "Missing"], rows);
+}
+
+fn scores_as_json(v: &[Score], json: Json) {
+    let records = v.iter().map(|s| {
+        json!({
+            "key": s.entry.key,
+            "file": s.entry.file.to_string_lossy(),
+            "line": s.entry.line,
+            "score": s.score,
+            "missing": s.missing
+        })
+    });
+    output::print_json(records, json, "completeness");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(pubtype: PubType, fields: &[(&str, &str)]) -> BibEntry {
+        BibEntry {
+            pubtype,
+            author: "Lamport, Leslie".to_string(),
+            date: "1986".to_string(),
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..BibEntry::empty()
+        }
+    }
+
+    #[test]
+    fn test_pages_only_for_parts() {
+        let article = entry(PubType::Article, &[("journal", "J"), ("doi", "10.1/x")]);
+        assert!(match score(&article) {
+            Score {
+                score: 80, missing, ..
+            } => missing == ["pages"],
+            s => {
+                eprintln!("score: {} missing {:?}", s.score, s.missing);
+                false
+            }
+        });
+        let incol = entry(PubType::Incol, &[("booktitle", "B"), ("url", "u")]);
+        assert!(match score(&incol) {
+            Score {
+                score: 80, missing, ..
+            } => missing == ["pages"],
+            s => {
+                eprintln!("score: {} missing {:?}", s.score, s.missing);
+                false
+            }
+        });
+        let book = entry(PubType::Book, &[("publisher", "P"), ("doi", "10.1/x")]);
+        assert!(match score(&book) {
+            Score {
+                score: 100,
+                missing,
+                ..
+            } => missing.is_empty(),
+            s => {
+                eprintln!("score: {} missing {:?}", s.score, s.missing);
+                false
+            }
+        });
+    }
+
+    #[test]
+    fn test_editor_for_author() {
+        let mut e = entry(
+            PubType::Proceedings,
+            &[("editor", "Knuth, Donald"), ("publisher", "P")],
+        );
+        e.author.clear();
+        assert!(match score(&e) {
+            Score {
+                score: 75, missing, ..
+            } => missing == ["doi/url"],
+            s => {
+                eprintln!("score: {} missing {:?}", s.score, s.missing);
+                false
+            }
+        });
+        e.fields.remove("editor");
+        e.date.clear();
+        assert!(match score(&e) {
+            Score {
+                score: 25, missing, ..
+            } => missing == ["author", "year", "doi/url"],
+            s => {
+                eprintln!("score: {} missing {:?}", s.score, s.missing);
+                false
+            }
+        });
+    }
+}
@@ -0,0 +1,254 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::Path;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::{author_title, BibMap};
+
+// A dated project of a corpus: the root document and its date.
+// The corpus file has one project per line, the date first:
+//
+//   2019 thesis/main.tex
+//   2021-03 papers/nlp/paper.tex
+//
+// Dates are ISO dates or parts of them, so they sort as strings.
+// Paths are relative to the directory of the corpus file.
+// Lines starting with '#' are comments.
+#[derive(Debug)]
+pub struct Project {
+    pub date: String,
+    pub root: OsString,
+}
+
+pub fn load(path: &OsStr) -> Result<Vec<Project>, String> {
+    let name = path.to_string_lossy();
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("cannot read corpus {}: {}", name, e))?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut v = Vec::new();
+    for (i, l) in text.lines().enumerate() {
+        let l = l.trim();
+        if l.is_empty() || l.starts_with('#') {
+            continue;
+        }
+        let Some((date, root)) = l.split_once(char::is_whitespace) else {
+            return Err(format!("{}:{}: date and document expected", name, i + 1));
+        };
+        if !date.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(format!("{}:{}: invalid date '{}'", name, i + 1, date));
+        }
+        v.push(Project {
+            date: date.to_string(),
+            root: dir.join(root.trim()).into_os_string(),
+        });
+    }
+    Ok(v)
+}
+
+// How a work is used across the corpus: the dates of the first
+// and the last project citing it, the number of projects citing it
+// and the number of citations in all of them.
+// A work may be cited under several keys, see identity.
+pub struct Usage {
+    pub key: String,
+    pub keys: Vec<String>,
+    pub first: String,
+    pub last: String,
+    pub projects: u32,
+    pub count: u32,
+}
+
+// Sorted by the first and then the last date, so that
+// the output reads like a history of the group's reading.
+pub fn compute(projects: &[(&Project, Vec<Citation>)], bib: &BibMap) -> Vec<Usage> {
+    let mut m: FxHashMap<String, Usage> = FxHashMap::default();
+    for (p, cites) in projects {
+        let mut counted = FxHashSet::default();
+        for c in cites {
+            let id = identity(&c.key, bib);
+            let u = m.entry(id.clone()).or_insert_with(|| Usage {
+                key: c.key.clone(),
+                keys: Vec::new(),
+                first: p.date.clone(),
+                last: p.date.clone(),
+                projects: 0,
+                count: 0,
+            });
+            u.count += 1;
+            if !u.keys.contains(&c.key) {
+                u.keys.push(c.key.clone());
+            }
+            if counted.insert(id) {
+                u.projects += 1;
+                if p.date < u.first {
+                    u.first = p.date.clone();
+                }
+                if p.date > u.last {
+                    u.last = p.date.clone();
+                }
+            }
+        }
+    }
+    let mut v: Vec<Usage> = m.into_values().collect();
+    for u in &mut v {
+        u.keys.sort_unstable();
+        u.key = u.keys[0].clone();
+    }
+    v.sort_unstable_by(|a, b| (&a.first, &a.last, &a.key).cmp(&(&b.first, &b.last, &b.key)));
+    v
+}
+
+// Projects often cite the same work under different keys from
+// different bib files, written in title case in one and in
+// sentence case in the other. Works with the same normalized title
+// are therefore one work; works without title are told by their key.
+fn identity(key: &str, bib: &BibMap) -> String {
+    let (_, title) = author_title(key, bib);
+    let t = normalize_title(title);
+    if t.is_empty() {
+        format!("key:{}", key)
+    } else {
+        format!("title:{}", t)
+    }
+}
+
+// lower case without braces and punctuation, single spaces
+pub fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn print_usage(v: &[Usage], bib: &BibMap, f: Format) {
+    match f {
+        Format::Json(j) => usage_as_json(v, bib, j),
+        Format::Tsv => usage_as_tsv(v, bib),
+        Format::Markdown => usage_as_markdown(v, bib),
+    }
+}
+
+fn usage_as_tsv(v: &[Usage], bib: &BibMap) {
+    for u in v {
+        let (author, title) = author_title(&u.key, bib);
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t{}\t{}",
+            u.keys.join(","),
+            author,
+            title,
+            u.first,
+            u.last,
+            u.projects,
+            u.count
+        );
+    }
+}
+
+fn usage_as_markdown(v: &[Usage], bib: &BibMap) {
+    let rows = v.iter().map(|u| {
+        let (author, title) = author_title(&u.key, bib);
+        vec![
+            u.keys.join(", "),
+            author.to_string(),
+            title.to_string(),
+            u.first.clone(),
+            u.last.clone(),
+            u.projects.to_string(),
+            u.count.to_string(),
+        ]
+    });
+    output::print_md_table(
+        &[
+            "Keys",
+            "Author",
+            "Title",
+            "First cited",
+            "Last cited",
+            "Projects",
+            "Count",
+        ],
+        rows,
+    );
+}
+
+fn usage_as_json(v: &[Usage], bib: &BibMap, json: Json) {
+    let records = v.iter().map(|u| {
+        let (author, title) = author_title(&u.key, bib);
+        json!({
+            "key": u.key,
+            "keys": u.keys,
+            "author": author,
+            "title": title,
+            "first": u.first,
+            "last": u.last,
+            "projects": u.projects,
+            "count": u.count
+        })
+    });
+    output::print_json(records, json, "corpus");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+    use crate::parser::BibEntry;
+
+    #[test]
+    fn test_same_title() {
+        let entry = |key: &str, title: &str| BibEntry {
+            key: key.to_string(),
+            title: title.to_string(),
+            ..BibEntry::empty()
+        };
+        let bib: BibMap = [
+            entry("knuth68", "The Art Of Computer Programming"),
+            entry("taocp", "The {Art} of computer programming"),
+            entry("other", "Concrete Mathematics"),
+        ]
+        .into_iter()
+        .map(|e| (e.key.clone(), e))
+        .collect();
+        let p1 = Project {
+            date: "2019".to_string(),
+            root: OsString::new(),
+        };
+        let p2 = Project {
+            date: "2021".to_string(),
+            root: OsString::new(),
+        };
+        let projects = vec![
+            (&p1, vec![citation("knuth68"), citation("other")]),
+            (&p2, vec![citation("taocp"), citation("knuth68")]),
+        ];
+        let v = compute(&projects, &bib);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0].key, "other");
+        assert_eq!(v[1].keys, vec!["knuth68", "taocp"]);
+        assert_eq!((v[1].first.as_str(), v[1].last.as_str()), ("2019", "2021"));
+        assert_eq!((v[1].projects, v[1].count), (2, 3));
+    }
+
+    #[test]
+    fn test_untitled_works() {
+        let p = Project {
+            date: "2020".to_string(),
+            root: OsString::new(),
+        };
+        // works not in the bib are told by their key, ties go by key
+        let projects = vec![(&p, vec![citation("b"), citation("a"), citation("b")])];
+        let v = compute(&projects, &BibMap::default());
+        let usages: Vec<(&str, u32, u32)> = v
+            .iter()
+            .map(|u| (u.key.as_str(), u.projects, u.count))
+            .collect();
+        assert_eq!(usages, [("a", 1, 1), ("b", 1, 2)]);
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use crate::netcache;
+use crate::netcache::{NetCache, Response};
+
+const API: &str = "https://api.crossref.org/works/";
+
+// Retractions are rare and notices do not go away,
+// so a week is fresh enough.
+const TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+fn url(doi: &str) -> String {
+    format!("{}{}", API, netcache::encode(doi))
+}
+
+// Reads the metadata of a work from the answer of CrossRef.
+// Returns None if CrossRef does not know the DOI
+// or if we are offline and it is not cached.
+fn work(url: &str, r: Option<Response>) -> Result<Option<serde_json::Value>, String> {
+    let Some(r) = r else {
+        return Ok(None);
+    };
+    match r.status {
+        200 => {
+            let js: serde_json::Value = serde_json::from_str(&r.body)
+                .map_err(|e| format!("invalid response for {}: {}", url, e))?;
+            Ok(Some(js["message"].clone()))
+        }
+        404 => Ok(None),
+        s => Err(format!("CrossRef answered {} for {}", s, url)),
+    }
+}
+
+// Looks up the works with the given normalized DOIs.
+pub fn works(net: &NetCache, dois: &[String]) -> Vec<Result<Option<serde_json::Value>, String>> {
+    let urls: Vec<String> = dois.iter().map(|d| url(d)).collect();
+    net.get_all(&urls, TTL)
+        .into_iter()
+        .zip(urls.iter())
+        .map(|(r, url)| work(url, r?))
+        .collect()
+}
+
+// Returns the DOIs of the retraction notices of a work.
+// CrossRef lists them, including those from Retraction Watch,
+// as updates of type 'retraction'.
+pub fn retractions(work: &serde_json::Value) -> Vec<String> {
+    work["updated-by"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|u| u["type"].as_str() == Some("retraction"))
+        .map(|u| u["DOI"].as_str().unwrap_or_default().to_string())
+        .collect()
+}
+
+// Returns the DOI of the published version of a preprint,
+// which CrossRef lists as relation 'is-preprint-of'.
+pub fn published(work: &serde_json::Value) -> Option<String> {
+    work["relation"]["is-preprint-of"]
+        .as_array()?
+        .iter()
+        .find(|r| r["id-type"].as_str() == Some("doi"))
+        .and_then(|r| r["id"].as_str())
+        .map(|d| d.to_lowercase())
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+use std::fmt::Display;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A date as biblatex writes it in the date field: YYYY, YYYY-MM
+// or YYYY-MM-DD, maybe marked as uncertain (?), approximate (~)
+// or both (%), which we ignore.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Date {
+    pub year: i32,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some(m) = self.month {
+            write!(f, "-{:02}", m)?;
+            if let Some(d) = self.day {
+                write!(f, "-{:02}", d)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// A date or a range of dates like 2019/2021; either end of a range
+// may be open, as in 2019/ or 2019/.., but not both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: Option<Date>,
+    pub end: Option<Date>,
+}
+
+impl Range {
+    // The year the work is dated by: the start or, if the start
+    // is open, the end.
+    pub fn year(&self) -> Option<i32> {
+        self.start.or(self.end).map(|d| d.year)
+    }
+}
+
+pub fn parse_range(s: &str) -> Option<Range> {
+    let s = s.trim();
+    let r = match s.split_once('/') {
+        None => {
+            let d = parse_date(s)?;
+            Range {
+                start: Some(d),
+                end: Some(d),
+            }
+        }
+        Some((a, b)) => Range {
+            start: open_or(a)?,
+            end: open_or(b)?,
+        },
+    };
+    if r.start.is_none() && r.end.is_none() {
+        return None;
+    }
+    Some(r)
+}
+
+// An open end is empty or '..'; Some(None) for open ends,
+// None for invalid dates.
+fn open_or(s: &str) -> Option<Option<Date>> {
+    match s.trim() {
+        "" | ".." => Some(None),
+        d => parse_date(d).map(Some),
+    }
+}
+
+pub fn parse_date(s: &str) -> Option<Date> {
+    let s = s.trim().trim_end_matches(['?', '~', '%']);
+    let mut parts = s.splitn(3, '-');
+    let year = parts.next()?;
+    if year.len() != 4 || !year.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let month = match parts.next() {
+        Some(m) => Some(number(m, 1, 12)?),
+        None => None,
+    };
+    let day = match parts.next() {
+        Some(d) => Some(number(d, 1, 31)?),
+        None => None,
+    };
+    Some(Date {
+        year: year.parse().ok()?,
+        month,
+        day,
+    })
+}
+
+fn number(s: &str, min: u32, max: u32) -> Option<u32> {
+    if s.len() != 2 {
+        return None;
+    }
+    s.parse().ok().filter(|n| (min..=max).contains(n))
+}
+
+pub static MONTHS: &[&str] = &[
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+// The number of a month given as number, English name
+// or its first three letters, as in the month field.
+pub fn month(s: &str) -> Option<u32> {
+    let s = s.trim().trim_end_matches('.').to_lowercase();
+    if let Ok(n) = s.parse::<u32>() {
+        return (1..=12).contains(&n).then_some(n);
+    }
+    if s.len() < 3 {
+        return None;
+    }
+    MONTHS
+        .iter()
+        .position(|m| m.starts_with(&s))
+        .map(|i| i as u32 + 1)
+}
+
+// The date from the year, month and day fields of BibTeX,
+// if the year is a plain year.
+pub fn from_fields(year: &str, month: Option<&str>, day: Option<&str>) -> Option<Date> {
+    let mut d = parse_date(year)?;
+    if d.month.is_some() {
+        return None;
+    }
+    d.month = month.and_then(self::month);
+    if d.month.is_some() {
+        d.day = day.and_then(|s| s.trim().parse().ok().filter(|n| (1..=31).contains(n)));
+    }
+    Some(d)
+}
+
+// The current year in UTC.
+pub fn this_year() -> i32 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    year_of_day((secs / 86400) as i64)
+}
+
+// The year of a day counted from 1970-01-01, by the algorithm
+// of Howard Hinnant, which counts years from March on.
+fn year_of_day(days: i64) -> i32 {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    // January and February belong to the next year
+    let march_based_month = (5 * doy + 2) / 153;
+    (era * 400 + yoe + i64::from(march_based_month >= 10)) as i32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        let d = |year, month, day| Some(Date { year, month, day });
+        assert_eq!(
+            parse_range("2020-03-01"),
+            Some(Range {
+                start: d(2020, Some(3), Some(1)),
+                end: d(2020, Some(3), Some(1)),
+            })
+        );
+        assert_eq!(
+            parse_range("2019/2021"),
+            Some(Range {
+                start: d(2019, None, None),
+                end: d(2021, None, None),
+            })
+        );
+        assert_eq!(parse_range("/2021").and_then(|r| r.year()), Some(2021));
+        assert_eq!(parse_range("2019~/..").and_then(|r| r.year()), Some(2019));
+        assert_eq!(parse_range("../"), None);
+        assert_eq!(parse_range("2020-13"), None);
+        assert_eq!(parse_range("ca. 2020"), None);
+    }
+
+    #[test]
+    fn test_year_of_day() {
+        assert_eq!(year_of_day(0), 1970);
+        assert_eq!(year_of_day(-1), 1969);
+        assert_eq!(year_of_day(20088), 2024);
+        assert_eq!(year_of_day(20089), 2025);
+    }
+
+    #[test]
+    fn test_from_fields() {
+        assert_eq!(month("Mar."), Some(3));
+        assert_eq!(month("september"), Some(9));
+        assert_eq!(month("ma"), None);
+        let d = from_fields("2020", Some("jun"), Some("5")).unwrap();
+        assert_eq!(d.to_string(), "2020-06-05");
+        assert_eq!(
+            from_fields("2020", None, Some("5")).unwrap().to_string(),
+            "2020"
+        );
+        assert_eq!(from_fields("forthcoming", None, None), None);
+    }
+}
@@ -0,0 +1,312 @@
+use std::ffi::OsString;
+use std::fs;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::diff;
+use crate::diff::Hunk;
+use crate::docs::Citation;
+use crate::error;
+use crate::lint;
+use crate::parser;
+use crate::parser::{BibEntry, Cite, TexConfig, TexItem};
+use crate::stats::BibMap;
+
+// An entry that is the same work as another entry, found like
+// the duplicate-doi lint finds them. Merging cites the work with the
+// key of the other entry everywhere and removes the entry from its
+// bib file. Of a cited and an uncited entry, the cited one is kept,
+// of two cited ones the one cited first, of two uncited ones the
+// first in the bib files.
+pub struct Rename<'a> {
+    pub from: &'a BibEntry,
+    pub to: &'a BibEntry,
+}
+
+pub fn renames<'a>(bib: &'a BibMap, cites: &[Citation]) -> Vec<Rename<'a>> {
+    let mut seen = FxHashSet::default();
+    let mut entries: Vec<&BibEntry> = cites
+        .iter()
+        .filter(|c| seen.insert(c.key.as_str()))
+        .filter_map(|c| bib.get(&c.key))
+        .collect();
+    let mut uncited: Vec<&BibEntry> = bib
+        .values()
+        .filter(|e| !seen.contains(e.key.as_str()))
+        .collect();
+    uncited.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    entries.extend(uncited);
+    lint::same_work(&entries)
+        .into_iter()
+        .map(|(i, f)| Rename {
+            from: entries[i],
+            to: entries[f],
+        })
+        .collect()
+}
+
+// Applies the renames to the documents and the bib files;
+// in a dry run, the changes are printed as a unified diff
+// instead of being written. Returns the number of changed files.
+pub fn apply(
+    renames: &[Rename],
+    docs: &[OsString],
+    tex: &TexConfig,
+    dry_run: bool,
+) -> Result<usize, String> {
+    let keys: FxHashMap<&str, &str> = renames
+        .iter()
+        .map(|r| (r.from.key.as_str(), r.to.key.as_str()))
+        .collect();
+    let mut changes = rewrite_docs(&keys, docs, tex)?;
+
+    // bib file -> lines where the entries to remove start
+    let mut removals: Vec<(&OsString, Vec<u64>)> = Vec::new();
+    for r in renames {
+        let e = r.from;
+        match removals.iter_mut().find(|(f, _)| *f == &e.file) {
+            Some((_, lines)) => lines.push(e.line),
+            None => removals.push((&e.file, vec![e.line])),
+        }
+    }
+    for (file, mut lines) in removals {
+        lines.sort_unstable();
+        let text = read(file)?;
+        let (new, hunks) = remove_entries(&text, &lines);
+        changes.push((file, new, hunks));
+    }
+
+    write(&changes, dry_run)?;
+    Ok(changes.len())
+}
+
+// Replaces the keys in the citations of the documents, but leaves
+// the bib files alone; in a dry run, the changes are printed as
+// a unified diff. Returns the number of changed files.
+pub fn rename_keys(
+    keys: &FxHashMap<&str, &str>,
+    docs: &[OsString],
+    tex: &TexConfig,
+    dry_run: bool,
+) -> Result<usize, String> {
+    let changes = rewrite_docs(keys, docs, tex)?;
+    write(&changes, dry_run)?;
+    Ok(changes.len())
+}
+
+type Change<'a> = (&'a OsString, String, Vec<Hunk>);
+
+fn rewrite_docs<'a>(
+    keys: &FxHashMap<&str, &str>,
+    docs: &'a [OsString],
+    tex: &TexConfig,
+) -> Result<Vec<Change<'a>>, String> {
+    let mut changes = Vec::new();
+    for file in docs {
+        let text = read(file)?;
+        let items = error::parse_source(file, text.as_bytes(), |s| {
+            parser::collect_cites_with(s, tex)
+        })
+        .map_err(|e| e.to_string())?;
+        let new = rewrite_keys(&text, keys, &items);
+        let hunks = diff::changed_lines(&text, &new);
+        if !hunks.is_empty() {
+            changes.push((file, new, hunks));
+        }
+    }
+    Ok(changes)
+}
+
+fn write(changes: &[Change], dry_run: bool) -> Result<(), String> {
+    for (file, new, hunks) in changes {
+        if dry_run {
+            print!("{}", diff::unified(&file.to_string_lossy(), hunks));
+        } else {
+            fs::write(file, new)
+                .map_err(|e| format!("cannot write {}: {}", file.to_string_lossy(), e))?;
+        }
+    }
+    Ok(())
+}
+
+fn read(file: &OsString) -> Result<String, String> {
+    fs::read_to_string(file).map_err(|e| format!("cannot read {}: {}", file.to_string_lossy(), e))
+}
+
+// Replaces the keys of the citations where the scanner found them;
+// keys it did not read from the text, like those in section titles,
+// are left alone.
+fn rewrite_keys(text: &str, keys: &FxHashMap<&str, &str>, items: &[TexItem]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut done = 0;
+    for i in items {
+        let TexItem::Cite(Cite {
+            key,
+            span: Some(span),
+            ..
+        }) = i
+        else {
+            continue;
+        };
+        let Some(to) = keys.get(key.as_str()) else {
+            continue;
+        };
+        out.push_str(&text[done..span.start]);
+        out.push_str(to);
+        done = span.end;
+    }
+    out.push_str(&text[done..]);
+    out
+}
+
+// Removes the entries starting at the lines, which are sorted,
+// up to their closing brace. Lines that are left empty are removed.
+fn remove_entries(text: &str, lines: &[u64]) -> (String, Vec<Hunk>) {
+    let starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |pos: usize| starts.partition_point(|s| *s <= pos);
+    let mut out = String::with_capacity(text.len());
+    let mut hunks = Vec::new();
+    let mut done = 0;
+    for &l in lines {
+        let Some(&from) = starts.get(l as usize - 1) else {
+            continue;
+        };
+        let Some(at) = text[from..].find('@').map(|i| from + i) else {
+            continue;
+        };
+        let Some(end) = entry_end(&text[at..]).map(|n| at + n) else {
+            continue;
+        };
+        if at < done {
+            continue;
+        }
+        let (first, last) = (line_of(at), line_of(end - 1));
+        let block_start = starts[first - 1];
+        let block_end = starts.get(last).copied().unwrap_or(text.len());
+        let kept = format!("{}{}", &text[block_start..at], &text[end..block_end]);
+        out.push_str(&text[done..block_start]);
+        let new: Vec<String> = if kept.trim().is_empty() {
+            Vec::new()
+        } else {
+            out.push_str(&kept);
+            vec![kept.trim_end_matches(['\n', '\r']).to_string()]
+        };
+        hunks.push(Hunk {
+            line: first,
+            old: text[block_start..block_end]
+                .lines()
+                .map(String::from)
+                .collect(),
+            new,
+        });
+        done = block_end;
+    }
+    out.push_str(&text[done..]);
+    (out, hunks)
+}
+
+// The length of an entry starting with '@' up to the brace or
+// parenthesis that closes it, or None if it is not closed.
+fn entry_end(s: &str) -> Option<usize> {
+    let open = s.find(['{', '('])?;
+    let close = if s.as_bytes()[open] == b'{' {
+        b'}'
+    } else {
+        b')'
+    };
+    let mut depth = 0;
+    for (i, b) in s.bytes().enumerate().skip(open + 1) {
+        match b {
+            b'{' => depth += 1,
+            b'}' | b')' if b == close && depth == 0 => return Some(i + 1),
+            b'}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+
+    fn entry(key: &str, doi: &str, line: u64) -> BibEntry {
+        let mut e = BibEntry::empty();
+        e.key = key.to_string();
+        e.fields.insert("doi".to_string(), doi.to_string());
+        e.file = OsString::from("refs.bib");
+        e.line = line;
+        e
+    }
+
+    fn merged(bib: &BibMap, cites: &[Citation]) -> Vec<(String, String)> {
+        renames(bib, cites)
+            .into_iter()
+            .map(|r| (r.from.key.clone(), r.to.key.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_renames() {
+        let bib: BibMap = [
+            entry("a", "10.1/x", 1),
+            entry("b", "10.1/x", 5),
+            entry("c", "10.1/y", 9),
+            entry("d", "10.1/y", 13),
+            entry("e", "10.1/z", 17),
+        ]
+        .into_iter()
+        .map(|e| (e.key.clone(), e))
+        .collect();
+        let s = |a: &str, b: &str| (a.to_string(), b.to_string());
+
+        // both cited: the one cited first is kept
+        let cites = vec![citation("b"), citation("a")];
+        assert_eq!(merged(&bib, &cites), vec![s("a", "b"), s("d", "c")]);
+
+        // only one cited: the uncited one is removed,
+        // even if it comes first in the bib file
+        let cites = vec![citation("d")];
+        assert_eq!(merged(&bib, &cites), vec![s("b", "a"), s("c", "d")]);
+    }
+
+    #[test]
+    fn test_rewrite_keys() {
+        let keys: FxHashMap<&str, &str> = [("b", "a")].into_iter().collect();
+        let tex = TexConfig {
+            cite_cmds: vec!["mycite".to_string()],
+            ..TexConfig::default()
+        };
+        let text = "\\cite{b} \\citep*[see][p.~2]{c, b}\n\
+                    \\ref{b} % \\cite{b}\n\
+                    \\textcite{bb,%\n  b} \\volcite{b}[2]{b}\n\
+                    \\autocites(see)()[1]{b}[2]{c,b} {b} \\mycite{b}\n\
+                    \\section{\\cite{b}} \\begin{comment}\\cite{b}\\end{comment}\n";
+        let items = pacosso::parse_string(text.to_string(), pacosso::Opts::default(), |s| {
+            parser::collect_cites_with(s, &tex)
+        })
+        .unwrap();
+        assert_eq!(
+            rewrite_keys(text, &keys, &items),
+            "\\cite{a} \\citep*[see][p.~2]{c, a}\n\
+             \\ref{b} % \\cite{b}\n\
+             \\textcite{bb,%\n  a} \\volcite{b}[2]{a}\n\
+             \\autocites(see)()[1]{a}[2]{c,a} {a} \\mycite{a}\n\
+             \\section{\\cite{b}} \\begin{comment}\\cite{b}\\end{comment}\n"
+        );
+    }
+
+    #[test]
+    fn test_remove_entries() {
+        let text = "@book{a,\n  title = {A}\n}\n\n@book{b,\n  title = {{B}}\n}\n@misc{c}\n";
+        let (new, hunks) = remove_entries(text, &[5]);
+        assert_eq!(new, "@book{a,\n  title = {A}\n}\n\n@misc{c}\n");
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].line, 5);
+        assert_eq!(hunks[0].old.len(), 3);
+        assert!(hunks[0].new.is_empty());
+    }
+}
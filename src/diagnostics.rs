@@ -0,0 +1,354 @@
+use serde_json::json;
+
+use crate::events;
+
+// The catalog of diagnostics. Every finding has a stable code,
+// which is used to look up its description, to select lint rules
+// and, in sources, to suppress it.
+// Codes starting with B concern the bib database and the citations,
+// codes starting with L are lint rules on cited entries.
+// Codes are never reused for another diagnostic.
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub description: &'static str,
+    pub fix: &'static str,
+}
+
+pub static CATALOG: &[Diagnostic] = &[
+    Diagnostic {
+        code: "B001",
+        name: "duplicate-key",
+        summary: "a citekey is defined more than once",
+        description: "Two entries in the bib files have the same citekey. \
+            The first definition is used, later ones are ignored, except \
+            for author and title with --merge-author and --merge-title; \
+            BibTeX does the same, but biber may pick another one.",
+        fix: "Remove the duplicate or give one of the entries another citekey \
+            and update the citations.",
+    },
+    Diagnostic {
+        code: "B002",
+        name: "missing-key",
+        summary: "a cited key is not in the bib database",
+        description: "The document cites a key that is not defined in any \
+            of the bib files. LaTeX prints '?' in its place. Keys of the bib \
+            files that are at most two edits away are suggested.",
+        fix: "Add the entry to the bib file, check the citekey for typos \
+            or load the bib file that defines it with --bib-include.",
+    },
+    Diagnostic {
+        code: "B003",
+        name: "bib-included-twice",
+        summary: "a bib file is included more than once",
+        description: "A bib file is loaded twice, either as an include of \
+            two master files or because it is given with --bib-include and \
+            included by a master file. It is only read the first time.",
+        fix: "Remove one of the includes.",
+    },
+    Diagnostic {
+        code: "B004",
+        name: "input-not-found",
+        summary: "a file included with \\input or \\include is not found",
+        description: "The file is looked up relative to the directory of the \
+            root document, in the search paths, in TEXINPUTS and, with \
+            --use-kpsewhich, by kpsewhich. Citations in the file are not counted.",
+        fix: "Correct the path or add its directory with --search-path.",
+    },
+    Diagnostic {
+        code: "B005",
+        name: "crossref-not-found",
+        summary: "the entry named in a crossref field is not in the bib database",
+        description: "Entries with a crossref field inherit the fields they lack, \
+            like booktitle and year, from the entry it names. If there is no such \
+            entry, nothing is inherited.",
+        fix: "Correct the key in the crossref field or add the parent entry.",
+    },
+    Diagnostic {
+        code: "B006",
+        name: "merge-conflict",
+        summary: "a citekey defined more than once has different authors or titles",
+        description: "When the bib files define a citekey more than once, the \
+            author and title are chosen by --merge-author and --merge-title, \
+            by default from the first definition. The warning lists all values \
+            and the chosen one; with --merge-log, they go to the log instead.",
+        fix: "Make the definitions agree or remove all but one of them.",
+    },
+    Diagnostic {
+        code: "B007",
+        name: "malformed-entry",
+        summary: "a malformed entry in a bib file was skipped (--lenient)",
+        description: "Without --lenient, a syntax error in a bib file ends the \
+            program. With --lenient, the entry is skipped up to the next line \
+            starting with '@' and the rest of the file is read. Citations of \
+            the skipped entry are reported as missing.",
+        fix: "Correct the entry at the given line.",
+    },
+    Diagnostic {
+        code: "B008",
+        name: "duplicate-field",
+        summary: "an entry has a field more than once",
+        description: "By default, a duplicated field is an error. With \
+            --duplicate-fields first-wins, the first value is used, as BibTeX \
+            does; with last-wins, the last one, as biber does.",
+        fix: "Remove one of the fields.",
+    },
+    Diagnostic {
+        code: "B009",
+        name: "unknown-entry-type",
+        summary: "a bib file has entries of a type BibTeX does not know",
+        description: "Entries of other types, like @online, @software or \
+            @dataset of biblatex or custom types, are read like @misc entries \
+            and reported with their type. BibTeX styles treat them as @misc \
+            and warn; with biblatex, they are fine if the style knows them.",
+        fix: "Ignore the warning if you use biblatex, otherwise use a standard type.",
+    },
+    Diagnostic {
+        code: "B010",
+        name: "natbib-command",
+        summary: "a natbib command has no biblatex equivalent",
+        description: "migrate rewrites natbib commands like \\citet and \\citep \
+            to their biblatex equivalents. Some commands, like \\citealt, \\citenum \
+            and the starred forms that give the full author list, have none \
+            and are left as they are.",
+        fix: "Rewrite the command by hand or load biblatex with the natbib option, \
+            which defines the natbib commands.",
+    },
+    Diagnostic {
+        code: "B011",
+        name: "inexact-key-match",
+        summary: "a cited key matches a key of the bib database only inexactly",
+        description: "With --key-match other than exact, a cited key that is not \
+            in the bib files is matched to a key that differs in case, in Unicode \
+            normalization or that it is the start of. The citation counts for \
+            that key, but LaTeX does not find it. A key that matches more than \
+            one key is left as it is.",
+        fix: "Cite the key as it is written in the bib file.",
+    },
+    Diagnostic {
+        code: "B012",
+        name: "cite-without-key",
+        summary: "a citation command has no citekey",
+        description: "The braces with the citekeys of a citation command are \
+            missing before the next command or the end of the paragraph, \
+            as in \\cite[p.~3] without {key}. The command is skipped; \
+            LaTeX would take the next group as the keys or fail.",
+        fix: "Add the citekeys in braces after the command.",
+    },
+    Diagnostic {
+        code: "L001",
+        name: "missing-title",
+        summary: "a cited entry has no title",
+        description: "Without title, the work cannot be identified in the \
+            bibliography and the stats group it with all other untitled \
+            works of the author.",
+        fix: "Add a title field.",
+    },
+    Diagnostic {
+        code: "L002",
+        name: "misc-without-year",
+        summary: "a cited @misc entry has no year (numeric styles)",
+        description: "Numeric styles usually sort by author and year; \
+            @misc entries, often web pages, without year end up in \
+            arbitrary places and readers cannot judge their currency.",
+        fix: "Add a year field, for web pages the year of the last visit.",
+    },
+    Diagnostic {
+        code: "L003",
+        name: "no-author-or-editor",
+        summary: "a cited entry has neither author nor editor (author-year styles)",
+        description: "Author-year styles build the citation label from the \
+            author or, if there is none, the editor. Without both, the label \
+            falls back to the key or the title.",
+        fix: "Add an author or editor field; for corporate authors, \
+            put the name in braces: author = {World Health Organization}.",
+    },
+    Diagnostic {
+        code: "L004",
+        name: "missing-year",
+        summary: "a cited entry has no year (author-year styles)",
+        description: "Author-year styles need the year for the citation label; \
+            without it, they print 'n.d.' or nothing.",
+        fix: "Add a year or date field.",
+    },
+    Diagnostic {
+        code: "L005",
+        name: "malformed-doi",
+        summary: "the doi field is not a DOI",
+        description: "A DOI has the form 10.<registrant>/<suffix>, where the \
+            registrant consists of numbers separated by dots. Resolver prefixes \
+            like https://doi.org/ are accepted and removed.",
+        fix: "Correct the DOI; copy it from the publisher's page.",
+    },
+    Diagnostic {
+        code: "L006",
+        name: "invalid-isbn",
+        summary: "the isbn field has an invalid ISBN",
+        description: "An ISBN has 10 or 13 digits, the last of which is a check \
+            digit; ISBN-10 may end in X. Hyphens and spaces are ignored.",
+        fix: "Correct the ISBN; often two digits are swapped.",
+    },
+    Diagnostic {
+        code: "L007",
+        name: "invalid-issn",
+        summary: "the issn field has an invalid ISSN",
+        description: "An ISSN has 8 digits, the last of which is a check digit \
+            that may be X. Hyphens and spaces are ignored.",
+        fix: "Correct the ISSN; often two digits are swapped.",
+    },
+    Diagnostic {
+        code: "L008",
+        name: "reversed-pages",
+        summary: "the last page of a range is before the first",
+        description: "A page range like 200--100 is reversed; a range like \
+            123--45 is abbreviated, which some styles print as it is.",
+        fix: "Write the range with the first page first and both pages in full.",
+    },
+    Diagnostic {
+        code: "L009",
+        name: "page-range-dash",
+        summary: "a page range uses a single hyphen",
+        description: "BibTeX styles print the pages as they are, so 12-34 \
+            appears with a hyphen where typography expects an en-dash. \
+            Some styles convert it, most do not.",
+        fix: "Write page ranges with two hyphens: 12--34.",
+    },
+    Diagnostic {
+        code: "L010",
+        name: "unrealistic-pages",
+        summary: "a page range is implausible",
+        description: "The range starts at page 0 or spans more than a thousand \
+            pages, which is likely a typo.",
+        fix: "Check the pages against the publication.",
+    },
+    Diagnostic {
+        code: "L011",
+        name: "duplicate-doi",
+        summary: "two cited keys have the same DOI",
+        description: "Two citekeys refer to the same work, identified by its \
+            normalized DOI; the bibliography lists it twice and the stats \
+            may count it as two works.",
+        fix: "Keep one of the entries and cite it with the same key everywhere.",
+    },
+    Diagnostic {
+        code: "L012",
+        name: "retracted",
+        summary: "a cited work is retracted",
+        description: "CrossRef lists a retraction notice for the DOI of the work. \
+            The check only runs with --crossref; the answers are cached, \
+            with --offline only the cache is used.",
+        fix: "Check whether the citation is still justified; if so, \
+            say in the text that the work was retracted and cite the notice.",
+    },
+    Diagnostic {
+        code: "L013",
+        name: "missing-required-field",
+        summary: "a cited entry lacks a field its type requires",
+        description: "BibTeX requires some fields per entry type, like journal \
+            and year for @article or school for @phdthesis; styles print \
+            incomplete references and BibTeX warns without them. \
+            A date counts as year. @misc and unknown types require nothing.",
+        fix: "Add the field, or use a type that fits the work better.",
+    },
+    Diagnostic {
+        code: "L014",
+        name: "min-recency",
+        summary: "a chapter cites too few recent works",
+        description: "With --recency, like --recency related-work:30:5, \
+            at least the given share of the citations in the chapter must be \
+            of works from the last years, counted back from the current year. \
+            Works without year do not count. Without --recency, the rule \
+            finds nothing.",
+        fix: "Cite more recent work in the chapter, or relax the requirement.",
+    },
+];
+
+// Looks up a diagnostic by code (case-insensitive) or name.
+pub fn lookup(id: &str) -> Option<&'static Diagnostic> {
+    CATALOG
+        .iter()
+        .find(|d| d.code.eq_ignore_ascii_case(id) || d.name == id)
+}
+
+// Prints a warning with its code; with --events, as event.
+pub fn warn(code: &str, msg: &str) {
+    if events::on() {
+        events::emit("warning", json!({ "code": code, "message": msg }));
+    } else {
+        eprintln!("warning[{}]: {}", code, msg);
+    }
+}
+
+// Prints the description of a diagnostic.
+pub fn explain(d: &Diagnostic) {
+    println!("{} {}: {}", d.code, d.name, d.summary);
+    println!();
+    println!("{}", d.description);
+    println!();
+    println!("Fix: {}", d.fix);
+}
+
+// Lists all diagnostics.
+pub fn list() {
+    for d in CATALOG {
+        println!("{}\t{}\t{}", d.code, d.name, d.summary);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rustc_hash::FxHashSet;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_unique_codes() {
+        let mut codes = FxHashSet::default();
+        let mut names = FxHashSet::default();
+        for d in CATALOG {
+            assert!(codes.insert(d.code), "code {} used twice", d.code);
+            assert!(names.insert(d.name), "name {} used twice", d.name);
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("B002").map(|d| d.name), Some("missing-key"));
+        assert_eq!(lookup("b002").map(|d| d.name), Some("missing-key"));
+        assert_eq!(lookup("missing-key").map(|d| d.code), Some("B002"));
+        assert!(lookup("B999").is_none());
+        assert!(lookup("Missing-Key").is_none());
+        assert!(lookup("").is_none());
+    }
+
+    // The codes of the warnings and the lint rules are literals
+    // in the sources, like warn("B004", ...) and code: "L001".
+    #[test]
+    fn test_emitted_codes_in_catalog() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut n = 0;
+        for f in fs::read_dir(src).unwrap() {
+            let path = f.unwrap().path();
+            if path.extension().is_none_or(|x| x != "rs") || path.ends_with("diagnostics.rs") {
+                continue;
+            }
+            let text = fs::read_to_string(&path).unwrap();
+            for (i, _) in text.match_indices('"') {
+                let Some(code) = text.get(i + 1..i + 5) else {
+                    continue;
+                };
+                let b = code.as_bytes();
+                if matches!(b[0], b'B' | b'L')
+                    && b[1..].iter().all(u8::is_ascii_digit)
+                    && text[i + 5..].starts_with('"')
+                {
+                    assert!(lookup(code).is_some(), "{} in {:?}", code, path);
+                    n += 1;
+                }
+            }
+        }
+        assert!(n > 0);
+    }
+}
@@ -0,0 +1,87 @@
+// A change to a text file: the lines starting at line
+// (counting from 1) are replaced by the new lines.
+#[derive(Debug, PartialEq)]
+pub struct Hunk {
+    pub line: usize,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+// The lines that differ between two versions of a text
+// with the same number of lines, one hunk per line.
+pub fn changed_lines(old: &str, new: &str) -> Vec<Hunk> {
+    old.lines()
+        .zip(new.lines())
+        .enumerate()
+        .filter(|(_, (o, n))| o != n)
+        .map(|(i, (o, n))| Hunk {
+            line: i + 1,
+            old: vec![o.to_string()],
+            new: vec![n.to_string()],
+        })
+        .collect()
+}
+
+// The hunks as unified diff without context, which patch can apply;
+// they must be in order and must not overlap.
+pub fn unified(name: &str, hunks: &[Hunk]) -> String {
+    let mut d = format!("--- a/{}\n+++ b/{}\n", name, name);
+    // how many lines the new version has more than the old
+    // before the current hunk
+    let mut shift: isize = 0;
+    for h in hunks {
+        let start = (h.line as isize + shift) as usize;
+        d.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(h.line, h.old.len()),
+            range(start, h.new.len())
+        ));
+        for l in &h.old {
+            d.push_str(&format!("-{}\n", l));
+        }
+        for l in &h.new {
+            d.push_str(&format!("+{}\n", l));
+        }
+        shift += h.new.len() as isize - h.old.len() as isize;
+    }
+    d
+}
+
+// "start,count" as diff writes it: a count of 1 is left out
+// and an empty range starts at the line before it.
+fn range(start: usize, count: usize) -> String {
+    match count {
+        1 => start.to_string(),
+        0 => format!("{},0", start - 1),
+        n => format!("{},{}", start, n),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unified() {
+        let lines = |ls: &[&str]| ls.iter().map(|l| l.to_string()).collect();
+        let hunks = vec![
+            Hunk {
+                line: 2,
+                old: lines(&["@book{b,", "}"]),
+                new: Vec::new(),
+            },
+            Hunk {
+                line: 7,
+                old: lines(&["x"]),
+                new: lines(&["y"]),
+            },
+        ];
+        assert_eq!(
+            unified("a.bib", &hunks),
+            "--- a/a.bib\n+++ b/a.bib\n\
+             @@ -2,2 +1,0 @@\n-@book{b,\n-}\n\
+             @@ -7 +5 @@\n-x\n+y\n"
+        );
+        assert_eq!(changed_lines("a\nb\nc\n", "a\nB\nc\n")[0].line, 2);
+    }
+}
@@ -0,0 +1,598 @@
+use std::ffi::{OsStr, OsString};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use rustc_hash::FxHashSet;
+use serde_json::json;
+
+use crate::auxfile;
+use crate::bcf;
+use crate::cache::ScanCache;
+use crate::diagnostics;
+use crate::error;
+use crate::error::SourceError;
+use crate::events;
+use crate::markdown;
+use crate::parser;
+use crate::parser::{Cite, CiteKind, Section, TexConfig, TexItem};
+use crate::resolve::{FileKind, Resolver, SearchConfig};
+use crate::typst;
+
+// The documents to scan for citations.
+// Root documents are the files given explicitly (or stdin);
+// their \input and \include commands are followed and resolved
+// relative to the directory of the root document.
+// Files found in directories are scanned as they are.
+// Every file is scanned only once.
+// With a cache, files that have not changed are not scanned,
+// but their items are taken from the cache.
+pub struct Documents {
+    pub roots: Vec<OsString>,
+    pub files: Vec<OsString>,
+    pub stdin: bool,
+    pub search: SearchConfig,
+    pub cache: Option<ScanCache>,
+    // additional citation commands and environments to skip,
+    // see --cite-cmd and --skip-env
+    pub tex: TexConfig,
+    // the syntax of all documents, see --syntax;
+    // without it, the syntax follows from the extension
+    pub syntax: Option<Syntax>,
+}
+
+impl Documents {
+    // The file to scan for a root document: with the aux or the bcf
+    // syntax, see --from-aux and --from-bcf, the file LaTeX or biblatex
+    // writes next to it.
+    fn root(&self, root: &OsStr) -> PathBuf {
+        let path = PathBuf::from(root);
+        match self.syntax {
+            Some(Syntax::Aux) => path.with_extension("aux"),
+            Some(Syntax::Bcf) => path.with_extension("bcf"),
+            _ => path,
+        }
+    }
+}
+
+// How a document is written, which decides how it is scanned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Syntax {
+    Tex,
+    // Pandoc's Markdown, with citations like [@key] and @key
+    Markdown,
+    // Typst, with citations like @key and #cite(<key>)
+    Typst,
+    // the aux files of a LaTeX run, with citations like \citation{key};
+    // root documents are read from their aux files
+    Aux,
+    // the control files biblatex writes for biber, with citations like
+    // <bcf:citekey>key</bcf:citekey>; root documents are read from them
+    Bcf,
+}
+
+impl Syntax {
+    // Markdown for the extensions md and markdown,
+    // Typst for typ, Aux for aux, Bcf for bcf, tex otherwise.
+    pub fn of(path: &Path) -> Syntax {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("md" | "markdown") => Syntax::Markdown,
+            Some("typ") => Syntax::Typst,
+            Some("aux") => Syntax::Aux,
+            Some("bcf") => Syntax::Bcf,
+            _ => Syntax::Tex,
+        }
+    }
+}
+
+impl Syntax {
+    // What is scanned for in documents of the syntax.
+    pub fn scanners(self) -> &'static [&'static str] {
+        match self {
+            Syntax::Tex => &["cite", "input", "include", "section", "quote", "newcommand"],
+            Syntax::Markdown => &["markdown-cite", "heading", "quote", "front-matter"],
+            Syntax::Typst => &["typst-cite", "include", "heading", "quote", "bibliography"],
+            Syntax::Aux => &["citation", "bibdata", "input"],
+            Syntax::Bcf => &["citekey", "datasource"],
+        }
+    }
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Syntax, String> {
+        match s {
+            "tex" => Ok(Syntax::Tex),
+            "markdown" => Ok(Syntax::Markdown),
+            "typst" => Ok(Syntax::Typst),
+            _ => Err(format!(
+                "unknown syntax '{}', tex, markdown or typst expected",
+                s
+            )),
+        }
+    }
+}
+
+// A citation of a citekey: where it is, in which section and in which
+// chapter, that is the unit of the highest level below parts, which is
+// the section in articles; its prenote and postnote, if any, whether
+// it follows a quotation and how the command presents it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub key: String,
+    pub file: OsString,
+    pub line: u64,
+    pub column: u64,
+    pub section: Option<String>,
+    pub chapter: Option<String>,
+    pub prenote: Option<String>,
+    pub postnote: Option<String>,
+    pub quoted: bool,
+    pub kind: CiteKind,
+}
+
+impl Citation {
+    fn new(c: Cite, file: &OsString, w: &Walk) -> Citation {
+        Citation {
+            key: c.key,
+            file: file.clone(),
+            line: c.line,
+            column: c.column,
+            section: w.section.clone(),
+            chapter: w.units[1..].iter().flatten().next().cloned(),
+            prenote: c.prenote,
+            postnote: c.postnote,
+            quoted: c.quoted,
+            kind: c.kind,
+        }
+    }
+
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.file.to_string_lossy(), self.line)
+    }
+}
+
+// \nocite{*} adds all entries of the database to the bibliography.
+// With all, it stands for a \nocite of every key, in the order given;
+// otherwise, it is dropped. Returns the citations and whether
+// there was a \nocite{*}.
+pub fn nocite_all(cites: Vec<Citation>, keys: &[&str], all: bool) -> (Vec<Citation>, bool) {
+    let mut found = false;
+    let mut v = Vec::with_capacity(cites.len());
+    for c in cites {
+        if c.key != "*" {
+            v.push(c);
+            continue;
+        }
+        if all && !found {
+            v.extend(keys.iter().map(|k| Citation {
+                key: k.to_string(),
+                ..c.clone()
+            }));
+        }
+        found = true;
+    }
+    (v, found)
+}
+
+// State while walking the documents.
+// Sections are numbered like LaTeX does, but without parts;
+// sections of files found in directories are not numbered,
+// since we do not know in which order they are included.
+struct Walk<'a> {
+    cache: Option<&'a ScanCache>,
+    // with the aliases of citation commands defined so far
+    tex: TexConfig,
+    syntax: Option<Syntax>,
+    search: &'a SearchConfig,
+    seen: FxHashSet<PathBuf>,
+    counters: [u32; 7],
+    units: [Option<String>; 7],
+    section: Option<String>,
+    cites: Vec<Citation>,
+    files: Vec<OsString>,
+    bibs: Vec<OsString>,
+    // whether to emit progress events, see events
+    events: bool,
+    // whether to hold back warnings: the walks that only look for the
+    // files and the bib files come with the walk of collect, which
+    // reports them
+    quiet: bool,
+}
+
+impl<'a> Walk<'a> {
+    fn new(docs: &'a Documents) -> Walk<'a> {
+        Walk {
+            cache: docs.cache.as_ref(),
+            tex: docs.tex.clone(),
+            syntax: docs.syntax,
+            search: &docs.search,
+            seen: FxHashSet::default(),
+            counters: Default::default(),
+            units: Default::default(),
+            section: None,
+            cites: Vec::new(),
+            files: Vec::new(),
+            bibs: Vec::new(),
+            events: false,
+            quiet: false,
+        }
+    }
+
+    fn warn(&self, code: &str, msg: &str) {
+        if !self.quiet {
+            diagnostics::warn(code, msg);
+        }
+    }
+
+    fn malformed(&self, file: &OsStr, name: &str, line: u64, column: u64) {
+        self.warn(
+            "B012",
+            &format!(
+                "\\{} without citekey in {}:{}:{}",
+                name,
+                file.to_string_lossy(),
+                line,
+                column
+            ),
+        );
+    }
+
+    // Bib files are resolved like \input files; if they are not found,
+    // loading them will tell.
+    fn bib(&mut self, name: String, resolver: &Resolver) {
+        let path = match resolver.resolve(&name, FileKind::Bib) {
+            Some(p) => p.into_os_string(),
+            None => OsString::from(name),
+        };
+        if !self.bibs.contains(&path) {
+            self.bibs.push(path);
+        }
+    }
+
+    fn cite(&mut self, c: Cite, file: &OsString) {
+        let c = Citation::new(c, file, self);
+        if self.events {
+            events::emit(
+                "citation-found",
+                json!({
+                    "file": c.file.to_string_lossy(),
+                    "key": c.key,
+                    "line": c.line,
+                    "column": c.column,
+                    "kind": c.kind.name()
+                }),
+            );
+        }
+        self.cites.push(c);
+    }
+
+    fn enter(&mut self, s: Section, numbered: bool) {
+        let level = s.level;
+        let name = self.name(s, numbered);
+        self.units[level] = Some(name.clone());
+        for u in self.units[level + 1..].iter_mut() {
+            *u = None;
+        }
+        self.section = Some(name);
+    }
+
+    fn name(&mut self, s: Section, numbered: bool) -> String {
+        if s.starred || !numbered {
+            return s.title;
+        }
+        self.counters[s.level] += 1;
+        for c in self.counters[s.level + 1..].iter_mut() {
+            *c = 0;
+        }
+        let counters = if s.level == 0 {
+            &self.counters[..1]
+        } else {
+            &self.counters[1..=s.level]
+        };
+        let number: Vec<String> = counters
+            .iter()
+            .skip_while(|c| **c == 0)
+            .map(|c| c.to_string())
+            .collect();
+        format!("{} {}", number.join("."), s.title)
+    }
+}
+
+// Returns the citations of all documents in document order.
+pub fn collect(docs: &Documents) -> Result<Vec<Citation>, SourceError> {
+    let mut w = Walk::new(docs);
+    w.events = events::on();
+
+    if docs.stdin {
+        let resolver = Resolver::new(Path::new(""), &docs.search);
+        let file = OsString::from("<stdin>");
+        if w.events {
+            events::emit("file-started", json!({ "file": "<stdin>" }));
+        }
+        let items = get_items_from_stdin(&docs.tex, docs.syntax.unwrap_or(Syntax::Tex))?;
+        walk(items, &file, &resolver, &mut w)?;
+        if w.events {
+            events::emit("file-done", json!({ "file": "<stdin>", "cached": false }));
+        }
+        return Ok(w.cites);
+    }
+
+    for root in &docs.roots {
+        let path = docs.root(root);
+        let resolver = Resolver::for_file(&path, &docs.search);
+        scan_file(&path, Some(&resolver), &mut w)?;
+    }
+
+    for file in &docs.files {
+        w.section = None;
+        w.units = Default::default();
+        scan_file(Path::new(file), None, &mut w)?;
+    }
+
+    Ok(w.cites)
+}
+
+// Returns the citations of a document given as text, like content
+// pasted into a web page; 'name' stands for the file in locations.
+// Without a filesystem, \input and \include are not followed.
+pub fn collect_text(name: &str, text: &str, tex: &TexConfig) -> Result<Vec<Citation>, SourceError> {
+    let docs = Documents {
+        roots: Vec::new(),
+        files: Vec::new(),
+        stdin: false,
+        search: SearchConfig::default(),
+        cache: None,
+        tex: tex.clone(),
+        syntax: None,
+    };
+    let mut w = Walk::new(&docs);
+    let file = OsString::from(name);
+    let items = match Syntax::of(Path::new(name)) {
+        Syntax::Tex => error::parse_source(&file, text.as_bytes(), |s| {
+            parser::collect_cites_with(s, tex)
+        })?,
+        syntax => markup_items(text, syntax),
+    };
+    for item in items {
+        match item {
+            TexItem::Cite(c) => w.cite(c, &file),
+            TexItem::Section(s) => w.enter(s, true),
+            TexItem::Input(_) | TexItem::Bib(_) | TexItem::Alias(..) | TexItem::Malformed(..) => (),
+        }
+    }
+    Ok(w.cites)
+}
+
+// Returns the files that are scanned, in the order in which
+// they are scanned. To follow \input and \include the documents
+// are scanned anyway, but stdin is not read; warnings are left to collect.
+pub fn files(docs: &Documents) -> Result<Vec<OsString>, SourceError> {
+    if docs.stdin {
+        return Ok(vec![OsString::from("<stdin>")]);
+    }
+    let mut w = Walk::new(docs);
+    w.quiet = true;
+    for root in &docs.roots {
+        let path = docs.root(root);
+        let resolver = Resolver::for_file(&path, &docs.search);
+        scan_file(&path, Some(&resolver), &mut w)?;
+    }
+    for file in &docs.files {
+        scan_file(Path::new(file), None, &mut w)?;
+    }
+    Ok(w.files)
+}
+
+// Returns the bib files named in the documents with \bibliography
+// or \addbibresource, in the order in which they are named.
+// Like files, stdin is not read.
+pub fn bib_files(docs: &Documents) -> Result<Vec<OsString>, SourceError> {
+    if docs.stdin {
+        return Ok(Vec::new());
+    }
+    let mut w = Walk::new(docs);
+    w.quiet = true;
+    for root in &docs.roots {
+        let path = docs.root(root);
+        let resolver = Resolver::for_file(&path, &docs.search);
+        scan_file(&path, Some(&resolver), &mut w)?;
+    }
+    for file in &docs.files {
+        scan_file(Path::new(file), None, &mut w)?;
+    }
+    Ok(w.bibs)
+}
+
+fn scan_file(
+    path: &Path,
+    resolver: Option<&Resolver>,
+    w: &mut Walk<'_>,
+) -> Result<(), SourceError> {
+    let canon = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if !w.seen.insert(canon.clone()) {
+        return Ok(());
+    }
+
+    let file = path.as_os_str().to_os_string();
+    w.files.push(file.clone());
+    if w.events {
+        events::emit("file-started", json!({ "file": file.to_string_lossy() }));
+    }
+    // scanning anything but tex is cheap, so it is not cached
+    let syntax = w.syntax.unwrap_or_else(|| Syntax::of(path));
+    let cached = w
+        .cache
+        .filter(|_| syntax == Syntax::Tex)
+        .and_then(|c| c.get(&canon, &w.tex.aliases));
+    let hit = cached.is_some();
+    let items = match cached {
+        Some(items) => items,
+        None if syntax != Syntax::Tex => get_items_from_markup(&file, syntax)?,
+        None => {
+            let items = get_items_from_file(&file, &w.tex)?;
+            if let Some(c) = w.cache {
+                c.put(&canon, &w.tex.aliases, &items);
+            }
+            items
+        }
+    };
+    match resolver {
+        Some(r) => walk(items, &file, r, w)?,
+        None => {
+            let resolver = Resolver::for_file(path, w.search);
+            for item in items {
+                match item {
+                    TexItem::Cite(c) => w.cite(c, &file),
+                    TexItem::Section(s) => w.enter(s, false),
+                    TexItem::Input(_) => (),
+                    TexItem::Bib(b) => w.bib(b, &resolver),
+                    TexItem::Alias(name, target) => w.tex.aliases.push((name, target)),
+                    TexItem::Malformed(name, line, column) => {
+                        w.malformed(&file, &name, line, column)
+                    }
+                }
+            }
+        }
+    }
+    if w.events {
+        events::emit(
+            "file-done",
+            json!({ "file": file.to_string_lossy(), "cached": hit }),
+        );
+    }
+    Ok(())
+}
+
+fn walk(
+    items: Vec<TexItem>,
+    file: &OsString,
+    resolver: &Resolver,
+    w: &mut Walk<'_>,
+) -> Result<(), SourceError> {
+    for item in items {
+        match item {
+            TexItem::Cite(c) => w.cite(c, file),
+            TexItem::Section(s) => w.enter(s, true),
+            TexItem::Bib(b) => w.bib(b, resolver),
+            TexItem::Alias(name, target) => w.tex.aliases.push((name, target)),
+            TexItem::Malformed(name, line, column) => w.malformed(file, &name, line, column),
+            TexItem::Input(f) => match resolver.resolve(&f, FileKind::Tex) {
+                Some(p) => scan_file(&p, Some(resolver), w)?,
+                None => w.warn("B004", &format!("Input file {} not found", f)),
+            },
+        }
+    }
+    Ok(())
+}
+
+fn get_items_from_file(path: &OsStr, tex: &TexConfig) -> Result<Vec<TexItem>, SourceError> {
+    error::parse_file(path, |s| parser::collect_cites_with(s, tex))
+}
+
+fn get_items_from_markup(path: &OsStr, syntax: Syntax) -> Result<Vec<TexItem>, SourceError> {
+    let source = std::fs::read(path).map_err(|e| SourceError::io(path, e))?;
+    Ok(markup_items(&String::from_utf8_lossy(&source), syntax))
+}
+
+// The items of a Markdown or Typst document, an aux or a control file,
+// which are scanned leniently: there is nothing these scanners reject.
+fn markup_items(text: &str, syntax: Syntax) -> Vec<TexItem> {
+    match syntax {
+        Syntax::Typst => typst::collect_cites(text),
+        Syntax::Aux => auxfile::collect_cites(text),
+        Syntax::Bcf => bcf::collect_cites(text),
+        _ => markdown::collect_cites(text),
+    }
+}
+
+// stdin is read at once, so that errors can show the offending line
+fn get_items_from_stdin(tex: &TexConfig, syntax: Syntax) -> Result<Vec<TexItem>, SourceError> {
+    let name = OsStr::new("<stdin>");
+    let mut source = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut source)
+        .map_err(|e| SourceError::io(name, e))?;
+    match syntax {
+        Syntax::Tex => error::parse_source(name, &source, |s| parser::collect_cites_with(s, tex)),
+        syntax => Ok(markup_items(&String::from_utf8_lossy(&source), syntax)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn documents(roots: Vec<OsString>, files: Vec<OsString>) -> Documents {
+        Documents {
+            roots,
+            files,
+            stdin: false,
+            search: SearchConfig::default(),
+            cache: None,
+            tex: TexConfig::default(),
+            syntax: None,
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let dir = env::temp_dir().join(format!("bibstats-docs-{}", std::process::id()));
+        fs::create_dir_all(dir.join("chapters")).unwrap();
+        fs::write(
+            dir.join("main.tex"),
+            "\\section{Intro}\\cite{a}\n\\input{chapters/one}\\input{chapters/one}\\input{missing}\n\
+             \\section{End}\\cite{c}\\bibliography{refs}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("chapters/one.tex"), "\\subsection{One}\\cite{b}\n").unwrap();
+        fs::write(dir.join("refs.bib"), "").unwrap();
+        fs::write(dir.join("notes.tex"), "\\section{Notes}\\cite{d}\n").unwrap();
+        let main = dir.join("main.tex").into_os_string();
+        let one = dir.join("chapters/one.tex").into_os_string();
+        let notes = dir.join("notes.tex").into_os_string();
+        let docs = documents(vec![main.clone()], vec![notes.clone(), main.clone()]);
+
+        // every file is scanned once, in the order it is included
+        assert!(match files(&docs) {
+            Ok(files) => files == [main.clone(), one.clone(), notes.clone()],
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        });
+        assert!(match bib_files(&docs) {
+            Ok(bibs) => bibs == [dir.join("refs.bib").into_os_string()],
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        });
+        // sections of files found in directories are not numbered
+        assert!(match collect(&docs) {
+            Ok(cites) => cites
+                .iter()
+                .map(|c| (c.key.as_str(), &c.file, c.section.as_deref()))
+                .eq([
+                    ("a", &main, Some("1 Intro")),
+                    ("b", &one, Some("1.1 One")),
+                    ("c", &main, Some("2 End")),
+                    ("d", &notes, Some("Notes")),
+                ]),
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let docs = documents(vec![OsString::from("/nonexistent/main.tex")], Vec::new());
+        assert!(collect(&docs).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use rustc_hash::FxHashMap;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::parser::{BibEntry, PubType};
+use crate::stats::BibMap;
+
+// A cited work that is not published yet: an @unpublished entry
+// or one whose pubstate says it is in preparation or submitted.
+// Many venues do not allow citing such works, or only with
+// the consent of their authors.
+pub struct Draft<'a> {
+    pub entry: &'a BibEntry,
+    pub state: String,
+    pub sites: Vec<&'a Citation>,
+}
+
+// The pubstates of biblatex for works that are not accepted yet;
+// forthcoming and inpress works may usually be cited.
+static DRAFT_STATES: &[&str] = &["inpreparation", "submitted"];
+
+// Returns the drafts in the order of their first citation.
+pub fn compute<'a>(bib: &'a BibMap, cites: &'a [Citation]) -> Vec<Draft<'a>> {
+    let mut v: Vec<Draft> = Vec::new();
+    let mut idx: FxHashMap<&str, usize> = FxHashMap::default();
+    for c in cites {
+        if let Some(&i) = idx.get(c.key.as_str()) {
+            v[i].sites.push(c);
+            continue;
+        }
+        let Some(e) = bib.get(&c.key) else {
+            continue;
+        };
+        if let Some(state) = state(e) {
+            idx.insert(&c.key, v.len());
+            v.push(Draft {
+                entry: e,
+                state,
+                sites: vec![c],
+            });
+        }
+    }
+    v
+}
+
+fn state(e: &BibEntry) -> Option<String> {
+    let pubstate = e
+        .field("pubstate")
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| DRAFT_STATES.contains(&s.as_str()));
+    match pubstate {
+        Some(s) => Some(s),
+        None if e.pubtype == PubType::Unpublished => Some("unpublished".to_string()),
+        None => None,
+    }
+}
+
+pub fn print_drafts(v: &[Draft], f: Format) {
+    match f {
+        Format::Json(j) => drafts_as_json(v, j),
+        Format::Tsv => drafts_as_tsv(v),
+        Format::Markdown => drafts_as_markdown(v),
+    }
+}
+
+fn locations(d: &Draft) -> Vec<String> {
+    d.sites.iter().map(|c| c.location()).collect()
+}
+
+fn drafts_as_tsv(v: &[Draft]) {
+    for d in v {
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t{}\t\"{}\"",
+            d.entry.key,
+            d.entry.author,
+            d.entry.title,
+            d.state,
+            d.sites.len(),
+            locations(d).join(",")
+        );
+    }
+}
+
+fn drafts_as_markdown(v: &[Draft]) {
+    let rows = v.iter().map(|d| {
+        vec![
+            d.entry.key.clone(),
+            d.entry.author.clone(),
+            d.entry.title.clone(),
+            d.state.clone(),
+            d.sites.len().to_string(),
+            locations(d).join(", "),
+        ]
+    });
+    output::print_md_table(
+        &["Key", "Author", "Title", "State", "Count", "Cited at"],
+        rows,
+    );
+}
+
+fn drafts_as_json(v: &[Draft], json: Json) {
+    let records = v.iter().map(|d| {
+        json!({
+            "key": d.entry.key,
+            "author": d.entry.author,
+            "title": d.entry.title,
+            "state": d.state,
+            "defined_at": d.entry.location(),
+            "count": d.sites.len(),
+            "sites": locations(d)
+        })
+    });
+    output::print_json(records, json, "drafts");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+
+    #[test]
+    fn test_drafts() {
+        let entry = |key: &str, pubtype: PubType, pubstate: Option<&str>| {
+            let mut e = BibEntry {
+                key: key.to_string(),
+                pubtype,
+                ..BibEntry::empty()
+            };
+            if let Some(s) = pubstate {
+                e.fields.insert("pubstate".to_string(), s.to_string());
+            }
+            (e.key.clone(), e)
+        };
+        let bib: BibMap = [
+            entry("pub", PubType::Article, Some("forthcoming")),
+            entry("prep", PubType::Article, Some("InPreparation")),
+            entry("unpub", PubType::Unpublished, None),
+        ]
+        .into_iter()
+        .collect();
+        let cite = |key: &str, line| Citation {
+            file: "a.tex".into(),
+            line,
+            ..citation(key)
+        };
+        let cites = vec![
+            cite("unpub", 1),
+            cite("pub", 2),
+            cite("prep", 3),
+            cite("missing", 4),
+            cite("unpub", 5),
+        ];
+        let v = compute(&bib, &cites);
+        let found: Vec<(&str, &str, usize)> = v
+            .iter()
+            .map(|d| (d.entry.key.as_str(), d.state.as_str(), d.sites.len()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("unpub", "unpublished", 2), ("prep", "inpreparation", 1)]
+        );
+    }
+
+    #[test]
+    fn test_draft_state() {
+        let entry = |pubtype, pubstate: &str| {
+            let mut e = BibEntry {
+                pubtype,
+                ..BibEntry::empty()
+            };
+            e.fields
+                .insert("pubstate".to_string(), pubstate.to_string());
+            e
+        };
+        // the pubstate wins over the type, unless it is not a draft state
+        assert_eq!(
+            state(&entry(PubType::Unpublished, " Submitted ")).as_deref(),
+            Some("submitted")
+        );
+        assert_eq!(
+            state(&entry(PubType::Unpublished, "forthcoming")).as_deref(),
+            Some("unpublished")
+        );
+        assert_eq!(state(&entry(PubType::Article, "inpress")), None);
+    }
+}
@@ -0,0 +1,170 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+
+use pacosso::{Opts, ParseError, ParseResult, Stream};
+
+// A parse error of a bib or tex file, reported like compilers do:
+// the file, line and column, the reason and the offending line
+// with a caret below the position.
+// Errors without a position, like I/O errors, have line 0.
+#[derive(Debug)]
+pub struct SourceError {
+    pub file: String,
+    pub line: u64,
+    pub column: u64,
+    pub snippet: String,
+    pub reason: String,
+}
+
+impl SourceError {
+    // The position of the parser is a byte offset into the source;
+    // we count lines ourselves, since the parser counts only
+    // the line breaks it skips as whitespace.
+    pub fn new(file: &OsStr, source: &[u8], e: ParseError) -> SourceError {
+        let (reason, offset) = explain(e);
+        SourceError::at(file, source, offset, reason)
+    }
+
+    // For errors of readers that do not use the parser,
+    // like that of Hayagriva files.
+    pub fn at(file: &OsStr, source: &[u8], offset: Option<usize>, reason: String) -> SourceError {
+        let mut err = SourceError {
+            file: file.to_string_lossy().to_string(),
+            line: 0,
+            column: 0,
+            snippet: String::new(),
+            reason,
+        };
+        if let Some(offset) = offset {
+            let offset = offset.min(source.len());
+            let start = source[..offset]
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map_or(0, |i| i + 1);
+            let end = source[offset..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map_or(source.len(), |i| offset + i);
+            err.line = source[..start].iter().filter(|b| **b == b'\n').count() as u64 + 1;
+            err.column = String::from_utf8_lossy(&source[start..offset])
+                .chars()
+                .count() as u64
+                + 1;
+            err.snippet = String::from_utf8_lossy(&source[start..end])
+                .trim_end_matches('\r')
+                .to_string();
+        }
+        err
+    }
+
+    // For errors found before the source could be read.
+    pub fn io(file: &OsStr, e: io::Error) -> SourceError {
+        SourceError::new(file, &[], ParseError::IOError(e))
+    }
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}: {}", self.file, self.reason);
+        }
+        writeln!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.reason
+        )?;
+        let margin = " ".repeat(self.line.to_string().len());
+        // tabs are kept, so that the caret stays below the position
+        let indent: String = self
+            .snippet
+            .chars()
+            .take(self.column as usize - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(f, "{} | {}", self.line, self.snippet)?;
+        write!(f, "{} | {}^", margin, indent)
+    }
+}
+
+// Parses a file read into memory, so that errors can show the offending line.
+pub fn parse_file<F, T>(path: &OsStr, parse: F) -> Result<T, SourceError>
+where
+    F: Fn(&mut Stream<io::Cursor<Vec<u8>>>) -> ParseResult<T>,
+{
+    let source = fs::read(path).map_err(|e| SourceError::io(path, e))?;
+    parse_source(path, &source, parse)
+}
+
+// Parses a source that is already read, like stdin; 'name' is
+// used in messages in place of the file name.
+pub fn parse_source<F, T>(name: &OsStr, source: &[u8], parse: F) -> Result<T, SourceError>
+where
+    F: Fn(&mut Stream<io::Cursor<Vec<u8>>>) -> ParseResult<T>,
+{
+    pacosso::parse_buffer(source, Opts::default(), parse)
+        .map_err(|e| SourceError::new(name, source, e))
+}
+
+// The reason in words and the byte offset where the error occurred.
+fn explain(e: ParseError) -> (String, Option<usize>) {
+    match e {
+        ParseError::Failed(msg, c) => (reason(&msg), Some(c.stream as usize)),
+        ParseError::Effect(msg, c, _) => (reason(&msg), Some(c.stream as usize)),
+        ParseError::Fatal(e) => explain(*e),
+        ParseError::IOError(e) => (format!("cannot read: {}", e), None),
+        ParseError::Option(msg) => (format!("invalid parser option: {}", msg), None),
+    }
+}
+
+// The parser reports bytes as numbers, as in 'expected byte: 61, have: 34'.
+fn reason(msg: &str) -> String {
+    if msg == "end of file" {
+        return "unexpected end of file".to_string();
+    }
+    let Some(rest) = msg.strip_prefix("expected byte: ") else {
+        return msg.to_string();
+    };
+    let Some((expected, have)) = rest.split_once(", have: ") else {
+        return msg.to_string();
+    };
+    match (expected.parse::<u8>(), have.parse::<u8>()) {
+        (Ok(e), Ok(h)) => format!("expected {}, found {}", show(e), show(h)),
+        _ => msg.to_string(),
+    }
+}
+
+fn show(b: u8) -> String {
+    match b {
+        b'\n' => "end of line".to_string(),
+        b if b.is_ascii_graphic() => format!("'{}'", b as char),
+        b' ' => "' '".to_string(),
+        b => format!("byte {}", b),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_source_error() {
+        let source = b"@book{a,\n  title = \"X\",\n  author  \"Y\"\n}\n";
+        let r = parse_source(OsStr::new("a.bib"), source, crate::parser::parse);
+        let e = r.unwrap_err();
+        assert_eq!((e.line, e.column), (3, 11));
+        assert_eq!(
+            e.to_string(),
+            "a.bib:3:11: expected '=', found '\"'\n\
+             3 |   author  \"Y\"\n  |           ^"
+        );
+
+        let e = SourceError::io(
+            OsStr::new("b.bib"),
+            io::Error::from(io::ErrorKind::NotFound),
+        );
+        assert_eq!(e.to_string(), "b.bib: cannot read: entity not found");
+    }
+}
@@ -0,0 +1,120 @@
+use rustc_hash::FxHashSet;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::lint::Style;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::BibMap;
+
+// How long a bibliography is rendered: the average number of lines
+// of an entry, including the space between entries, and the lines
+// on a page. Author-year styles need more lines, since they give
+// the names in full and start with the year.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Model {
+    pub lines_per_entry: f64,
+    pub lines_per_page: u32,
+}
+
+impl Model {
+    // The defaults fit a single-column article class page.
+    pub fn for_style(style: Option<Style>) -> Model {
+        let lines_per_entry = match style {
+            Some(Style::Numeric) => 2.5,
+            Some(Style::AuthorYear) => 3.0,
+            None => 2.75,
+        };
+        Model {
+            lines_per_entry,
+            lines_per_page: 50,
+        }
+    }
+}
+
+// The estimate for the works cited that are in the database;
+// the others do not show up in the bibliography.
+pub struct Estimate {
+    pub entries: usize,
+    pub model: Model,
+}
+
+impl Estimate {
+    pub fn lines(&self) -> f64 {
+        self.entries as f64 * self.model.lines_per_entry
+    }
+
+    // in tenths of pages; it is a rough estimate after all
+    pub fn pages(&self) -> f64 {
+        let pages = self.lines() / self.model.lines_per_page.max(1) as f64;
+        (pages * 10.0).ceil() / 10.0
+    }
+}
+
+pub fn compute(bib: &BibMap, cites: &[Citation], model: Model) -> Estimate {
+    let entries: FxHashSet<&str> = cites
+        .iter()
+        .filter(|c| bib.contains_key(&c.key))
+        .map(|c| c.key.as_str())
+        .collect();
+    Estimate {
+        entries: entries.len(),
+        model,
+    }
+}
+
+pub fn print_estimate(e: &Estimate, f: Format) {
+    match f {
+        Format::Json(j) => estimate_as_json(e, j),
+        Format::Tsv => println!(
+            "{}\t{}\t{}\t{}\t{}",
+            e.entries,
+            e.model.lines_per_entry,
+            e.model.lines_per_page,
+            (e.lines().ceil() as u64),
+            e.pages()
+        ),
+        Format::Markdown => output::print_md_table(
+            &[
+                "Entries",
+                "Lines per entry",
+                "Lines per page",
+                "Lines",
+                "Pages",
+            ],
+            std::iter::once(vec![
+                e.entries.to_string(),
+                e.model.lines_per_entry.to_string(),
+                e.model.lines_per_page.to_string(),
+                (e.lines().ceil() as u64).to_string(),
+                e.pages().to_string(),
+            ]),
+        ),
+    }
+}
+
+fn estimate_as_json(e: &Estimate, json: Json) {
+    let record = json!({
+        "entries": e.entries,
+        "lines_per_entry": e.model.lines_per_entry,
+        "lines_per_page": e.model.lines_per_page,
+        "lines": (e.lines().ceil() as u64),
+        "pages": e.pages()
+    });
+    output::print_json(std::iter::once(record), json, "page-estimate");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pages() {
+        let e = Estimate {
+            entries: 41,
+            model: Model::for_style(Some(Style::Numeric)),
+        };
+        assert_eq!(e.lines(), 102.5);
+        assert_eq!(e.pages(), 2.1);
+    }
+}
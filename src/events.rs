@@ -0,0 +1,35 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde_json::{json, Value};
+
+// Progress events for tools that wrap bibstats, like GUIs:
+// with --events, every event is a JSON object on a line of its own
+// on stderr, e.g.
+//
+//   {"event":"file-started","file":"main.tex"}
+//   {"event":"citation-found","file":"main.tex","key":"knuth84","line":3,"column":1,"kind":"plain"}
+//   {"event":"warning","code":"B002","message":"Citekey x not in database"}
+//   {"event":"file-done","file":"main.tex","cached":false}
+//
+// Warnings are then events instead of text; other messages are not.
+static ON: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ON.store(true, Ordering::Relaxed);
+}
+
+pub fn on() -> bool {
+    ON.load(Ordering::Relaxed)
+}
+
+// Prints the event with its fields, which must be an object.
+pub fn emit(event: &str, fields: Value) {
+    if !on() {
+        return;
+    }
+    let mut js = json!({ "event": event });
+    if let (Some(o), Value::Object(fs)) = (js.as_object_mut(), fields) {
+        o.extend(fs);
+    }
+    eprintln!("{}", js);
+}
@@ -0,0 +1,94 @@
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::slice;
+
+use serde_json::json;
+
+use crate::api;
+
+// The C ABI for editor plugins, in C, C++ or, with a FFI, Lua:
+// bib and LaTeX are passed as bytes with their length, results are
+// returned as JSON text, which the caller frees with bibstats_free.
+// On errors the JSON is an object {"error": message}.
+// See include/bibstats.h.
+
+/// The entries of the bib text.
+///
+/// # Safety
+/// bib must point to len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bibstats_parse_bib(bib: *const u8, len: usize) -> *mut c_char {
+    json(api::entries(&text(bib, len)))
+}
+
+/// The citations of the LaTeX text.
+///
+/// # Safety
+/// tex must point to len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bibstats_scan_tex(tex: *const u8, len: usize) -> *mut c_char {
+    json(api::citations(&text(tex, len)))
+}
+
+/// The citations of the LaTeX text per author and work.
+///
+/// # Safety
+/// bib and tex must point to bib_len and tex_len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bibstats_stats(
+    bib: *const u8,
+    bib_len: usize,
+    tex: *const u8,
+    tex_len: usize,
+) -> *mut c_char {
+    json(api::stats(&text(bib, bib_len), &text(tex, tex_len)))
+}
+
+/// Frees a result.
+///
+/// # Safety
+/// s must be a result of this library that is not freed yet, or null.
+#[no_mangle]
+pub unsafe extern "C" fn bibstats_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+// Invalid UTF-8 is replaced, as when reading files.
+unsafe fn text(p: *const u8, len: usize) -> String {
+    if p.is_null() || len == 0 {
+        return String::new();
+    }
+    String::from_utf8_lossy(slice::from_raw_parts(p, len)).into_owned()
+}
+
+fn json(r: Result<serde_json::Value, String>) -> *mut c_char {
+    let js = r.unwrap_or_else(|e| json!({ "error": e }));
+    // JSON escapes control characters, so there is no NUL in it
+    CString::new(js.to_string()).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_ffi() {
+        let tex = b"\\cite{a}";
+        unsafe {
+            let r = bibstats_scan_tex(tex.as_ptr(), tex.len());
+            let s = CStr::from_ptr(r).to_str().unwrap().to_string();
+            bibstats_free(r);
+            assert!(s.starts_with("[{\"chapter\":null,"));
+            let bib = b"@book{a,";
+            let r = bibstats_parse_bib(bib.as_ptr(), bib.len());
+            assert!(CStr::from_ptr(r)
+                .to_str()
+                .unwrap()
+                .starts_with("{\"error\":"));
+            bibstats_free(r);
+        }
+    }
+}
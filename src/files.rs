@@ -3,20 +3,28 @@ use std::ffi::OsString;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub fn get_bib_file(bib: &Option<OsString>) -> Result<OsString, String> {
+use crate::resolve::{FileKind, Resolver, SearchConfig};
+
+// A bib file given by name is looked up in the search paths and BIBINPUTS
+// if it is not found relative to the current directory.
+pub fn get_bib_file(bib: &Option<OsString>, search: &SearchConfig) -> Result<OsString, String> {
     match bib {
-        Some(file) => Ok(file.clone()),
+        Some(file) => {
+            let name = file.to_string_lossy();
+            match Resolver::new(Path::new(""), search).resolve(&name, FileKind::Bib) {
+                Some(p) => Ok(p.into_os_string()),
+                None => Ok(file.clone()),
+            }
+        }
         None => find_bib(),
     }
 }
 
-pub fn get_all_files(
-    files: &Vec<OsString>,
-    dirs: &Vec<OsString>,
-    ext: &Vec<OsString>,
-) -> Result<Vec<OsString>, String> {
-    let mut v = files.clone();
-    let extset: HashSet<OsString> = ext.clone().into_iter().collect();
+// Returns all files with one of the given extensions
+// found in the given directories.
+pub fn get_dir_files(dirs: &[OsString], ext: &[OsString]) -> Result<Vec<OsString>, String> {
+    let mut v = Vec::new();
+    let extset: HashSet<OsString> = ext.iter().cloned().collect();
     get_files_from_dirs(dirs, &extset, &mut v)?;
     Ok(v)
 }
@@ -24,17 +32,15 @@ pub fn get_all_files(
 fn find_bib() -> Result<OsString, String> {
     let p: OsString = ".".into();
     if let Ok(entries) = fs::read_dir(&p) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let fname = entry.file_name();
-                match Path::new(&fname).extension() {
-                    Some(ext) => {
-                        if ext == "bib" {
-                            return Ok(fname);
-                        }
+        for entry in entries.flatten() {
+            let fname = entry.file_name();
+            match Path::new(&fname).extension() {
+                Some(ext) => {
+                    if ext == "bib" {
+                        return Ok(fname);
                     }
-                    None => continue,
                 }
+                None => continue,
             }
         }
     }
@@ -42,7 +48,7 @@ fn find_bib() -> Result<OsString, String> {
 }
 
 fn get_files_from_dirs(
-    dirs: &Vec<OsString>,
+    dirs: &[OsString],
     extset: &HashSet<OsString>,
     v: &mut Vec<OsString>,
 ) -> Result<(), String> {
@@ -57,24 +63,22 @@ fn get_files_from_dir(
     extset: &HashSet<OsString>,
     v: &mut Vec<OsString>,
 ) -> Result<(), String> {
-    if let Ok(entries) = fs::read_dir(&dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let fname = entry.file_name();
-                let p = Path::new(&fname);
-                if p.is_dir() {
-                    get_files_from_dir(&fname, extset, v)?;
-                    continue;
-                }
-                match p.extension() {
-                    Some(ext) => {
-                        if extset.contains(ext) {
-                            let p: PathBuf = [dir, &fname].iter().collect();
-                            v.push(p.into_os_string());
-                        }
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let fname = entry.file_name();
+            let p = Path::new(&fname);
+            if p.is_dir() {
+                get_files_from_dir(&fname, extset, v)?;
+                continue;
+            }
+            match p.extension() {
+                Some(ext) => {
+                    if extset.contains(ext) {
+                        let p: PathBuf = [dir, &fname].iter().collect();
+                        v.push(p.into_os_string());
                     }
-                    None => continue,
                 }
+                None => continue,
             }
         }
     }
@@ -0,0 +1,23 @@
+// Fixtures shared by the tests.
+
+use std::ffi::OsString;
+
+use crate::docs::Citation;
+use crate::parser::CiteKind;
+
+// A citation of key at the first line of an unnamed file,
+// for tests to fill in what they need.
+pub fn citation(key: &str) -> Citation {
+    Citation {
+        key: key.to_string(),
+        file: OsString::new(),
+        line: 1,
+        column: 1,
+        section: None,
+        chapter: None,
+        prenote: None,
+        postnote: None,
+        quoted: false,
+        kind: CiteKind::Plain,
+    }
+}
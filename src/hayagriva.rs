@@ -0,0 +1,528 @@
+// Hayagriva, the YAML bibliography format of Typst: a mapping
+// of citekeys to entries, like
+//
+//     knuth84:
+//       type: book
+//       title: The TeXbook
+//       author: Knuth, Donald E.
+//       date: 1984
+//
+// The entries are read into the fields of BibTeX, so that they
+// are counted like those of bib files. Only the part of YAML that
+// bibliographies use is read: block mappings and sequences, flow
+// sequences and mappings, plain and quoted scalars, literal and
+// folded block scalars and comments.
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use crate::parser::{BibEntry, BibFile, PubType};
+
+// What is wrong where, as a byte offset into the text.
+pub type YamlError = (usize, String);
+
+// Reads the entries of a Hayagriva file.
+pub fn parse(text: &str) -> Result<BibFile, YamlError> {
+    let mut y = Yaml {
+        lines: lines(text),
+        pos: 0,
+    };
+    let mut entries = Vec::new();
+    while let Some(l) = y.peek() {
+        if l.indent > 0 {
+            return Err((l.offset, "unexpected indentation".to_string()));
+        }
+        let (start, line) = (l.offset, l.number);
+        let (key, value) = key_value(l.text).ok_or((start, "citekey expected".to_string()))?;
+        if !value.is_empty() {
+            return Err((start, format!("entry {} is no mapping", key)));
+        }
+        y.pos += 1;
+        let Value::Object(fields) = y.node(1)? else {
+            return Err((start, format!("entry {} is no mapping", key)));
+        };
+        let end = y.peek().map_or(text.len(), |l| l.offset);
+        let span = start..start + text[start..end].trim_end().len();
+        entries.push(entry(unquoted(key), &fields, line, span));
+    }
+    Ok(BibFile {
+        entries,
+        ..BibFile::default()
+    })
+}
+
+// The types of Hayagriva with a BibTeX equivalent; the type of
+// articles and chapters depends on their parent, see entry.
+static TYPES: &[(&str, PubType)] = &[
+    ("article", PubType::Article),
+    ("book", PubType::Book),
+    ("anthology", PubType::Book),
+    ("chapter", PubType::Inbook),
+    ("anthos", PubType::Incol),
+    ("proceedings", PubType::Proceedings),
+    ("report", PubType::Techreport),
+    ("thesis", PubType::Phd),
+    ("manuscript", PubType::Unpublished),
+    ("misc", PubType::Misc),
+];
+
+// Fields named differently in BibTeX.
+static FIELDS: &[(&str, &str)] = &[
+    ("page-range", "pages"),
+    ("issue", "number"),
+    ("location", "address"),
+];
+
+// The identifiers in serial-number named differently in BibTeX.
+static SERIALS: &[(&str, &str)] = &[("arxiv", "eprint")];
+
+fn entry(
+    key: String,
+    fields: &Map<String, Value>,
+    line: u64,
+    span: std::ops::Range<usize>,
+) -> BibEntry {
+    let mut e = BibEntry {
+        key,
+        line,
+        span,
+        ..BibEntry::empty()
+    };
+    let kind = fields
+        .get("type")
+        .and_then(text)
+        .unwrap_or_default()
+        .to_lowercase();
+    let parent = match fields.get("parent") {
+        Some(Value::Array(ps)) => ps.first().and_then(Value::as_object),
+        Some(p) => p.as_object(),
+        None => None,
+    };
+    let parent_kind = parent
+        .and_then(|p| p.get("type"))
+        .and_then(text)
+        .unwrap_or_default()
+        .to_lowercase();
+    e.pubtype = match (kind.as_str(), parent_kind.as_str()) {
+        ("article", "proceedings" | "conference") => PubType::Inproc,
+        ("chapter" | "anthos", "proceedings" | "conference") => PubType::Inproc,
+        ("chapter", "anthology") => PubType::Incol,
+        ("web", _) => PubType::Other("online".to_string()),
+        ("", _) => PubType::Misc,
+        (k, _) => TYPES
+            .iter()
+            .find(|(n, _)| *n == k)
+            .map_or_else(|| PubType::Other(k.to_string()), |(_, t)| t.clone()),
+    };
+
+    let mut map = HashMap::new();
+    for (name, v) in fields {
+        match name.as_str() {
+            "type" | "parent" => (),
+            "title" => e.title = text(v).unwrap_or_default(),
+            "author" => e.author = names(v),
+            "editor" => {
+                map.insert("editor".to_string(), names(v));
+            }
+            "date" => e.date = text(v).unwrap_or_default(),
+            "publisher" => {
+                let p = v.get("name").unwrap_or(v);
+                map.insert("publisher".to_string(), text(p).unwrap_or_default());
+            }
+            "url" => {
+                let url = v.get("value").unwrap_or(v);
+                map.insert("url".to_string(), text(url).unwrap_or_default());
+                if let Some(d) = v.get("date").and_then(text) {
+                    map.insert("urldate".to_string(), d);
+                }
+            }
+            "serial-number" => match v {
+                Value::Object(ids) => {
+                    for (id, n) in ids {
+                        let id = SERIALS
+                            .iter()
+                            .find(|(s, _)| s == id)
+                            .map_or(id.as_str(), |(_, b)| b);
+                        map.insert(id.to_string(), text(n).unwrap_or_default());
+                    }
+                }
+                n => {
+                    map.insert("number".to_string(), text(n).unwrap_or_default());
+                }
+            },
+            _ => {
+                let name = FIELDS
+                    .iter()
+                    .find(|(h, _)| h == name)
+                    .map_or(name.as_str(), |(_, b)| b);
+                if let Some(t) = text(v) {
+                    map.insert(name.to_string(), t);
+                }
+            }
+        }
+    }
+
+    // the parent is the journal of an article or the book of
+    // a chapter, which may also give the volume, the date and so on
+    if let Some(p) = parent {
+        if let Some(t) = p.get("title").and_then(text) {
+            let name = match e.pubtype {
+                PubType::Article => "journal",
+                _ => "booktitle",
+            };
+            map.entry(name.to_string()).or_insert(t);
+        }
+        for (name, v) in p {
+            let name = FIELDS
+                .iter()
+                .find(|(h, _)| h == name)
+                .map_or(name.as_str(), |(_, b)| b);
+            match name {
+                "type" | "title" | "parent" | "author" => (),
+                "date" => {
+                    if e.date.is_empty() {
+                        e.date = text(v).unwrap_or_default();
+                    }
+                }
+                "editor" => {
+                    map.entry("editor".to_string()).or_insert_with(|| names(v));
+                }
+                _ => {
+                    if let Some(t) = text(v) {
+                        map.entry(name.to_string()).or_insert(t);
+                    }
+                }
+            }
+        }
+    }
+    e.fields = map;
+    e
+}
+
+// A value as text; titles and the like may be given as mappings
+// with the text as value and, for instance, a short form.
+fn text(v: &Value) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Object(m) => m.get("value").and_then(text),
+        Value::Array(vs) => Some(vs.iter().filter_map(text).collect::<Vec<_>>().join(", ")),
+        _ => None,
+    }
+}
+
+// A person or a list of persons, given as "Doe, Jane" or as
+// mappings of name and given-name, joined like BibTeX does.
+fn names(v: &Value) -> String {
+    let name = |v: &Value| match v {
+        Value::Object(m) => {
+            let last = m.get("name").and_then(text).unwrap_or_default();
+            match m.get("given-name").and_then(text) {
+                Some(g) => format!("{}, {}", last, g),
+                None => last,
+            }
+        }
+        v => text(v).unwrap_or_default(),
+    };
+    match v {
+        Value::Array(vs) => vs.iter().map(name).collect::<Vec<_>>().join(" and "),
+        v => name(v),
+    }
+}
+
+// A line of the YAML text: its indentation, its content without
+// comment and surrounding space, the line as it is for block
+// scalars, the offset of the content in the text and the line number.
+struct Line<'a> {
+    indent: usize,
+    text: &'a str,
+    raw: &'a str,
+    offset: usize,
+    number: u64,
+}
+
+fn lines(text: &str) -> Vec<Line<'_>> {
+    let mut v = Vec::new();
+    let mut offset = 0;
+    for (i, l) in text.split_inclusive('\n').enumerate() {
+        let raw = l.trim_end_matches(['\n', '\r']);
+        let indent = raw.len() - raw.trim_start_matches(' ').len();
+        let content = without_comment(raw).trim();
+        // the document markers
+        let content = if content == "---" || content == "..." {
+            ""
+        } else {
+            content
+        };
+        v.push(Line {
+            indent,
+            text: content,
+            raw,
+            offset: offset + indent,
+            number: i as u64 + 1,
+        });
+        offset += l.len();
+    }
+    v
+}
+
+// A comment starts with '#' at the start or after a space,
+// outside of quotes.
+fn without_comment(l: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in l.char_indices() {
+        match (quote, c) {
+            (None, '#') if prev.is_whitespace() => return &l[..i],
+            (None, '"' | '\'') if prev.is_whitespace() || prev == ':' => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => (),
+        }
+        prev = c;
+    }
+    l
+}
+
+struct Yaml<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+impl<'a> Yaml<'a> {
+    // The next line with content.
+    fn peek(&mut self) -> Option<&Line<'a>> {
+        while self.pos < self.lines.len() && self.lines[self.pos].text.is_empty() {
+            self.pos += 1;
+        }
+        self.lines.get(self.pos)
+    }
+
+    // A block mapping or sequence indented by at least indent.
+    fn node(&mut self, indent: usize) -> Result<Value, YamlError> {
+        let Some(l) = self.peek() else {
+            return Ok(Value::Null);
+        };
+        if l.indent < indent {
+            return Ok(Value::Null);
+        }
+        let indent = l.indent;
+        if is_item(l.text) {
+            self.sequence(indent)
+        } else {
+            self.mapping(indent)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value, YamlError> {
+        let mut items = Vec::new();
+        while let Some(l) = self.peek() {
+            if l.indent != indent || !is_item(l.text) {
+                break;
+            }
+            let rest = l.text[1..].trim_start();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(self.node(indent + 1)?);
+            } else if key_value(rest).is_some() {
+                // a mapping starting on the line of the item,
+                // indented like the text after the dash
+                let l = &mut self.lines[self.pos];
+                let shift = l.text.len() - rest.len();
+                l.indent += shift;
+                l.offset += shift;
+                l.text = rest;
+                let indent = l.indent;
+                items.push(self.mapping(indent)?);
+            } else {
+                self.pos += 1;
+                items.push(scalar(rest));
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value, YamlError> {
+        let mut m = Map::new();
+        while let Some(l) = self.peek() {
+            if l.indent < indent {
+                break;
+            }
+            let offset = l.offset;
+            if l.indent > indent {
+                return Err((offset, "unexpected indentation".to_string()));
+            }
+            if is_item(l.text) {
+                break;
+            }
+            let (key, value) =
+                key_value(l.text).ok_or((offset, "key: value expected".to_string()))?;
+            self.pos += 1;
+            let value = if value.is_empty() {
+                match self.peek() {
+                    Some(n) if n.indent > indent => self.node(indent + 1)?,
+                    Some(n) if n.indent == indent && is_item(n.text) => self.sequence(indent)?,
+                    _ => Value::Null,
+                }
+            } else if value.starts_with(['|', '>']) {
+                self.block_scalar(indent, value.starts_with('|'))
+            } else {
+                scalar(value)
+            };
+            m.insert(unquoted(key), value);
+        }
+        Ok(Value::Object(m))
+    }
+
+    // The lines indented more than the key, without their common
+    // indentation; literal scalars keep the line breaks, folded ones
+    // join the lines with spaces.
+    fn block_scalar(&mut self, indent: usize, literal: bool) -> Value {
+        let start = self.pos;
+        while self.pos < self.lines.len() {
+            let l = &self.lines[self.pos];
+            if !l.raw.trim().is_empty() && l.indent <= indent {
+                break;
+            }
+            self.pos += 1;
+        }
+        let block = &self.lines[start..self.pos];
+        let common = block
+            .iter()
+            .filter(|l| !l.raw.trim().is_empty())
+            .map(|l| l.indent)
+            .min()
+            .unwrap_or(0);
+        let text: Vec<&str> = block
+            .iter()
+            .map(|l| l.raw.get(common..).unwrap_or("").trim_end())
+            .collect();
+        let sep = if literal { "\n" } else { " " };
+        Value::String(text.join(sep).trim().to_string())
+    }
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+// The key and the value of a line like key: value, where the key
+// may be quoted and the value may be empty.
+fn key_value(text: &str) -> Option<(&str, &str)> {
+    let end = match text.chars().next()? {
+        q @ ('"' | '\'') => text[1..].find(q)? + 2,
+        '[' | '{' => return None,
+        _ => 0,
+    };
+    let colon = end
+        + text[end..]
+            .find(": ")
+            .or_else(|| text[end..].strip_suffix(':').map(str::len))?;
+    Some((text[..colon].trim(), text[colon + 1..].trim()))
+}
+
+// A scalar, a flow sequence like [a, b] or a flow mapping
+// like {value: x, short: y}; nothing is converted to numbers.
+fn scalar(s: &str) -> Value {
+    let s = s.trim();
+    if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        return Value::Array(
+            split_flow(inner)
+                .into_iter()
+                .filter(|i| !i.is_empty())
+                .map(scalar)
+                .collect(),
+        );
+    }
+    if let Some(inner) = s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+        let m = split_flow(inner)
+            .into_iter()
+            .filter_map(|i| {
+                let (k, v) = key_value(i)?;
+                Some((unquoted(k), scalar(v)))
+            })
+            .collect();
+        return Value::Object(m);
+    }
+    match s {
+        "~" | "null" | "" => Value::Null,
+        s => Value::String(unquoted(s)),
+    }
+}
+
+// The items of a flow collection, separated by commas outside
+// of quotes and nested collections.
+fn split_flow(s: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut depth, mut quote, mut start) = (0, None, 0);
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[' | '{') => depth += 1,
+            (None, ']' | '}') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                items.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    items.push(s[start..].trim());
+    items
+}
+
+fn unquoted(s: &str) -> String {
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        s[1..s.len() - 1]
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\")
+    } else if s.len() >= 2 && s.starts_with('\'') && s.ends_with('\'') {
+        s[1..s.len() - 1].replace("''", "'")
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "# my library\n\
+                    knuth84:\n  type: Book\n  title: The TeXbook\n  author: Knuth, Donald E.\n  date: 1984\n  \
+                    serial-number:\n    isbn: 0-201-13447-0\n\n\
+                    \"lamport:86\":\n  type: article\n  title: {value: 'LaTeX: A Document', short: LaTeX}\n  \
+                    author:\n    - Lamport, Leslie\n    - name: Knuth\n      given-name: Donald\n  \
+                    page-range: 1-10 # pages\n  parent:\n    type: periodical\n    title: TUGboat\n    \
+                    volume: 7\n    date: 1986-03\n  abstract: |\n    First line.\n\n    # not a comment\n";
+        let bib = parse(text).unwrap();
+        let e = &bib.entries[0];
+        assert_eq!(
+            (e.key.as_str(), &e.pubtype, e.line),
+            ("knuth84", &PubType::Book, 2)
+        );
+        assert_eq!(e.title, "The TeXbook");
+        assert_eq!(e.year(), Some("1984"));
+        assert_eq!(e.field("isbn"), Some("0-201-13447-0"));
+        let source = &text[e.span.clone()];
+        assert!(source.starts_with("knuth84:") && source.ends_with("0-201-13447-0"));
+
+        let e = &bib.entries[1];
+        assert_eq!(
+            (e.key.as_str(), &e.pubtype),
+            ("lamport:86", &PubType::Article)
+        );
+        assert_eq!(e.title, "LaTeX: A Document");
+        assert_eq!(e.author, "Lamport, Leslie and Knuth, Donald");
+        assert_eq!(e.field("pages"), Some("1-10"));
+        assert_eq!(e.field("journal"), Some("TUGboat"));
+        assert_eq!(e.field("volume"), Some("7"));
+        assert_eq!(e.year(), Some("1986"));
+        assert_eq!(e.field("abstract"), Some("First line.\n\n# not a comment"));
+
+        let err = parse("a:\n  title: A\n    author: B\n").unwrap_err();
+        assert_eq!((err.0, err.1.as_str()), (18, "unexpected indentation"));
+    }
+}
@@ -0,0 +1,153 @@
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::BibMap;
+
+// How old the works cited in each section are: the citations
+// per section and decade of publication, for heatmaps.
+// Sections are in document order, decades in ascending order;
+// citations of works without year have no decade.
+pub struct Heatmap {
+    pub sections: Vec<Option<String>>,
+    pub decades: Vec<Option<i32>>,
+    // counts[section][decade]
+    pub counts: Vec<Vec<u32>>,
+}
+
+pub fn compute(bib: &BibMap, cites: &[Citation]) -> Heatmap {
+    let decade = |c: &Citation| {
+        bib.get(&c.key)
+            .and_then(|e| e.year_number())
+            .map(|y| y.div_euclid(10) * 10)
+    };
+    let mut sections: Vec<Option<String>> = Vec::new();
+    let mut decades: Vec<Option<i32>> = Vec::new();
+    for c in cites {
+        if !sections.contains(&c.section) {
+            sections.push(c.section.clone());
+        }
+        let d = decade(c);
+        if !decades.contains(&d) {
+            decades.push(d);
+        }
+    }
+    // None, for works without year, sorts last
+    decades.sort_unstable_by_key(|d| (d.is_none(), *d));
+
+    let mut counts = vec![vec![0; decades.len()]; sections.len()];
+    for c in cites {
+        let s = sections.iter().position(|s| *s == c.section).unwrap();
+        let d = decade(c);
+        let d = decades.iter().position(|x| *x == d).unwrap();
+        counts[s][d] += 1;
+    }
+    Heatmap {
+        sections,
+        decades,
+        counts,
+    }
+}
+
+fn decade_name(d: &Option<i32>) -> String {
+    match d {
+        Some(d) => format!("{}s", d),
+        None => "n.d.".to_string(),
+    }
+}
+
+// The cells with citations, for tools that read long tables.
+fn cells(h: &Heatmap) -> impl Iterator<Item = (&str, &Option<i32>, u32)> {
+    h.sections.iter().enumerate().flat_map(move |(i, s)| {
+        h.decades
+            .iter()
+            .zip(&h.counts[i])
+            .filter(|(_, n)| **n > 0)
+            .map(move |(d, n)| (s.as_deref().unwrap_or(""), d, *n))
+    })
+}
+
+pub fn print_heatmap(h: &Heatmap, f: Format) {
+    match f {
+        Format::Json(j) => heatmap_as_json(h, j),
+        Format::Tsv => heatmap_as_tsv(h),
+        Format::Markdown => heatmap_as_markdown(h),
+    }
+}
+
+fn heatmap_as_tsv(h: &Heatmap) {
+    for (s, d, n) in cells(h) {
+        println!("\"{}\"\t\"{}\"\t{}", s, decade_name(d), n);
+    }
+}
+
+// In Markdown, the matrix itself, with a column per decade.
+fn heatmap_as_markdown(h: &Heatmap) {
+    let names: Vec<String> = h.decades.iter().map(decade_name).collect();
+    let mut header = vec!["Section"];
+    header.extend(names.iter().map(String::as_str));
+    let rows = h.sections.iter().zip(&h.counts).map(|(s, counts)| {
+        let mut row = vec![output::md_cell(s.as_deref().unwrap_or(""))];
+        row.extend(counts.iter().map(|n| n.to_string()));
+        row
+    });
+    output::print_md_table(&header, rows);
+}
+
+fn heatmap_as_json(h: &Heatmap, json: Json) {
+    let records = cells(h).map(|(s, d, n)| {
+        json!({
+            "section": s,
+            "decade": d,
+            "count": n
+        })
+    });
+    output::print_json(records, json, "heatmap");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+    use crate::parser::BibEntry;
+
+    #[test]
+    fn test_heatmap() {
+        let bib: BibMap = [("a", "1987"), ("b", "2021-05"), ("c", "")]
+            .iter()
+            .map(|(k, date)| {
+                let e = BibEntry {
+                    key: k.to_string(),
+                    date: date.to_string(),
+                    ..BibEntry::empty()
+                };
+                (k.to_string(), e)
+            })
+            .collect();
+        let cite = |key: &str, section: &str| Citation {
+            section: Some(section.to_string()),
+            ..citation(key)
+        };
+        let cites = vec![
+            cite("b", "1 Intro"),
+            cite("c", "1 Intro"),
+            cite("a", "2 History"),
+            cite("a", "2 History"),
+            cite("b", "2 History"),
+        ];
+        let h = compute(&bib, &cites);
+        assert_eq!(h.decades, vec![Some(1980), Some(2020), None]);
+        assert_eq!(h.counts, vec![vec![0, 1, 1], vec![2, 1, 0]]);
+        assert_eq!(cells(&h).count(), 4);
+    }
+
+    #[test]
+    fn test_no_section_no_year() {
+        // citations outside sections and of works not in the bib
+        let cites = vec![citation("a"), citation("b")];
+        let h = compute(&BibMap::default(), &cites);
+        assert_eq!((h.sections, h.decades), (vec![None], vec![None]));
+        assert_eq!(h.counts, vec![vec![2]]);
+    }
+}
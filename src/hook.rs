@@ -0,0 +1,116 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// The git pre-commit hook runs validate on the staged tex files;
+// if only bib files are staged, it validates the bib files alone.
+// The options for the bib files are those given to 'hook install'.
+pub fn script(opts: &[&OsStr]) -> String {
+    let bibstats = command(opts);
+    format!(
+        r#"#!/bin/sh
+# pre-commit hook installed by 'bibstats hook install':
+# checks that the staged tex and bib files parse and all citekeys resolve
+IFS='
+'
+tex=$(git diff --cached --name-only --diff-filter=ACMR -- '*.tex')
+bib=$(git diff --cached --name-only --diff-filter=ACMR -- '*.bib')
+if [ -z "$tex" ] && [ -z "$bib" ]; then
+    exit 0
+fi
+if [ -z "$tex" ]; then
+    exec {bibstats} validate </dev/null
+fi
+exec {bibstats} validate $tex
+"#
+    )
+}
+
+// A local hook for the pre-commit framework, which passes
+// the staged files that match to validate.
+pub fn yaml(opts: &[&OsStr]) -> String {
+    format!(
+        r#"repos:
+  - repo: local
+    hooks:
+      - id: bibstats-validate
+        name: bibstats validate
+        entry: {} validate
+        language: system
+        files: \.(tex|bib)$
+"#,
+        command(opts)
+    )
+}
+
+// Writes the hook into the hooks directory of the repository
+// in the current directory; an existing hook is only replaced with force.
+pub fn install(opts: &[&OsStr], force: bool) -> Result<PathBuf, String> {
+    let out = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()
+        .map_err(|e| format!("cannot run git: {}", e))?;
+    if !out.status.success() {
+        return Err(format!(
+            "not in a git repository: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    let dir = PathBuf::from(String::from_utf8_lossy(&out.stdout).trim());
+    let path = dir.join("pre-commit");
+    if path.exists() && !force {
+        return Err(format!(
+            "{} exists, use --force to replace it",
+            path.display()
+        ));
+    }
+    fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&path, script(opts)))
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    make_executable(&path)?;
+    Ok(path)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &std::path::Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("cannot make {} executable: {}", path.display(), e))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_: &std::path::Path) -> Result<(), String> {
+    Ok(())
+}
+
+fn command(opts: &[&OsStr]) -> String {
+    let mut words = vec!["bibstats".to_string()];
+    words.extend(opts.iter().map(|o| quote(&o.to_string_lossy())));
+    words.join(" ")
+}
+
+// Quotes a word for the shell if it needs it.
+fn quote(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_./=:".contains(&b));
+    if plain {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let opts = [OsStr::new("-b"), OsStr::new("my refs.bib")];
+        assert_eq!(command(&opts), "bibstats -b 'my refs.bib'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert!(script(&opts).contains("exec bibstats -b 'my refs.bib' validate $tex"));
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use ureq::tls::{parse_pem, PemItem, RootCerts, TlsConfig};
+use ureq::Proxy;
+
+// The configuration of the HTTP client all network code uses.
+// The proxy is taken from ALL_PROXY, HTTPS_PROXY or HTTP_PROXY
+// (or their lowercase forms), hosts in NO_PROXY are accessed directly.
+// With a CA certificate file, the certificates in it are trusted
+// instead of the built-in roots, which is what a TLS-intercepting
+// corporate proxy needs.
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub ca_cert: Option<PathBuf>,
+    pub timeout: Duration,
+}
+
+impl Default for HttpConfig {
+    fn default() -> HttpConfig {
+        HttpConfig {
+            ca_cert: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+pub fn agent(cfg: &HttpConfig) -> Result<ureq::Agent, String> {
+    let mut tls = TlsConfig::builder();
+    if let Some(path) = &cfg.ca_cert {
+        tls = tls.root_certs(RootCerts::new_with_certs(&read_certs(path)?));
+    }
+    let config = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(Some(cfg.timeout))
+        .user_agent(concat!("bibstats/", env!("CARGO_PKG_VERSION")))
+        .proxy(Proxy::try_from_env())
+        .tls_config(tls.build())
+        .build();
+    Ok(config.into())
+}
+
+fn read_certs(path: &Path) -> Result<Vec<ureq::tls::Certificate<'static>>, String> {
+    let pem = fs::read(path)
+        .map_err(|e| format!("cannot read CA certificate {}: {}", path.display(), e))?;
+    let mut certs = Vec::new();
+    for item in parse_pem(&pem) {
+        match item {
+            Ok(PemItem::Certificate(c)) => certs.push(c),
+            Ok(_) => (),
+            Err(e) => return Err(format!("invalid CA certificate {}: {}", path.display(), e)),
+        }
+    }
+    if certs.is_empty() {
+        return Err(format!("no certificate in {}", path.display()));
+    }
+    Ok(certs)
+}
@@ -0,0 +1,201 @@
+// Identifiers of works: DOIs, ISBNs and ISSNs.
+
+// Prefixes under which DOIs are commonly written.
+const DOI_PREFIXES: [&str; 5] = [
+    "https://doi.org/",
+    "http://doi.org/",
+    "https://dx.doi.org/",
+    "http://dx.doi.org/",
+    "doi:",
+];
+
+// Normalizes a DOI: strips resolver prefixes and lowercases it
+// (DOIs are case-insensitive). Fails if the result is not
+// a DOI, i.e. 10.<registrant>/<suffix>, where the registrant
+// consists of dot-separated numbers.
+pub fn normalize_doi(doi: &str) -> Result<String, String> {
+    let mut d = doi.trim();
+    for p in DOI_PREFIXES {
+        if d.len() >= p.len() && d[..p.len()].eq_ignore_ascii_case(p) {
+            d = d[p.len()..].trim_start();
+            break;
+        }
+    }
+    let d = d.to_lowercase();
+    let (prefix, suffix) = match d.split_once('/') {
+        Some(ps) => ps,
+        None => return Err(format!("DOI '{}' has no suffix", doi)),
+    };
+    let mut parts = prefix.split('.');
+    if parts.next() != Some("10") {
+        return Err(format!("DOI '{}' does not start with '10.'", doi));
+    }
+    let registrant: Vec<&str> = parts.collect();
+    if registrant.is_empty()
+        || registrant
+            .iter()
+            .any(|r| r.is_empty() || !r.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return Err(format!("DOI '{}' has an invalid registrant", doi));
+    }
+    if suffix.is_empty() || suffix.chars().any(|c| c.is_whitespace()) {
+        return Err(format!("DOI '{}' has an invalid suffix", doi));
+    }
+    Ok(d)
+}
+
+// Removes hyphens and spaces and an "ISBN" or "ISSN" label,
+// so that identifiers can be compared.
+fn strip(id: &str, label: &str) -> String {
+    let id = id.trim();
+    let id = match id.get(..label.len()) {
+        Some(l) if l.eq_ignore_ascii_case(label) => id[label.len()..].trim_start_matches(':'),
+        _ => id,
+    };
+    id.chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn digit(c: char) -> Option<u32> {
+    c.to_digit(10)
+}
+
+// Normalizes an ISBN-10 or ISBN-13 (hyphens removed)
+// and verifies its check digit.
+pub fn normalize_isbn(isbn: &str) -> Result<String, String> {
+    let n = strip(isbn, "ISBN");
+    let chars: Vec<char> = n.chars().collect();
+    let ok = match chars.len() {
+        10 => {
+            let mut sum = 0;
+            for (i, c) in chars.iter().enumerate() {
+                let d = match (digit(*c), *c) {
+                    (Some(d), _) => d,
+                    (None, 'X') if i == 9 => 10,
+                    _ => return Err(format!("ISBN '{}' has invalid characters", isbn)),
+                };
+                sum += d * (10 - i as u32);
+            }
+            sum % 11 == 0
+        }
+        13 => {
+            let mut sum = 0;
+            for (i, c) in chars.iter().enumerate() {
+                let d = match digit(*c) {
+                    Some(d) => d,
+                    None => return Err(format!("ISBN '{}' has invalid characters", isbn)),
+                };
+                sum += if i % 2 == 0 { d } else { 3 * d };
+            }
+            sum % 10 == 0
+        }
+        _ => return Err(format!("ISBN '{}' has neither 10 nor 13 digits", isbn)),
+    };
+    if !ok {
+        return Err(format!("ISBN '{}' has a wrong check digit", isbn));
+    }
+    Ok(n)
+}
+
+// Normalizes an ISSN (hyphen removed) and verifies its check digit.
+pub fn normalize_issn(issn: &str) -> Result<String, String> {
+    let n = strip(issn, "ISSN");
+    let chars: Vec<char> = n.chars().collect();
+    if chars.len() != 8 {
+        return Err(format!("ISSN '{}' does not have 8 digits", issn));
+    }
+    let mut sum = 0;
+    for (i, c) in chars.iter().enumerate() {
+        let d = match (digit(*c), *c) {
+            (Some(d), _) => d,
+            (None, 'X') if i == 7 => 10,
+            _ => return Err(format!("ISSN '{}' has invalid characters", issn)),
+        };
+        sum += d * (8 - i as u32);
+    }
+    if sum % 11 != 0 {
+        return Err(format!("ISSN '{}' has a wrong check digit", issn));
+    }
+    Ok(n)
+}
+
+// Fields may hold several identifiers, e.g. for print and online editions.
+pub fn split_ids(field: &str) -> impl Iterator<Item = &str> {
+    field
+        .split([',', ';'])
+        .map(|i| i.trim())
+        .filter(|i| !i.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_doi() {
+        for d in [
+            "10.1000/ABC.123",
+            "https://doi.org/10.1000/abc.123",
+            "http://dx.doi.org/10.1000/abc.123",
+            "doi: 10.1000/abc.123",
+            " DOI:10.1000/Abc.123 ",
+        ] {
+            assert_eq!(normalize_doi(d), Ok("10.1000/abc.123".to_string()));
+        }
+        assert_eq!(
+            normalize_doi("10.1000.10/x"),
+            Ok("10.1000.10/x".to_string())
+        );
+    }
+
+    #[test]
+    fn test_malformed_doi() {
+        for d in [
+            "",
+            "10.1000",
+            "11.1000/abc",
+            "10./abc",
+            "10.1a/abc",
+            "10.1000/",
+            "10.1000/a b",
+            "https://example.org/10.1000/abc",
+        ] {
+            assert!(normalize_doi(d).is_err(), "{} should be malformed", d);
+        }
+    }
+
+    #[test]
+    fn test_isbn() {
+        assert_eq!(
+            normalize_isbn("978-3-16-148410-0"),
+            Ok("9783161484100".to_string())
+        );
+        assert_eq!(
+            normalize_isbn("ISBN 0-306-40615-2"),
+            Ok("0306406152".to_string())
+        );
+        assert_eq!(
+            normalize_isbn("0-8044-2957-x"),
+            Ok("080442957X".to_string())
+        );
+        for i in [
+            "978-3-16-148410-1",
+            "0-306-40615-3",
+            "12345",
+            "97831614841X0",
+        ] {
+            assert!(normalize_isbn(i).is_err(), "{} should be invalid", i);
+        }
+    }
+
+    #[test]
+    fn test_issn() {
+        assert_eq!(normalize_issn("0378-5955"), Ok("03785955".to_string()));
+        assert_eq!(normalize_issn("ISSN 2434-561x"), Ok("2434561X".to_string()));
+        for i in ["0378-5954", "0378-595", "X378-5955"] {
+            assert!(normalize_issn(i).is_err(), "{} should be invalid", i);
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use rustc_hash::FxHashMap;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::parser::BibEntry;
+use crate::stats::BibMap;
+
+// How a group relates to its parent:
+// independent of it, restricted to the entries of the parent
+// or extended by the entries of its subgroups.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Context {
+    Independent,
+    Intersection,
+    Union,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    All,
+    // Entries assigned to the group by its name in the groups field
+    // or, in files written by old versions of JabRef, by their keys.
+    Static(Vec<String>),
+    // Entries with the term in the field; regular expressions
+    // are not supported and such groups have no entries.
+    Keyword {
+        field: String,
+        term: String,
+        case: bool,
+        regex: bool,
+    },
+    // search groups and other kinds we do not evaluate
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub level: usize,
+    pub name: String,
+    pub context: Context,
+    pub kind: Kind,
+}
+
+// The citations of a group: the distinct cited entries in the group
+// and how often they are cited.
+pub struct Usage<'a> {
+    pub group: &'a Group,
+    pub path: String,
+    pub entries: usize,
+    pub count: u32,
+}
+
+// Reads the group tree from the jabref-meta comments of the bib file,
+// written as 'grouping:' by JabRef 3 and later and 'groupstree:' before.
+// Every group is terminated by ';' and its fields by '\;'.
+pub fn groups(comments: &[String]) -> Result<Vec<Group>, String> {
+    let mut v = Vec::new();
+    for c in comments {
+        let meta = match c.trim_start().strip_prefix("jabref-meta:") {
+            Some(m) => m.trim_start(),
+            None => continue,
+        };
+        let tree = match meta
+            .strip_prefix("grouping:")
+            .or_else(|| meta.strip_prefix("groupstree:"))
+        {
+            Some(t) => t,
+            None => continue,
+        };
+        for g in split_groups(tree) {
+            v.push(group(&g)?);
+        }
+    }
+    Ok(v)
+}
+
+// Splits the tree into groups and the groups into fields,
+// undoing the escapes.
+fn split_groups(tree: &str) -> Vec<Vec<String>> {
+    let mut groups = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = tree.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => fields.push(std::mem::take(&mut field)),
+                Some(c) => field.push(c),
+                None => field.push('\\'),
+            },
+            ';' => {
+                fields.push(std::mem::take(&mut field));
+                groups.push(std::mem::take(&mut fields));
+            }
+            '\n' | '\r' => (),
+            c => field.push(c),
+        }
+    }
+    if !field.trim().is_empty() {
+        fields.push(field);
+        groups.push(fields);
+    }
+    groups
+}
+
+fn group(fields: &[String]) -> Result<Group, String> {
+    let head = fields[0].trim_start();
+    let bad = || format!("invalid JabRef group '{}'", head);
+    let (level, rest) = head.split_once(' ').ok_or_else(bad)?;
+    let level: usize = level.parse().map_err(|_| bad())?;
+    let (kind, name) = rest.split_once(':').ok_or_else(bad)?;
+    let field = |i: usize| fields.get(i).map_or("", |f| f.as_str());
+    let context = match field(1) {
+        "1" => Context::Intersection,
+        "2" => Context::Union,
+        _ => Context::Independent,
+    };
+    let kind = match kind {
+        "AllEntriesGroup" => Kind::All,
+        "StaticGroup" => Kind::Static(Vec::new()),
+        // the keys follow the context, the last field is empty
+        "ExplicitGroup" => Kind::Static(
+            fields
+                .iter()
+                .skip(2)
+                .filter(|k| !k.is_empty())
+                .cloned()
+                .collect(),
+        ),
+        "KeywordGroup" => Kind::Keyword {
+            field: field(2).to_lowercase(),
+            term: field(3).to_string(),
+            case: field(4) == "1",
+            regex: field(5) == "1",
+        },
+        k => Kind::Other(k.to_string()),
+    };
+    Ok(Group {
+        level,
+        name: name.to_string(),
+        context,
+        kind,
+    })
+}
+
+impl Group {
+    // The entry matches the group's own criterion.
+    fn matches(&self, e: &BibEntry) -> bool {
+        match &self.kind {
+            Kind::All => true,
+            Kind::Static(keys) => {
+                keys.contains(&e.key)
+                    || e.field("groups")
+                        .is_some_and(|gs| gs.split(',').any(|g| g.trim() == self.name))
+            }
+            Kind::Keyword {
+                field,
+                term,
+                case,
+                regex,
+            } => {
+                if *regex || term.is_empty() {
+                    return false;
+                }
+                let value = match field.as_str() {
+                    "author" => Some(e.author.as_str()),
+                    "title" => Some(e.title.as_str()),
+                    "date" => Some(e.date.as_str()),
+                    f => e.field(f),
+                };
+                match value {
+                    Some(v) if *case => v.contains(term.as_str()),
+                    Some(v) => v.to_lowercase().contains(&term.to_lowercase()),
+                    None => false,
+                }
+            }
+            Kind::Other(_) => false,
+        }
+    }
+}
+
+// Whether the entry is in the group at index i, considering its context.
+fn contains(groups: &[Group], i: usize, e: &BibEntry) -> bool {
+    let g = &groups[i];
+    let own = match g.context {
+        Context::Union => g.matches(e) || children(groups, i).any(|c| contains(groups, c, e)),
+        _ => g.matches(e),
+    };
+    own && (g.context != Context::Intersection
+        || parent(groups, i).is_none_or(|p| contains(groups, p, e)))
+}
+
+fn children(groups: &[Group], i: usize) -> impl Iterator<Item = usize> + '_ {
+    let level = groups[i].level;
+    (i + 1..groups.len())
+        .take_while(move |&j| groups[j].level > level)
+        .filter(move |&j| groups[j].level == level + 1)
+}
+
+fn parent(groups: &[Group], i: usize) -> Option<usize> {
+    (0..i).rev().find(|&j| groups[j].level < groups[i].level)
+}
+
+// The citations of every group in the order of the tree,
+// except the group of all entries at the root.
+pub fn compute<'a>(groups: &'a [Group], bib: &BibMap, cites: &[Citation]) -> Vec<Usage<'a>> {
+    let mut counts: FxHashMap<&str, u32> = FxHashMap::default();
+    for c in cites {
+        *counts.entry(&c.key).or_default() += 1;
+    }
+    let mut path: Vec<&Group> = Vec::new();
+    let mut v = Vec::new();
+    for (i, g) in groups.iter().enumerate() {
+        while path.last().is_some_and(|p| p.level >= g.level) {
+            path.pop();
+        }
+        if g.kind == Kind::All {
+            continue;
+        }
+        path.push(g);
+        let names: Vec<&str> = path.iter().map(|p| p.name.as_str()).collect();
+        let mut u = Usage {
+            group: g,
+            path: names.join(" > "),
+            entries: 0,
+            count: 0,
+        };
+        for (key, n) in &counts {
+            if bib.get(*key).is_some_and(|e| contains(groups, i, e)) {
+                u.entries += 1;
+                u.count += n;
+            }
+        }
+        v.push(u);
+    }
+    v
+}
+
+pub fn print_usage(v: &[Usage], f: Format) {
+    match f {
+        Format::Json(j) => usage_as_json(v, j),
+        Format::Tsv => usage_as_tsv(v),
+        Format::Markdown => usage_as_markdown(v),
+    }
+}
+
+fn usage_as_tsv(v: &[Usage]) {
+    for u in v {
+        println!("\"{}\"\t{}\t{}", u.path, u.entries, u.count);
+    }
+}
+
+fn usage_as_markdown(v: &[Usage]) {
+    let rows = v
+        .iter()
+        .map(|u| vec![u.path.clone(), u.entries.to_string(), u.count.to_string()]);
+    output::print_md_table(&["Group", "Entries", "Count"], rows);
+}
+
+fn usage_as_json(v: &[Usage], json: Json) {
+    let records = v.iter().map(|u| {
+        json!({
+            "group": u.group.name,
+            "path": u.path,
+            "level": u.group.level,
+            "entries": u.entries,
+            "count": u.count
+        })
+    });
+    output::print_json(records, json, "jabref-groups");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_groups() {
+        let meta = "jabref-meta: grouping:\n0 AllEntriesGroup:;\n1 StaticGroup:Chapter 1\\;0\\;1\\;\\;\\;\\;;\n2 KeywordGroup:ML\\;1\\;keywords\\;learning\\;0\\;0\\;1\\;\\;\\;\\;;\n1 ExplicitGroup:Old\\;0\\;marx\\;smith\\;;\n";
+        let gs = groups(&[meta.to_string()]).unwrap();
+        assert_eq!(gs.len(), 4);
+        assert_eq!(gs[0].kind, Kind::All);
+        assert_eq!(gs[1].name, "Chapter 1");
+        assert_eq!(
+            gs[2],
+            Group {
+                level: 2,
+                name: "ML".to_string(),
+                context: Context::Intersection,
+                kind: Kind::Keyword {
+                    field: "keywords".to_string(),
+                    term: "learning".to_string(),
+                    case: false,
+                    regex: false,
+                },
+            }
+        );
+        assert_eq!(
+            gs[3].kind,
+            Kind::Static(vec!["marx".to_string(), "smith".to_string()])
+        );
+        assert_eq!(parent(&gs, 2), Some(1));
+        assert_eq!(children(&gs, 0).collect::<Vec<_>>(), vec![1, 3]);
+    }
+}
@@ -0,0 +1,299 @@
+use std::str::FromStr;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::diagnostics;
+use crate::docs::Citation;
+use crate::stats::BibMap;
+
+// How cited keys are matched to the keys of the database.
+// BibTeX and biber match exactly, which is the default;
+// the other strategies find keys that differ in case,
+// in the Unicode normalization, like an 'é' typed as 'e' with
+// combining accent, or that were truncated, for instance by
+// an editor that cuts keys at a length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyMatch {
+    Exact,
+    CaseInsensitive,
+    Nfc,
+    Nfkc,
+    Prefix,
+}
+
+impl FromStr for KeyMatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KeyMatch, String> {
+        match s {
+            "exact" => Ok(KeyMatch::Exact),
+            "case-insensitive" | "ignore-case" => Ok(KeyMatch::CaseInsensitive),
+            "nfc" => Ok(KeyMatch::Nfc),
+            "nfkc" => Ok(KeyMatch::Nfkc),
+            "prefix" => Ok(KeyMatch::Prefix),
+            _ => Err(format!(
+                "unknown key match '{}', 'exact', 'case-insensitive', \
+                 'nfc', 'nfkc' or 'prefix' expected",
+                s
+            )),
+        }
+    }
+}
+
+// Truncated keys shorter than this match too much.
+const MIN_PREFIX: usize = 3;
+
+impl KeyMatch {
+    fn normalize(self, key: &str) -> String {
+        match self {
+            KeyMatch::Exact | KeyMatch::Prefix => key.to_string(),
+            KeyMatch::CaseInsensitive => key.to_lowercase(),
+            KeyMatch::Nfc => key.nfc().collect(),
+            KeyMatch::Nfkc => key.nfkc().collect(),
+        }
+    }
+
+    // The keys of the database the cited key matches.
+    fn candidates<'a>(
+        self,
+        key: &str,
+        keys: &[&'a str],
+        index: &FxHashMap<String, Vec<&'a str>>,
+    ) -> Vec<&'a str> {
+        match self {
+            KeyMatch::Exact => Vec::new(),
+            KeyMatch::Prefix if key.chars().count() < MIN_PREFIX => Vec::new(),
+            KeyMatch::Prefix => keys
+                .iter()
+                .filter(|k| k.starts_with(key))
+                .copied()
+                .collect(),
+            _ => index.get(&self.normalize(key)).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+// Replaces the cited keys that are not in the database by the key
+// they match, if they match exactly one. Every such key is reported
+// once, since LaTeX does not find it; so are keys that match more
+// than one key, which are left as they are.
+pub fn apply(cites: Vec<Citation>, bib: &BibMap, m: KeyMatch) -> Vec<Citation> {
+    if m == KeyMatch::Exact {
+        return cites;
+    }
+    let mut keys: Vec<&str> = bib.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    let mut index: FxHashMap<String, Vec<&str>> = FxHashMap::default();
+    for k in &keys {
+        index.entry(m.normalize(k)).or_default().push(k);
+    }
+    let mut matched: FxHashMap<String, Option<String>> = FxHashMap::default();
+    cites
+        .into_iter()
+        .map(|mut c| {
+            if c.key == "*" || bib.contains_key(&c.key) {
+                return c;
+            }
+            let found = matched.entry(c.key.clone()).or_insert_with(|| {
+                match m.candidates(&c.key, &keys, &index).as_slice() {
+                    [] => None,
+                    [k] => {
+                        diagnostics::warn(
+                            "B011",
+                            &format!("Citekey {} matched {} at {}", c.key, k, c.location()),
+                        );
+                        Some(k.to_string())
+                    }
+                    ks => {
+                        diagnostics::warn(
+                            "B011",
+                            &format!(
+                                "Citekey {} matches {} at {}",
+                                c.key,
+                                ks.join(", "),
+                                c.location()
+                            ),
+                        );
+                        None
+                    }
+                }
+            });
+            if let Some(k) = found {
+                c.key = k.clone();
+            }
+            c
+        })
+        .collect()
+}
+
+// Cited keys that are not in the database are often typos;
+// the keys that are at most this many edits away are suggested.
+const MAX_DISTANCE: usize = 2;
+
+// The keys of the database closest to a missing key,
+// the closest first, at most three.
+pub fn suggestions<'a>(key: &str, bib: &'a BibMap) -> Vec<&'a str> {
+    let n = key.chars().count();
+    let mut v: Vec<(usize, &str)> = bib
+        .keys()
+        .filter(|k| k.chars().count().abs_diff(n) <= MAX_DISTANCE)
+        .map(|k| (distance(key, k), k.as_str()))
+        .filter(|(d, _)| *d <= MAX_DISTANCE)
+        .collect();
+    v.sort_unstable();
+    v.into_iter().take(3).map(|(_, k)| k).collect()
+}
+
+// The hint for a missing key, like "; did you mean `capital`?",
+// or nothing.
+pub fn did_you_mean(key: &str, bib: &BibMap) -> String {
+    let ks: Vec<String> = suggestions(key, bib)
+        .iter()
+        .map(|k| format!("`{}`", k))
+        .collect();
+    match ks.as_slice() {
+        [] => String::new(),
+        [k] => format!("; did you mean {}?", k),
+        [ks @ .., k] => format!("; did you mean {} or {}?", ks.join(", "), k),
+    }
+}
+
+// The cited keys that are not in the database but close to exactly
+// one key of it, with that key, in the order of their first citation.
+pub fn fixes<'a>(bib: &'a BibMap, cites: &'a [Citation]) -> Vec<(&'a str, &'a str)> {
+    let mut seen = FxHashSet::default();
+    cites
+        .iter()
+        .filter(|c| c.key != "*" && !bib.contains_key(&c.key) && seen.insert(c.key.as_str()))
+        .filter_map(|c| match suggestions(&c.key, bib).as_slice() {
+            [k] => Some((c.key.as_str(), *k)),
+            _ => None,
+        })
+        .collect()
+}
+
+// The Levenshtein distance of the strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let subst = prev + usize::from(ca != *cb);
+            prev = row[j + 1];
+            row[j + 1] = subst.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+    use crate::parser::BibEntry;
+
+    #[test]
+    fn test_key_match() {
+        let bib: BibMap = ["Knuth1984", "knuth1974", "Erd\u{e9}s1947", "dijkstra68"]
+            .iter()
+            .map(|k| {
+                (
+                    k.to_string(),
+                    BibEntry {
+                        key: k.to_string(),
+                        ..BibEntry::empty()
+                    },
+                )
+            })
+            .collect();
+        let cites = |keys: &[&str]| -> Vec<Citation> { keys.iter().map(|k| citation(k)).collect() };
+        let keys = |m: KeyMatch, ks: &[&str]| -> Vec<String> {
+            apply(cites(ks), &bib, m)
+                .into_iter()
+                .map(|c| c.key)
+                .collect()
+        };
+        let ks = ["knuth1984", "Erde\u{301}s1947", "dijkstra", "knuth"];
+        assert_eq!(keys(KeyMatch::Exact, &ks), ks);
+        assert_eq!(
+            keys(KeyMatch::CaseInsensitive, &ks),
+            ["Knuth1984", "Erde\u{301}s1947", "dijkstra", "knuth"]
+        );
+        assert_eq!(
+            keys(KeyMatch::Nfc, &ks),
+            ["knuth1984", "Erd\u{e9}s1947", "dijkstra", "knuth"]
+        );
+        assert_eq!(
+            keys(KeyMatch::Prefix, &ks),
+            ["knuth1984", "Erde\u{301}s1947", "dijkstra68", "knuth1974"]
+        );
+        assert_eq!(
+            keys(KeyMatch::Prefix, &["Knuth", "di"]),
+            ["Knuth1984", "di"]
+        );
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(distance("capital", "capital"), 0);
+        assert_eq!(distance("captial", "capital"), 2);
+        assert_eq!(distance("kapital", "capital"), 1);
+        assert_eq!(distance("", "abc"), 3);
+        let bib: BibMap = ["capital", "capita", "marx1867", "Capital2"]
+            .iter()
+            .map(|k| {
+                (
+                    k.to_string(),
+                    BibEntry {
+                        key: k.to_string(),
+                        ..BibEntry::empty()
+                    },
+                )
+            })
+            .collect();
+        assert_eq!(suggestions("captal", &bib), ["capital", "capita"]);
+        assert_eq!(
+            did_you_mean("kapital", &bib),
+            "; did you mean `capital`, `Capital2` or `capita`?"
+        );
+        assert_eq!(did_you_mean("engels", &bib), "");
+        let cites: Vec<Citation> = ["kapital", "marx1876", "capital", "marx1876", "engels"]
+            .iter()
+            .map(|k| citation(k))
+            .collect();
+        assert_eq!(fixes(&bib, &cites), [("marx1876", "marx1867")]);
+    }
+
+    #[test]
+    fn test_ambiguous_keys() {
+        let bib: BibMap = ["Knuth84", "KNUTH84", "knuth1984"]
+            .iter()
+            .map(|k| {
+                (
+                    k.to_string(),
+                    BibEntry {
+                        key: k.to_string(),
+                        ..BibEntry::empty()
+                    },
+                )
+            })
+            .collect();
+        let keys = |m: KeyMatch, ks: &[&str]| -> Vec<String> {
+            let cites = ks.iter().map(|k| citation(k)).collect();
+            apply(cites, &bib, m).into_iter().map(|c| c.key).collect()
+        };
+        // keys matching more than one key and \nocite{*} stay as they are
+        assert_eq!(
+            keys(KeyMatch::CaseInsensitive, &["knuth84", "*"]),
+            ["knuth84", "*"]
+        );
+        assert_eq!(keys(KeyMatch::Prefix, &["Knuth"]), ["Knuth84"]);
+        assert_eq!(
+            keys(KeyMatch::Prefix, &["knuth", "kn"]),
+            ["knuth1984", "kn"]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::parser::CiteKind;
+
+// How the citations of a chapter are presented: the number of
+// narrative, parenthetical, footnote, plain and \nocite citations,
+// in the order of CiteKind::ALL. Citations before the first chapter
+// and in documents without chapters or sections have no chapter.
+pub struct Chapter {
+    pub chapter: Option<String>,
+    pub counts: [u32; CiteKind::ALL.len()],
+}
+
+impl Chapter {
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+}
+
+// Returns the chapters in document order.
+// A chapter split across several files, which happens with
+// files found in directories, is counted where it first occurs.
+pub fn compute(cites: &[Citation]) -> Vec<Chapter> {
+    let mut v: Vec<Chapter> = Vec::new();
+    for c in cites {
+        let i = match v.iter().position(|ch| ch.chapter == c.chapter) {
+            Some(i) => i,
+            None => {
+                v.push(Chapter {
+                    chapter: c.chapter.clone(),
+                    counts: [0; CiteKind::ALL.len()],
+                });
+                v.len() - 1
+            }
+        };
+        let k = CiteKind::ALL.iter().position(|k| *k == c.kind).unwrap();
+        v[i].counts[k] += 1;
+    }
+    v
+}
+
+pub fn print_kinds(v: &[Chapter], f: Format) {
+    match f {
+        Format::Json(j) => kinds_as_json(v, j),
+        Format::Tsv => kinds_as_tsv(v),
+        Format::Markdown => kinds_as_markdown(v),
+    }
+}
+
+fn kinds_as_tsv(v: &[Chapter]) {
+    for ch in v {
+        let counts: Vec<String> = ch.counts.iter().map(|n| n.to_string()).collect();
+        println!(
+            "\"{}\"\t{}\t{}",
+            ch.chapter.as_deref().unwrap_or(""),
+            counts.join("\t"),
+            ch.total()
+        );
+    }
+}
+
+fn kinds_as_markdown(v: &[Chapter]) {
+    let rows = v.iter().map(|ch| {
+        let mut row = vec![ch.chapter.clone().unwrap_or_default()];
+        row.extend(ch.counts.iter().map(|n| n.to_string()));
+        row.push(ch.total().to_string());
+        row
+    });
+    output::print_md_table(
+        &[
+            "Chapter",
+            "Narrative",
+            "Parenthetical",
+            "Footnote",
+            "Plain",
+            "Nocite",
+            "Total",
+        ],
+        rows,
+    );
+}
+
+fn kinds_as_json(v: &[Chapter], json: Json) {
+    let records = v.iter().map(|ch| {
+        let mut js = json!({
+            "chapter": ch.chapter,
+            "total": ch.total()
+        });
+        for (k, n) in CiteKind::ALL.iter().zip(ch.counts) {
+            js[k.name()] = n.into();
+        }
+        js
+    });
+    output::print_json(records, json, "cite-kinds");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+
+    #[test]
+    fn test_kinds() {
+        let cite = |chapter: Option<&str>, kind| Citation {
+            chapter: chapter.map(String::from),
+            kind,
+            ..citation("a")
+        };
+        let cites = vec![
+            cite(None, CiteKind::Plain),
+            cite(Some("1 Intro"), CiteKind::Narrative),
+            cite(Some("1 Intro"), CiteKind::Footnote),
+            cite(Some("2 Method"), CiteKind::Parenthetical),
+            cite(Some("1 Intro"), CiteKind::Narrative),
+            cite(Some("2 Method"), CiteKind::Nocite),
+        ];
+        let v = compute(&cites);
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[0].counts, [0, 0, 0, 1, 0]);
+        assert_eq!(v[1].chapter.as_deref(), Some("1 Intro"));
+        assert_eq!(v[1].counts, [2, 0, 1, 0, 0]);
+        assert_eq!(v[2].counts, [0, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_kinds_split_chapter() {
+        // a chapter that comes back is counted where it first occurs
+        let cite = |chapter: &str| Citation {
+            chapter: Some(chapter.to_string()),
+            ..citation("a")
+        };
+        let v = compute(&[cite("Notes"), cite("Data"), cite("Notes")]);
+        let chapters: Vec<(&str, u32)> = v
+            .iter()
+            .map(|ch| (ch.chapter.as_deref().unwrap(), ch.total()))
+            .collect();
+        assert_eq!(chapters, vec![("Notes", 2), ("Data", 1)]);
+    }
+}
@@ -0,0 +1,34 @@
+//! The library behind the bibstats command: reading bib files,
+//! scanning LaTeX, Markdown and Typst documents for citations and
+//! counting them per author.
+//! The command adds the reports and the command line on top of it.
+
+pub mod api;
+pub mod auxfile;
+pub mod bcf;
+pub mod bib;
+pub mod cache;
+pub mod dates;
+pub mod diagnostics;
+pub mod docs;
+pub mod error;
+pub mod events;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(test)]
+mod fixtures;
+pub mod hayagriva;
+pub mod ids;
+pub mod keymatch;
+pub mod markdown;
+pub mod names;
+pub mod output;
+pub mod parser;
+#[cfg(feature = "python")]
+mod python;
+pub mod resolve;
+pub mod stats;
+pub mod typst;
+#[cfg(feature = "wasm")]
+mod wasm;
+pub mod weights;
@@ -0,0 +1,831 @@
+use std::ffi::OsString;
+use std::str::FromStr;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde_json::json;
+
+#[cfg(feature = "net")]
+use crate::crossref;
+use crate::diagnostics;
+use crate::docs::Citation;
+use crate::ids;
+use crate::keymatch;
+#[cfg(feature = "net")]
+use crate::netcache::NetCache;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::pages;
+use crate::parser::{BibEntry, CiteKind, PubType};
+use crate::stats::BibMap;
+use crate::suppress::Suppressions;
+
+// The family of the bibliography style.
+// Some checks only make sense for one of them:
+// numeric styles sort by year where there is no label to sort by,
+// author-year styles build the label from author (or editor) and year.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Numeric,
+    AuthorYear,
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Style, String> {
+        match s {
+            "numeric" => Ok(Style::Numeric),
+            "author-year" | "authoryear" => Ok(Style::AuthorYear),
+            _ => Err(format!(
+                "unknown style '{}', 'numeric' or 'author-year' expected",
+                s
+            )),
+        }
+    }
+}
+
+// What the rules check.
+pub struct Input<'a> {
+    pub bib: &'a BibMap,
+    pub cites: &'a [Citation],
+    // later definitions of citekeys already in bib
+    pub duplicates: &'a [BibEntry],
+    // the shares of recent works chapters must cite, see --recency,
+    // and the year they are counted back from
+    pub recency: &'a [Recency],
+    pub year: i32,
+    // rules that look things up on the net only run with it
+    #[cfg(feature = "net")]
+    pub net: Option<&'a NetCache>,
+}
+
+// A check on a cited entry, on all cited entries together,
+// returning the index of the entry with each finding,
+// or on the whole input.
+enum Check {
+    Entry(fn(&BibEntry) -> Option<String>),
+    All(fn(&[&BibEntry]) -> Vec<(usize, String)>),
+    Input(fn(&'static str, &Input) -> Vec<Finding>),
+}
+
+// A rule is identified by the code of its diagnostic.
+// A rule without styles applies to all styles.
+pub struct Rule {
+    pub code: &'static str,
+    pub styles: &'static [Style],
+    check: Check,
+}
+
+pub static RULES: &[Rule] = &[
+    Rule {
+        code: "B001",
+        styles: &[],
+        check: Check::Input(duplicate_key),
+    },
+    Rule {
+        code: "B002",
+        styles: &[],
+        check: Check::Input(missing_key),
+    },
+    Rule {
+        code: "L001",
+        styles: &[],
+        check: Check::Entry(missing_title),
+    },
+    Rule {
+        code: "L002",
+        styles: &[Style::Numeric],
+        check: Check::Entry(misc_without_year),
+    },
+    Rule {
+        code: "L003",
+        styles: &[Style::AuthorYear],
+        check: Check::Entry(no_author_or_editor),
+    },
+    Rule {
+        code: "L004",
+        styles: &[Style::AuthorYear],
+        check: Check::Entry(missing_year),
+    },
+    Rule {
+        code: "L005",
+        styles: &[],
+        check: Check::Entry(malformed_doi),
+    },
+    Rule {
+        code: "L006",
+        styles: &[],
+        check: Check::Entry(invalid_isbn),
+    },
+    Rule {
+        code: "L007",
+        styles: &[],
+        check: Check::Entry(invalid_issn),
+    },
+    Rule {
+        code: "L008",
+        styles: &[],
+        check: Check::Entry(reversed_pages),
+    },
+    Rule {
+        code: "L009",
+        styles: &[],
+        check: Check::Entry(page_range_dash),
+    },
+    Rule {
+        code: "L010",
+        styles: &[],
+        check: Check::Entry(unrealistic_pages),
+    },
+    Rule {
+        code: "L011",
+        styles: &[],
+        check: Check::All(duplicate_doi),
+    },
+    #[cfg(feature = "net")]
+    Rule {
+        code: "L012",
+        styles: &[],
+        check: Check::Input(retracted),
+    },
+    Rule {
+        code: "L013",
+        styles: &[],
+        check: Check::Entry(missing_required),
+    },
+    Rule {
+        code: "L014",
+        styles: &[],
+        check: Check::Input(min_recency),
+    },
+];
+
+fn duplicate_key(code: &'static str, input: &Input) -> Vec<Finding> {
+    input
+        .duplicates
+        .iter()
+        .map(|e| {
+            let msg = format!(
+                "duplicate citekey {}, first defined at {}",
+                e.key,
+                input.bib[&e.key].location()
+            );
+            Finding::at(code, e, msg)
+        })
+        .collect()
+}
+
+// Every citation of a missing key is a finding;
+// keys close to it are suggested.
+fn missing_key(code: &'static str, input: &Input) -> Vec<Finding> {
+    let mut hints: FxHashMap<&str, String> = FxHashMap::default();
+    input
+        .cites
+        .iter()
+        .filter(|c| !input.bib.contains_key(&c.key))
+        .map(|c| {
+            let hint = hints
+                .entry(&c.key)
+                .or_insert_with(|| keymatch::did_you_mean(&c.key, input.bib));
+            Finding {
+                code,
+                key: c.key.clone(),
+                file: c.file.clone(),
+                line: c.line,
+                message: format!("citekey {} not in database{}", c.key, hint),
+            }
+        })
+        .collect()
+}
+
+fn missing_title(e: &BibEntry) -> Option<String> {
+    if e.title.trim().is_empty() {
+        return Some("entry has no title".to_string());
+    }
+    None
+}
+
+fn misc_without_year(e: &BibEntry) -> Option<String> {
+    if e.pubtype == PubType::Misc && e.year().is_none() {
+        return Some("misc entry without year".to_string());
+    }
+    None
+}
+
+fn no_author_or_editor(e: &BibEntry) -> Option<String> {
+    if e.author.trim().is_empty() && e.field("editor").is_none() {
+        return Some("entry has neither author nor editor".to_string());
+    }
+    None
+}
+
+fn missing_year(e: &BibEntry) -> Option<String> {
+    if e.year().is_none() {
+        return Some("entry has no year".to_string());
+    }
+    None
+}
+
+fn malformed_doi(e: &BibEntry) -> Option<String> {
+    ids::normalize_doi(e.field("doi")?).err()
+}
+
+fn invalid_isbn(e: &BibEntry) -> Option<String> {
+    let errs: Vec<String> = ids::split_ids(e.field("isbn")?)
+        .filter_map(|i| ids::normalize_isbn(i).err())
+        .collect();
+    if errs.is_empty() {
+        None
+    } else {
+        Some(errs.join("; "))
+    }
+}
+
+fn invalid_issn(e: &BibEntry) -> Option<String> {
+    let errs: Vec<String> = ids::split_ids(e.field("issn")?)
+        .filter_map(|i| ids::normalize_issn(i).err())
+        .collect();
+    if errs.is_empty() {
+        None
+    } else {
+        Some(errs.join("; "))
+    }
+}
+
+fn reversed_pages(e: &BibEntry) -> Option<String> {
+    let pages = e.field("pages")?;
+    let (start, end) = pages::parse(pages).iter().find_map(|r| {
+        let (start, end) = r.numbers()?;
+        if end < start {
+            Some((start, end))
+        } else {
+            None
+        }
+    })?;
+    // 123--45 is a common abbreviation of 123--145
+    if end.to_string().len() < start.to_string().len() {
+        return Some(format!(
+            "abbreviated page range '{}', write the last page in full",
+            pages
+        ));
+    }
+    Some(format!("reversed page range {}--{}", start, end))
+}
+
+fn page_range_dash(e: &BibEntry) -> Option<String> {
+    let pages = e.field("pages")?;
+    if pages::parse(pages)
+        .iter()
+        .any(|r| matches!(r.end, Some((pages::Sep::Hyphen, _))))
+    {
+        return Some(format!("page range '{}' uses '-' instead of '--'", pages));
+    }
+    None
+}
+
+// A contribution of more than MAX_PAGES pages
+// or one starting at page 0 is likely a typo.
+const MAX_PAGES: u64 = 1000;
+
+fn unrealistic_pages(e: &BibEntry) -> Option<String> {
+    let pages = e.field("pages")?;
+    pages::parse(pages).iter().find_map(|r| match r.numbers() {
+        Some((0, _)) => Some(format!("page range '{}' starts at page 0", pages)),
+        Some((start, end)) if end > start && end - start >= MAX_PAGES => Some(format!(
+            "page range '{}' spans {} pages",
+            pages,
+            end - start + 1
+        )),
+        _ => None,
+    })
+}
+
+// The fields BibTeX requires per entry type; "a|b" means either.
+// @misc requires nothing, as do the types BibTeX does not know.
+static REQUIRED: &[(PubType, &[&str])] = &[
+    (PubType::Article, &["author", "title", "journal", "year"]),
+    (
+        PubType::Book,
+        &["author|editor", "title", "publisher", "year"],
+    ),
+    (PubType::Booklet, &["title"]),
+    (
+        PubType::Inbook,
+        &[
+            "author|editor",
+            "title",
+            "chapter|pages",
+            "publisher",
+            "year",
+        ],
+    ),
+    (
+        PubType::Incol,
+        &["author", "title", "booktitle", "publisher", "year"],
+    ),
+    (PubType::Inproc, &["author", "title", "booktitle", "year"]),
+    (PubType::Manual, &["title"]),
+    (PubType::Masters, &["author", "title", "school", "year"]),
+    (PubType::Phd, &["author", "title", "school", "year"]),
+    (PubType::Proceedings, &["title", "year"]),
+    (
+        PubType::Techreport,
+        &["author", "title", "institution", "year"],
+    ),
+    (PubType::Unpublished, &["author", "title", "note"]),
+];
+
+// The year may also be given as date, as biblatex does.
+fn has_field(e: &BibEntry, name: &str) -> bool {
+    match name {
+        "author" => !e.author.trim().is_empty(),
+        "title" => !e.title.trim().is_empty(),
+        "year" => e.year().is_some(),
+        _ => e.field(name).is_some(),
+    }
+}
+
+fn missing_required(e: &BibEntry) -> Option<String> {
+    let (_, fields) = REQUIRED.iter().find(|(t, _)| *t == e.pubtype)?;
+    let missing: Vec<String> = fields
+        .iter()
+        .filter(|f| !f.split('|').any(|f| has_field(e, f)))
+        .map(|f| f.replace('|', " or "))
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "{} entry lacks required {}",
+        e.pubtype,
+        missing.join(", ")
+    ))
+}
+
+// Different citekeys for the same work, identified by the normalized DOI.
+fn duplicate_doi(es: &[&BibEntry]) -> Vec<(usize, String)> {
+    same_work(es)
+        .into_iter()
+        .map(|(i, f)| {
+            let (e, f) = (es[i], es[f]);
+            let doi = e.doi().unwrap_or_default();
+            (
+                i,
+                format!("DOI {} is also cited as {} ({})", doi, f.key, f.location()),
+            )
+        })
+        .collect()
+}
+
+// The entries that are the same work as an earlier entry,
+// with the index of the earlier entry.
+pub fn same_work(es: &[&BibEntry]) -> Vec<(usize, usize)> {
+    let mut first: FxHashMap<String, usize> = FxHashMap::default();
+    let mut v = Vec::new();
+    for (i, e) in es.iter().enumerate() {
+        let Some(doi) = e.doi() else {
+            continue;
+        };
+        match first.get(&doi) {
+            Some(&f) => v.push((i, f)),
+            None => {
+                first.insert(doi, i);
+            }
+        }
+    }
+    v
+}
+
+// A minimum share of recent works in a chapter, given as
+// unit:percent:years, like related-work:30:5 for at least 30% of the
+// citations in the chapter Related Work from the last 5 years.
+// The chapter is the unit of the highest level below parts, the
+// section in articles; its title matches the unit when both are the
+// same without number, in lower case and with every run of other
+// characters than letters and digits replaced by -.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recency {
+    pub unit: String,
+    pub percent: u32,
+    pub years: u32,
+}
+
+impl FromStr for Recency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Recency, String> {
+        let err = || format!("invalid recency '{}', unit:percent:years expected", s);
+        let mut parts = s.rsplitn(3, ':');
+        let (Some(years), Some(percent), Some(unit)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(err());
+        };
+        let percent: u32 = percent
+            .trim()
+            .trim_end_matches('%')
+            .parse()
+            .map_err(|_| err())?;
+        let years: u32 = years.trim().parse().map_err(|_| err())?;
+        if unit_name(unit).is_empty() || percent > 100 || years == 0 {
+            return Err(err());
+        }
+        Ok(Recency {
+            unit: unit_name(unit),
+            percent,
+            years,
+        })
+    }
+}
+
+// The title without its number, like 2.1 in 2.1 Related Work,
+// in lower case with dashes between the words.
+fn unit_name(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .skip_while(|w| w.bytes().all(|b| b.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Chapters citing too few recent works. Citations of works without
+// year, of keys not in the database and \nocite do not count;
+// the finding is at the first citation of the chapter.
+fn min_recency(code: &'static str, input: &Input) -> Vec<Finding> {
+    let mut v = Vec::new();
+    for r in input.recency {
+        let since = input.year - r.years as i32 + 1;
+        let mut first: Option<&Citation> = None;
+        let (mut recent, mut dated) = (0, 0);
+        for c in input.cites {
+            if c.kind == CiteKind::Nocite
+                || c.chapter.as_deref().map(unit_name).as_ref() != Some(&r.unit)
+            {
+                continue;
+            }
+            let Some(year) = input
+                .bib
+                .get(&c.key)
+                .and_then(|e| e.year()?.parse::<i32>().ok())
+            else {
+                continue;
+            };
+            first.get_or_insert(c);
+            dated += 1;
+            if year >= since {
+                recent += 1;
+            }
+        }
+        let Some(c) = first else {
+            continue;
+        };
+        if recent * 100 < dated * r.percent {
+            v.push(Finding {
+                code,
+                key: String::new(),
+                file: c.file.clone(),
+                line: c.line,
+                message: format!(
+                    "{} of {} citations in {} are from {} or later, {}% required",
+                    recent,
+                    dated,
+                    c.chapter.as_deref().unwrap_or_default(),
+                    since,
+                    r.percent
+                ),
+            });
+        }
+    }
+    v
+}
+
+// Asks CrossRef for retraction notices of cited works with DOI;
+// failed lookups are reported, but do not stop the other checks.
+#[cfg(feature = "net")]
+fn retracted(code: &'static str, input: &Input) -> Vec<Finding> {
+    let Some(net) = input.net else {
+        return Vec::new();
+    };
+    let mut seen = FxHashSet::default();
+    let entries: Vec<(&BibEntry, String)> = input
+        .cites
+        .iter()
+        .filter(|c| seen.insert(c.key.as_str()))
+        .filter_map(|c| input.bib.get(&c.key))
+        .filter_map(|e| Some((e, e.doi()?)))
+        .collect();
+    let dois: Vec<String> = entries.iter().map(|(_, d)| d.clone()).collect();
+
+    let mut v = Vec::new();
+    for ((e, doi), work) in entries.iter().zip(crossref::works(net, &dois)) {
+        match work {
+            Ok(Some(w)) => {
+                let notices = crossref::retractions(&w);
+                if !notices.is_empty() {
+                    v.push(Finding::at(
+                        code,
+                        e,
+                        format!("{} is retracted, see {}", doi, notices.join(", ")),
+                    ));
+                }
+            }
+            Ok(None) => (),
+            Err(err) => eprintln!("Error: {}", err),
+        }
+    }
+    v
+}
+
+// Which rules to run: the rules for the style, or, without style,
+// the rules that apply to all styles. With only, exactly the given
+// rules are run, whatever the style; skip removes rules.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub style: Option<Style>,
+    pub only: Vec<String>,
+    pub skip: Vec<String>,
+}
+
+// Rules are selected by code or name.
+fn is_rule(id: &str, r: &Rule) -> bool {
+    diagnostics::lookup(id).is_some_and(|d| d.code == r.code)
+}
+
+impl Config {
+    // Checks that all rules are known.
+    pub fn validate(&self) -> Result<(), String> {
+        for id in self.only.iter().chain(self.skip.iter()) {
+            if !RULES.iter().any(|r| is_rule(id, r)) {
+                return Err(format!("unknown lint rule '{}'", id));
+            }
+        }
+        Ok(())
+    }
+
+    fn enabled(&self, r: &Rule) -> bool {
+        if self.skip.iter().any(|id| is_rule(id, r)) {
+            return false;
+        }
+        if !self.only.is_empty() {
+            return self.only.iter().any(|id| is_rule(id, r));
+        }
+        match self.style {
+            Some(st) => r.styles.is_empty() || r.styles.contains(&st),
+            None => r.styles.is_empty(),
+        }
+    }
+}
+
+// A finding of a rule: the citekey concerned and where it is,
+// in the bib file for findings on entries, in the document otherwise.
+#[derive(Debug)]
+pub struct Finding {
+    pub code: &'static str,
+    pub key: String,
+    pub file: OsString,
+    pub line: u64,
+    pub message: String,
+}
+
+impl Finding {
+    fn at(code: &'static str, e: &BibEntry, message: String) -> Finding {
+        Finding {
+            code,
+            key: e.key.clone(),
+            file: e.file.clone(),
+            line: e.line,
+            message,
+        }
+    }
+
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.file.to_string_lossy(), self.line)
+    }
+
+    pub fn name(&self) -> &'static str {
+        diagnostics::lookup(self.code).map_or("", |d| d.name)
+    }
+}
+
+// Checks every cited entry once, in the order of first citation.
+// Citekeys not in the database are only reported as missing.
+// Findings on the whole input come first,
+// the findings on entries are grouped by entry.
+pub fn check(cfg: &Config, input: &Input) -> Vec<Finding> {
+    let rules: Vec<&Rule> = RULES.iter().filter(|r| cfg.enabled(r)).collect();
+    let mut seen = FxHashSet::default();
+    let entries: Vec<&BibEntry> = input
+        .cites
+        .iter()
+        .filter(|c| seen.insert(c.key.as_str()))
+        .filter_map(|c| input.bib.get(&c.key))
+        .collect();
+
+    let mut general = Vec::new();
+    let mut found: Vec<Vec<Finding>> = entries.iter().map(|_| Vec::new()).collect();
+    for r in &rules {
+        match r.check {
+            Check::Entry(check) => {
+                for (i, e) in entries.iter().enumerate() {
+                    if let Some(message) = check(e) {
+                        found[i].push(Finding::at(r.code, e, message));
+                    }
+                }
+            }
+            Check::All(check) => {
+                for (i, message) in check(&entries) {
+                    found[i].push(Finding::at(r.code, entries[i], message));
+                }
+            }
+            Check::Input(check) => general.extend(check(r.code, input)),
+        }
+    }
+    general.extend(found.into_iter().flatten());
+    general
+}
+
+// Removes the findings suppressed by comments in the sources
+// and returns how many were suppressed per code.
+pub fn suppress(fs: Vec<Finding>) -> (Vec<Finding>, Vec<(&'static str, usize)>) {
+    let mut sup = Suppressions::default();
+    let kept = fs
+        .into_iter()
+        .filter(|f| !sup.is_suppressed(&f.file, f.line, f.code))
+        .collect();
+    (kept, sup.counts())
+}
+
+pub fn print_findings(fs: &[Finding], f: Format) {
+    match f {
+        Format::Json(j) => findings_as_json(fs, j),
+        Format::Tsv => findings_as_tsv(fs),
+        Format::Markdown => findings_as_markdown(fs),
+    }
+}
+
+fn findings_as_tsv(fs: &[Finding]) {
+    for f in fs {
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"",
+            f.location(),
+            f.code,
+            f.name(),
+            f.key,
+            f.message
+        );
+    }
+}
+
+fn findings_as_markdown(fs: &[Finding]) {
+    let rows = fs.iter().map(|f| {
+        vec![
+            f.location(),
+            f.code.to_string(),
+            f.name().to_string(),
+            f.key.clone(),
+            f.message.clone(),
+        ]
+    });
+    output::print_md_table(&["Location", "Code", "Rule", "Key", "Message"], rows);
+}
+
+fn findings_as_json(fs: &[Finding], json: Json) {
+    let records = fs.iter().map(|f| {
+        json!({
+            "code": f.code,
+            "rule": f.name(),
+            "key": f.key,
+            "file": f.file.to_string_lossy(),
+            "line": f.line,
+            "message": f.message
+        })
+    });
+    output::print_json(records, json, "lint");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+
+    #[test]
+    fn test_rules_in_catalog() {
+        for r in RULES {
+            assert!(diagnostics::lookup(r.code).is_some(), "{}", r.code);
+        }
+    }
+
+    #[test]
+    fn test_min_recency() {
+        let bib: BibMap = [("old", "1990"), ("new", "2024"), ("undated", "")]
+            .iter()
+            .map(|(k, d)| {
+                let e = BibEntry {
+                    key: k.to_string(),
+                    date: d.to_string(),
+                    ..BibEntry::empty()
+                };
+                (k.to_string(), e)
+            })
+            .collect();
+        let cites: Vec<Citation> = [
+            ("old", "Introduction"),
+            ("new", "2 Related Work"),
+            ("old", "Related Work"),
+            ("old", "Related Work"),
+            ("undated", "Related Work"),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (k, ch))| Citation {
+            file: OsString::from("doc.tex"),
+            line: i as u64 + 1,
+            chapter: Some(ch.to_string()),
+            ..citation(k)
+        })
+        .collect();
+        let recency: Vec<Recency> = vec![
+            "related-work:40:5".parse().unwrap(),
+            "Related Work:30%:5".parse().unwrap(),
+        ];
+        let input = Input {
+            bib: &bib,
+            cites: &cites,
+            duplicates: &[],
+            recency: &recency,
+            year: 2026,
+            #[cfg(feature = "net")]
+            net: None,
+        };
+        let fs = min_recency("L014", &input);
+        assert_eq!(fs.len(), 1);
+        assert_eq!(fs[0].line, 2);
+        assert_eq!(
+            fs[0].message,
+            "1 of 3 citations in 2 Related Work are from 2022 or later, 40% required"
+        );
+        assert!("related-work:30".parse::<Recency>().is_err());
+        assert!("related-work:130:5".parse::<Recency>().is_err());
+    }
+
+    #[test]
+    fn test_min_recency_threshold() {
+        let bib: BibMap = [("old", "1990"), ("new", "2024")]
+            .iter()
+            .map(|(k, d)| {
+                let e = BibEntry {
+                    key: k.to_string(),
+                    date: d.to_string(),
+                    ..BibEntry::empty()
+                };
+                (k.to_string(), e)
+            })
+            .collect();
+        let cite = |key: &str, kind| Citation {
+            chapter: Some("Related Work".to_string()),
+            kind,
+            ..citation(key)
+        };
+        // exactly the share required is enough and \nocite does not count
+        let cites = vec![
+            cite("old", CiteKind::Plain),
+            cite("new", CiteKind::Plain),
+            cite("old", CiteKind::Nocite),
+        ];
+        let recency: Vec<Recency> = vec![
+            "related-work:50:5".parse().unwrap(),
+            "conclusion:100:5".parse().unwrap(),
+        ];
+        let input = Input {
+            bib: &bib,
+            cites: &cites,
+            duplicates: &[],
+            recency: &recency,
+            year: 2026,
+            #[cfg(feature = "net")]
+            net: None,
+        };
+        assert!(min_recency("L014", &input).is_empty());
+    }
+
+    #[test]
+    fn test_missing_required() {
+        let mut e = BibEntry {
+            pubtype: PubType::Book,
+            title: "T".to_string(),
+            date: "2020".to_string(),
+            ..BibEntry::empty()
+        };
+        assert_eq!(
+            missing_required(&e).as_deref(),
+            Some("book entry lacks required author or editor, publisher")
+        );
+        e.fields.insert("editor".to_string(), "E".to_string());
+        e.fields.insert("publisher".to_string(), "P".to_string());
+        assert_eq!(missing_required(&e), None);
+        e.pubtype = PubType::Other("online".to_string());
+        e.title.clear();
+        assert_eq!(missing_required(&e), None);
+    }
+}
@@ -1,9 +1,50 @@
 use once_cell::sync::Lazy;
 
+use bibstats::{
+    bib, cache, dates, diagnostics, docs, error, events, ids, keymatch, names, output, parser,
+    resolve, stats, weights,
+};
+
+mod affiliations;
+mod annotate;
+mod backref;
+mod baseline;
+mod bibliography;
+mod budget;
+mod capabilities;
 mod cli;
+mod compare;
+mod completeness;
+mod corpus;
+#[cfg(feature = "net")]
+mod crossref;
+mod dedup;
+mod diff;
+mod drafts;
+mod estimate;
 mod files;
-mod parser;
-mod stats;
+#[cfg(test)]
+mod fixtures;
+mod heatmap;
+mod hook;
+#[cfg(feature = "net")]
+mod http;
+mod jabref;
+mod kinds;
+mod lint;
+mod merge;
+mod migrate;
+#[cfg(feature = "net")]
+mod netcache;
+mod overlap;
+mod pages;
+mod preprints;
+mod quotes;
+mod show;
+mod suppress;
+mod timeline;
+#[cfg(feature = "net")]
+mod unpaywall;
 
 fn main() {
     Lazy::force(&cli::PARSED_COMMANDS);
@@ -13,47 +54,1085 @@ fn main() {
         std::process::exit(1);
     }
 
-    let b = files::get_bib_file(&cli::PARSED_COMMANDS.bib);
-    if b.is_err() {
+    if cli::PARSED_COMMANDS.capabilities {
+        println!("{:#}", capabilities::describe());
+        return;
+    }
+
+    if let Some(cli::Command::Explain(e)) = &cli::PARSED_COMMANDS.command {
+        match &e.code {
+            None => diagnostics::list(),
+            Some(code) => match diagnostics::lookup(code) {
+                Some(d) => diagnostics::explain(d),
+                None => {
+                    eprintln!("Error: unknown diagnostic '{}'", code);
+                    std::process::exit(1);
+                }
+            },
+        }
+        return;
+    }
+
+    check_features();
+
+    if cli::PARSED_COMMANDS.events {
+        events::enable();
+    }
+
+    if let Some(cli::Command::Hook(h)) = &cli::PARSED_COMMANDS.command {
+        let cli::HookAction::Install(i) = &h.action;
+        hook_install(i);
+        return;
+    }
+
+    let opts = parser::Options {
+        lenient: cli::PARSED_COMMANDS.lenient,
+        duplicate_fields: cli::PARSED_COMMANDS.duplicate_fields,
+        keep_braces: cli::PARSED_COMMANDS.keep_braces,
+        spans: false,
+    };
+    let search = resolve::SearchConfig::new(
+        &cli::PARSED_COMMANDS.search_path,
+        cli::PARSED_COMMANDS.use_kpsewhich,
+    );
+
+    if let Some(cli::Command::Migrate(m)) = &cli::PARSED_COMMANDS.command {
+        migrate(&documents(search), m.dry_run);
+        return;
+    }
+
+    let format = if cli::PARSED_COMMANDS.tsv {
+        output::Format::Tsv
+    } else if cli::PARSED_COMMANDS.markdown {
+        output::Format::Markdown
+    } else {
+        output::Format::Json(output::Json {
+            array: cli::PARSED_COMMANDS.jsonarray,
+            version: cli::PARSED_COMMANDS
+                .output_version
+                .unwrap_or(output::Version::V1),
+        })
+    };
+
+    // each project has its own bib files
+    if let Some(cli::Command::Compare(c)) = &cli::PARSED_COMMANDS.command {
+        compare(c, &search, opts, format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.dry_run
+        && !cli::PARSED_COMMANDS.apply_merge
+        && !cli::PARSED_COMMANDS.fix_keys
+    {
+        dry_run(&search, &documents(search.clone()), format);
+        return;
+    }
+
+    let (master, bib_include) = master_bib(&search);
+
+    let b = match bib::load(&master, &bib_include, &search, opts) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(cli::Command::Show(sh)) = &cli::PARSED_COMMANDS.command {
+        let (bibmap, _) = merge_bib(b.entries);
+        show(&bibmap, sh);
+        return;
+    }
+
+    if let Some(path) = &cli::PARSED_COMMANDS.corpus {
+        corpus(path, b.entries, search, format);
+        return;
+    }
+
+    let docs = documents(search);
+
+    // the bib files in the order in which they are loaded
+    let mut bib_files: Vec<std::ffi::OsString> = Vec::new();
+    for e in &b.entries {
+        if bib_files.last() != Some(&e.file) && !bib_files.contains(&e.file) {
+            bib_files.push(e.file.clone());
+        }
+    }
+
+    let (bibmap, duplicates) = merge_bib(b.entries);
+
+    let cites = match docs::collect(&docs) {
+        Ok(cites) => cites,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let cites = keymatch::apply(cites, &bibmap, cli::PARSED_COMMANDS.key_match);
+    let mut cites = nocite_all(cites, &bibmap);
+
+    if let Some(cli::Command::Validate(_)) = &cli::PARSED_COMMANDS.command {
+        validate(&bib_files, &bibmap, &duplicates, b.skipped.len(), &cites);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.apply_merge {
+        apply_merge(&bibmap, &cites, &docs, cli::PARSED_COMMANDS.dry_run);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.fix_keys {
+        fix_keys(&bibmap, &cites, &docs, cli::PARSED_COMMANDS.dry_run);
+        return;
+    }
+
+    // the reports see only the citations of entries with the keywords
+    let keywords = &cli::PARSED_COMMANDS.keyword;
+    if !keywords.is_empty() {
+        cites.retain(|c| {
+            bibmap
+                .get(&c.key)
+                .is_some_and(|e| keywords.iter().any(|k| e.has_keyword(k)))
+        });
+    }
+
+    if let Some(out) = &cli::PARSED_COMMANDS.annotate_bib {
+        let doc = if docs.stdin {
+            "stdin".to_string()
+        } else {
+            docs.roots
+                .iter()
+                .chain(docs.files.iter())
+                .map(|f| f.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if let Err(e) = annotate::annotate_bib(
+            out,
+            &bib_files,
+            &bibmap,
+            &cites,
+            &cli::PARSED_COMMANDS.annotate_field,
+            &doc,
+        ) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if cli::PARSED_COMMANDS.timeline {
+        timeline::print_timeline(timeline::compute(&cites), &bibmap, format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.lint {
+        lint(&bibmap, &cites, &duplicates, format);
+        return;
+    }
+
+    // lint reports them as findings
+    let mut sup = suppress::Suppressions::default();
+    stats::warn_duplicates(&bibmap, sup.duplicates(&duplicates));
+    suppress::report(&sup.counts(), "warning", "warnings");
+
+    if cli::PARSED_COMMANDS.completeness {
+        let scores = completeness::compute(&bibmap, &cites, cli::PARSED_COMMANDS.min_score);
+        completeness::print_scores(&scores, format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.where_cited {
+        backref::print_locations(backref::compute(&cites), &bibmap, format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.backref {
+        backref::print_backrefs(backref::compute(&cites), &bibmap, format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.cite_kinds {
+        kinds::print_kinds(&kinds::compute(&cites), format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.quotations {
+        quotes::print_usage(&quotes::usage(&cites), &bibmap, format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.cited_pages {
+        quotes::print_pages(&quotes::pages(&cites), &bibmap, format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.quotes {
+        quotes::print_quotes(&quotes::compute(&cites), &bibmap, format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.drafts {
+        drafts::print_drafts(&drafts::compute(&bibmap, &cites), format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.heatmap {
+        heatmap::print_heatmap(&heatmap::compute(&bibmap, &cites), format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.page_estimate {
+        let mut model = estimate::Model::for_style(cli::PARSED_COMMANDS.style);
+        if let Some(n) = cli::PARSED_COMMANDS.lines_per_entry {
+            model.lines_per_entry = n;
+        }
+        if let Some(n) = cli::PARSED_COMMANDS.lines_per_page {
+            model.lines_per_page = n;
+        }
+        estimate::print_estimate(&estimate::compute(&bibmap, &cites, model), format);
+        return;
+    }
+
+    if let Some(max) = cli::PARSED_COMMANDS.max_refs {
+        let b = budget::compute(&cites, max);
+        budget::print_budget(&b, &bibmap, format);
+        if b.over() > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(path) = &cli::PARSED_COMMANDS.affiliations {
+        let aff = match affiliations::Affiliations::load(path) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let shares =
+            affiliations::compute(&aff, &bibmap, &cites, cli::PARSED_COMMANDS.per_institution);
+        affiliations::print_shares(&shares, format);
+        return;
+    }
+
+    if let Some(markup) = cli::PARSED_COMMANDS.bibliography {
+        let items = bibliography::compute(&bibmap, &cites);
+        bibliography::print_bibliography(&items, markup);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.preprints {
+        let works = preprints::compute(&bibmap, &cites);
+        #[cfg(feature = "net")]
+        let works = match crossref_cache() {
+            Some(net) => preprints::find_published(works, &bibmap, &net),
+            None => works,
+        };
+        preprints::print_works(&works, &bibmap, format);
+        return;
+    }
+
+    #[cfg(feature = "net")]
+    if let Some(email) = &cli::PARSED_COMMANDS.unpaywall {
+        let access = unpaywall::lookup(&net_cache(), email, &bibmap, &cites);
+        unpaywall::print_access(&access, &bibmap, format);
+        return;
+    }
+
+    if let Some(path) = &cli::PARSED_COMMANDS.reference {
+        let r = match bib::load(path, &[], &docs.search, opts) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        overlap::print_overlap(&overlap::compute(&bibmap, &cites, &r.entries), format);
+        return;
+    }
+
+    if cli::PARSED_COMMANDS.jabref_groups {
+        let groups = match jabref::groups(&b.comments) {
+            Ok(gs) if gs.is_empty() => {
+                eprintln!("Error: no JabRef groups in the bib file");
+                std::process::exit(1);
+            }
+            Ok(gs) => gs,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        jabref::print_usage(&jabref::compute(&groups, &bibmap, &cites), format);
+        return;
+    }
+
+    let mut weights = match &cli::PARSED_COMMANDS.weights {
+        Some(path) => match weights::Weights::load(path) {
+            Ok(w) => Some(w),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    // citations of keys not in the database are not counted anyway;
+    // where their warning is suppressed, they are dropped silently
+    let mut sup = suppress::Suppressions::default();
+    cites.retain(|c| bibmap.contains_key(&c.key) || !sup.is_suppressed(&c.file, c.line, "B002"));
+    suppress::report(&sup.counts(), "warning", "warnings");
+    let authors = stats::compute(
+        &bibmap,
+        &cites,
+        weights.as_mut(),
+        cli::PARSED_COMMANDS.whole_authors,
+    );
+    stats::print_stats(authors, &bibmap, format, cli::PARSED_COMMANDS.full);
+}
+
+// Maps the citekeys to their entries; citekeys defined more than
+// once get the author and title chosen by the merge policies.
+// Works without author are credited to their editors.
+fn merge_bib(entries: Vec<parser::BibEntry>) -> (stats::BibMap, Vec<parser::BibEntry>) {
+    let (mut bibmap, duplicates) = stats::bib_to_map(entries);
+    let conflicts = merge::merge(
+        &mut bibmap,
+        &duplicates,
+        cli::PARSED_COMMANDS.merge_author,
+        cli::PARSED_COMMANDS.merge_title,
+    );
+    if let Err(e) = merge::report(&conflicts, cli::PARSED_COMMANDS.merge_log.as_deref()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    if !cli::PARSED_COMMANDS.no_editor_fallback {
+        stats::editor_fallback(&mut bibmap);
+    }
+    (bibmap, duplicates)
+}
+
+// The master bib file and the bib files to load with it:
+// the one given with -b or else those named in the documents
+// or else the first bib file in the current directory.
+fn master_bib(search: &resolve::SearchConfig) -> (std::ffi::OsString, Vec<std::ffi::OsString>) {
+    let mut extra = Vec::new();
+    let master = match &cli::PARSED_COMMANDS.bib {
+        Some(_) => files::get_bib_file(&cli::PARSED_COMMANDS.bib, search),
+        None => {
+            let named = match docs::bib_files(&documents(search.clone())) {
+                Ok(named) => named,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            match named.split_first() {
+                Some((first, rest)) => {
+                    extra.extend_from_slice(rest);
+                    Ok(first.clone())
+                }
+                None => files::get_bib_file(&None, search),
+            }
+        }
+    };
+    let Ok(master) = master else {
         eprintln!("No bib file found. I give up.");
         std::process::exit(1);
+    };
+    extra.extend(cli::PARSED_COMMANDS.bib_include.iter().cloned());
+    (master, extra)
+}
+
+// Expands or drops \nocite{*}, as --nocite-all says.
+fn nocite_all(cites: Vec<docs::Citation>, bibmap: &stats::BibMap) -> Vec<docs::Citation> {
+    let mut keys: Vec<&str> = bibmap.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    let all = cli::PARSED_COMMANDS.nocite_all;
+    let (cites, found) = docs::nocite_all(cites, &keys, all);
+    if found && !all {
+        eprintln!("\\nocite{{*}} ignored; with --nocite-all, every entry counts as cited once");
     }
-    let b = b.unwrap();
+    cites
+}
 
-    let ext = if cli::PARSED_COMMANDS.ext.is_empty() {
-        vec!["tex".into()]
+// Reports whether all citekeys resolve and are defined once;
+// parse errors have ended the program before.
+fn validate(
+    bib_files: &[std::ffi::OsString],
+    bibmap: &stats::BibMap,
+    duplicates: &[parser::BibEntry],
+    skipped: usize,
+    cites: &[docs::Citation],
+) {
+    // warnings suppressed in the sources do not fail validation
+    let mut sup = suppress::Suppressions::default();
+    let duplicates = sup.duplicates(duplicates);
+    stats::warn_duplicates(bibmap, duplicates.iter().copied());
+    let missing = sup.missing(bibmap, cites);
+    for c in &missing {
+        diagnostics::warn(
+            "B002",
+            &format!(
+                "Citekey {} not in database at {}{}",
+                c.key,
+                c.location(),
+                keymatch::did_you_mean(&c.key, bibmap)
+            ),
+        );
+    }
+    suppress::report(&sup.counts(), "warning", "warnings");
+    let keys: rustc_hash::FxHashSet<&str> = cites.iter().map(|c| c.key.as_str()).collect();
+    if missing.is_empty() && duplicates.is_empty() && skipped == 0 {
+        println!(
+            "ok: {} entries in {} bib files, {} citations of {} keys",
+            bibmap.len(),
+            bib_files.len(),
+            cites.len(),
+            keys.len()
+        );
     } else {
-        cli::PARSED_COMMANDS.ext.clone()
+        print!(
+            "failed: {} missing keys, {} duplicate keys",
+            missing.len(),
+            duplicates.len()
+        );
+        // only in lenient mode
+        if skipped > 0 {
+            print!(", {} malformed entries", skipped);
+        }
+        println!();
+        std::process::exit(1);
+    }
+}
+
+// Installs the pre-commit hook or prints the pre-commit configuration,
+// passing on the options that select the bib files.
+fn hook_install(i: &cli::HookInstall) {
+    let cmds = &cli::PARSED_COMMANDS;
+    let mut opts: Vec<&std::ffi::OsStr> = Vec::new();
+    if let Some(b) = &cmds.bib {
+        opts.extend([std::ffi::OsStr::new("-b"), b]);
+    }
+    for b in &cmds.bib_include {
+        opts.extend([std::ffi::OsStr::new("--bib-include"), b]);
+    }
+    for p in &cmds.search_path {
+        opts.extend([std::ffi::OsStr::new("--search-path"), p]);
+    }
+    if i.yaml {
+        print!("{}", hook::yaml(&opts));
+        return;
+    }
+    match hook::install(&opts, i.force) {
+        Ok(path) => eprintln!("pre-commit hook installed in {}", path.display()),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Like migration, merging works on files only.
+fn apply_merge(
+    bibmap: &stats::BibMap,
+    cites: &[docs::Citation],
+    docs: &docs::Documents,
+    dry_run: bool,
+) {
+    if docs.stdin {
+        eprintln!("Error: --apply-merge needs the documents as files");
+        std::process::exit(1);
+    }
+    let renames = dedup::renames(bibmap, cites);
+    if renames.is_empty() {
+        eprintln!("no duplicate entries to merge");
+        return;
+    }
+    for r in &renames {
+        eprintln!(
+            "{} ({}) is merged into {}",
+            r.from.key,
+            r.from.location(),
+            r.to.key
+        );
+    }
+    let n = match docs::files(docs).map_err(|e| e.to_string()) {
+        Ok(fs) => dedup::apply(&renames, &fs, &docs.tex, dry_run),
+        Err(e) => Err(e),
+    };
+    match n {
+        Ok(n) if dry_run => eprintln!("{} files would change", n),
+        Ok(n) => eprintln!("{} files changed", n),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn fix_keys(
+    bibmap: &stats::BibMap,
+    cites: &[docs::Citation],
+    docs: &docs::Documents,
+    dry_run: bool,
+) {
+    if docs.stdin {
+        eprintln!("Error: --fix-keys needs the documents as files");
+        std::process::exit(1);
+    }
+    let fixes = keymatch::fixes(bibmap, cites);
+    if fixes.is_empty() {
+        eprintln!("no cited keys to fix");
+        return;
+    }
+    for (from, to) in &fixes {
+        eprintln!("{} is replaced by {}", from, to);
+    }
+    let keys = fixes.into_iter().collect();
+    let n = match docs::files(docs).map_err(|e| e.to_string()) {
+        Ok(fs) => dedup::rename_keys(&keys, &fs, &docs.tex, dry_run),
+        Err(e) => Err(e),
     };
+    match n {
+        Ok(n) if dry_run => eprintln!("{} files would change", n),
+        Ok(n) => eprintln!("{} files changed", n),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    let ignore_files =
-        cli::PARSED_COMMANDS.files.is_empty() && cli::PARSED_COMMANDS.dirs.is_empty();
+fn show(bibmap: &stats::BibMap, sh: &cli::Show) {
+    let Some(e) = bibmap.get(&sh.key) else {
+        eprintln!(
+            "Error: citekey {} not in database{}",
+            sh.key,
+            keymatch::did_you_mean(&sh.key, bibmap)
+        );
+        std::process::exit(1);
+    };
+    let text = if sh.formatted {
+        Ok(show::format(e))
+    } else {
+        show::source(e)
+    };
+    match text {
+        Ok(t) => println!("{}", t),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    let fs = files::get_all_files(
-        &cli::PARSED_COMMANDS.files,
-        &cli::PARSED_COMMANDS.dirs,
-        &ext,
-    );
+// Migration works on files; stdin cannot be rewritten.
+fn migrate(docs: &docs::Documents, dry_run: bool) {
+    if docs.stdin {
+        eprintln!("Error: migrate needs the documents as files");
+        std::process::exit(1);
+    }
+    let n = match docs::files(docs).map_err(|e| e.to_string()) {
+        Ok(fs) => migrate::migrate(&fs, dry_run),
+        Err(e) => Err(e),
+    };
+    let n = match n {
+        Ok(0) => {
+            eprintln!("no natbib commands to migrate");
+            return;
+        }
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let lines = if n == 1 { "line" } else { "lines" };
+    if dry_run {
+        eprintln!("{} {} would change", n, lines);
+    } else {
+        eprintln!(
+            "{} {} changed; load biblatex instead of natbib \
+             and replace \\bibliography with \\addbibresource and \\printbibliography",
+            n, lines
+        );
+    }
+}
+
+// The documents given on the command line: files, directories or stdin.
+fn documents(search: resolve::SearchConfig) -> docs::Documents {
+    let ext = extensions();
+
+    let mut roots = cli::PARSED_COMMANDS.files.clone();
+    if let Some(cli::Command::Validate(v)) = &cli::PARSED_COMMANDS.command {
+        roots.extend(
+            v.files
+                .iter()
+                .filter(|f| std::path::Path::new(f).extension() != Some("bib".as_ref()))
+                .cloned(),
+        );
+    }
+
+    let ignore_files = roots.is_empty() && cli::PARSED_COMMANDS.dirs.is_empty();
+
+    let fs = files::get_dir_files(&cli::PARSED_COMMANDS.dirs, &ext);
     if fs.is_err() {
         eprintln!("Error: {:?}", fs);
         std::process::exit(1);
     }
     let fs = fs.unwrap();
 
-    if !ignore_files && fs.is_empty() {
+    if !ignore_files && fs.is_empty() && roots.is_empty() {
         eprintln!("No files found!");
         std::process::exit(1);
     }
 
-    match stats::compute(b, fs, ignore_files) {
-        Ok(authors) => stats::print_stats(
-            authors,
-            if cli::PARSED_COMMANDS.tsv {
-                stats::Format::Tsv
+    let cache = if cli::PARSED_COMMANDS.changed || cli::PARSED_COMMANDS.since.is_some() {
+        let changed = match cache::changed_files(cli::PARSED_COMMANDS.since.as_deref()) {
+            Ok(fs) => fs,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let dir = match &cli::PARSED_COMMANDS.cache_dir {
+            Some(d) => std::path::PathBuf::from(d),
+            None => cache::default_dir(),
+        };
+        // the items found depend on the citation commands
+        // and the environments skipped
+        let tex = tex_config();
+        let scan = if tex == parser::TexConfig::default() {
+            dir.join("scan")
+        } else {
+            let id = format!("{};{}", tex.cite_cmds.join(","), tex.skip_envs.join(","));
+            dir.join(format!("scan-{:016x}", cache::fnv1a(&id)))
+        };
+        Some(cache::ScanCache::new(scan, changed))
+    } else {
+        None
+    };
+
+    docs::Documents {
+        roots,
+        files: fs,
+        stdin: ignore_files,
+        search,
+        cache,
+        tex: tex_config(),
+        syntax: syntax(),
+    }
+}
+
+fn tex_config() -> parser::TexConfig {
+    parser::TexConfig {
+        cite_cmds: cli::PARSED_COMMANDS.cite_cmd.clone(),
+        skip_envs: cli::PARSED_COMMANDS.skip_env.clone(),
+        ..Default::default()
+    }
+}
+
+// The syntax of the documents, see --syntax, --from-aux and --from-bcf.
+fn syntax() -> Option<docs::Syntax> {
+    let cmds = &cli::PARSED_COMMANDS;
+    if cmds.from_aux && cmds.from_bcf {
+        eprintln!("Error: --from-aux and --from-bcf exclude each other");
+        std::process::exit(1);
+    }
+    if cmds.from_aux {
+        Some(docs::Syntax::Aux)
+    } else if cmds.from_bcf {
+        Some(docs::Syntax::Bcf)
+    } else {
+        cmds.syntax
+    }
+}
+
+// The extensions of the documents in directories; if none are given,
+// tex or, with --syntax markdown, md and markdown and, with --syntax typst, typ
+// and, with --from-aux or --from-bcf, aux or bcf.
+fn extensions() -> Vec<std::ffi::OsString> {
+    if cli::PARSED_COMMANDS.ext.is_empty() {
+        match syntax() {
+            Some(docs::Syntax::Markdown) => vec!["md".into(), "markdown".into()],
+            Some(docs::Syntax::Typst) => vec!["typ".into()],
+            Some(docs::Syntax::Aux) => vec!["aux".into()],
+            Some(docs::Syntax::Bcf) => vec!["bcf".into()],
+            _ => vec!["tex".into()],
+        }
+    } else {
+        cli::PARSED_COMMANDS.ext.clone()
+    }
+}
+
+// The report the options select; stats if none.
+fn report() -> &'static str {
+    let cmds = &cli::PARSED_COMMANDS;
+    if cmds.corpus.is_some() {
+        "corpus"
+    } else if cmds.timeline {
+        "timeline"
+    } else if cmds.lint {
+        "lint"
+    } else if cmds.completeness {
+        "completeness"
+    } else if cmds.backref {
+        "backref"
+    } else if cmds.where_cited {
+        "where"
+    } else if cmds.cite_kinds {
+        "cite-kinds"
+    } else if cmds.quotations {
+        "quotations"
+    } else if cmds.cited_pages {
+        "cited-pages"
+    } else if cmds.quotes {
+        "quotes"
+    } else if cmds.drafts {
+        "drafts"
+    } else if cmds.heatmap {
+        "heatmap"
+    } else if cmds.page_estimate {
+        "page-estimate"
+    } else if cmds.max_refs.is_some() {
+        "budget"
+    } else if cmds.affiliations.is_some() {
+        "affiliations"
+    } else if cmds.bibliography.is_some() {
+        "bibliography"
+    } else if cmds.preprints {
+        "preprints"
+    } else if cmds.unpaywall.is_some() {
+        "open-access"
+    } else if cmds.reference.is_some() {
+        "overlap"
+    } else if cmds.jabref_groups {
+        "jabref-groups"
+    } else {
+        "stats"
+    }
+}
+
+// Prints what would be read and how the result would be printed.
+// The bib files included by the bib files are not listed,
+// since we would have to parse them.
+// Without -b, the bib files are named in the documents; they are
+// not looked up, only where they come from is printed.
+fn dry_run(search: &resolve::SearchConfig, docs: &docs::Documents, format: output::Format) {
+    if cli::PARSED_COMMANDS.bib.is_some() {
+        let (master, _) = master_bib(search);
+        println!("bib: {}", master.to_string_lossy());
+    } else {
+        println!(
+            "bib: named in the documents by \\bibliography or \\addbibresource, \
+             else the first .bib file in the working directory"
+        );
+    }
+    for f in &cli::PARSED_COMMANDS.bib_include {
+        println!("bib: {}", f.to_string_lossy());
+    }
+    // the syntax of each document decides the scanners
+    let mut syntaxes = Vec::new();
+    if cli::PARSED_COMMANDS.corpus.is_none() {
+        match docs::files(docs) {
+            Ok(fs) => {
+                for f in fs {
+                    println!("document: {}", f.to_string_lossy());
+                    let syntax = docs
+                        .syntax
+                        .unwrap_or_else(|| docs::Syntax::of(std::path::Path::new(&f)));
+                    if !syntaxes.contains(&syntax) {
+                        syntaxes.push(syntax);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if syntaxes.is_empty() {
+        syntaxes.push(docs.syntax.unwrap_or(docs::Syntax::Tex));
+    }
+    let mut scanners: Vec<&str> = Vec::new();
+    for s in syntaxes.iter().flat_map(|s| s.scanners()) {
+        if !scanners.contains(s) {
+            scanners.push(s);
+        }
+    }
+    println!("scanners: {}", scanners.join(", "));
+    println!("report: {}", report());
+    match format {
+        output::Format::Json(j) => println!(
+            "format: {} (version {})",
+            if j.array { "jsonarray" } else { "json" },
+            if j.version == output::Version::V1 {
+                1
             } else {
-                stats::Format::Json(cli::PARSED_COMMANDS.jsonarray)
+                2
+            }
+        ),
+        output::Format::Tsv => println!("format: tsv"),
+        output::Format::Markdown => println!("format: markdown"),
+    }
+}
+
+// Prints the lint findings, except those suppressed by comments
+// or recorded in the baseline, and exits with 1 if there are any.
+fn lint(
+    bibmap: &stats::BibMap,
+    cites: &[docs::Citation],
+    duplicates: &[parser::BibEntry],
+    format: output::Format,
+) {
+    let cfg = lint::Config {
+        style: cli::PARSED_COMMANDS.style,
+        only: cli::PARSED_COMMANDS.rule.clone(),
+        skip: cli::PARSED_COMMANDS.skip_rule.clone(),
+    };
+    if let Err(e) = cfg.validate() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    #[cfg(feature = "net")]
+    let net = crossref_cache();
+    let input = lint::Input {
+        bib: bibmap,
+        cites,
+        duplicates,
+        recency: &cli::PARSED_COMMANDS.recency,
+        year: dates::this_year(),
+        #[cfg(feature = "net")]
+        net: net.as_ref(),
+    };
+    let (mut findings, suppressed) = lint::suppress(lint::check(&cfg, &input));
+    suppress::report(&suppressed, "finding", "findings");
+
+    if let Some(path) = &cli::PARSED_COMMANDS.baseline {
+        let exists = std::path::Path::new(path).exists();
+        if !exists || cli::PARSED_COMMANDS.update_baseline {
+            let b = baseline::Baseline::from_findings(&findings);
+            if let Err(e) = b.save(path) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            let n = b.len();
+            let findings = if n == 1 { "finding" } else { "findings" };
+            eprintln!(
+                "{} {} recorded in baseline {}",
+                n,
+                findings,
+                path.to_string_lossy()
+            );
+            return;
+        }
+        let b = match baseline::Baseline::load(path) {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let (new, known, fixed) = b.filter(findings);
+        findings = new;
+        let of = |n: usize| if n == 1 { "finding" } else { "findings" };
+        eprintln!("{} {} in baseline", known, of(known));
+        if fixed > 0 {
+            let are = if fixed == 1 { "is" } else { "are" };
+            eprintln!(
+                "{} {} in baseline {} fixed, run with --update-baseline to drop them",
+                fixed,
+                of(fixed),
+                are
+            );
+        }
+    }
+
+    lint::print_findings(&findings, format);
+    if !findings.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+// Reports the first and last citation of every work
+// across the projects of a corpus, which share the bib database.
+fn corpus(
+    path: &std::ffi::OsStr,
+    b: Vec<parser::BibEntry>,
+    search: resolve::SearchConfig,
+    format: output::Format,
+) {
+    let projects = match corpus::load(path) {
+        Ok(ps) => ps,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let (bibmap, duplicates) = merge_bib(b);
+    stats::warn_duplicates(&bibmap, &duplicates);
+
+    let mut cites = Vec::with_capacity(projects.len());
+    for p in &projects {
+        let docs = docs::Documents {
+            roots: vec![p.root.clone()],
+            files: Vec::new(),
+            stdin: false,
+            search: search.clone(),
+            cache: None,
+            tex: tex_config(),
+            syntax: syntax(),
+        };
+        match docs::collect(&docs) {
+            Ok(cs) => cites.push((p, cs)),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    corpus::print_usage(&corpus::compute(&cites, &bibmap), &bibmap, format);
+}
+
+fn compare(
+    c: &cli::Compare,
+    search: &resolve::SearchConfig,
+    opts: parser::Options,
+    format: output::Format,
+) {
+    let load = |path| match project(path, search, opts) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let (a, b) = (load(&c.a), load(&c.b));
+    compare::print_comparison(&compare::compute(&a, &b), &a, &b, format);
+}
+
+// A project is a directory with tex and bib files or a root document
+// with the bib files in its directory.
+fn project(
+    path: &std::ffi::OsStr,
+    search: &resolve::SearchConfig,
+    opts: parser::Options,
+) -> Result<compare::Project, String> {
+    let p = std::path::Path::new(path);
+    if !p.exists() {
+        return Err(format!("no project {}", path.to_string_lossy()));
+    }
+    let (dir, docs) = if p.is_dir() {
+        (
+            path.to_os_string(),
+            docs::Documents {
+                roots: Vec::new(),
+                files: files::get_dir_files(&[path.to_os_string()], &extensions())?,
+                stdin: false,
+                search: search.clone(),
+                cache: None,
+                tex: tex_config(),
+                syntax: syntax(),
+            },
+        )
+    } else {
+        let dir = match p.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d.as_os_str().to_os_string(),
+            _ => ".".into(),
+        };
+        (
+            dir,
+            docs::Documents {
+                roots: vec![path.to_os_string()],
+                files: Vec::new(),
+                stdin: false,
+                search: search.clone(),
+                cache: None,
+                tex: tex_config(),
+                syntax: syntax(),
             },
+        )
+    };
+    let bibs = files::get_dir_files(&[dir], &["bib".into()])?;
+    let Some((master, extra)) = bibs.split_first() else {
+        return Err(format!("no bib file for {}", path.to_string_lossy()));
+    };
+    let b = bib::load(master, extra, search, opts).map_err(|e| e.to_string())?;
+    let (bib, duplicates) = merge_bib(b.entries);
+    stats::warn_duplicates(&bib, &duplicates);
+    let cites = docs::collect(&docs).map_err(|e| e.to_string())?;
+    let cites = keymatch::apply(cites, &bib, cli::PARSED_COMMANDS.key_match);
+    let cites = nocite_all(cites, &bib);
+    Ok(compare::Project { bib, cites })
+}
+
+// Options that need a feature this build lacks are errors
+// rather than silently ignored.
+#[cfg(not(feature = "net"))]
+fn check_features() {
+    let net = [
+        ("--crossref", cli::PARSED_COMMANDS.crossref),
+        ("--ca-cert", cli::PARSED_COMMANDS.ca_cert.is_some()),
+        ("--unpaywall", cli::PARSED_COMMANDS.unpaywall.is_some()),
+        ("--offline", cli::PARSED_COMMANDS.offline),
+        // given, unless they have their default values
+        (
+            "--net-concurrency",
+            cli::PARSED_COMMANDS.net_concurrency != 2,
         ),
-        Err(e) => eprintln!("Error: {:?}", e),
+        ("--net-rate", cli::PARSED_COMMANDS.net_rate != 5.0),
+    ];
+    if let Some((flag, _)) = net.iter().find(|(_, on)| *on) {
+        eprintln!(
+            "Error: {} is not available, bibstats was built without the 'net' feature",
+            flag
+        );
+        std::process::exit(1);
+    }
+}
+
+#[cfg(feature = "net")]
+fn check_features() {}
+
+// The cache for CrossRef lookups, if they are asked for.
+#[cfg(feature = "net")]
+fn crossref_cache() -> Option<netcache::NetCache> {
+    cli::PARSED_COMMANDS.crossref.then(net_cache)
+}
+
+#[cfg(feature = "net")]
+fn net_cache() -> netcache::NetCache {
+    let dir = match &cli::PARSED_COMMANDS.cache_dir {
+        Some(d) => std::path::PathBuf::from(d),
+        None => cache::default_dir(),
+    };
+    let agent = http_agent();
+    let limits = netcache::Limits {
+        concurrency: cli::PARSED_COMMANDS.net_concurrency,
+        rate: cli::PARSED_COMMANDS.net_rate,
+    };
+    netcache::NetCache::new(dir, cli::PARSED_COMMANDS.offline, agent, limits)
+}
+
+// The HTTP client for all network code.
+#[cfg(feature = "net")]
+fn http_agent() -> ureq::Agent {
+    let cfg = http::HttpConfig {
+        ca_cert: cli::PARSED_COMMANDS
+            .ca_cert
+            .as_ref()
+            .map(std::path::PathBuf::from),
+        ..Default::default()
+    };
+    match http::agent(&cfg) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
     }
 }
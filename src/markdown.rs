@@ -0,0 +1,425 @@
+// Citations in Pandoc's Markdown: [see @doe99, p. 33; @roe05]
+// in brackets, which are parenthetical, and @doe99 or
+// @doe99 [p. 33] in the text, which are narrative.
+
+use crate::parser::{Cite, CiteKind, Section, TexItem};
+
+// Characters that may join the parts of a citekey, like the colon
+// of @doe:99, but not end it, like the period ending a sentence.
+const KEY_PUNCTUATION: &str = ":.#$%&-+?<>~/";
+
+// The quotation marks that close quotations, as in parser.
+const CLOSERS: [&str; 6] = ["''", "\"", "\u{201d}", "\u{201c}", "\u{bb}", "\u{ab}"];
+
+// Scans a Markdown document for citations, headings and the bib
+// files named in the bibliography field of its YAML front matter.
+// The front matter, code blocks and code spans are not scanned.
+// Headings of level 1 are sections, those of level 2 subsections
+// and so on; citations in block quotes or after a quotation mark
+// are quoted.
+pub fn collect_cites(text: &str) -> Vec<TexItem> {
+    let mut items: Vec<(usize, TexItem)> = Vec::new();
+    let mut masked = String::with_capacity(text.len());
+    let mut quoted_lines = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+    let front = front_matter(text);
+    if let Some((n, bibs)) = &front {
+        items.extend(bibs.iter().map(|b| (0, TexItem::Bib(b.clone()))));
+        masked.push_str(&blank(&text[..*n]));
+        quoted_lines.resize(text[..*n].matches('\n').count(), false);
+    }
+    let start = front.as_ref().map_or(0, |(n, _)| *n);
+
+    for line in text[start..].split_inclusive('\n') {
+        let pos = masked.len();
+        let t = line.trim_start();
+        let indent = line.len() - t.len();
+        if let Some((c, n)) = fence {
+            if t.starts_with(&c.to_string().repeat(n)) && t.trim_start_matches(c).trim().is_empty()
+            {
+                fence = None;
+            }
+            masked.push_str(&blank(line));
+            quoted_lines.push(false);
+            continue;
+        }
+        if indent < 4 && (t.starts_with("```") || t.starts_with("~~~")) {
+            let c = t.chars().next().unwrap_or('`');
+            fence = Some((c, t.len() - t.trim_start_matches(c).len()));
+            masked.push_str(&blank(line));
+            quoted_lines.push(false);
+            continue;
+        }
+
+        // the markers of block quotes, which may be nested
+        let mut rest = t;
+        let mut quoted = false;
+        while indent < 4 && rest.starts_with('>') {
+            quoted = true;
+            rest = rest[1..].trim_start_matches([' ', '\t']);
+        }
+        quoted_lines.push(quoted);
+        let markers = line.len() - rest.len();
+        masked.push_str(&blank(&line[..markers]));
+        masked.push_str(&line[markers..]);
+
+        if let Some(s) = heading(rest) {
+            items.push((pos, TexItem::Section(s)));
+        }
+    }
+
+    let starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let place = |pos: usize| {
+        let line = starts.partition_point(|s| *s <= pos);
+        (line, (pos - starts[line - 1] + 1) as u64)
+    };
+    let bs = masked.as_bytes();
+    let mut i = 0;
+    while i < bs.len() {
+        match bs[i] {
+            b'\\' => i += 1,
+            b'`' => i = code_span_end(&masked, i),
+            // the destination of a link
+            b'(' if i > 0 && bs[i - 1] == b']' => {
+                i = masked[i..].find(')').map_or(i + 1, |n| i + n + 1);
+                continue;
+            }
+            b'[' => {
+                if let Some((cites, end)) = bracketed(&masked, i) {
+                    let (line, column) = place(i);
+                    let quoted = quoted_lines[line - 1] || after_quotation(&masked[..i]);
+                    items.extend(cites.into_iter().map(|(key, prenote, postnote)| {
+                        let cite = Cite {
+                            key,
+                            line: line as u64,
+                            column,
+                            prenote,
+                            postnote,
+                            quoted,
+                            kind: CiteKind::Parenthetical,
+                            span: None,
+                        };
+                        (i, TexItem::Cite(cite))
+                    }));
+                    i = end;
+                    continue;
+                }
+            }
+            b'@' if masked[..i].chars().next_back().is_none_or(may_precede) => {
+                if let Some(n) = citekey_len(&masked[i + 1..]) {
+                    let key = unbraced(&masked[i + 1..i + 1 + n]);
+                    let (line, column) = place(i);
+                    let quoted = quoted_lines[line - 1] || after_quotation(&masked[..i]);
+                    let mut end = i + 1 + n;
+                    let postnote = locator(&masked[end..]).map(|(note, n)| {
+                        end += n;
+                        note
+                    });
+                    let cite = Cite {
+                        key,
+                        line: line as u64,
+                        column,
+                        prenote: None,
+                        postnote,
+                        quoted,
+                        kind: CiteKind::Narrative,
+                        span: None,
+                    };
+                    items.push((i, TexItem::Cite(cite)));
+                    i = end;
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    items.sort_by_key(|(pos, _)| *pos);
+    items.into_iter().map(|(_, i)| i).collect()
+}
+
+// The length of the front matter, which starts with the first line
+// and ends with a line of --- or ..., and the bib files it names as
+// bibliography: refs.bib, as a list in brackets or as items below.
+fn front_matter(text: &str) -> Option<(usize, Vec<String>)> {
+    let mut lines = text.split_inclusive('\n');
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+    let mut len = text.find('\n')? + 1;
+    let mut bibs = Vec::new();
+    let mut in_list = false;
+    for l in lines {
+        len += l.len();
+        let t = l.trim();
+        if t == "---" || t == "..." {
+            return Some((len, bibs));
+        }
+        if in_list && t.starts_with("- ") {
+            bibs.push(unquoted(&t[2..]));
+            continue;
+        }
+        in_list = false;
+        if let Some(v) = l.strip_prefix("bibliography:").map(str::trim) {
+            match v.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                Some(list) => bibs.extend(list.split(',').map(unquoted)),
+                None if v.is_empty() => in_list = true,
+                None => bibs.push(unquoted(v)),
+            }
+        }
+    }
+    None
+}
+
+fn unquoted(s: &str) -> String {
+    s.trim().trim_matches(['"', '\'']).to_string()
+}
+
+// Blanks out everything but the line breaks, keeping the positions.
+pub(crate) fn blank(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c == '\n' {
+                "\n".to_string()
+            } else {
+                " ".repeat(c.len_utf8())
+            }
+        })
+        .collect()
+}
+
+// An ATX heading, like ## Methods {#sec:methods}; unnumbered
+// headings are marked with {-} or {.unnumbered}.
+fn heading(l: &str) -> Option<Section> {
+    let level = l.len() - l.trim_start_matches('#').len();
+    let rest = &l[level..];
+    if !(1..=6).contains(&level) || !(rest.trim().is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    let mut title = rest.trim();
+    let mut starred = false;
+    if let Some(open) = title.rfind('{').filter(|_| title.ends_with('}')) {
+        let attrs = &title[open + 1..title.len() - 1];
+        starred = attrs
+            .split_whitespace()
+            .any(|a| a == "-" || a == ".unnumbered");
+        title = title[..open].trim_end();
+    }
+    let title = title.trim_end_matches('#').trim_end();
+    Some(Section {
+        level: (level + 1).min(6),
+        starred,
+        title: title.to_string(),
+    })
+}
+
+// Where the code span starting with a run of backticks ends, after
+// the closing run of the same length; without one, the run is text.
+fn code_span_end(s: &str, i: usize) -> usize {
+    let run = s[i..].len() - s[i..].trim_start_matches('`').len();
+    let mut j = i + run;
+    while let Some(k) = s[j..].find('`') {
+        let start = j + k;
+        let n = s[start..].len() - s[start..].trim_start_matches('`').len();
+        if n == run {
+            return start + n;
+        }
+        j = start + n;
+    }
+    i + run
+}
+
+// A citekey with its prenote and postnote.
+type Part = (String, Option<String>, Option<String>);
+
+// The citations in brackets starting at i and where they end;
+// every part separated by a semicolon must have a citekey, and
+// brackets of links and spans, followed by (, [ or {, are no citations.
+fn bracketed(s: &str, i: usize) -> Option<(Vec<Part>, usize)> {
+    let close = closing_bracket(s, i)?;
+    if s[close + 1..].starts_with(['(', '[', '{']) {
+        return None;
+    }
+    let cites = s[i + 1..close]
+        .split(';')
+        .map(citation)
+        .collect::<Option<Vec<_>>>()?;
+    Some((cites, close + 1))
+}
+
+// The bracket closing the one at i, within the paragraph.
+fn closing_bracket(s: &str, i: usize) -> Option<usize> {
+    let bs = s.as_bytes();
+    let mut depth = 0;
+    let mut j = i + 1;
+    while j < bs.len() {
+        match bs[j] {
+            b'\\' => j += 1,
+            b'[' => depth += 1,
+            b']' if depth == 0 => return Some(j),
+            b']' => depth -= 1,
+            b'\n' if s[j + 1..].trim_start_matches([' ', '\t']).starts_with('\n') => return None,
+            _ => (),
+        }
+        j += 1;
+    }
+    None
+}
+
+// A part of a citation in brackets, like see @doe99, p. 33 or
+// -@doe99, which suppresses the author; the text before the key
+// is the prenote, the text after it the postnote.
+fn citation(part: &str) -> Option<Part> {
+    let at = part.char_indices().find_map(|(k, c)| {
+        let before = part[..k].trim_end_matches('-');
+        let start = c == '@' && (before.is_empty() || before.ends_with(char::is_whitespace));
+        start.then_some(k)
+    })?;
+    let n = citekey_len(&part[at + 1..])?;
+    let key = unbraced(&part[at + 1..at + 1 + n]);
+    let note = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+    let prenote = note(part[..at].trim_end_matches('-'));
+    let postnote = note(part[at + 1 + n..].trim_start().trim_start_matches(','));
+    Some((key, prenote, postnote))
+}
+
+// The length of the citekey after the @, which is either in braces
+// or starts with a letter, a digit or _ and ends with one of them.
+fn citekey_len(s: &str) -> Option<usize> {
+    if s.starts_with('{') {
+        let close = s.find('}')?;
+        return (close > 1 && !s[..close].contains('\n')).then_some(close + 1);
+    }
+    let mut end = 0;
+    let mut chars = s.char_indices().peekable();
+    while let Some((k, c)) = chars.next() {
+        if is_key_char(Some(c)) {
+            end = k + c.len_utf8();
+        } else if !(end > 0
+            && KEY_PUNCTUATION.contains(c)
+            && is_key_char(chars.peek().map(|(_, c)| *c)))
+        {
+            break;
+        }
+    }
+    (end > 0).then_some(end)
+}
+
+// Whether a citation in the text may follow the character;
+// addresses like me@example.com and URLs are no citations.
+fn may_precede(c: char) -> bool {
+    c.is_whitespace() || "([{\"'*_~\u{2018}\u{201c}".contains(c)
+}
+
+fn is_key_char(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn unbraced(key: &str) -> String {
+    key.trim_start_matches('{')
+        .trim_end_matches('}')
+        .to_string()
+}
+
+// The locator in brackets after a citation in the text, like
+// @doe99 [p. 33], and its length with the space before it.
+fn locator(s: &str) -> Option<(String, usize)> {
+    let rest = s.trim_start_matches([' ', '\t']);
+    if !rest.starts_with('[') {
+        return None;
+    }
+    let start = s.len() - rest.len();
+    let close = closing_bracket(s, start)?;
+    let note = s[start + 1..close].trim();
+    if s[close + 1..].starts_with(['(', '[', '{']) || citation(note).is_some() {
+        return None;
+    }
+    Some((note.to_string(), close + 1))
+}
+
+// Whether the text ends with a closing quotation mark,
+// but for spaces, punctuation and emphasis.
+pub(crate) fn after_quotation(s: &str) -> bool {
+    let s = s.trim_end_matches(|c: char| c.is_whitespace() || ".,;:~*_".contains(c));
+    CLOSERS.iter().any(|c| s.ends_with(c))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cites(text: &str) -> Vec<Cite> {
+        collect_cites(text)
+            .into_iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_cites() {
+        let text = "As @doe99 [p. 33] shows, it works [see @roe05, ch. 2; -@{doe:2000}].\n\
+                    Mail me at me@example.com, see [the docs](https://x.org/@a) and `@b`.\n\
+                    ```\n@c\n```\n\
+                    > Quoted [@d]\n\
+                    \"So it is\" [@e]. Also @f. [@g,\n  p. 3]\n";
+        let cs = cites(text);
+        let keys: Vec<&str> = cs.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, vec!["doe99", "roe05", "doe:2000", "d", "e", "f", "g"]);
+        assert_eq!(
+            (cs[0].kind, cs[0].postnote.as_deref()),
+            (CiteKind::Narrative, Some("p. 33"))
+        );
+        assert_eq!(
+            (
+                cs[1].kind,
+                cs[1].prenote.as_deref(),
+                cs[1].postnote.as_deref()
+            ),
+            (CiteKind::Parenthetical, Some("see"), Some("ch. 2"))
+        );
+        assert_eq!((cs[1].line, cs[1].column), (1, 35));
+        assert_eq!(
+            (cs[2].prenote.as_deref(), cs[2].postnote.as_deref()),
+            (None, None)
+        );
+        assert_eq!(cs[3].line, 6);
+        let quoted: Vec<bool> = cs.iter().map(|c| c.quoted).collect();
+        assert_eq!(quoted, vec![false, false, false, true, true, false, false]);
+        assert_eq!((cs[6].line, cs[6].postnote.as_deref()), (7, Some("p. 3")));
+    }
+
+    #[test]
+    fn test_front_matter_and_headings() {
+        let text = "---\ntitle: On @x\nbibliography:\n  - refs.bib\n  - \"more.bib\"\n---\n\
+                    # Introduction {#sec:intro}\n\n## Related work ##\n\n# References {-}\n";
+        let items = collect_cites(text);
+        assert_eq!(
+            items,
+            vec![
+                TexItem::Bib("refs.bib".to_string()),
+                TexItem::Bib("more.bib".to_string()),
+                TexItem::Section(Section {
+                    level: 2,
+                    starred: false,
+                    title: "Introduction".to_string()
+                }),
+                TexItem::Section(Section {
+                    level: 3,
+                    starred: false,
+                    title: "Related work".to_string()
+                }),
+                TexItem::Section(Section {
+                    level: 2,
+                    starred: true,
+                    title: "References".to_string()
+                }),
+            ]
+        );
+    }
+}
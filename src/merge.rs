@@ -0,0 +1,205 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use rustc_hash::FxHashMap;
+
+use crate::diagnostics;
+use crate::parser::BibEntry;
+use crate::stats::BibMap;
+
+// How to pick the author or title of a citekey defined
+// more than once with different values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    // the first definition, as without merging
+    First,
+    // the longest value, which is often the complete one
+    Longest,
+    // the value from the bib file modified last
+    NewestFile,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Policy, String> {
+        match s {
+            "prefer-first" => Ok(Policy::First),
+            "prefer-longest" => Ok(Policy::Longest),
+            "prefer-newest-file" => Ok(Policy::NewestFile),
+            _ => Err(format!(
+                "unknown merge policy '{}', \
+                 prefer-first, prefer-longest or prefer-newest-file expected",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Policy::First => "prefer-first",
+            Policy::Longest => "prefer-longest",
+            Policy::NewestFile => "prefer-newest-file",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// A field with different values in the definitions of a citekey:
+// the values with the location of their definition
+// and the value chosen by the policy.
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub key: String,
+    pub field: &'static str,
+    pub values: Vec<(String, String)>,
+    pub chosen: String,
+    pub policy: Policy,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "conflicting {} for {}:", self.field, self.key)?;
+        for (i, (v, loc)) in self.values.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{} \"{}\" at {}", sep, v, loc)?;
+        }
+        write!(f, "; using \"{}\" ({})", self.chosen, self.policy)
+    }
+}
+
+// Resolves the author and title of citekeys defined more than once
+// according to the policies and returns every conflict.
+// The other fields stay those of the first definition.
+pub fn merge(
+    bib: &mut BibMap,
+    duplicates: &[BibEntry],
+    author: Policy,
+    title: Policy,
+) -> Vec<Conflict> {
+    let mut by_key: FxHashMap<&str, Vec<&BibEntry>> = FxHashMap::default();
+    for d in duplicates {
+        by_key.entry(d.key.as_str()).or_default().push(d);
+    }
+    let mut keys: Vec<&str> = by_key.keys().copied().collect();
+    keys.sort_unstable();
+
+    let mut conflicts = Vec::new();
+    for key in keys {
+        let Some(first) = bib.get(key) else {
+            continue;
+        };
+        let mut defs = vec![first];
+        defs.extend(&by_key[key]);
+        let c_author = resolve(&defs, "author", |e| &e.author, author);
+        let c_title = resolve(&defs, "title", |e| &e.title, title);
+        let e = bib.get_mut(key).unwrap();
+        if let Some(c) = c_author {
+            e.author = c.chosen.clone();
+            conflicts.push(c);
+        }
+        if let Some(c) = c_title {
+            e.title = c.chosen.clone();
+            conflicts.push(c);
+        }
+    }
+    conflicts
+}
+
+fn resolve(
+    defs: &[&BibEntry],
+    field: &'static str,
+    get: fn(&BibEntry) -> &String,
+    policy: Policy,
+) -> Option<Conflict> {
+    let first = get(defs[0]);
+    if defs.iter().all(|e| get(e) == first) {
+        return None;
+    }
+    // max_by_key returns the last maximum; reversed, ties go to the first
+    let chosen = match policy {
+        Policy::First => defs[0],
+        Policy::Longest => defs
+            .iter()
+            .rev()
+            .max_by_key(|e| get(e).chars().count())
+            .unwrap(),
+        Policy::NewestFile => defs.iter().rev().max_by_key(|e| modified(e)).unwrap(),
+    };
+    Some(Conflict {
+        key: defs[0].key.clone(),
+        field,
+        values: defs
+            .iter()
+            .map(|e| (get(e).clone(), e.location()))
+            .collect(),
+        chosen: get(chosen).clone(),
+        policy,
+    })
+}
+
+// files we cannot stat count as oldest
+fn modified(e: &BibEntry) -> SystemTime {
+    fs::metadata(&e.file)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+// The merge log has one line per conflict; without log file
+// the conflicts are reported as warnings.
+pub fn report(conflicts: &[Conflict], log: Option<&std::ffi::OsStr>) -> Result<(), String> {
+    match log {
+        None => {
+            for c in conflicts {
+                diagnostics::warn("B006", &c.to_string());
+            }
+            Ok(())
+        }
+        Some(path) => {
+            let text: String = conflicts.iter().map(|c| format!("{}\n", c)).collect();
+            fs::write(path, text)
+                .map_err(|e| format!("cannot write merge log {}: {}", path.to_string_lossy(), e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stats;
+
+    fn entry(key: &str, author: &str, title: &str, line: u64) -> BibEntry {
+        BibEntry {
+            key: key.to_string(),
+            author: author.to_string(),
+            title: title.to_string(),
+            line,
+            ..BibEntry::empty()
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let (mut bib, duplicates) = stats::bib_to_map(vec![
+            entry("knuth", "Knuth, D.", "The Art of Computer Programming", 1),
+            entry("knuth", "Knuth, Donald E.", "TAOCP", 9),
+            entry("same", "A", "B", 12),
+            entry("same", "A", "B", 20),
+        ]);
+        let conflicts = merge(&mut bib, &duplicates, Policy::Longest, Policy::First);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(bib["knuth"].author, "Knuth, Donald E.");
+        assert_eq!(bib["knuth"].title, "The Art of Computer Programming");
+        assert_eq!(
+            conflicts[0].to_string(),
+            "conflicting author for knuth: \"Knuth, D.\" at :1, \
+             \"Knuth, Donald E.\" at :9; using \"Knuth, Donald E.\" (prefer-longest)"
+        );
+        assert_eq!(conflicts[1].field, "title");
+    }
+}
@@ -0,0 +1,164 @@
+use std::ffi::OsString;
+use std::fs;
+
+use crate::diagnostics;
+use crate::diff;
+use crate::parser;
+
+// The natbib commands with an equivalent in biblatex; the arguments,
+// including pre- and postnote in brackets, stay as they are.
+// \citealp prints no parentheses, like \cite of biblatex.
+// Capitalized forms, as at the start of a sentence, map likewise.
+static COMMANDS: &[(&str, &str)] = &[
+    ("citet", "textcite"),
+    ("citep", "parencite"),
+    ("citealp", "cite"),
+    ("citeyearpar", "parencite*"),
+];
+
+// natbib commands biblatex has no equivalent for;
+// they are reported and left as they are, like the starred forms
+// of the commands above, which give the full author list.
+static NATBIB_ONLY: &[&str] = &[
+    "citealt",
+    "citetext",
+    "citenum",
+    "citetalias",
+    "citepalias",
+    "citefullauthor",
+    "citestyle",
+];
+
+// Rewrites the natbib commands in a document; the commands
+// that cannot be migrated are returned with their line.
+// Comments are left alone.
+pub fn rewrite(text: &str) -> (String, Vec<(usize, String)>) {
+    let mut out = String::with_capacity(text.len());
+    let mut skipped = Vec::new();
+    for (i, l) in text.split_inclusive('\n').enumerate() {
+        let (code, comment) = l.split_at(comment_start(l));
+        let (new, left) = rewrite_code(code);
+        skipped.extend(left.into_iter().map(|c| (i + 1, c)));
+        out.push_str(&new);
+        out.push_str(comment);
+    }
+    (out, skipped)
+}
+
+// The start of a comment: a '%' that is not escaped.
+fn comment_start(l: &str) -> usize {
+    let bs = l.as_bytes();
+    let mut i = 0;
+    while i < bs.len() {
+        match bs[i] {
+            b'\\' => i += 1,
+            b'%' => return i,
+            _ => (),
+        }
+        i += 1;
+    }
+    l.len()
+}
+
+// Citation commands are found as the scanner finds them,
+// so that we touch nothing else.
+fn rewrite_code(code: &str) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(code.len());
+    let mut skipped = Vec::new();
+    let mut rest = code;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..=i]);
+        rest = &rest[i + 1..];
+        let n = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        if n == 0 {
+            // an escaped character like \\ or \%
+            if let Some(c) = rest.chars().next() {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+            continue;
+        }
+        let name = &rest[..n];
+        let lower = name.to_ascii_lowercase();
+        let starred = rest[n..].starts_with('*');
+        let target = COMMANDS.iter().find(|(c, _)| *c == lower);
+        match target {
+            None if NATBIB_ONLY.contains(&lower.as_str()) => {
+                skipped.push(format!("\\{}", name));
+                out.push_str(name);
+            }
+            _ if parser::cite_kind(name).is_none() => out.push_str(name),
+            Some((_, t)) if !starred => out.push_str(&capitalize_like(t, name)),
+            Some(_) => {
+                skipped.push(format!("\\{}*", name));
+                out.push_str(name);
+            }
+            None => out.push_str(name),
+        }
+        rest = &rest[n..];
+    }
+    out.push_str(rest);
+    (out, skipped)
+}
+
+fn capitalize_like(target: &str, name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_uppercase()) {
+        target[..1].to_uppercase() + &target[1..]
+    } else {
+        target.to_string()
+    }
+}
+
+// Migrates the documents; in a dry run, the changes are printed
+// as a unified diff instead of being written.
+// Returns the number of changed lines.
+pub fn migrate(files: &[OsString], dry_run: bool) -> Result<usize, String> {
+    let mut total = 0;
+    for file in files {
+        let name = file.to_string_lossy();
+        let text = fs::read_to_string(file).map_err(|e| format!("cannot read {}: {}", name, e))?;
+        let (new, skipped) = rewrite(&text);
+        for (line, cmd) in skipped {
+            diagnostics::warn(
+                "B010",
+                &format!("{} at {}:{} has no biblatex equivalent", cmd, name, line),
+            );
+        }
+        let changes = diff::changed_lines(&text, &new);
+        if changes.is_empty() {
+            continue;
+        }
+        total += changes.len();
+        if dry_run {
+            print!("{}", diff::unified(&name, &changes));
+        } else {
+            fs::write(file, new).map_err(|e| format!("cannot write {}: {}", name, e))?;
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rewrite() {
+        let text = "\\Citet{a} and \\citep[see][p.~3]{b,c}.\n\
+                    50\\% in \\citeyearpar{d}, \\citet*{e} % \\citep{f}\n\
+                    \\citealt{g} \\citation{h} \\textcite{i}\n";
+        let (new, skipped) = rewrite(text);
+        assert_eq!(
+            new,
+            "\\Textcite{a} and \\parencite[see][p.~3]{b,c}.\n\
+             50\\% in \\parencite*{d}, \\citet*{e} % \\citep{f}\n\
+             \\citealt{g} \\citation{h} \\textcite{i}\n"
+        );
+        assert_eq!(
+            skipped,
+            vec![(2, "\\citet*".to_string()), (3, "\\citealt".to_string())]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+// Splits a BibTeX name list like "Smith, Adam and Karl Marx"
+// into the names of the persons. Names are separated by 'and'
+// as a word outside braces, in any case; "others", which stands
+// for et al., is left out. Names written as "Last, First" are
+// turned into "First Last", so that both forms count as one person.
+// Names with more than one comma ("von Last, Jr, First") are kept.
+pub fn split(list: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = list.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b if depth == 0 && b.is_ascii_whitespace() && at_and(&bytes[i + 1..]) => {
+                names.push(&list[start..i]);
+                i += 4;
+                start = i + 1;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    names.push(&list[start.min(list.len())..]);
+    names
+        .into_iter()
+        .map(str::trim)
+        .filter(|n| !n.is_empty() && *n != "others")
+        .map(normalize)
+        .collect()
+}
+
+// The editors of a work as a name list in which every
+// editor is marked, like "Karl Marx (ed.) and Friedrich Engels (ed.)",
+// so that they count apart from the same persons as authors.
+pub fn as_editors(list: &str) -> String {
+    split(list)
+        .iter()
+        .map(|n| format!("{} (ed.)", n))
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+// "and" followed by whitespace
+fn at_and(bs: &[u8]) -> bool {
+    bs.len() > 4 && bs[..3].eq_ignore_ascii_case(b"and") && bs[3].is_ascii_whitespace()
+}
+
+fn normalize(name: &str) -> String {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let commas = name.matches(',').count();
+    match name.split_once(',') {
+        Some((last, first)) if commas == 1 && !first.trim().is_empty() => {
+            format!("{} {}", first.trim(), last.trim())
+        }
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split("Marx, Karl and Friedrich  Engels AND others"),
+            vec!["Karl Marx", "Friedrich Engels"]
+        );
+        assert_eq!(
+            split("{Barnes and Noble} and Sandy"),
+            vec!["{Barnes and Noble}", "Sandy"]
+        );
+        assert_eq!(split("Alexander Anderson"), vec!["Alexander Anderson"]);
+        assert_eq!(split("von Last, Jr, First"), vec!["von Last, Jr, First"]);
+        assert!(split("").is_empty());
+    }
+
+    #[test]
+    fn test_as_editors() {
+        let eds = as_editors("Marx, Karl and Friedrich Engels");
+        assert_eq!(eds, "Karl Marx (ed.) and Friedrich Engels (ed.)");
+        assert_eq!(
+            split(&eds),
+            vec!["Karl Marx (ed.)", "Friedrich Engels (ed.)"]
+        );
+    }
+}
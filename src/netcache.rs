@@ -0,0 +1,268 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+use crate::cache::fnv1a;
+
+// A response as far as we are interested in it.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+// The answer to a GET request: None if we are offline
+// and the URL is not cached.
+pub type Answer = Result<Option<Response>, String>;
+
+// An on-disk cache of HTTP GET requests shared by all features
+// that look things up on the net. Every response is kept in a file
+// named after the hash of the URL together with the time it was fetched.
+// Responses younger than the TTL the caller asks for are served from
+// the cache; older ones are fetched again and, if that fails,
+// served anyway. Offline, only the cache is used, whatever the age.
+// Server errors and 'too many requests' are not cached,
+// but other client errors are: a DOI that is not found
+// will not be found tomorrow either.
+// Requests to the net, but not answers from the cache,
+// are subject to the limits. If the cache cannot be written,
+// for instance in a container without a writable home,
+// we warn once and go on without caching.
+pub struct NetCache {
+    dir: PathBuf,
+    offline: bool,
+    agent: ureq::Agent,
+    limits: Limits,
+    // the earliest time for the next request
+    next: Mutex<Instant>,
+    warned: AtomicBool,
+}
+
+// How many requests may run at the same time
+// and how many may be started per second (0 for no limit).
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub concurrency: usize,
+    pub rate: f64,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            concurrency: 2,
+            rate: 5.0,
+        }
+    }
+}
+
+impl NetCache {
+    // The agent must not treat HTTP error statuses as errors.
+    pub fn new(dir: PathBuf, offline: bool, agent: ureq::Agent, limits: Limits) -> NetCache {
+        NetCache {
+            dir,
+            offline,
+            agent,
+            limits,
+            next: Mutex::new(Instant::now()),
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    // Gets all URLs with at most as many requests at a time
+    // as the limits allow; the results are in the order of the URLs.
+    pub fn get_all(&self, urls: &[String], ttl: Duration) -> Vec<Answer> {
+        let workers = self.limits.concurrency.clamp(1, urls.len().max(1));
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Answer>>> = Mutex::new(urls.iter().map(|_| None).collect());
+        thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= urls.len() {
+                        break;
+                    }
+                    let r = self.get(&urls[i], ttl);
+                    results.lock().unwrap()[i] = Some(r);
+                });
+            }
+        });
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.unwrap_or_else(|| Err("request not run".to_string())))
+            .collect()
+    }
+
+    pub fn get(&self, url: &str, ttl: Duration) -> Answer {
+        let path = self.dir.join(format!("{:016x}.json", fnv1a(url)));
+        let cached = read(&path, url);
+
+        if let Some((fetched, r)) = &cached {
+            if self.offline || now().saturating_sub(*fetched) < ttl.as_secs() {
+                return Ok(Some(r.clone()));
+            }
+        }
+        if self.offline {
+            return Ok(None);
+        }
+
+        match self.fetch(url) {
+            Ok(r) => {
+                if r.status < 500 && r.status != 429 {
+                    if let Err(e) = write(&path, url, &r) {
+                        if !self.warned.swap(true, Ordering::Relaxed) {
+                            eprintln!("{}, responses are not cached", e);
+                        }
+                    }
+                }
+                Ok(Some(r))
+            }
+            Err(e) => match cached {
+                Some((_, r)) => {
+                    eprintln!("{}, using the cached response", e);
+                    Ok(Some(r))
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    // Waits for the next slot the rate allows.
+    fn wait(&self) {
+        if self.limits.rate <= 0.0 {
+            return;
+        }
+        let interval = Duration::from_secs_f64(1.0 / self.limits.rate);
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let at = (*next).max(Instant::now());
+            *next = at + interval;
+            at
+        };
+        thread::sleep(at.saturating_duration_since(Instant::now()));
+    }
+
+    fn fetch(&self, url: &str) -> Result<Response, String> {
+        self.wait();
+        let mut resp = self
+            .agent
+            .get(url)
+            .call()
+            .map_err(|e| format!("cannot get {}: {}", url, e))?;
+        let status = resp.status().as_u16();
+        let body = resp
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| format!("cannot read {}: {}", url, e))?;
+        Ok(Response { status, body })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// A cache file that cannot be read or belongs to another URL
+// (a hash collision) counts as missing.
+fn read(path: &Path, url: &str) -> Option<(u64, Response)> {
+    let text = fs::read_to_string(path).ok()?;
+    let js: serde_json::Value = serde_json::from_str(&text).ok()?;
+    if js["url"].as_str()? != url {
+        return None;
+    }
+    Some((
+        js["fetched"].as_u64()?,
+        Response {
+            status: js["status"].as_u64()? as u16,
+            body: js["body"].as_str()?.to_string(),
+        },
+    ))
+}
+
+fn write(path: &Path, url: &str, r: &Response) -> Result<(), String> {
+    let js = json!({
+        "url": url,
+        "fetched": now(),
+        "status": r.status,
+        "body": r.body
+    });
+    let dir = path.parent().unwrap_or(Path::new(""));
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(path, js.to_string()))
+        .map_err(|e| format!("cannot write cache file {}: {}", path.display(), e))
+}
+
+// Percent-encodes everything but unreserved characters and '/'.
+pub fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http;
+    use std::env;
+
+    #[test]
+    fn test_offline_cache() {
+        let dir = env::temp_dir().join(format!("bibstats-test-{}", std::process::id()));
+        let agent = http::agent(&http::HttpConfig::default()).unwrap();
+        let net = NetCache::new(dir.clone(), true, agent, Limits::default());
+        let url = "https://example.org/works/10.1000/x";
+        assert!(matches!(net.get(url, Duration::ZERO), Ok(None)));
+
+        let path = dir.join(format!("{:016x}.json", fnv1a(url)));
+        let r = Response {
+            status: 200,
+            body: "{}".to_string(),
+        };
+        assert!(write(&path, url, &r).is_ok());
+        // offline, stale answers are used
+        match net.get(url, Duration::ZERO) {
+            Ok(Some(r)) => assert!(r.status == 200 && r.body == "{}"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(read(&path, "https://example.org/other").is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let agent = http::agent(&http::HttpConfig::default()).unwrap();
+        let limits = Limits {
+            concurrency: 2,
+            rate: 20.0,
+        };
+        let net = NetCache::new(env::temp_dir(), true, agent, limits);
+        let start = Instant::now();
+        for _ in 0..3 {
+            net.wait();
+        }
+        // the first request runs at once, the others wait 50ms each
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            encode("10.1016/s0140-6736(97)11096-0"),
+            "10.1016/s0140-6736%2897%2911096-0"
+        );
+    }
+}
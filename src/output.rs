@@ -0,0 +1,105 @@
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+// How reports are printed:
+// as JSON, as tab-separated values or as Markdown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json(Json),
+    Tsv,
+    Markdown,
+}
+
+// JSON output in version 1 is a stream of objects or, with array,
+// one array of them. Version 2 is one document with the records
+// and what they are about, whatever array says.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Json {
+    pub array: bool,
+    pub version: Version,
+}
+
+// The versions of the JSON output. A version never changes
+// once released; new structure goes into a new version.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Version, String> {
+        match s {
+            "1" => Ok(Version::V1),
+            "2" => Ok(Version::V2),
+            _ => Err(format!("unknown output version '{}', 1 or 2 expected", s)),
+        }
+    }
+}
+
+// Prints the JSON records of a report in the requested version.
+pub fn print_json<I: IntoIterator<Item = Value>>(values: I, json: Json, report: &str) {
+    match json.version {
+        Version::V1 => print_json_v1(values, json.array),
+        Version::V2 => print_json_v2(values, report),
+    }
+}
+
+// {"version":2,"report":"stats","generator":"bibstats 0.1.0","records":[...]}
+fn print_json_v2<I: IntoIterator<Item = Value>>(values: I, report: &str) {
+    let records: Vec<Value> = values.into_iter().collect();
+    let js = json!({
+        "version": 2,
+        "report": report,
+        "generator": concat!("bibstats ", env!("CARGO_PKG_VERSION")),
+        "records": records
+    });
+    println!("{}", js);
+}
+
+// Prints JSON objects as a stream, one object per line,
+// or, with array, as one JSON array.
+fn print_json_v1<I: IntoIterator<Item = Value>>(values: I, with_array: bool) {
+    let mut first = true;
+    if with_array {
+        println!("[");
+    }
+    for js in values {
+        // print comma if we are in an array
+        if !first {
+            if with_array {
+                println!(",")
+            } else {
+                println!()
+            }
+        }
+
+        print!("{}", js);
+
+        if first {
+            first = false;
+        }
+    }
+    println!();
+    if with_array {
+        println!("]");
+    }
+}
+
+// Escapes the characters that would break a Markdown table cell.
+pub fn md_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+// Prints a Markdown table with the given header.
+pub fn print_md_table<I: IntoIterator<Item = Vec<String>>>(header: &[&str], rows: I) {
+    println!("| {} |", header.join(" | "));
+    println!("|{}", "---|".repeat(header.len()));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|c| md_cell(c)).collect();
+        println!("| {} |", cells.join(" | "));
+    }
+}
@@ -0,0 +1,173 @@
+use rustc_hash::FxHashSet;
+use serde_json::json;
+
+use crate::compare;
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::parser::BibEntry;
+use crate::stats::BibMap;
+
+// How the works cited overlap with a reference bibliography,
+// like the reading list of a course or the bibliography of a survey:
+// how many of the works cited are in the reference, how many
+// of the works of the reference are cited and the canonical works
+// that are missing, in the order of the reference bib file.
+// Works are the same as for compare, by DOI or normalized title.
+pub struct Overlap<'a> {
+    pub cited: usize,
+    pub cited_in_reference: usize,
+    pub reference: usize,
+    pub reference_cited: usize,
+    pub missing: Vec<&'a BibEntry>,
+}
+
+impl Overlap<'_> {
+    // the share of the works cited that are in the reference
+    pub fn precision(&self) -> f64 {
+        ratio(self.cited_in_reference, self.cited)
+    }
+
+    // the share of the works of the reference that are cited
+    pub fn recall(&self) -> f64 {
+        ratio(self.reference_cited, self.reference)
+    }
+}
+
+fn ratio(n: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        n as f64 / total as f64
+    }
+}
+
+pub fn compute<'a>(bib: &BibMap, cites: &[Citation], reference: &'a [BibEntry]) -> Overlap<'a> {
+    let mut seen = FxHashSet::default();
+    let cited: Vec<Vec<String>> = cites
+        .iter()
+        .filter(|c| seen.insert(c.key.as_str()))
+        .map(|c| compare::identities(&c.key, bib.get(&c.key)))
+        .collect();
+    let mut seen = FxHashSet::default();
+    let reference: Vec<(&BibEntry, Vec<String>)> = reference
+        .iter()
+        .filter(|e| seen.insert(e.key.as_str()))
+        .map(|e| (e, compare::identities(&e.key, Some(e))))
+        .collect();
+
+    let cited_ids: FxHashSet<&str> = cited.iter().flatten().map(String::as_str).collect();
+    let reference_ids: FxHashSet<&str> = reference
+        .iter()
+        .flat_map(|(_, is)| is)
+        .map(String::as_str)
+        .collect();
+    let missing: Vec<&BibEntry> = reference
+        .iter()
+        .filter(|(_, is)| !is.iter().any(|id| cited_ids.contains(id.as_str())))
+        .map(|(e, _)| *e)
+        .collect();
+    Overlap {
+        cited: cited.len(),
+        cited_in_reference: cited
+            .iter()
+            .filter(|is| is.iter().any(|id| reference_ids.contains(id.as_str())))
+            .count(),
+        reference: reference.len(),
+        reference_cited: reference.len() - missing.len(),
+        missing,
+    }
+}
+
+// The metrics go to stderr, the missing works are the report.
+pub fn print_overlap(o: &Overlap, f: Format) {
+    eprintln!(
+        "{} of {} works cited are in the reference (precision {:.2}), \
+         {} of {} works of the reference are cited (recall {:.2})",
+        o.cited_in_reference,
+        o.cited,
+        o.precision(),
+        o.reference_cited,
+        o.reference,
+        o.recall()
+    );
+    match f {
+        Format::Json(j) => overlap_as_json(o, j),
+        Format::Tsv => {
+            for e in &o.missing {
+                println!("\"{}\"\t\"{}\"\t\"{}\"", e.key, e.author, e.title);
+            }
+        }
+        Format::Markdown => {
+            let rows = o
+                .missing
+                .iter()
+                .map(|e| vec![e.key.clone(), e.author.clone(), e.title.clone()]);
+            output::print_md_table(&["Key", "Author", "Title"], rows);
+        }
+    }
+}
+
+fn overlap_as_json(o: &Overlap, json: Json) {
+    let records = o.missing.iter().map(|e| {
+        json!({
+            "key": e.key,
+            "author": e.author,
+            "title": e.title
+        })
+    });
+    output::print_json(records, json, "overlap");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+
+    #[test]
+    fn test_overlap() {
+        let entry = |key: &str, title: &str| BibEntry {
+            key: key.to_string(),
+            title: title.to_string(),
+            ..BibEntry::empty()
+        };
+        let bib: BibMap = [
+            entry("knuth84", "Literate Programming"),
+            entry("wirth", "Algorithms + Data Structures = Programs"),
+        ]
+        .into_iter()
+        .map(|e| (e.key.clone(), e))
+        .collect();
+        let cites: Vec<Citation> = ["knuth84", "wirth", "knuth84", "nobib"]
+            .iter()
+            .map(|k| citation(k))
+            .collect();
+        let reference = vec![
+            entry("Knuth:1984", "Literate programming."),
+            entry("dijkstra68", "Go To Statement Considered Harmful"),
+            entry("hoare", "Communicating Sequential Processes"),
+            entry("dijkstra68", "Go To Statement Considered Harmful"),
+        ];
+        let o = compute(&bib, &cites, &reference);
+        assert_eq!((o.cited_in_reference, o.cited), (1, 3));
+        assert_eq!((o.reference_cited, o.reference), (1, 3));
+        let missing: Vec<&str> = o.missing.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(missing, vec!["dijkstra68", "hoare"]);
+        assert_eq!(o.recall(), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_ratios() {
+        // without citations or reference, the shares are 0, not NaN
+        let o = compute(&BibMap::default(), &[], &[]);
+        assert_eq!((o.cited, o.reference), (0, 0));
+        assert_eq!((o.precision(), o.recall()), (0.0, 0.0));
+        let reference = vec![BibEntry {
+            key: "knuth84".to_string(),
+            ..BibEntry::empty()
+        }];
+        let o = compute(&BibMap::default(), &[citation("knuth84")], &reference);
+        assert_eq!((o.precision(), o.recall()), (1.0, 1.0));
+        assert!(o.missing.is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+// The pages field: a page, a range or a list of them,
+// e.g. "12", "12--34", "S1--S9, 101".
+
+// How the ends of a range are separated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sep {
+    // --, the en-dash in BibTeX
+    Dashes,
+    // - or a unicode hyphen
+    Hyphen,
+    // a unicode en-dash or em-dash
+    Unicode,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Range<'a> {
+    pub start: &'a str,
+    pub end: Option<(Sep, &'a str)>,
+}
+
+impl Range<'_> {
+    // The numbers of the first and the last page, if both are numbers
+    // with the same prefix, like "S12" and "S20".
+    pub fn numbers(&self) -> Option<(u64, u64)> {
+        let (_, end) = self.end?;
+        let (p1, n1) = split_number(self.start)?;
+        let (p2, n2) = split_number(end)?;
+        if p1 != p2 {
+            return None;
+        }
+        Some((n1, n2))
+    }
+}
+
+fn split_number(page: &str) -> Option<(&str, u64)> {
+    let i = page.find(|c: char| c.is_ascii_digit())?;
+    let n = page[i..].parse().ok()?;
+    Some((&page[..i], n))
+}
+
+pub fn parse(pages: &str) -> Vec<Range<'_>> {
+    pages
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(range)
+        .collect()
+}
+
+fn range(p: &str) -> Range<'_> {
+    let seps: [(&str, Sep); 5] = [
+        ("--", Sep::Dashes),
+        ("\u{2013}", Sep::Unicode),
+        ("\u{2014}", Sep::Unicode),
+        ("\u{2010}", Sep::Hyphen),
+        ("-", Sep::Hyphen),
+    ];
+    for (s, sep) in seps {
+        if let Some((start, end)) = p.split_once(s) {
+            // more dashes than two are still a range
+            let end = end.trim_start_matches('-');
+            return Range {
+                start: start.trim(),
+                end: Some((sep, end.trim())),
+            };
+        }
+    }
+    Range {
+        start: p,
+        end: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_pages() {
+        assert_eq!(
+            parse("12"),
+            vec![Range {
+                start: "12",
+                end: None
+            }]
+        );
+        assert_eq!(
+            parse("12 -- 34, S1-S9"),
+            vec![
+                Range {
+                    start: "12",
+                    end: Some((Sep::Dashes, "34"))
+                },
+                Range {
+                    start: "S1",
+                    end: Some((Sep::Hyphen, "S9"))
+                }
+            ]
+        );
+        assert_eq!(parse("12\u{2013}34")[0].end, Some((Sep::Unicode, "34")));
+        assert_eq!(parse("200--100")[0].numbers(), Some((200, 100)));
+        assert_eq!(parse("S1--T9")[0].numbers(), None);
+    }
+}
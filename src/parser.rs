@@ -1,8 +1,16 @@
 use pacosso::{ParseError, ParseResult, Stream};
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fmt;
 use std::fmt::Display;
 use std::io::Read;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::dates;
+use crate::ids;
 
 #[derive(Debug, PartialEq)]
 pub struct BibEntry {
@@ -11,10 +19,39 @@ pub struct BibEntry {
     pub author: String,
     pub title: String,
     pub date: String,
+    // all other fields by their lowercase name
+    pub fields: HashMap<String, String>,
+    // the bib file the entry comes from; set by the loader
+    pub file: OsString,
+    // the line where the entry starts (starting at 1)
+    pub line: u64,
+    // the bytes of the entry in its file, from the '@'
+    // to the closing brace
+    pub span: Range<usize>,
+    // the bytes of the citekey and the fields, if the options ask for them
+    pub spans: Option<Spans>,
+}
+
+// Where the parts of an entry are in its file, as byte ranges,
+// for tools that change the file in place.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Spans {
+    pub key: Range<usize>,
+    // the fields by their lowercase name; of fields given more than
+    // once, the one whose value is taken
+    pub fields: HashMap<String, FieldSpan>,
+}
+
+// A field from its name to the end of its value and the value,
+// with its braces or quotes and the pieces joined with '#'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSpan {
+    pub field: Range<usize>,
+    pub value: Range<usize>,
 }
 
-#[allow(dead_code)]
 impl BibEntry {
+    #[allow(dead_code)]
     pub fn empty() -> BibEntry {
         Self {
             pubtype: PubType::Misc,
@@ -22,8 +59,68 @@ impl BibEntry {
             author: "".to_string(),
             title: "".to_string(),
             date: "".to_string(),
+            fields: HashMap::new(),
+            file: OsString::new(),
+            line: 0,
+            span: 0..0,
+            spans: None,
+        }
+    }
+
+    // the year from the date or, if it is not a valid date,
+    // from the year field; for ranges with open start the end year
+    pub fn year(&self) -> Option<&str> {
+        let d = self.date.trim();
+        match dates::parse_range(d) {
+            Some(_) => d.trim_start_matches(['.', '/']).get(..4),
+            None => self.field("year"),
         }
     }
+
+    // the year of the date as number
+    pub fn year_number(&self) -> Option<i32> {
+        dates::parse_range(&self.date)?.year()
+    }
+
+    // the value of a field other than author, title and date,
+    // if it is given and not empty
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    }
+
+    // the normalized DOI, if the entry has a valid one;
+    // it identifies the work independent of the citekey
+    pub fn doi(&self) -> Option<String> {
+        ids::normalize_doi(self.field("doi")?).ok()
+    }
+
+    // the keywords of the entry, which are separated
+    // by commas or, as in some exports, by semicolons
+    pub fn keywords(&self) -> Vec<&str> {
+        self.field("keywords")
+            .map(|ks| {
+                ks.split([',', ';'])
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // whether the entry has the keyword, in any case
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.keywords()
+            .iter()
+            .any(|k| k.eq_ignore_ascii_case(keyword))
+    }
+
+    // where the entry is defined as "file:line"
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.file.to_string_lossy(), self.line)
+    }
 }
 
 impl Display for BibEntry {
@@ -32,23 +129,227 @@ impl Display for BibEntry {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PubType {
     Book,
     Article,
     Incol,
     Inproc,
     Misc,
+    Booklet,
+    Inbook,
+    Manual,
+    Masters,
+    Phd,
+    Proceedings,
+    Techreport,
+    Unpublished,
+    // any other type, like @online of biblatex, by its lowercase name
+    Other(String),
 }
 
+// The standard BibTeX entry types by name;
+// @conference is an old alias of @inproceedings.
+static TYPES: &[(&str, PubType)] = &[
+    ("article", PubType::Article),
+    ("book", PubType::Book),
+    ("booklet", PubType::Booklet),
+    ("conference", PubType::Inproc),
+    ("inbook", PubType::Inbook),
+    ("incollection", PubType::Incol),
+    ("inproceedings", PubType::Inproc),
+    ("manual", PubType::Manual),
+    ("mastersthesis", PubType::Masters),
+    ("misc", PubType::Misc),
+    ("phdthesis", PubType::Phd),
+    ("proceedings", PubType::Proceedings),
+    ("techreport", PubType::Techreport),
+    ("unpublished", PubType::Unpublished),
+];
+
 impl Display for PubType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self)
+        let name = match self {
+            PubType::Inproc => "inproceedings",
+            PubType::Other(name) => name,
+            t => TYPES
+                .iter()
+                .find(|(_, pt)| pt == t)
+                .map_or("misc", |(n, _)| n),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A parsed bib file: the entries it defines,
+// the paths of the bib files it includes
+// and the content of its @comment blocks;
+// in lenient mode also the entries skipped as malformed.
+#[derive(Debug, Default)]
+pub struct BibFile {
+    pub entries: Vec<BibEntry>,
+    pub includes: Vec<String>,
+    pub comments: Vec<String>,
+    pub skipped: Vec<Skipped>,
+    pub duplicate_fields: Vec<DuplicateField>,
+}
+
+// A malformed entry skipped in lenient mode:
+// the line where it starts and what is wrong with it.
+#[derive(Debug, PartialEq)]
+pub struct Skipped {
+    pub line: u64,
+    pub msg: String,
+}
+
+// A field given more than once in an entry,
+// with the line where the entry starts.
+#[derive(Debug, PartialEq)]
+pub struct DuplicateField {
+    pub line: u64,
+    pub key: String,
+    pub field: String,
+}
+
+// How to parse bib files.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Options {
+    // skip malformed entries instead of failing
+    pub lenient: bool,
+    // what to do with fields given more than once in an entry
+    pub duplicate_fields: FieldPolicy,
+    // keep the braces in values, as in "The {DNA} Book"
+    pub keep_braces: bool,
+    // record the bytes of the citekeys and fields, see Spans
+    pub spans: bool,
+}
+
+// BibTeX uses the first of duplicated fields and warns, biber the last.
+// Exports of reference managers often have duplicated fields.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FieldPolicy {
+    #[default]
+    Error,
+    FirstWins,
+    LastWins,
+}
+
+impl FromStr for FieldPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FieldPolicy, String> {
+        match s {
+            "error" => Ok(FieldPolicy::Error),
+            "first-wins" => Ok(FieldPolicy::FirstWins),
+            "last-wins" => Ok(FieldPolicy::LastWins),
+            _ => Err(format!(
+                "unknown policy '{}', error, first-wins or last-wins expected",
+                s
+            )),
+        }
+    }
+}
+
+// The @string macros defined so far by their lowercase name.
+type Macros = HashMap<String, String>;
+
+// Like BibTeX, we predefine the macros jan to dec
+// as the English names of the months.
+fn months() -> Macros {
+    dates::MONTHS
+        .iter()
+        .map(|m| {
+            let name = m[..1].to_uppercase() + &m[1..];
+            (m[..3].to_string(), name)
+        })
+        .collect()
+}
+
+#[allow(dead_code)]
+pub fn parse<R: Read>(s: &mut Stream<R>) -> ParseResult<BibFile> {
+    parse_with(s, Options::default())
+}
+
+pub fn parse_with<R: Read>(s: &mut Stream<R>, opts: Options) -> ParseResult<BibFile> {
+    let mut bib = BibFile::default();
+    let mut macros = months();
+    loop {
+        skip_space(s)?;
+        if eof(s) {
+            break;
+        }
+        let line = line(s);
+        let start = s.position().stream;
+        match item(s, opts, &mut bib, &mut macros) {
+            Ok(()) => (),
+            Err(ParseError::Failed(msg, _)) if opts.lenient => {
+                bib.skipped.push(Skipped { line, msg });
+                recover(s, start)?;
+            }
+            Err(e @ ParseError::Effect(..)) if opts.lenient => {
+                bib.skipped.push(Skipped {
+                    line,
+                    msg: e.to_string(),
+                });
+                recover(s, start)?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(bib)
+}
+
+fn item<R: Read>(
+    s: &mut Stream<R>,
+    opts: Options,
+    bib: &mut BibFile,
+    macros: &mut Macros,
+) -> ParseResult<()> {
+    match s.peek_byte()? {
+        b'\\' => bib.includes.push(include(s)?),
+        b'@' if at_command(s, b"@comment") => {
+            if let Some(c) = comment(s)? {
+                bib.comments.push(c);
+            }
+        }
+        b'@' if at_command(s, b"@preamble") => preamble(s)?,
+        b'@' if at_command(s, b"@string") => {
+            let (k, v) = string(s, macros, opts.keep_braces)?;
+            macros.insert(k, v);
+        }
+        _ => {
+            let mut dups = Vec::new();
+            let entry = bibentry(s, macros, opts, &mut dups)?;
+            bib.duplicate_fields
+                .extend(dups.into_iter().map(|field| DuplicateField {
+                    line: entry.line,
+                    key: entry.key.clone(),
+                    field,
+                }));
+            bib.entries.push(entry);
+        }
     }
+    Ok(())
 }
 
-pub fn parse<R: Read>(s: &mut Stream<R>) -> ParseResult<Vec<BibEntry>> {
-    s.many_one(bibentry)
+// After an error, we continue with the next entry, which starts
+// with '@' at the beginning of a line; an '@' within a line
+// may be part of a field, like an email address.
+// An error at an '@' after the start of the item, as in an entry
+// that lacks its closing brace, means that the next entry starts there.
+fn recover<R: Read>(s: &mut Stream<R>, start: u64) -> ParseResult<()> {
+    let mut at_next = s.position().stream > start;
+    let mut line_start = false;
+    while !eof(s) {
+        let b = s.peek_byte()?;
+        if b == b'@' && (at_next || line_start) {
+            break;
+        }
+        next_byte(s)?;
+        at_next = false;
+        line_start = b == b'\n' || (line_start && b.is_ascii_whitespace());
+    }
+    Ok(())
 }
 
 #[allow(dead_code)]
@@ -56,114 +357,313 @@ fn fail<R: Read>(s: &mut Stream<R>, msg: String) -> ParseResult<BibEntry> {
     s.fail(&msg, BibEntry::empty())
 }
 
-fn bibentry<R: Read>(s: &mut Stream<R>) -> ParseResult<BibEntry> {
-    s.skip_whitespace()?;
+// The names of duplicated fields are added to dups,
+// unless the policy makes them an error.
+fn bibentry<R: Read>(
+    s: &mut Stream<R>,
+    macros: &Macros,
+    opts: Options,
+    dups: &mut Vec<String>,
+) -> ParseResult<BibEntry> {
+    skip_space(s)?;
+    let line = line(s);
+    let start = s.position().stream as usize;
     s.byte(b'@')?;
     let pubtype = pubtype(s)?;
-    s.skip_whitespace()?;
-    body(s, pubtype)
+    skip_space(s)?;
+    let mut entry = body(s, pubtype, macros, opts, dups)?;
+    entry.line = line;
+    entry.span = start..s.position().stream as usize;
+    Ok(entry)
 }
 
-fn pubtype<R: Read>(s: &mut Stream<R>) -> ParseResult<PubType> {
-    let book = |p: &mut Stream<R>| -> ParseResult<PubType> {
-        p.string_ic("book")?;
-        Ok(PubType::Book)
-    };
-    let article = |p: &mut Stream<R>| -> ParseResult<PubType> {
-        p.string_ic("article")?;
-        Ok(PubType::Article)
-    };
-    let inproc = |p: &mut Stream<R>| -> ParseResult<PubType> {
-        p.string_ic("inproceedings")?;
-        Ok(PubType::Inproc)
-    };
-    let incol = |p: &mut Stream<R>| -> ParseResult<PubType> {
-        p.string_ic("incollection")?;
-        Ok(PubType::Incol)
-    };
-    let misc = |p: &mut Stream<R>| -> ParseResult<PubType> {
-        p.string_ic("misc")?;
-        Ok(PubType::Misc)
-    };
-    let choices = [book, article, inproc, incol, misc];
-    s.choice(&choices[..])
+// The current line starting at 1.
+// pacosso counts lines from 0 once the stream is initialised
+// and only in whitespace; newlines consumed otherwise
+// must be counted with count_lines().
+fn line<R: Read>(s: &mut Stream<R>) -> u64 {
+    let p = s.position();
+    if p.stream == 0 {
+        1
+    } else {
+        p.line + 1
+    }
 }
 
-fn body<R: Read>(s: &mut Stream<R>, pt: PubType) -> ParseResult<BibEntry> {
+// The column of the next byte starting at 1.
+// Like the line, pacosso counts the first line from 0,
+// the others from 1.
+fn column<R: Read>(s: &mut Stream<R>) -> u64 {
+    let p = s.position();
+    if p.stream == 0 {
+        1
+    } else if p.line == 0 {
+        p.lpos + 1
+    } else {
+        p.lpos
+    }
+}
+
+// Whether the next bytes are the command, in any case.
+fn at_command<R: Read>(s: &mut Stream<R>, cmd: &[u8]) -> bool {
+    match s.peek_bytes(cmd.len()) {
+        Ok(bs) => bs.eq_ignore_ascii_case(cmd),
+        Err(_) => false,
+    }
+}
+
+// @comment{...}, where JabRef keeps its metadata.
+// Like BibTeX, we also accept @comment(...), which we skip,
+// and @comment followed by text, which ends with the line.
+fn comment<R: Read>(s: &mut Stream<R>) -> ParseResult<Option<String>> {
+    s.byte(b'@')?;
+    s.string_ic("comment")?;
+    s.skip_whitespace()?;
+    match s.peek_byte()? {
+        b'{' => Ok(Some(brace_group(s)?)),
+        b'(' => skip_group(s, b'(', b')').map(|_| None),
+        _ => skip_line(s).map(|_| None),
+    }
+}
+
+// @preamble{...} or @preamble(...) is for BibTeX and the styles;
+// we skip it.
+fn preamble<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    s.byte(b'@')?;
+    s.string_ic("preamble")?;
     s.skip_whitespace()?;
+    if s.peek_byte()? == b'(' {
+        skip_group(s, b'(', b')')
+    } else {
+        skip_group(s, b'{', b'}')
+    }
+}
+
+// @string{name = value}; the value may use the macros defined before.
+fn string<R: Read>(
+    s: &mut Stream<R>,
+    macros: &Macros,
+    keep_braces: bool,
+) -> ParseResult<(String, String)> {
+    s.byte(b'@')?;
+    s.string_ic("string")?;
+    skip_space(s)?;
     s.byte(b'{')?;
+    let (name, value, _) = header(s, macros, keep_braces)?;
+    skip_space(s)?;
+    s.byte(b'}')?;
+    Ok((name, value))
+}
+
+// Skips whitespace and comment lines starting with '%', which
+// hand-maintained bib files have between entries and fields.
+// In values, '%' is an ordinary character, as in URLs.
+fn skip_space<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    loop {
+        s.skip_whitespace()?;
+        if eof(s) || s.peek_byte()? != b'%' {
+            return Ok(());
+        }
+        skip_line(s)?;
+    }
+}
+
+// Skips to the end of the line including the newline.
+fn skip_line<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    while !eof(s) {
+        if next_byte(s)? == b'\n' {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Master bib files may pull in other bib files with
+// \input{file} or \include{file} between entries.
+// BibTeX itself ignores these lines.
+fn include<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
+    s.byte(b'\\')?;
+    let cmd = alphanum(s, false)?;
+    if cmd != "input" && cmd != "include" {
+        return s.fail(
+            &format!("unexpected command '\\{}', '\\input' expected", cmd),
+            String::new(),
+        );
+    }
     s.skip_whitespace()?;
+    s.byte(b'{')?;
+    let path = chars_until_closer(s, '}', false)?;
+    s.byte(b'}')?;
+    Ok(path.trim().to_string())
+}
+
+// The type is read as a whole word, so that @book does not
+// match the start of @booklet. Types we do not know,
+// like those of biblatex or custom ones, are kept by name.
+fn pubtype<R: Read>(s: &mut Stream<R>) -> ParseResult<PubType> {
+    let name = alphanum(s, false)?;
+    match TYPES.iter().find(|(n, _)| n.eq_ignore_ascii_case(&name)) {
+        Some((_, pt)) => Ok(pt.clone()),
+        None => Ok(PubType::Other(name.to_lowercase())),
+    }
+}
+
+fn body<R: Read>(
+    s: &mut Stream<R>,
+    pt: PubType,
+    macros: &Macros,
+    opts: Options,
+    dups: &mut Vec<String>,
+) -> ParseResult<BibEntry> {
+    skip_space(s)?;
+    s.byte(b'{')?;
+    skip_space(s)?;
+    let key_start = s.position().stream as usize;
     let k = citekey(s)?;
+    let key_span = key_start..key_start + k.len();
     s.byte(b',')?;
-    let hs = headers(s)?;
+    let (mut hs, fields) = headers(s, macros, opts, dups)?;
     s.byte(b'}')?;
 
+    // like biblatex, we make the date from year, month and day
+    // if there is none
+    let mut date = hs.remove("date").unwrap_or_default();
+    if date.trim().is_empty() {
+        let field = |name: &str| hs.get(name).map(|v| v.as_str());
+        if let Some(d) =
+            dates::from_fields(field("year").unwrap_or(""), field("month"), field("day"))
+        {
+            date = d.to_string();
+        }
+    }
+
+    // we own the headers, so we move the values out
+    // instead of copying them
     Ok(BibEntry {
         pubtype: pt,
         key: k,
-        author: if hs.contains_key("author") {
-            hs["author"].to_string()
-        } else {
-            "".to_string()
-        },
-        title: if hs.contains_key("title") {
-            hs["title"].to_string()
-        } else {
-            "".to_string()
-        },
-        date: if hs.contains_key("date") {
-            hs["date"].to_string()
-        } else {
-            "".to_string()
-        },
+        author: hs.remove("author").unwrap_or_default(),
+        title: hs.remove("title").unwrap_or_default(),
+        date,
+        fields: hs,
+        file: OsString::new(),
+        line: 0,
+        span: 0..0,
+        spans: fields.map(|fields| Spans {
+            key: key_span,
+            fields,
+        }),
     })
 }
 
 // The citekey can be any combination of alphanumeric characters including the characters "-", "_", and ":".
 fn citekey<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
-    s.skip_whitespace()?;
+    skip_space(s)?;
     let k = alphanum(s, true)?;
-    s.skip_whitespace()?;
+    skip_space(s)?;
     Ok(k)
 }
 
-fn headers<R: Read>(s: &mut Stream<R>) -> ParseResult<HashMap<String, String>> {
+// The fields by their lowercase name and, if the options
+// ask for them, where they are.
+type Fields = (HashMap<String, String>, Option<HashMap<String, FieldSpan>>);
+
+fn headers<R: Read>(
+    s: &mut Stream<R>,
+    macros: &Macros,
+    opts: Options,
+    dups: &mut Vec<String>,
+) -> ParseResult<Fields> {
     let mut m = HashMap::new();
+    let mut spans = opts.spans.then(HashMap::new);
     loop {
-        let (k, v) = header(s)?;
-        let _ = match m.insert(k.clone(), v) {
-            Some(_) => {
-                return Err(ParseError::Failed(
-                    format!("duplicated key '{}' in BibEntry", k.clone()),
-                    s.position(),
-                ))
-            }
-            _ => true,
+        let (k, v, span) = header(s, macros, opts.keep_braces)?;
+        let taken = match m.entry(k.clone()) {
+            Entry::Occupied(mut e) => match opts.duplicate_fields {
+                FieldPolicy::Error => {
+                    return Err(ParseError::Failed(
+                        format!("duplicated key '{}' in BibEntry", e.key()),
+                        s.position(),
+                    ))
+                }
+                FieldPolicy::FirstWins => {
+                    dups.push(e.key().clone());
+                    false
+                }
+                FieldPolicy::LastWins => {
+                    dups.push(e.key().clone());
+                    e.insert(v);
+                    true
+                }
+            },
+            Entry::Vacant(e) => {
+                e.insert(v);
+                true
+            }
         };
-        s.skip_whitespace()?;
+        if let Some(spans) = spans.as_mut().filter(|_| taken) {
+            spans.insert(k, span);
+        }
+        skip_space(s)?;
         let ch = s.peek_byte()?;
         if ch != b',' {
             break;
         }
         s.byte(b',')?;
     }
-    Ok(m)
+    Ok((m, spans))
 }
 
-fn header<R: Read>(s: &mut Stream<R>) -> ParseResult<(String, String)> {
-    s.skip_whitespace()?;
-    let k = alphanum(s, false)?;
-    s.skip_whitespace()?;
+fn header<R: Read>(
+    s: &mut Stream<R>,
+    macros: &Macros,
+    keep_braces: bool,
+) -> ParseResult<(String, String, FieldSpan)> {
+    skip_space(s)?;
+    let start = s.position().stream as usize;
+    // field names are case-insensitive
+    let k = alphanum(s, false)?.to_lowercase();
+    skip_space(s)?;
     s.byte(b'=')?;
-    let v = value(s)?;
-    Ok((k, v))
+    let (v, value) = value(s, macros, keep_braces)?;
+    let field = start..value.end;
+    Ok((k, v, FieldSpan { field, value }))
 }
 
-// the values of field can either be enclosed in { } or " "
+// A value is a sequence of pieces joined with '#';
+// its bytes end with the last piece.
+fn value<R: Read>(
+    s: &mut Stream<R>,
+    macros: &Macros,
+    keep_braces: bool,
+) -> ParseResult<(String, Range<usize>)> {
+    skip_space(s)?;
+    let start = s.position().stream as usize;
+    let mut v = piece(s, macros, keep_braces)?;
+    let mut end = s.position().stream as usize;
+    skip_space(s)?;
+    while s.peek_byte()? == b'#' {
+        s.byte(b'#')?;
+        v.push_str(&piece(s, macros, keep_braces)?);
+        end = s.position().stream as usize;
+        skip_space(s)?;
+    }
+    Ok((v, start..end))
+}
+
+// the pieces of values can either be enclosed in { } or " "
 // plain numbers do not need to be enclosed
-fn value<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
-    s.skip_whitespace()?;
+// and names of @string macros are replaced by their value
+fn piece<R: Read>(s: &mut Stream<R>, macros: &Macros, keep_braces: bool) -> ParseResult<String> {
+    skip_space(s)?;
     let b = s.peek_byte()?;
+    if b.is_ascii_alphabetic() {
+        let name = alphanum(s, true)?;
+        return match macros.get(&name.to_lowercase()) {
+            Some(v) => Ok(v.clone()),
+            None => s.fail(&format!("undefined macro '{}'", name), String::new()),
+        };
+    }
     let closer = if b == b'"' {
         b'"'
     } else if b == b'{' {
@@ -182,163 +682,1116 @@ fn value<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
     if closer != b'0' {
         s.byte(b)?;
     }
-    let v = chars_until_closer(s, closer as char)?;
+    let v = chars_until_closer(s, closer as char, keep_braces)?;
     if closer != b'0' {
         s.byte(closer)?;
     }
-    s.skip_whitespace()?;
     Ok(v)
 }
 
+// alphanum and chars_until_closer work on bytes:
+// ASCII bytes are decided on directly, multi-byte characters
+// are only decoded where their class matters (alphanum) and
+// the String is allocated once at the end.
 fn alphanum<R: Read>(s: &mut Stream<R>, ext: bool) -> ParseResult<String> {
-    let mut v: Vec<char> = Vec::new();
+    let mut v: Vec<u8> = Vec::new();
     loop {
-        let ch = s.peek_character()?;
-        if !ch.is_alphanumeric() {
-            if !ext || (ch != '-' && ch != '_' && ch != ':') {
+        let b = s.peek_byte()?;
+        if b.is_ascii() {
+            if !b.is_ascii_alphanumeric() && (!ext || (b != b'-' && b != b'_' && b != b':')) {
                 break;
             }
+            s.any_byte()?;
+            v.push(b);
+            continue;
+        }
+        let ch = s.peek_character()?;
+        if !ch.is_alphanumeric() {
+            break;
         }
         s.character(ch)?;
-        v.push(ch);
+        let mut buf = [0; 4];
+        v.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
     }
-    Ok(v.into_iter().collect())
+    utf8_string(s, v)
 }
 
-fn chars_until_closer<R: Read>(s: &mut Stream<R>, closer: char) -> ParseResult<String> {
-    let mut v: Vec<char> = Vec::new();
+// The closer is always ASCII and bytes of multi-byte UTF-8 sequences
+// are never ASCII, so we can copy bytes without decoding them.
+// As in BibTeX, the closer ends the value only outside of nested
+// braces, as in {The {DNA} Book} or "A {"} B". The braces are kept,
+// if asked for, since they protect words like {DNA} from being
+// lowercased by the styles; otherwise they are dropped.
+fn chars_until_closer<R: Read>(
+    s: &mut Stream<R>,
+    closer: char,
+    keep_braces: bool,
+) -> ParseResult<String> {
+    let closer = closer as u8;
+    let mut v: Vec<u8> = Vec::new();
+    let mut depth = 0;
     loop {
-        let ch = s.peek_character()?;
-        if closer == '0' {
-            if !ch.is_ascii_digit() {
+        let b = s.peek_byte()?;
+        if closer == b'0' {
+            if !b.is_ascii_digit() {
                 break;
             }
-        } else if ch == closer {
+        } else if b == closer && depth == 0 {
             break;
         }
-        s.character(ch)?;
-        if ch != '{' && ch != '}' {
-            v.push(ch);
+        s.any_byte()?;
+        match b {
+            b'\n' => s.count_lines(),
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            _ => (),
+        }
+        if keep_braces || (b != b'{' && b != b'}') {
+            v.push(b);
         }
     }
-    Ok(v.into_iter().collect())
+    utf8_string(s, v)
 }
 
-pub fn collect_cites<R: Read>(s: &mut Stream<R>) -> ParseResult<Vec<String>> {
-    let mut v = Vec::new();
-
-    loop {
-        if eof(s) {
-            break;
-        }
-        let b = s.any_byte()?;
-        if b != b'\\' {
-            continue;
-        }
+fn utf8_string<R: Read>(s: &mut Stream<R>, v: Vec<u8>) -> ParseResult<String> {
+    match String::from_utf8(v) {
+        Ok(v) => Ok(v),
+        Err(e) => s.fail(&format!("invalid UTF-8: {}", e), String::new()),
+    }
+}
 
-        if ignore(s) {
-            ignore_text(s)?;
-            continue;
-        }
+// What the tex scanner finds in a document, in document order.
+#[derive(Debug, PartialEq)]
+pub enum TexItem {
+    // a citekey
+    Cite(Cite),
+    // a file included with \input or \include
+    Input(String),
+    // the start of a sectioning unit
+    Section(Section),
+    // a bib file named in \bibliography or \addbibresource
+    Bib(String),
+    // a command defined as a citation command with the command it
+    // stands for, like mycite and autocite for
+    // \newcommand{\mycite}[1]{\autocite{#1}}
+    Alias(String, String),
+    // a citation command without key, which ends at the next command
+    // or the end of the paragraph, with its name, line and column
+    Malformed(String, u64, u64),
+}
 
-        if !cite(s) {
-            continue;
-        }
-        s.skip_whitespace()?;
+// A citekey and the line of the command citing it.
+#[derive(Debug, PartialEq)]
+pub struct Cite {
+    pub key: String,
+    pub line: u64,
+    // the column of the backslash of the command, starting at 1
+    pub column: u64,
+    // the first of two optional arguments, as in \cite[see][p.~5]{key}
+    pub prenote: Option<String>,
+    // the last optional argument of the command, as in \cite[p.~5]{key}
+    pub postnote: Option<String>,
+    // whether the command stands in or just after a quotation,
+    // like \enquote or the quote environment, as its source
+    pub quoted: bool,
+    // how the command presents the citation
+    pub kind: CiteKind,
+    // the bytes of the key in the document, for tools that change
+    // the document in place; None for keys not read from the text
+    // where they stand, like those in titles and definitions
+    pub span: Option<Range<usize>>,
+}
 
-        move_to_citekey(s)?;
+// How a citation command presents the citation: in the text,
+// like \textcite, in parentheses, like \parencite, in a footnote,
+// like \footcite, as the style decides, like \cite, or not at all,
+// like \nocite, which only adds the work to the bibliography.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CiteKind {
+    Plain,
+    Narrative,
+    Parenthetical,
+    Footnote,
+    Nocite,
+}
 
-        // consider list of citekeys, e.g.
-        // \cite{a, b, c}
-        s.skip_whitespace()?;
-        let mut ks = citekeylist(s)?;
-        s.skip_whitespace()?;
-        s.byte(b'}')?;
+impl CiteKind {
+    pub const ALL: [CiteKind; 5] = [
+        CiteKind::Narrative,
+        CiteKind::Parenthetical,
+        CiteKind::Footnote,
+        CiteKind::Plain,
+        CiteKind::Nocite,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CiteKind::Plain => "plain",
+            CiteKind::Narrative => "narrative",
+            CiteKind::Parenthetical => "parenthetical",
+            CiteKind::Footnote => "footnote",
+            CiteKind::Nocite => "nocite",
+        }
+    }
 
-        v.append(&mut ks);
+    pub fn from_name(name: &str) -> Option<CiteKind> {
+        CiteKind::ALL.into_iter().find(|k| k.name() == name)
     }
+}
 
-    Ok(v)
+// The citation commands of biblatex and natbib by kind;
+// other commands starting with 'cite', like \cite and \citeyear, are plain.
+// \autocite is parenthetical in most styles, \smartcite a footnote.
+static CITE_KINDS: &[(&str, CiteKind)] = &[
+    ("textcite", CiteKind::Narrative),
+    ("textcites", CiteKind::Narrative),
+    ("citet", CiteKind::Narrative),
+    ("citealt", CiteKind::Narrative),
+    ("citeauthor", CiteKind::Narrative),
+    ("parencite", CiteKind::Parenthetical),
+    ("parencites", CiteKind::Parenthetical),
+    ("autocite", CiteKind::Parenthetical),
+    ("autocites", CiteKind::Parenthetical),
+    ("citep", CiteKind::Parenthetical),
+    ("citealp", CiteKind::Parenthetical),
+    ("citeyearpar", CiteKind::Parenthetical),
+    ("footcite", CiteKind::Footnote),
+    ("footcitetext", CiteKind::Footnote),
+    ("footcites", CiteKind::Footnote),
+    ("footfullcite", CiteKind::Footnote),
+    ("smartcite", CiteKind::Footnote),
+    ("smartcites", CiteKind::Footnote),
+    ("fullcite", CiteKind::Plain),
+    ("supercite", CiteKind::Plain),
+    ("supercites", CiteKind::Plain),
+    // biblatex's citations of a volume of a multi-volume work
+    ("volcite", CiteKind::Plain),
+    ("pvolcite", CiteKind::Parenthetical),
+    ("fvolcite", CiteKind::Footnote),
+    ("ftvolcite", CiteKind::Footnote),
+    ("svolcite", CiteKind::Footnote),
+    ("tvolcite", CiteKind::Narrative),
+    ("avolcite", CiteKind::Parenthetical),
+    ("nocite", CiteKind::Nocite),
+    // natbib's aliases, defined with \defcitealias
+    ("citetalias", CiteKind::Narrative),
+    ("citepalias", CiteKind::Parenthetical),
+    ("citefullauthor", CiteKind::Narrative),
+];
+
+// The commands of biblatex and natbib with a capitalized variant,
+// like \Textcite, for the start of a sentence, which capitalizes
+// the prefix of the last name, as in "Van Gogh".
+static CAPITALIZED: &[&str] = &[
+    "cite",
+    "cites",
+    "parencite",
+    "parencites",
+    "footcite",
+    "footcites",
+    "footcitetext",
+    "textcite",
+    "textcites",
+    "smartcite",
+    "smartcites",
+    "autocite",
+    "autocites",
+    "citeauthor",
+    "citet",
+    "citep",
+    "citealt",
+    "citealp",
+    "volcite",
+    "pvolcite",
+    "fvolcite",
+    "ftvolcite",
+    "svolcite",
+    "tvolcite",
+    "avolcite",
+];
+
+// The name of the command a capitalized variant stands for,
+// like cite for Cite, or None if the name is not one.
+fn uncapitalized(name: &str) -> Option<String> {
+    let first = name.chars().next().filter(char::is_ascii_uppercase)?;
+    let n = format!("{}{}", first.to_ascii_lowercase(), &name[1..]);
+    CAPITALIZED.contains(&n.as_str()).then_some(n)
 }
 
-fn eof<R: Read>(s: &mut Stream<R>) -> bool {
-    match s.eof() {
-        Ok(()) => true,
-        Err(_) => false,
-    }
+// The volume citations take the volume in braces before
+// the pages and the citekey: \volcite[see]{2}[12--15]{key}.
+pub fn volcite(name: &str) -> bool {
+    name.ends_with("volcite") || name == "Volcite"
 }
 
-fn cite<R: Read>(s: &mut Stream<R>) -> bool {
-    match s.string("cite") {
-        Ok(()) => true,
-        Err(_) => false,
-    }
+// The multicite commands of biblatex take several groups of citekeys,
+// each with its own notes: \autocites[p.~1]{a}[p.~2]{b,c}.
+// Global notes in parentheses before the groups are skipped.
+pub fn multicite(name: &str) -> bool {
+    name.ends_with("cites") && cite_kind(name).is_some()
 }
 
-fn ignore<R: Read>(s: &mut Stream<R>) -> bool {
-    match s.string("ignore") {
-        Ok(()) => true,
-        Err(_) => false,
-    }
+// Commands starting with 'cite' that do not take citekeys:
+// \citetext of natbib takes text to put in parentheses,
+// the others configure natbib.
+static NOT_CITES: &[&str] = &["citetext", "citestyle", "citeindextrue", "citeindexfalse"];
+
+// A sectioning command like \chapter or \section.
+// The level is 0 for \part, 1 for \chapter, 2 for \section and so on.
+#[derive(Debug, PartialEq)]
+pub struct Section {
+    pub level: usize,
+    pub starred: bool,
+    pub title: String,
 }
 
-fn ignore_text<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
-    s.skip_whitespace()?;
-    let b = s.any_byte()?;
-    if b != b'{' {
-        return Ok(());
-    }
+// sectioning commands by level
+const SECTIONS: [&str; 7] = [
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+];
+
+// Whether we are in or just after a quotation, which is the case
+// after closing quotation marks, in the quoting commands of csquotes
+// like \enquote{...} and in quote environments;
+// whitespace, ties and punctuation between the quotation
+// and the citation do not matter.
+#[derive(Default)]
+struct Quotes {
+    depth: usize,
+    enquotes: Vec<usize>,
+    pending: bool,
+    envs: usize,
+    last: [u8; 3],
+    after: bool,
+}
 
-    let mut count = 1;
-    loop {
-        let b = s.any_byte()?;
-        if b == b'}' {
-            count -= 1;
-            if count == 0 {
-                break;
+// Whether we are in math, inline like $x$ and \(x\) or displayed
+// like $$x$$ and \[x\], where quotation marks are primes, as in
+// $f''(x)$. Math cannot span paragraphs, so that a stray $ only
+// confuses the rest of its paragraph.
+#[derive(Default)]
+struct Math {
+    on: bool,
+    newline: bool,
+}
+
+impl Math {
+    fn byte(&mut self, b: u8) {
+        if b == b'\n' {
+            if self.newline {
+                self.on = false;
             }
-        } else if b == b'{' {
-            count += 1;
+            self.newline = true;
+        } else if !b.is_ascii_whitespace() {
+            self.newline = false;
         }
     }
-    Ok(())
 }
 
-fn move_to_citekey<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
-    let mut nest = 0i8;
-    loop {
-        if eof(s) {
-            return s.fail("Cite without key", ());
+// the quoting commands of csquotes with one argument;
+// \blockquote may take the citation as optional argument
+static QUOTE_COMMANDS: &[&str] = &["enquote", "textquote", "blockquote"];
+
+// the environments of LaTeX and csquotes for display quotations
+static QUOTE_ENVS: &[&str] = &["quote", "quotation", "displayquote"];
+
+// Environments whose content is printed as it is, like the code
+// of a LaTeX tutorial; citations in them are not citations.
+static VERBATIM_ENVS: &[&str] = &[
+    "verbatim",
+    "Verbatim",
+    "BVerbatim",
+    "LVerbatim",
+    "lstlisting",
+    "minted",
+];
+
+// The commands of biblatex that add a bib file, which,
+// unlike \bibliography, take one file with extension.
+static BIB_RESOURCES: &[&str] = &["addbibresource", "addglobalbib"];
+
+// Environments that comment out their content, like that
+// of the comment package.
+static SKIPPED_ENVS: &[&str] = &["comment"];
+
+// '' and the curly quotation marks, which close quotations in one
+// language or another, and », « and " of German and French;
+// in UTF-8, all but the first two end with the bytes below.
+static CLOSERS: &[&[u8]] = &[
+    b"''",
+    b"\"",
+    "\u{201d}".as_bytes(),
+    "\u{201c}".as_bytes(),
+    "\u{bb}".as_bytes(),
+    "\u{ab}".as_bytes(),
+];
+
+impl Quotes {
+    fn byte(&mut self, b: u8) {
+        if b.is_ascii_whitespace() || b"~.,;:".contains(&b) {
+            return;
         }
-        let b = s.any_byte()?;
-        if b == b'[' {
-            nest += 1;
-            continue;
-        }
-        if b == b']' {
-            nest -= 1;
-            continue;
-        }
-        if b != b'{' || nest > 0 {
-            continue;
+        self.last = [self.last[1], self.last[2], b];
+        self.after = CLOSERS.iter().any(|c| self.last.ends_with(c));
+        if b == b'{' {
+            self.depth += 1;
+            if self.pending {
+                self.enquotes.push(self.depth);
+                self.pending = false;
+            }
+        } else if b == b'}' {
+            if self.enquotes.last() == Some(&self.depth) {
+                self.enquotes.pop();
+                self.after = true;
+            }
+            self.depth = self.depth.saturating_sub(1);
         }
-        break;
     }
-    Ok(())
-}
 
-fn citekeylist<R: Read>(s: &mut Stream<R>) -> ParseResult<Vec<String>> {
-    let mut v = Vec::new();
+    // \enquote or \enquote*; its argument follows
+    fn enquote(&mut self) {
+        self.pending = true;
+    }
 
-    loop {
-        s.skip_whitespace()?;
-        let k = citekey(s)?;
-        s.skip_whitespace()?;
+    fn environment(&mut self, begin: bool) {
+        if begin {
+            self.envs += 1;
+        } else {
+            self.envs = self.envs.saturating_sub(1);
+            self.after = true;
+        }
+    }
 
-        v.push(k);
+    fn quoted(&self) -> bool {
+        self.after || self.pending || self.envs > 0 || !self.enquotes.is_empty()
+    }
 
-        let b = s.peek_byte()?;
-        if b == b',' {
+    fn cited(&mut self) {
+        self.after = false;
+        self.last = [0; 3];
+    }
+}
+
+#[allow(dead_code)]
+pub fn collect_cites<R: Read>(s: &mut Stream<R>) -> ParseResult<Vec<TexItem>> {
+    collect_cites_with(s, &TexConfig::default())
+}
+
+// What the scanner counts as citations and what it skips
+// in addition to the commands and environments it knows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TexConfig {
+    // names of citation commands, without backslash, like the
+    // wrappers packages define
+    pub cite_cmds: Vec<String>,
+    // names of environments whose content is skipped
+    pub skip_envs: Vec<String>,
+    // citation commands defined in the documents scanned before,
+    // with the command they stand for, see TexItem::Alias
+    pub aliases: Vec<(String, String)>,
+}
+
+impl TexConfig {
+    // The command an alias stands for or the command itself.
+    fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map_or(name, |(_, t)| t.as_str())
+    }
+
+    // The kind of a citation command, which is not an alias.
+    fn cite_kind(&self, name: &str) -> Option<CiteKind> {
+        cite_kind(name).or_else(|| {
+            self.cite_cmds
+                .iter()
+                .any(|c| c == name)
+                .then_some(CiteKind::Plain)
+        })
+    }
+
+    fn skips(&self, env: &str) -> bool {
+        let name = env.trim_end_matches('*');
+        VERBATIM_ENVS.contains(&name)
+            || SKIPPED_ENVS.contains(&name)
+            || self.skip_envs.iter().any(|e| e == env || e == name)
+    }
+}
+
+// Collects the citations, sections and inputs.
+// Comments, from '%' to the end of the line, are skipped;
+// \% is a percent sign. So is verbatim text, like \verb|...|
+// and the content of verbatim and lstlisting environments,
+// of comment environments and of the environments to skip.
+// Escaped braces, like \{, do not open or close groups, and
+// quotation marks in math, like in $f''$, are primes.
+pub fn collect_cites_with<R: Read>(
+    s: &mut Stream<R>,
+    tex: &TexConfig,
+) -> ParseResult<Vec<TexItem>> {
+    let mut v = Vec::new();
+    let mut quotes = Quotes::default();
+    let mut math = Math::default();
+    // like in LaTeX, definitions apply to the rest of the document
+    let mut tex = Cow::Borrowed(tex);
+
+    loop {
+        if eof(s) {
+            break;
+        }
+        let b = next_byte(s)?;
+        if b == b'%' {
+            skip_line(s)?;
+            continue;
+        }
+        if b != b'\\' {
+            math.byte(b);
+            if b == b'$' {
+                if !eof(s) && s.peek_byte()? == b'$' {
+                    next_byte(s)?;
+                }
+                math.on = !math.on;
+            } else if !math.on {
+                quotes.byte(b);
+            }
+            continue;
+        }
+        math.byte(b);
+        // \% and \\, so that a '%' after them is seen correctly,
+        // and \{, \} and \$, which are characters
+        if !eof(s) && matches!(s.peek_byte()?, b'%' | b'\\' | b'{' | b'}' | b'$') {
+            next_byte(s)?;
+            continue;
+        }
+        if !eof(s) && matches!(s.peek_byte()?, b'(' | b'[' | b')' | b']') {
+            math.on = matches!(next_byte(s)?, b'(' | b'[');
+            continue;
+        }
+        let line = line(s);
+        let column = column(s) - 1;
+
+        if QUOTE_COMMANDS.iter().any(|c| command(s, c)) {
+            quotes.enquote();
+            continue;
+        }
+
+        let begin = command(s, "begin");
+        if begin || command(s, "end") {
+            if let Some(env) = environment(s)? {
+                if QUOTE_ENVS.contains(&env.trim_end_matches('*')) {
+                    quotes.environment(begin);
+                }
+                if begin && tex.skips(&env) {
+                    skip_until(s, format!("\\end{{{}}}", env).as_bytes())?;
+                }
+            }
+            continue;
+        }
+
+        if command(s, "verb") || command(s, "lstinline") {
+            inline_verbatim(s)?;
+            continue;
+        }
+
+        if ignore(s) {
+            ignore_text(s)?;
+            continue;
+        }
+
+        if input(s) {
+            if let Some(f) = input_file(s)? {
+                v.push(TexItem::Input(f));
+            }
+            continue;
+        }
+
+        if command(s, "bibliography") {
+            if let Some(names) = environment(s)? {
+                v.extend(
+                    names
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|n| !n.is_empty())
+                        .map(|n| TexItem::Bib(n.to_string())),
+                );
+            }
+            continue;
+        }
+
+        if BIB_RESOURCES.iter().any(|c| command(s, c)) {
+            s.skip_whitespace()?;
+            if !eof(s) && s.peek_byte()? == b'[' {
+                skip_tex_group(s, b'[', b']')?;
+            }
+            if let Some(name) = environment(s)? {
+                v.push(TexItem::Bib(name));
+            }
+            continue;
+        }
+
+        if let Some(level) = SECTIONS.iter().position(|c| command(s, c)) {
+            section(s, level, (line, column), &tex, &mut v)?;
+            continue;
+        }
+
+        if let Some(def) = DEFINITIONS.iter().find(|c| command(s, c)) {
+            let start = v.len();
+            definition(s, def, (line, column), &tex, &mut v)?;
+            for i in &v[start..] {
+                if let TexItem::Alias(name, target) = i {
+                    tex.to_mut().aliases.push((name.clone(), target.clone()));
+                }
+            }
+            continue;
+        }
+
+        let Some((name, kind)) = cite(s, &tex)? else {
+            continue;
+        };
+        s.skip_whitespace()?;
+        // the starred variants, like \cite* and \citeauthor*,
+        // only print the citation differently
+        if !eof(s) && s.peek_byte()? == b'*' {
+            s.byte(b'*')?;
+            s.skip_whitespace()?;
+        }
+
+        let Some(args) = move_to_citekey(s)? else {
+            v.push(TexItem::Malformed(name, line, column));
+            continue;
+        };
+        let (mut prenote, mut postnote) = notes(args);
+        if volcite(&name) {
+            chars_until_closer(s, '}', false)?;
+            s.byte(b'}')?;
+            s.skip_whitespace()?;
+            let Some(args) = move_to_citekey(s)? else {
+                v.push(TexItem::Malformed(name, line, column));
+                continue;
+            };
+            (prenote, postnote) = notes(args);
+        }
+        // \nocite prints nothing, a quotation before it is cited later
+        let quoted = kind != CiteKind::Nocite && quotes.quoted();
+        if kind != CiteKind::Nocite {
+            quotes.cited();
+        }
+
+        loop {
+            // consider list of citekeys, e.g.
+            // \cite{a, b, c}
+            s.skip_whitespace()?;
+            let ks = citekeylist(s)?;
+            s.skip_whitespace()?;
+            s.byte(b'}')?;
+
+            v.extend(ks.into_iter().map(|(key, span)| {
+                TexItem::Cite(Cite {
+                    key,
+                    line,
+                    column,
+                    prenote: prenote.clone(),
+                    postnote: postnote.clone(),
+                    quoted,
+                    kind,
+                    span: Some(span),
+                })
+            }));
+
+            // the next group of a multicite command, with its own notes
+            if !multicite(&name) {
+                break;
+            }
+            skip_space(s)?;
+            if eof(s) || !matches!(s.peek_byte()?, b'[' | b'{') {
+                break;
+            }
+            let Some(args) = move_to_citekey(s)? else {
+                v.push(TexItem::Malformed(name, line, column));
+                break;
+            };
+            (prenote, postnote) = notes(args);
+        }
+    }
+
+    Ok(v)
+}
+
+// The prenote and the postnote of the optional arguments;
+// with one argument, it is the postnote.
+fn notes(mut args: Vec<String>) -> (Option<String>, Option<String>) {
+    let post = args.pop();
+    (args.pop(), post)
+}
+
+// Consumes one byte and counts the line if it is a newline.
+fn next_byte<R: Read>(s: &mut Stream<R>) -> ParseResult<u8> {
+    let b = s.any_byte()?;
+    if b == b'\n' {
+        s.count_lines();
+    }
+    Ok(b)
+}
+
+// \section*[short title]{title}
+// Citations in the title are reported after the section,
+// at the line and column of the section command.
+fn section<R: Read>(
+    s: &mut Stream<R>,
+    level: usize,
+    (line, column): (u64, u64),
+    tex: &TexConfig,
+    v: &mut Vec<TexItem>,
+) -> ParseResult<()> {
+    s.skip_whitespace()?;
+    let starred = s.peek_byte()? == b'*';
+    if starred {
+        s.byte(b'*')?;
+        s.skip_whitespace()?;
+    }
+    if s.peek_byte()? == b'[' {
+        skip_tex_group(s, b'[', b']')?;
+        s.skip_whitespace()?;
+    }
+    if s.peek_byte()? != b'{' {
+        return Ok(());
+    }
+    let title = tex_group(s)?;
+    let cites = if title.contains("\\") {
+        pacosso::parse_string(title.clone(), pacosso::Opts::default(), |s| {
+            collect_cites_with(s, tex)
+        })?
+    } else {
+        Vec::new()
+    };
+    v.push(TexItem::Section(Section {
+        level,
+        starred,
+        title: title.split_whitespace().collect::<Vec<&str>>().join(" "),
+    }));
+    v.extend(cites.into_iter().map(|i| match i {
+        TexItem::Cite(c) => TexItem::Cite(Cite {
+            line,
+            column,
+            span: None,
+            ..c
+        }),
+        i => i,
+    }));
+    Ok(())
+}
+
+// The commands that define commands.
+const DEFINITIONS: [&str; 6] = [
+    "newcommand",
+    "renewcommand",
+    "providecommand",
+    "DeclareRobustCommand",
+    "def",
+    "let",
+];
+
+// \newcommand{\mycite}[1]{\autocite{#1}} and its friends,
+// \def\mycite#1{\autocite{#1}} and \let\mycite\autocite:
+// a command defined as nothing but a citation command with its
+// parameters is an alias of that command. Other definitions are
+// scanned for citations, so that fixed citations, like
+// \newcommand{\knuth}{\cite{knuth84}}, are found at the line
+// and column of the definition; parameters are not citekeys.
+fn definition<R: Read>(
+    s: &mut Stream<R>,
+    def: &str,
+    (line, column): (u64, u64),
+    tex: &TexConfig,
+    v: &mut Vec<TexItem>,
+) -> ParseResult<()> {
+    s.skip_whitespace()?;
+    if !eof(s) && s.peek_byte()? == b'*' {
+        s.byte(b'*')?;
+    }
+    let Some(name) = defined_name(s)? else {
+        return Ok(());
+    };
+    s.skip_whitespace()?;
+    let body = if def == "let" {
+        if !eof(s) && s.peek_byte()? == b'=' {
+            s.byte(b'=')?;
+            s.skip_whitespace()?;
+        }
+        if eof(s) || s.peek_byte()? != b'\\' {
+            return Ok(());
+        }
+        s.byte(b'\\')?;
+        format!("\\{}", letters(s)?)
+    } else {
+        // the number of parameters and the default of the first
+        // or, for \def, the parameter text
+        loop {
+            if eof(s) {
+                return Ok(());
+            }
+            match s.peek_byte()? {
+                b'{' => break,
+                b'[' => skip_tex_group(s, b'[', b']')?,
+                b'#' | b'0'..=b'9' if def == "def" => {
+                    next_byte(s)?;
+                }
+                _ => return Ok(()),
+            }
+            s.skip_whitespace()?;
+        }
+        tex_group(s)?
+    };
+    if let Some(target) = alias(&body, tex) {
+        v.push(TexItem::Alias(name, target));
+    } else if body.contains('\\') {
+        let items = pacosso::parse_string(body, pacosso::Opts::default(), |s| {
+            collect_cites_with(s, tex)
+        })?;
+        v.extend(items.into_iter().filter_map(|i| match i {
+            TexItem::Cite(c) if !c.key.contains('#') => Some(TexItem::Cite(Cite {
+                line,
+                column,
+                span: None,
+                ..c
+            })),
+            i @ TexItem::Alias(..) => Some(i),
+            _ => None,
+        }));
+    }
+    Ok(())
+}
+
+// The command defined, as in {\mycite} or \mycite.
+fn defined_name<R: Read>(s: &mut Stream<R>) -> ParseResult<Option<String>> {
+    s.skip_whitespace()?;
+    let braced = !eof(s) && s.peek_byte()? == b'{';
+    if braced {
+        s.byte(b'{')?;
+        s.skip_whitespace()?;
+    }
+    if eof(s) || s.peek_byte()? != b'\\' {
+        return Ok(None);
+    }
+    s.byte(b'\\')?;
+    let name = letters(s)?;
+    if braced {
+        s.skip_whitespace()?;
+        // internal names, like \my@cite, are not aliases
+        if eof(s) || s.peek_byte()? != b'}' {
+            return Ok(None);
+        }
+        s.byte(b'}')?;
+    }
+    Ok(Some(name).filter(|n| !n.is_empty()))
+}
+
+// The citation command a definition stands for, if it is
+// nothing but that command with parameters, like \autocite[#1]{#2}.
+fn alias(body: &str, tex: &TexConfig) -> Option<String> {
+    let rest = body.trim_start().strip_prefix('\\')?;
+    let n = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    let target = tex.resolve(&rest[..n]);
+    let params = rest[n..]
+        .chars()
+        .all(|c| c.is_ascii_digit() || c.is_whitespace() || "#[]{}*".contains(c));
+    (params && tex.cite_kind(target).is_some()).then(|| target.to_string())
+}
+
+// The name of the environment after \begin or \end.
+fn environment<R: Read>(s: &mut Stream<R>) -> ParseResult<Option<String>> {
+    s.skip_whitespace()?;
+    if eof(s) || s.peek_byte()? != b'{' {
+        return Ok(None);
+    }
+    tex_group(s).map(|name| Some(name.trim().to_string()))
+}
+
+// Skips the input up to and including the end,
+// which must not repeat its first byte; if there is no end,
+// up to the end of the input.
+fn skip_until<R: Read>(s: &mut Stream<R>, end: &[u8]) -> ParseResult<()> {
+    let mut matched = 0;
+    while matched < end.len() && !eof(s) {
+        let b = next_byte(s)?;
+        matched = if b == end[matched] {
+            matched + 1
+        } else {
+            usize::from(b == end[0])
+        };
+    }
+    Ok(())
+}
+
+// \verb|text|, \verb*|text| and \lstinline[options]|text|;
+// like \verb, \lstinline also takes its text in braces.
+fn inline_verbatim<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    if eof(s) {
+        return Ok(());
+    }
+    if s.peek_byte()? == b'*' {
+        s.byte(b'*')?;
+    } else if s.peek_byte()? == b'[' {
+        skip_until(s, b"]")?;
+    }
+    if eof(s) {
+        return Ok(());
+    }
+    match next_byte(s)? {
+        b'{' => skip_until(s, b"}"),
+        b'\n' => Ok(()),
+        d => skip_until(s, &[d]),
+    }
+}
+
+// Reads a group in balanced braces and returns its content.
+fn brace_group<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
+    let v = balanced(s, b'{', b'}', false)?;
+    utf8_string(s, v)
+}
+
+// Skips a group like [optional argument], which may be nested.
+fn skip_group<R: Read>(s: &mut Stream<R>, open: u8, close: u8) -> ParseResult<()> {
+    balanced(s, open, close, false).map(|_| ())
+}
+
+// Like brace_group and skip_group, but for tex documents,
+// in which escaped braces, like the set braces in $\{x\}$,
+// are characters; bib files count them like BibTeX does.
+fn tex_group<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
+    let v = balanced(s, b'{', b'}', true)?;
+    utf8_string(s, v)
+}
+
+fn skip_tex_group<R: Read>(s: &mut Stream<R>, open: u8, close: u8) -> ParseResult<()> {
+    balanced(s, open, close, true).map(|_| ())
+}
+
+// The content of a group from open to the matching close.
+fn balanced<R: Read>(s: &mut Stream<R>, open: u8, close: u8, tex: bool) -> ParseResult<Vec<u8>> {
+    s.byte(open)?;
+    let mut v = Vec::new();
+    let mut count = 1;
+    loop {
+        let b = next_byte(s)?;
+        if tex && b == b'\\' {
+            v.push(b);
+            v.push(next_byte(s)?);
+            continue;
+        }
+        if b == close {
+            count -= 1;
+            if count == 0 {
+                break;
+            }
+        } else if b == open {
+            count += 1;
+        }
+        v.push(b);
+    }
+    Ok(v)
+}
+
+fn eof<R: Read>(s: &mut Stream<R>) -> bool {
+    match s.eof() {
+        Ok(()) => true,
+        Err(_) => false,
+    }
+}
+
+// Reads the name of a command and returns its kind
+// if it is a citation command; for aliases, the name
+// is that of the command they stand for.
+fn cite<R: Read>(s: &mut Stream<R>, tex: &TexConfig) -> ParseResult<Option<(String, CiteKind)>> {
+    let name = tex.resolve(&letters(s)?).to_string();
+    Ok(tex.cite_kind(&name).map(|k| (name, k)))
+}
+
+// The name of a command after the backslash.
+fn letters<R: Read>(s: &mut Stream<R>) -> ParseResult<String> {
+    let mut name = Vec::new();
+    while !eof(s) && s.peek_byte()?.is_ascii_alphabetic() {
+        name.push(s.any_byte()?);
+    }
+    utf8_string(s, name)
+}
+
+// The kind of a citation command given by its name without
+// backslash, or None if it is not a citation command.
+// Like LaTeX, the name is case-sensitive: \Textcite is the
+// capitalized variant of \textcite, \TEXTCITE is not a citation.
+pub fn cite_kind(name: &str) -> Option<CiteKind> {
+    match CITE_KINDS.iter().find(|(n, _)| *n == name) {
+        Some((_, k)) => Some(*k),
+        None if NOT_CITES.contains(&name) => None,
+        None if name.starts_with("cite") => Some(CiteKind::Plain),
+        None => cite_kind(&uncapitalized(name)?),
+    }
+}
+
+// \input and \include, but not \includegraphics and friends
+fn input<R: Read>(s: &mut Stream<R>) -> bool {
+    command(s, "input") || command(s, "include")
+}
+
+// Consumes the command name if the input continues with it
+// and the name is not just the prefix of a longer command name.
+fn command<R: Read>(s: &mut Stream<R>, name: &str) -> bool {
+    let n = name.len();
+    let bs = match s.peek_bytes(n + 1) {
+        Ok(bs) => bs,
+        // the command may be the last thing in the input
+        Err(_) => match s.peek_bytes(n) {
+            Ok(bs) => bs,
+            Err(_) => return false,
+        },
+    };
+    if &bs[..n] != name.as_bytes() || (bs.len() > n && bs[n].is_ascii_alphabetic()) {
+        return false;
+    }
+    s.string(name).is_ok()
+}
+
+// The file name of an \input, which, as in plain TeX,
+// may also be given without braces.
+fn input_file<R: Read>(s: &mut Stream<R>) -> ParseResult<Option<String>> {
+    s.skip_whitespace()?;
+    if eof(s) {
+        return Ok(None);
+    }
+    let b = s.peek_byte()?;
+    if b == b'{' {
+        s.byte(b'{')?;
+        let f = chars_until_closer(s, '}', false)?;
+        s.byte(b'}')?;
+        return Ok(Some(f.trim().to_string()));
+    }
+    let mut v = Vec::new();
+    loop {
+        if eof(s) {
+            break;
+        }
+        let b = s.peek_byte()?;
+        if b.is_ascii_whitespace() || b == b'\\' || b == b'%' || b == b'}' {
+            break;
+        }
+        s.any_byte()?;
+        v.push(b);
+    }
+    if v.is_empty() {
+        return Ok(None);
+    }
+    utf8_string(s, v).map(Some)
+}
+
+fn ignore<R: Read>(s: &mut Stream<R>) -> bool {
+    match s.string("ignore") {
+        Ok(()) => true,
+        Err(_) => false,
+    }
+}
+
+fn ignore_text<R: Read>(s: &mut Stream<R>) -> ParseResult<()> {
+    s.skip_whitespace()?;
+    if s.peek_byte()? != b'{' {
+        next_byte(s)?;
+        return Ok(());
+    }
+    skip_tex_group(s, b'{', b'}')
+}
+
+// Moves to the opening brace of the citekeys and returns
+// the optional arguments in brackets before it, like the
+// prenote and postnote in \cite[see][p.~5]{key}.
+fn move_to_citekey<R: Read>(s: &mut Stream<R>) -> ParseResult<Option<Vec<String>>> {
+    let mut nest = 0i8;
+    let mut args = Vec::new();
+    let mut arg = Vec::new();
+    let mut blank_line = false;
+    loop {
+        // a command without key ends at the next command, outside
+        // of the optional arguments, or at the end of the paragraph
+        if eof(s) || (nest <= 0 && s.peek_byte()? == b'\\') {
+            return Ok(None);
+        }
+        let b = next_byte(s)?;
+        if b == b'\n' {
+            if blank_line {
+                return Ok(None);
+            }
+            blank_line = true;
+        } else if b == b'%' {
+            // a comment, which also swallows the line break,
+            // as in \cite[p.~3]%\n{key}
+            skip_line(s)?;
+            while !eof(s) && matches!(s.peek_byte()?, b' ' | b'\t') {
+                next_byte(s)?;
+            }
+            blank_line = true;
+            continue;
+        } else if !b.is_ascii_whitespace() {
+            blank_line = false;
+        }
+        if b == b'\\' && nest > 0 && !eof(s) {
+            // an escaped character in a note, like 50\%
+            arg.push(b);
+            arg.push(next_byte(s)?);
+            continue;
+        }
+        if b == b'[' {
+            nest += 1;
+            if nest == 1 {
+                continue;
+            }
+        } else if b == b']' {
+            nest -= 1;
+            if nest == 0 {
+                let a = utf8_string(s, std::mem::take(&mut arg))?;
+                args.push(a.trim().to_string());
+                continue;
+            }
+        } else if b == b'{' && nest <= 0 {
+            break;
+        }
+        if nest > 0 {
+            arg.push(b);
+        }
+    }
+    Ok(Some(args))
+}
+
+fn citekeylist<R: Read>(s: &mut Stream<R>) -> ParseResult<Vec<(String, Range<usize>)>> {
+    let mut v = Vec::new();
+
+    loop {
+        skip_space(s)?;
+        let start = s.position().stream as usize;
+        // \nocite{*}, all entries of the database
+        let k = if s.peek_byte()? == b'*' {
+            s.byte(b'*')?;
+            "*".to_string()
+        } else {
+            citekey(s)?
+        };
+        s.skip_whitespace()?;
+
+        // an empty key, as after the comma of {a,%\n}
+        if !k.is_empty() {
+            let span = start..start + k.len();
+            v.push((k, span));
+        }
+
+        let b = s.peek_byte()?;
+        if b == b',' {
             s.byte(b',')?;
             continue;
         }
@@ -354,6 +1807,17 @@ mod test {
     use super::*;
     use pacosso::{options::Opts, parse_string};
 
+    // the citekeys found by collect_cites
+    fn cite_keys<R: Read>(s: &mut Stream<R>) -> ParseResult<Vec<String>> {
+        Ok(collect_cites(s)?
+            .into_iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some(c.key),
+                _ => None,
+            })
+            .collect())
+    }
+
     fn karl() -> BibEntry {
         BibEntry {
             pubtype: PubType::Book,
@@ -361,40 +1825,322 @@ mod test {
             author: "Karl Marx".to_string(),
             title: "Das Kapital".to_string(),
             date: "1867".to_string(),
+            fields: HashMap::new(),
+            file: OsString::new(),
+            line: 1,
+            span: 0..0,
+            spans: None,
+        }
+    }
+
+    fn mao() -> BibEntry {
+        BibEntry {
+            pubtype: PubType::Book,
+            key: "prac".to_string(),
+            author: "毛澤東".to_string(),
+            title: "On Practice".to_string(),
+            date: "1937".to_string(),
+            fields: HashMap::new(),
+            file: OsString::new(),
+            line: 1,
+            span: 0..0,
+            spans: None,
+        }
+    }
+
+    fn wei() -> BibEntry {
+        BibEntry {
+            pubtype: PubType::Book,
+            key: "ideology".to_string(),
+            author: "Wei Wei Zhang".to_string(),
+            title: "Ideology and Economic Reform".to_string(),
+            date: "1996".to_string(),
+            fields: HashMap::new(),
+            file: OsString::new(),
+            line: 1,
+            span: 0..0,
+            spans: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_simple_entry_quoted() {
+        let s = r#"@book{capital,
+            author = "Karl Marx", 
+            title = "Das Kapital",
+            date = "1867"
+        }"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries.len() == 1
+                    && be.entries[0]
+                        == BibEntry {
+                            span: 0..120,
+                            spans: None,
+                            ..karl()
+                        }
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_simple_entry_curly() {
+        let s = r#"@book{capital,
+            author = "Karl Marx", 
+            title = {Das Kapital},
+            date = "1867"
+        }"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {}", be.entries[0]);
+                be.entries.len() == 1
+                    && be.entries[0]
+                        == BibEntry {
+                            span: 0..120,
+                            spans: None,
+                            ..karl()
+                        }
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_simple_entry_mao() {
+        let s = r#"@book{prac,
+            author = {毛澤東}, 
+            title = "On Practice",
+            date = "1937"
+        }"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries.len() == 1
+                    && be.entries[0]
+                        == BibEntry {
+                            span: 0..117,
+                            spans: None,
+                            ..mao()
+                        }
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_simple_entry_year_no_quote() {
+        let s = r#"@book{prac,
+            author = {毛澤東}, 
+            title = "On Practice",
+            date = 1937
+        }"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries.len() == 1
+                    && be.entries[0]
+                        == BibEntry {
+                            span: 0..115,
+                            spans: None,
+                            ..mao()
+                        }
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_simple_entry_wsp_key() {
+        let s = r#"@book{ prac ,
+            author = {毛澤東}, 
+            title = "On Practice",
+            date = 1937
+        }"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries.len() == 1
+                    && be.entries[0]
+                        == BibEntry {
+                            span: 0..117,
+                            spans: None,
+                            ..mao()
+                        }
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_simple_entry_quotes_and_curly() {
+        let s = r#"@book{ ideology,
+            author = "{Wei Wei} Zhang", 
+            title = "Ideology and Economic Reform",
+            date = 1996
+        }"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?} | {:?}", be, wei());
+                be.entries.len() == 1
+                    && be.entries[0]
+                        == BibEntry {
+                            span: 0..143,
+                            spans: None,
+                            ..wei()
+                        }
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_unicode_key() {
+        let s = r#"@book{毛澤東1937:prac,
+            author = {毛澤東},
+            title = "On Practice",
+            date = 1937
+        }"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries.len() == 1
+                    && be.entries[0].key == "毛澤東1937:prac"
+                    && be.entries[0].author == "毛澤東"
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_includes() {
+        let s = r#"\input{economy/classics}
+        @book{capital,
+            author = "Karl Marx",
+            title = "Das Kapital",
+            date = "1867"
         }
+        \include{ philosophy.bib }"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries.len() == 1
+                    && be.entries[0]
+                        == BibEntry {
+                            line: 2,
+                            span: 33..152,
+                            spans: None,
+                            ..karl()
+                        }
+                    && be.includes == vec!["economy/classics", "philosophy.bib"]
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_comment_lines() {
+        let s = "% bibstats: disable=L001\n@book{capital,\n author = \"Karl Marx\",\n title = \"Das Kapital\",\n date = \"1867\"\n}\n%";
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries
+                    == vec![BibEntry {
+                        line: 2,
+                        span: 25..103,
+                        spans: None,
+                        ..karl()
+                    }]
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let s = "@Comment{jabref-meta: databaseType:bibtex;}\n@book{capital,\n author = \"Karl Marx\",\n title = \"Das Kapital\",\n date = \"1867\"\n}\n@comment{a {nested}\n comment}";
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries
+                    == vec![BibEntry {
+                        line: 2,
+                        span: 44..122,
+                        spans: None,
+                        ..karl()
+                    }]
+                    && be.comments
+                        == vec![
+                            "jabref-meta: databaseType:bibtex;".to_string(),
+                            "a {nested}\n comment".to_string(),
+                        ]
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
     }
 
-    fn mao() -> BibEntry {
-        BibEntry {
-            pubtype: PubType::Book,
-            key: "prac".to_string(),
-            author: "毛澤東".to_string(),
-            title: "On Practice".to_string(),
-            date: "1937".to_string(),
-        }
+    #[test]
+    fn test_fail_unknown_command() {
+        let s = r#"\usepackage{economy}"#;
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
-    fn wei() -> BibEntry {
-        BibEntry {
-            pubtype: PubType::Book,
-            key: "ideology".to_string(),
-            author: "Wei Wei Zhang".to_string(),
-            title: "Ideology and Economic Reform".to_string(),
-            date: "1996".to_string(),
-        }
+    #[test]
+    fn test_fail_star_key() {
+        // only \nocite takes *
+        let s = "@book{*,\n  author = \"Karl Marx\",\n}\n";
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err());
     }
 
     #[test]
-    fn test_parse_simple_entry_quoted() {
+    fn test_parse_lines() {
         let s = r#"@book{capital,
-            author = "Karl Marx", 
-            title = "Das Kapital",
+            author = "Karl Marx",
+            title = {Das
+                     Kapital},
             date = "1867"
+        }
+
+        @book{prac,
+            author = {毛澤東},
+            title = "On Practice",
+            date = 1937
         }"#;
         assert!(match parse_string(s.to_string(), Opts::default(), parse) {
             Ok(be) => {
                 println!("success: {:?}", be);
-                be.len() == 1 && be[0] == karl()
+                be.entries.len() == 2 && be.entries[0].line == 1 && be.entries[1].line == 8
             }
             Err(e) => {
                 eprintln!("error: {:?}", e);
@@ -404,16 +2150,23 @@ mod test {
     }
 
     #[test]
-    fn test_parse_simple_entry_curly() {
+    fn test_parse_fields() {
         let s = r#"@book{capital,
-            author = "Karl Marx", 
-            title = {Das Kapital},
-            date = "1867"
+            Author = "Karl Marx",
+            TITLE = "Das Kapital",
+            date = "1867",
+            Publisher = {Meissner},
+            year = 1867
         }"#;
         assert!(match parse_string(s.to_string(), Opts::default(), parse) {
             Ok(be) => {
-                println!("success: {}", be[0]);
-                be.len() == 1 && be[0] == karl()
+                println!("success: {:?}", be);
+                let e = &be.entries[0];
+                e.author == "Karl Marx"
+                    && e.title == "Das Kapital"
+                    && e.field("publisher") == Some("Meissner")
+                    && e.year() == Some("1867")
+                    && e.field("editor").is_none()
             }
             Err(e) => {
                 eprintln!("error: {:?}", e);
@@ -423,16 +2176,21 @@ mod test {
     }
 
     #[test]
-    fn test_parse_simple_entry_mao() {
-        let s = r#"@book{prac,
-            author = {毛澤東}, 
-            title = "On Practice",
-            date = "1937"
-        }"#;
+    fn test_parse_dates() {
+        let s = r#"@book{a, title = "A", year = 2020, month = {March}}
+        @book{b, title = "B", date = {2019/2021}, year = 2018}
+        @book{c, title = "C", year = {forthcoming}}"#;
         assert!(match parse_string(s.to_string(), Opts::default(), parse) {
             Ok(be) => {
                 println!("success: {:?}", be);
-                be.len() == 1 && be[0] == mao()
+                let es = &be.entries;
+                es[0].date == "2020-03"
+                    && es[0].year() == Some("2020")
+                    && es[1].year() == Some("2019")
+                    && es[1].year_number() == Some(2019)
+                    && es[2].date.is_empty()
+                    && es[2].year() == Some("forthcoming")
+                    && es[2].year_number().is_none()
             }
             Err(e) => {
                 eprintln!("error: {:?}", e);
@@ -442,16 +2200,69 @@ mod test {
     }
 
     #[test]
-    fn test_parse_simple_entry_year_no_quote() {
-        let s = r#"@book{prac,
-            author = {毛澤東}, 
-            title = "On Practice",
-            date = 1937
-        }"#;
+    fn test_parse_month_macros() {
+        let s = r#"@string{sep = "Sept."}
+        @book{a, title = "A", year = 2020, month = mar}
+        @book{b, title = "B", year = 2021, month = DEC # "~1"}
+        @book{c, title = "C", year = 2022, month = sep}"#;
+        let be = parse_string(s.to_string(), Opts::default(), parse).unwrap();
+        let es = &be.entries;
+        assert_eq!(es[0].field("month"), Some("March"));
+        assert_eq!(es[0].date, "2020-03");
+        assert_eq!(es[1].field("month"), Some("December~1"));
+        assert_eq!(es[2].field("month"), Some("Sept."));
+        assert_eq!(es[2].date, "2022-09");
+    }
+
+    #[test]
+    fn test_keywords() {
+        let mut e = BibEntry::empty();
+        assert!(e.keywords().is_empty());
+        e.fields.insert(
+            "keywords".to_string(),
+            "Machine-Learning, nlp;; surveys ".to_string(),
+        );
+        assert_eq!(e.keywords(), vec!["Machine-Learning", "nlp", "surveys"]);
+        assert!(e.has_keyword("machine-learning"));
+        assert!(!e.has_keyword("learning"));
+    }
+
+    #[test]
+    fn test_parse_nested_braces() {
+        let s = r#"@book{a, title = {The {DNA} of {B{\"u}cher}}, author = "A {"} B"}"#;
+        let be = parse_string(s.to_string(), Opts::default(), parse).unwrap();
+        assert_eq!(be.entries[0].title, r#"The DNA of B\"ucher"#);
+        assert_eq!(be.entries[0].author, r#"A " B"#);
+
+        let opts = Options {
+            keep_braces: true,
+            ..Options::default()
+        };
+        let be = parse_string(s.to_string(), Opts::default(), |s| parse_with(s, opts)).unwrap();
+        assert_eq!(be.entries[0].title, r#"The {DNA} of {B{\"u}cher}"#);
+        assert_eq!(be.entries[0].author, r#"A {"} B"#);
+    }
+
+    #[test]
+    fn test_parse_types() {
+        let s = r#"@booklet{a, title = "A"}
+        @PhdThesis{b, title = "B"}
+        @techreport{c, title = "C"}
+        @conference{d, title = "D"}
+        @Book{e, title = "E"}"#;
         assert!(match parse_string(s.to_string(), Opts::default(), parse) {
             Ok(be) => {
                 println!("success: {:?}", be);
-                be.len() == 1 && be[0] == mao()
+                let types: Vec<PubType> = be.entries.iter().map(|e| e.pubtype.clone()).collect();
+                types
+                    == vec![
+                        PubType::Booklet,
+                        PubType::Phd,
+                        PubType::Techreport,
+                        PubType::Inproc,
+                        PubType::Book,
+                    ]
+                    && be.entries[1].pubtype.to_string() == "phdthesis"
             }
             Err(e) => {
                 eprintln!("error: {:?}", e);
@@ -461,16 +2272,27 @@ mod test {
     }
 
     #[test]
-    fn test_parse_simple_entry_wsp_key() {
-        let s = r#"@book{ prac ,
-            author = {毛澤東}, 
-            title = "On Practice",
-            date = 1937
+    fn test_skip_preamble_and_comments() {
+        let s = r#"@preamble{ "\newcommand{\noop}[1]{}" }
+        @PREAMBLE("x")
+        @comment(skipped)
+        @comment this line is skipped
+        @book{capital,
+         author = "Karl Marx",
+         title = "Das Kapital",
+         date = "1867"
         }"#;
         assert!(match parse_string(s.to_string(), Opts::default(), parse) {
             Ok(be) => {
                 println!("success: {:?}", be);
-                be.len() == 1 && be[0] == mao()
+                be.entries
+                    == vec![BibEntry {
+                        line: 5,
+                        span: 134..244,
+                        spans: None,
+                        ..karl()
+                    }]
+                    && be.comments.is_empty()
             }
             Err(e) => {
                 eprintln!("error: {:?}", e);
@@ -480,16 +2302,43 @@ mod test {
     }
 
     #[test]
-    fn test_parse_simple_entry_quotes_and_curly() {
-        let s = r#"@book{ ideology,
-            author = "{Wei Wei} Zhang", 
-            title = "Ideology and Economic Reform",
-            date = 1996
-        }"#;
+    fn test_skip_comment_lines() {
+        let s = r#"% Marx
+        @book{capital, % the first volume
+         % author = "Friedrich Engels",
+         author = "Karl Marx",
+         title = "Das Kapital", % in German
+         date = "1867"
+         % that's all
+        }
+        % the end"#;
         assert!(match parse_string(s.to_string(), Opts::default(), parse) {
             Ok(be) => {
-                println!("success: {:?} | {:?}", be, wei());
-                be.len() == 1 && be[0] == wei()
+                println!("success: {:?}", be);
+                be.entries
+                    == vec![BibEntry {
+                        line: 2,
+                        span: 15..218,
+                        spans: None,
+                        ..karl()
+                    }]
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        let s = r#"@String{ACM = "ACM Press"}
+        @string{press = acm}
+        @book{a, title = "A", publisher = Press}"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                be.entries.len() == 1 && be.entries[0].field("publisher") == Some("ACM Press")
             }
             Err(e) => {
                 eprintln!("error: {:?}", e);
@@ -498,6 +2347,112 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_parse_concatenation() {
+        let s = r#"@string{marx = "Karl Marx"}
+        @book{a, author = marx # " and " # {Friedrich Engels},
+          title = "Das " #"Kapital", date = 18 # 67}"#;
+        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
+            Ok(be) => {
+                println!("success: {:?}", be);
+                let e = &be.entries[0];
+                e.author == "Karl Marx and Friedrich Engels"
+                    && e.title == "Das Kapital"
+                    && e.date == "1867"
+            }
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn test_fail_undefined_string() {
+        let s = r#"@book{a, title = "A", publisher = acm}"#;
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
+    }
+
+    #[test]
+    fn test_parse_unknown_type() {
+        let s = r#"@Online{a, title = "A"}"#;
+        let bib = parse_string(s.to_string(), Opts::default(), parse).unwrap();
+        assert_eq!(bib.entries[0].pubtype, PubType::Other("online".to_string()));
+        assert_eq!(bib.entries[0].pubtype.to_string(), "online");
+    }
+
+    #[test]
+    fn test_lenient() {
+        let s = r#"@book{a, title = "A", author = "x@y.org"}
+@book{b title = "B"}
+@book{c, title = "C"
+@book{d, title = "D" author = x@y.org}
+@book{e, title = "E"}
+"#;
+        let opts = Options {
+            lenient: true,
+            ..Options::default()
+        };
+        let lenient = |s: &mut Stream<_>| parse_with(s, opts);
+        let bib = parse_string(s.to_string(), Opts::default(), lenient).unwrap();
+        let keys: Vec<&str> = bib.entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["a", "e"]);
+        let lines: Vec<u64> = bib.skipped.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 3, 4]);
+        assert!(bib.skipped[0].msg.contains("expected byte"));
+    }
+
+    #[test]
+    fn test_parse_spans() {
+        let s = "@string{pub = {ACM}}\n\
+                 @book{knuth:84,\n  Title = {The {\\TeX}book} ,\n  publisher = pub # \" Press\",\n  year = 1984}";
+        let opts = Options {
+            spans: true,
+            ..Options::default()
+        };
+        let bib = parse_string(s.to_string(), Opts::default(), |s| parse_with(s, opts)).unwrap();
+        let e = &bib.entries[0];
+        let spans = e.spans.as_ref().unwrap();
+        assert_eq!(&s[spans.key.clone()], "knuth:84");
+        let field = |name: &str| {
+            let f = &spans.fields[name];
+            (&s[f.field.clone()], &s[f.value.clone()])
+        };
+        assert_eq!(
+            field("title"),
+            ("Title = {The {\\TeX}book}", "{The {\\TeX}book}")
+        );
+        assert_eq!(field("publisher").1, "pub # \" Press\"");
+        assert_eq!(field("year").1, "1984");
+        assert_eq!(&s[e.span.clone()], &s[21..]);
+
+        let bib = parse_string(s.to_string(), Opts::default(), parse).unwrap();
+        assert_eq!(bib.entries[0].spans, None);
+    }
+
+    #[test]
+    fn test_duplicate_fields() {
+        let s = r#"@book{a, title = "A", year = 1999, title = "B"}"#;
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err());
+        for (policy, title) in [(FieldPolicy::FirstWins, "A"), (FieldPolicy::LastWins, "B")] {
+            let opts = Options {
+                duplicate_fields: policy,
+                ..Options::default()
+            };
+            let bib =
+                parse_string(s.to_string(), Opts::default(), |s| parse_with(s, opts)).unwrap();
+            assert_eq!(bib.entries[0].title, title);
+            assert_eq!(
+                bib.duplicate_fields,
+                vec![DuplicateField {
+                    line: 1,
+                    key: "a".to_string(),
+                    field: "title".to_string()
+                }]
+            );
+        }
+    }
+
     #[test]
     fn test_fail_author_no_quotes() {
         let s = r#"@book{ ideology,
@@ -505,10 +2460,7 @@ mod test {
             title = "Ideology and Economic Reform",
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
@@ -518,10 +2470,7 @@ mod test {
             title = Ideology and Economic Reform,
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
@@ -531,10 +2480,7 @@ mod test {
             title = "Ideology and Economic Reform"
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
@@ -544,10 +2490,7 @@ mod test {
             title = "Ideology and Economic Reform",
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
@@ -557,10 +2500,7 @@ mod test {
             title = "Ideology and Economic Reform",
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
@@ -570,10 +2510,7 @@ mod test {
             title = "Ideology and Economic Reform",
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     //TODO: no key is ok
@@ -600,10 +2537,7 @@ mod test {
             title = "Ideology and Economic Reform",
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
@@ -613,10 +2547,7 @@ mod test {
             = "Ideology and Economic Reform",
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
@@ -626,10 +2557,7 @@ mod test {
             = "Ideology and Economic Reform",
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
@@ -639,17 +2567,14 @@ mod test {
             = "Ideology and Economic Reform",
             date = 1996
         }"#;
-        assert!(match parse_string(s.to_string(), Opts::default(), parse) {
-            Ok(_) => false,
-            Err(_) => true,
-        })
+        assert!(parse_string(s.to_string(), Opts::default(), parse).is_err())
     }
 
     #[test]
     fn test_find_simple_cite() {
         let s = "this is some text\\cite{work}. With some more text.";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) => cites.len() == 1 && cites[0] == "work",
                 Err(e) => {
                     eprintln!("error: {:?}", e);
@@ -663,7 +2588,7 @@ mod test {
     fn test_find_3_cites() {
         let s = "this is some text\\cite{misc}. With some more text.\\cite{book}. And still\\cite{article} more.";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) =>
                     cites.len() == 3
                         && cites[0] == "misc"
@@ -681,7 +2606,7 @@ mod test {
     fn test_find_cite_with_opt() {
         let s = "this is some text\\cite[p. 1]{book}.";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) => {
                     println!("have: {:?}", cites);
                     cites.len() == 1 && cites[0] == "book"
@@ -698,7 +2623,7 @@ mod test {
     fn test_find_3_cites_with_opt() {
         let s = "this is some text\\cite[p. 1]{book}. Still more text to come\\cite[blabla][pp. 100-120]{article}. An more\\cite[]{misc}.";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) => {
                     println!("have: {:?}", cites);
                     cites.len() == 3
@@ -718,7 +2643,7 @@ mod test {
     fn test_find_nested_cite() {
         let s = "this is some text\\cite[this is {nested}][p. 1]{book}.";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) => {
                     println!("have: {:?}", cites);
                     cites.len() == 1 && cites[0] == "book"
@@ -736,7 +2661,7 @@ mod test {
         let s = "this is some text\\cite[p. 1, {a nested comment}]{book}.\
                  Still more text to come\\cite[blabla, \\speech{and sho on}][pp. 100-120]{article}. An more\\cite[]{misc}.";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) => {
                     println!("have: {:?}", cites);
                     cites.len() == 3
@@ -756,7 +2681,7 @@ mod test {
     fn test_find_multi_cite() {
         let s = "this is some text\\cite[p. 1]{book, article, misc}.";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) => {
                     println!("have: {:?}", cites);
                     cites.len() == 3
@@ -776,7 +2701,7 @@ mod test {
     fn test_find_multi_cites() {
         let s = "this is some text\\cite[p. 1]{book, article, misc}. And it goes on\\cite{book, inproc}.";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) => {
                     println!("have: {:?}", cites);
                     cites.len() == 5
@@ -798,8 +2723,8 @@ mod test {
     fn test_ignore_cite() {
         let s = "this is some text\\ignore{\\cite[p. 1]{book, article, misc}.}";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
-                Ok(cites) => cites.len() == 0,
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
+                Ok(cites) => cites.is_empty(),
                 Err(e) => {
                     eprintln!("error: {:?}", e);
                     false
@@ -812,7 +2737,7 @@ mod test {
     fn test_ignore_cite_read_cite() {
         let s = "this is some text\\ignore{\\cite[p. 1]{book, article, misc}.} and it goes on\\cite[p. 2]{book}";
         assert!(
-            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+            match parse_string(s.to_string(), Opts::default(), cite_keys) {
                 Ok(cites) => cites.len() == 1 && cites[0] == "book",
                 Err(e) => {
                     eprintln!("error: {:?}", e);
@@ -825,10 +2750,566 @@ mod test {
     #[test]
     fn test_fail_infinite_ignore() {
         let s = "this is some text\\ignore{\\cite[p. 1]{book, article, misc}.";
+        assert!(parse_string(s.to_string(), Opts::default(), cite_keys).is_err())
+    }
+
+    #[test]
+    fn test_find_inputs() {
+        let s = "\\input{chapters/intro}\\includegraphics{fig.png}\\cite{book}\n\\include {chapters/main.tex}\\input appendix";
+        assert!(
+            match parse_string(s.to_string(), Opts::default(), collect_cites) {
+                Ok(items) => {
+                    println!("have: {:?}", items);
+                    items
+                        == vec![
+                            TexItem::Input("chapters/intro".to_string()),
+                            TexItem::Cite(Cite {
+                                key: "book".to_string(),
+                                line: 1,
+                                column: 48,
+                                prenote: None,
+                                postnote: None,
+                                quoted: false,
+                                kind: CiteKind::Plain,
+                                span: Some(53..57),
+                            }),
+                            TexItem::Input("chapters/main.tex".to_string()),
+                            TexItem::Input("appendix".to_string()),
+                        ]
+                }
+                Err(e) => {
+                    eprintln!("error: {:?}", e);
+                    false
+                }
+            }
+        )
+    }
+
+    #[test]
+    fn test_find_notes() {
+        let s = "\\cite[see][p.~5]{a} \\cite[p.~6]{b} \\cite[cf.][]{c} \\cite{d}";
+        let notes: Vec<(Option<String>, Option<String>)> =
+            parse_string(s.to_string(), Opts::default(), collect_cites)
+                .unwrap()
+                .into_iter()
+                .filter_map(|i| match i {
+                    TexItem::Cite(c) => Some((c.prenote, c.postnote)),
+                    _ => None,
+                })
+                .collect();
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(
+            notes,
+            vec![
+                (some("see"), some("p.~5")),
+                (None, some("p.~6")),
+                (some("cf."), some("")),
+                (None, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_bib_resources() {
+        let s = "\\bibliographystyle{plain}\\bibliography{refs, ../shared/more}\n\
+                 \\addbibresource[datatype=bibtex]{main.bib} \\addglobalbib {global.bib}";
+        let items = parse_string(s.to_string(), Opts::default(), collect_cites).unwrap();
+        assert_eq!(
+            items,
+            vec![
+                TexItem::Bib("refs".to_string()),
+                TexItem::Bib("../shared/more".to_string()),
+                TexItem::Bib("main.bib".to_string()),
+                TexItem::Bib("global.bib".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_quotes() {
+        let s = "``Words''~\\cite[p.~5]{a} and \\enquote{more \\cite{b}} then\n\\enquote*{x}.\\cite[see][]{c}\\cite{d} \u{201e}Wort\u{201c} \\cite{e}";
+        let cites: Vec<(String, Option<String>, bool)> =
+            parse_string(s.to_string(), Opts::default(), collect_cites)
+                .unwrap()
+                .into_iter()
+                .filter_map(|i| match i {
+                    TexItem::Cite(c) => Some((c.key, c.postnote, c.quoted)),
+                    _ => None,
+                })
+                .collect();
+        let note = |n: &str| Some(n.to_string());
+        assert_eq!(
+            cites,
+            vec![
+                ("a".to_string(), note("p.~5"), true),
+                ("b".to_string(), None, true),
+                ("c".to_string(), note(""), true),
+                ("d".to_string(), None, false),
+                ("e".to_string(), None, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_with_escaped_braces_and_math() {
+        let s = "\\enquote{a \\{ b} said \\cite{a} with $f''(x)$ \\cite{b}\n\
+                 \\ignore{a \\} \\cite{x}} \\(g''\\) \\cite{c} and \\[ \\{y\\} \\] \\cite{d}\n\
+                 \\section{Sets $\\{x\\}$} It costs 5$ here.\n\n\
+                 ``Yes'' \\cite{e}";
+        let items = parse_string(s.to_string(), Opts::default(), collect_cites).unwrap();
+        let found: Vec<(&str, bool)> = items
+            .iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some((c.key.as_str(), c.quoted)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a", false),
+                ("b", false),
+                ("c", false),
+                ("d", false),
+                ("e", true)
+            ]
+        );
+        assert!(items.contains(&TexItem::Section(Section {
+            level: 2,
+            starred: false,
+            title: "Sets $\\{x\\}$".to_string()
+        })));
+    }
+
+    #[test]
+    fn test_find_cite_without_key() {
+        let s = "\\cite[p.~3] and \\cite{a}\n\\textcite see\n\n{b} \\cite{c} \\cite[x]";
+        let items = parse_string(s.to_string(), Opts::default(), collect_cites).unwrap();
+        assert_eq!(
+            items,
+            vec![
+                TexItem::Malformed("cite".to_string(), 1, 1),
+                TexItem::Cite(Cite {
+                    key: "a".to_string(),
+                    line: 1,
+                    column: 17,
+                    prenote: None,
+                    postnote: None,
+                    quoted: false,
+                    kind: CiteKind::Plain,
+                    span: Some(22..23),
+                }),
+                TexItem::Malformed("textcite".to_string(), 2, 1),
+                TexItem::Cite(Cite {
+                    key: "c".to_string(),
+                    line: 4,
+                    column: 5,
+                    prenote: None,
+                    postnote: None,
+                    quoted: false,
+                    kind: CiteKind::Plain,
+                    span: Some(50..51),
+                }),
+                TexItem::Malformed("cite".to_string(), 4, 14),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_cite_across_lines() {
+        let s =
+            "\\cite[see %\n p.~3, 50\\%]%\n{a,%\n b%\n , c}\n\\cites{d,%\n}%\n% more\n[ch. 2]{e}";
+        let items = parse_string(s.to_string(), Opts::default(), collect_cites).unwrap();
+        let found: Vec<(&str, Option<&str>)> = items
+            .iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some((c.key.as_str(), c.postnote.as_deref())),
+                _ => None,
+            })
+            .collect();
+        let note = Some("see p.~3, 50\\%");
+        assert_eq!(
+            found,
+            vec![
+                ("a", note),
+                ("b", note),
+                ("c", note),
+                ("d", None),
+                ("e", Some("ch. 2"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_quote_environments() {
+        let s = "\\begin{quote}Words \\cite{a}\n\\end{quote}\n\\cite{b} and \\cite{c}\n\\begin{itemize}\\end{itemize}\\cite{d} \\blockquote[\\cite{e}]{Text}";
+        let cites: Vec<(String, bool)> =
+            parse_string(s.to_string(), Opts::default(), collect_cites)
+                .unwrap()
+                .into_iter()
+                .filter_map(|i| match i {
+                    TexItem::Cite(c) => Some((c.key, c.quoted)),
+                    _ => None,
+                })
+                .collect();
+        let found: Vec<(&str, bool)> = cites.iter().map(|(k, q)| (k.as_str(), *q)).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a", true),
+                ("b", true),
+                ("c", false),
+                ("d", false),
+                ("e", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_cite_kinds() {
+        let s = "\\textcite{a} \\Parencite[p.~2]{b} \\footcite{c}\\citep{d}\\citeyear{e}\\cite{f}\\textit{g}";
+        let kinds: Vec<CiteKind> = parse_string(s.to_string(), Opts::default(), collect_cites)
+            .unwrap()
+            .into_iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some(c.kind),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                CiteKind::Narrative,
+                CiteKind::Parenthetical,
+                CiteKind::Footnote,
+                CiteKind::Parenthetical,
+                CiteKind::Plain,
+                CiteKind::Plain
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_starred_cites() {
+        let s = "\\cite*{a} \\citeauthor*{b} \\Textcite*[p.~1]{c}\n\
+                 \\autocite *[see][]{d} \\parencite*{e, f} \\mycite*{g}";
+        let tex = TexConfig {
+            cite_cmds: vec!["mycite".to_string()],
+            ..TexConfig::default()
+        };
+        let cites: Vec<(String, u64, Option<String>)> =
+            parse_string(s.to_string(), Opts::default(), |s| {
+                collect_cites_with(s, &tex)
+            })
+            .unwrap()
+            .into_iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some((c.key, c.line, c.postnote)),
+                _ => None,
+            })
+            .collect();
+        let keys: Vec<&str> = cites.iter().map(|(k, _, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(cites[2].2.as_deref(), Some("p.~1"));
+        assert_eq!((cites[3].1, cites[3].2.as_deref()), (2, Some("")));
+    }
+
+    #[test]
+    fn test_find_capitalized_cites() {
+        let s = "\\Cite{a} \\Citep{b} \\Textcite{c} \\Citeauthor{d} \\Autocites{e}{f}\n\
+                 \\Volcite{2}{g} \\CITE{h} \\TextCite{i} \\Citeyear{j} \\Mycite{k}";
+        let tex = TexConfig {
+            cite_cmds: vec!["mycite".to_string()],
+            ..TexConfig::default()
+        };
+        let cites: Vec<(String, CiteKind)> = parse_string(s.to_string(), Opts::default(), |s| {
+            collect_cites_with(s, &tex)
+        })
+        .unwrap()
+        .into_iter()
+        .filter_map(|i| match i {
+            TexItem::Cite(c) => Some((c.key, c.kind)),
+            _ => None,
+        })
+        .collect();
+        assert_eq!(
+            cites,
+            vec![
+                ("a".to_string(), CiteKind::Plain),
+                ("b".to_string(), CiteKind::Parenthetical),
+                ("c".to_string(), CiteKind::Narrative),
+                ("d".to_string(), CiteKind::Narrative),
+                ("e".to_string(), CiteKind::Parenthetical),
+                ("f".to_string(), CiteKind::Parenthetical),
+                ("g".to_string(), CiteKind::Plain),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_cite_aliases() {
+        let s = "\\newcommand{\\mycite}[1]{\\autocite{#1}}\n\
+                 \\newcommand*\\tc[2][]{\\textcite[#1]{#2}}\n\
+                 \\let\\pc = \\parencite \\def\\fc#1{\\footcite{#1}}\n\
+                 \\newcommand{\\knuth}{see \\cite{knuth84}} \\renewcommand{\\emph}[1]{\\textit{#1}}\n\
+                 \\mycite{a} \\tc[p.~2]{b} \\pc{c} \\fc{d} \\knuth \\emph{e}";
+        let items = parse_string(s.to_string(), Opts::default(), collect_cites).unwrap();
+        let aliases: Vec<(&str, &str)> = items
+            .iter()
+            .filter_map(|i| match i {
+                TexItem::Alias(name, target) => Some((name.as_str(), target.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            aliases,
+            vec![
+                ("mycite", "autocite"),
+                ("tc", "textcite"),
+                ("pc", "parencite"),
+                ("fc", "footcite")
+            ]
+        );
+        let cites: Vec<(String, u64, CiteKind, Option<String>)> = items
+            .into_iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some((c.key, c.line, c.kind, c.postnote)),
+                _ => None,
+            })
+            .collect();
+        let keys: Vec<&str> = cites.iter().map(|(k, _, _, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["knuth84", "a", "b", "c", "d"]);
+        assert_eq!(cites[0].1, 4);
+        assert_eq!(cites[1].2, CiteKind::Parenthetical);
+        assert_eq!(
+            (cites[2].2, cites[2].3.as_deref()),
+            (CiteKind::Narrative, Some("p.~2"))
+        );
+        assert_eq!(cites[4].2, CiteKind::Footnote);
+    }
+
+    #[test]
+    fn test_find_biblatex_cites() {
+        let s = "\\Textcite{a} \\autocite*[see][]{b} \\smartcite{c} \\fullcite{d}\n\
+                 \\footfullcite{e} \\supercite{f} \\citetitle*{g} \\citeurl{h}\n\
+                 \\volcite[see]{2}[12--15]{i} \\Ftvolcite{3}{j}";
+        let cites: Vec<(String, CiteKind, Option<String>)> =
+            parse_string(s.to_string(), Opts::default(), collect_cites)
+                .unwrap()
+                .into_iter()
+                .filter_map(|i| match i {
+                    TexItem::Cite(c) => Some((c.key, c.kind, c.postnote)),
+                    _ => None,
+                })
+                .collect();
+        let keys: Vec<&str> = cites.iter().map(|(k, _, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"]);
+        assert_eq!(cites[4].1, CiteKind::Footnote);
+        assert_eq!(cites[8].2.as_deref(), Some("12--15"));
+        assert_eq!(
+            (cites[9].1, cites[9].2.as_deref()),
+            (CiteKind::Footnote, None)
+        );
+    }
+
+    #[test]
+    fn test_find_multicites() {
+        let s = "\\autocites[p.~1]{a}[p.~2]{b, c} text {x}\n\
+                 \\Textcites(see)()[][3]{d}\n  {e} \\cites{f}.";
+        let cites: Vec<(String, CiteKind, Option<String>)> =
+            parse_string(s.to_string(), Opts::default(), collect_cites)
+                .unwrap()
+                .into_iter()
+                .filter_map(|i| match i {
+                    TexItem::Cite(c) => Some((c.key, c.kind, c.postnote)),
+                    _ => None,
+                })
+                .collect();
+        let keys: Vec<&str> = cites.iter().map(|(k, _, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "c", "d", "e", "f"]);
+        assert_eq!(cites[0].2.as_deref(), Some("p.~1"));
+        assert_eq!(cites[2].2.as_deref(), Some("p.~2"));
+        assert_eq!(
+            (cites[3].1, cites[3].2.as_deref()),
+            (CiteKind::Narrative, Some("3"))
+        );
+        assert_eq!(cites[4].2, None);
+    }
+
+    #[test]
+    fn test_find_nocite() {
+        let s = "\\enquote{x} \\nocite{a}\\cite{b} \\nocite{ * }";
+        let cites: Vec<(String, CiteKind, bool)> =
+            parse_string(s.to_string(), Opts::default(), collect_cites)
+                .unwrap()
+                .into_iter()
+                .filter_map(|i| match i {
+                    TexItem::Cite(c) => Some((c.key, c.kind, c.quoted)),
+                    _ => None,
+                })
+                .collect();
+        assert_eq!(
+            cites,
+            vec![
+                ("a".to_string(), CiteKind::Nocite, false),
+                ("b".to_string(), CiteKind::Plain, true),
+                ("*".to_string(), CiteKind::Nocite, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_user_cites() {
+        let s = "\\mycite{a} \\MyCite[p.~2]{b} \\section{On \\mycite{c}} \\mycitex{d}";
+        let tex = TexConfig {
+            cite_cmds: vec!["mycite".to_string()],
+            ..TexConfig::default()
+        };
+        let keys: Vec<String> = parse_string(s.to_string(), Opts::default(), |s| {
+            collect_cites_with(s, &tex)
+        })
+        .unwrap()
+        .into_iter()
+        .filter_map(|i| match i {
+            TexItem::Cite(c) => Some(c.key),
+            _ => None,
+        })
+        .collect();
+        // like LaTeX, names are case-sensitive
+        assert_eq!(keys, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_skip_tex_comments() {
+        let s = "% \\cite{old}\n50\\% of \\cite{a} % see \\cite{b}\n\
+                 a line break\\\\% \\cite{c}\n\\cite{d,% e\n f}";
+        let items = parse_string(s.to_string(), Opts::default(), collect_cites).unwrap();
+        let cites: Vec<(String, u64)> = items
+            .into_iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some((c.key, c.line)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            cites,
+            vec![
+                ("a".to_string(), 2),
+                ("d".to_string(), 4),
+                ("f".to_string(), 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skip_verbatim() {
+        let s = "\\cite{a}\n\\begin{verbatim}\n\\cite{b} \\end{itemize}\n\\end{verbatim}\n\
+                 \\begin{lstlisting}[language=TeX]\n% \\cite{c}\n\\end{lstlisting}\n\
+                 Type \\verb|\\cite{d}| or \\verb*+\\cite{e}+ \\lstinline{\\cite{f}}\n\
+                 and \\verbatiminput{x.tex} \\cite{g}";
+        let keys = parse_string(s.to_string(), Opts::default(), cite_keys).unwrap();
+        assert_eq!(keys, vec!["a", "g"]);
+    }
+
+    #[test]
+    fn test_skip_envs() {
+        let s = "\\cite{a}\n\\begin{comment}\n\\cite{b}\n\\end{comment}\n\
+                 \\begin{draft}\\cite{c}\\end{draft} \\begin{answer*}\\cite{d}\\end{answer*}\n\
+                 \\begin{quote}\\cite{e}\\end{quote}";
+        let tex = TexConfig {
+            skip_envs: vec!["draft".to_string(), "answer".to_string()],
+            ..TexConfig::default()
+        };
+        let keys: Vec<String> = parse_string(s.to_string(), Opts::default(), |s| {
+            collect_cites_with(s, &tex)
+        })
+        .unwrap()
+        .into_iter()
+        .filter_map(|i| match i {
+            TexItem::Cite(c) => Some(c.key),
+            _ => None,
+        })
+        .collect();
+        assert_eq!(keys, vec!["a", "e"]);
+        let keys = parse_string(s.to_string(), Opts::default(), cite_keys).unwrap();
+        assert_eq!(keys, vec!["a", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_find_natbib_cites() {
+        let s =
+            "\\citep{a} \\citet[p.~1]{b} \\citealp[see][2]{c,d} \\citealt*{e} \\citeauthor{f}\n\
+                 \\citeyear{g} \\citeyearpar{h} \\Citet{i} \\citep*[e.g.][]{j} \\citepalias{k}\n\
+                 \\citetext{priv. comm.} \\citestyle{plainnat} \\citenum{l}";
+        let keys: Vec<String> = parse_string(s.to_string(), Opts::default(), collect_cites)
+            .unwrap()
+            .into_iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some(c.key),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec!["a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l"]
+        );
+    }
+
+    #[test]
+    fn test_find_sections() {
+        let s = "\\chapter{Intro}\\cite{a}\n\\section*[short]{A {long}\n title \\cite{b}}\n\n\\sectionmark{x}\\cite{c}";
         assert!(
             match parse_string(s.to_string(), Opts::default(), collect_cites) {
-                Ok(_) => false,
-                Err(_) => true,
+                Ok(items) => {
+                    println!("have: {:?}", items);
+                    items
+                        == vec![
+                            TexItem::Section(Section {
+                                level: 1,
+                                starred: false,
+                                title: "Intro".to_string(),
+                            }),
+                            TexItem::Cite(Cite {
+                                key: "a".to_string(),
+                                line: 1,
+                                column: 16,
+                                prenote: None,
+                                postnote: None,
+                                quoted: false,
+                                kind: CiteKind::Plain,
+                                span: Some(21..22),
+                            }),
+                            TexItem::Section(Section {
+                                level: 2,
+                                starred: true,
+                                title: "A {long} title \\cite{b}".to_string(),
+                            }),
+                            TexItem::Cite(Cite {
+                                key: "b".to_string(),
+                                line: 2,
+                                column: 1,
+                                prenote: None,
+                                postnote: None,
+                                quoted: false,
+                                kind: CiteKind::Plain,
+                                span: None,
+                            }),
+                            TexItem::Cite(Cite {
+                                key: "c".to_string(),
+                                line: 5,
+                                column: 16,
+                                prenote: None,
+                                postnote: None,
+                                quoted: false,
+                                kind: CiteKind::Plain,
+                                span: Some(89..90),
+                            }),
+                        ]
+                }
+                Err(e) => {
+                    eprintln!("error: {:?}", e);
+                    false
+                }
             }
         )
     }
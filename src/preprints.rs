@@ -0,0 +1,281 @@
+use rustc_hash::FxHashMap;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::parser::{BibEntry, PubType};
+use crate::stats::{author_title, BibMap};
+
+#[cfg(feature = "net")]
+use crate::crossref;
+#[cfg(feature = "net")]
+use crate::netcache::NetCache;
+
+// Whether a cited work is a preprint, a peer-reviewed article
+// or paper or something else, like a book or a thesis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Preprint,
+    PeerReviewed,
+    Other,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Preprint => "preprint",
+            Status::PeerReviewed => "peer-reviewed",
+            Status::Other => "other",
+        }
+    }
+}
+
+// The preprint servers as they are named in eprinttype,
+// archiveprefix, journal, howpublished, publisher or url.
+static SERVERS: &[&str] = &[
+    "arxiv",
+    "biorxiv",
+    "medrxiv",
+    "chemrxiv",
+    "psyarxiv",
+    "socarxiv",
+    "techrxiv",
+    "eartharxiv",
+    "ssrn",
+    "researchsquare",
+    "research square",
+    "preprints.org",
+    "osf preprints",
+];
+
+static FIELDS: &[&str] = &[
+    "eprinttype",
+    "archiveprefix",
+    "journal",
+    "journaltitle",
+    "howpublished",
+    "publisher",
+    "url",
+];
+
+// The DOI prefixes of preprint servers; bioRxiv and medRxiv
+// share theirs with the journals of Cold Spring Harbor,
+// but their DOIs start with the date of posting.
+static DOI_PREFIXES: &[&str] = &[
+    "10.48550/",
+    "10.2139/ssrn",
+    "10.21203/rs.",
+    "10.20944/preprints",
+    "10.26434/chemrxiv",
+    "10.31219/osf.io",
+    "10.31234/osf.io",
+    "10.31235/osf.io",
+];
+
+pub fn classify(e: &BibEntry) -> Status {
+    let from_server = FIELDS
+        .iter()
+        .filter_map(|f| e.field(f))
+        .map(str::to_lowercase)
+        .any(|v| SERVERS.iter().any(|s| v.contains(s)));
+    let preprint_doi = e.doi().is_some_and(|d| {
+        DOI_PREFIXES.iter().any(|p| d.starts_with(p))
+            || d.strip_prefix("10.1101/")
+                .is_some_and(|s| s.len() > 5 && s[..4].bytes().all(|b| b.is_ascii_digit()))
+    });
+    // @online and @misc entries with an eprint are posted, not published
+    let posted = matches!(
+        &e.pubtype,
+        PubType::Misc | PubType::Unpublished | PubType::Other(_)
+    ) && e.field("eprint").is_some();
+    if from_server || preprint_doi || posted {
+        Status::Preprint
+    } else if matches!(e.pubtype, PubType::Article | PubType::Inproc) {
+        Status::PeerReviewed
+    } else {
+        Status::Other
+    }
+}
+
+// A cited work with its status and number of citations and,
+// for preprints looked up at CrossRef, the DOI of the published version.
+pub struct Work {
+    pub key: String,
+    pub status: Status,
+    pub count: u32,
+    pub published: Option<String>,
+}
+
+// The works cited that are in the database, in the order
+// of their first citation.
+pub fn compute(bib: &BibMap, cites: &[Citation]) -> Vec<Work> {
+    let mut v: Vec<Work> = Vec::new();
+    let mut idx: FxHashMap<&str, usize> = FxHashMap::default();
+    for c in cites {
+        if let Some(&i) = idx.get(c.key.as_str()) {
+            v[i].count += 1;
+            continue;
+        }
+        let Some(e) = bib.get(&c.key) else {
+            continue;
+        };
+        idx.insert(&c.key, v.len());
+        v.push(Work {
+            key: c.key.clone(),
+            status: classify(e),
+            count: 1,
+            published: None,
+        });
+    }
+    v
+}
+
+// Looks up the preprints with DOI at CrossRef for their published
+// versions; failed lookups are reported, but do not stop the others.
+#[cfg(feature = "net")]
+pub fn find_published(mut works: Vec<Work>, bib: &BibMap, net: &NetCache) -> Vec<Work> {
+    let mut preprints: Vec<(&mut Work, String)> = works
+        .iter_mut()
+        .filter(|w| w.status == Status::Preprint)
+        .filter_map(|w| {
+            let doi = bib.get(&w.key)?.doi()?;
+            Some((w, doi))
+        })
+        .collect();
+    let dois: Vec<String> = preprints.iter().map(|(_, d)| d.clone()).collect();
+    for ((w, _), work) in preprints.iter_mut().zip(crossref::works(net, &dois)) {
+        match work {
+            Ok(Some(js)) => w.published = crossref::published(&js),
+            Ok(None) => (),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+    works
+}
+
+// The share of preprints among the preprints and peer-reviewed works.
+pub fn ratio(works: &[Work]) -> f64 {
+    let n = |s| works.iter().filter(|w| w.status == s).count();
+    let (pre, peer) = (n(Status::Preprint), n(Status::PeerReviewed));
+    if pre + peer == 0 {
+        0.0
+    } else {
+        pre as f64 / (pre + peer) as f64
+    }
+}
+
+pub fn print_works(v: &[Work], bib: &BibMap, f: Format) {
+    let n = |s| v.iter().filter(|w| w.status == s).count();
+    eprintln!(
+        "{} preprints, {} peer-reviewed, {} other works cited; {:.0}% preprints",
+        n(Status::Preprint),
+        n(Status::PeerReviewed),
+        n(Status::Other),
+        ratio(v) * 100.0
+    );
+    let published = v.iter().filter(|w| w.published.is_some()).count();
+    if published > 0 {
+        eprintln!("{} preprints have been published", published);
+    }
+    match f {
+        Format::Json(j) => works_as_json(v, bib, j),
+        Format::Tsv => works_as_tsv(v, bib),
+        Format::Markdown => works_as_markdown(v, bib),
+    }
+}
+
+fn works_as_tsv(v: &[Work], bib: &BibMap) {
+    for w in v {
+        let (author, title) = author_title(&w.key, bib);
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t{}\t\"{}\"",
+            w.key,
+            author,
+            title,
+            w.status.name(),
+            w.count,
+            w.published.as_deref().unwrap_or("")
+        );
+    }
+}
+
+fn works_as_markdown(v: &[Work], bib: &BibMap) {
+    let rows = v.iter().map(|w| {
+        let (author, title) = author_title(&w.key, bib);
+        vec![
+            w.key.clone(),
+            author.to_string(),
+            title.to_string(),
+            w.status.name().to_string(),
+            w.count.to_string(),
+            w.published.clone().unwrap_or_default(),
+        ]
+    });
+    output::print_md_table(
+        &["Key", "Author", "Title", "Status", "Count", "Published"],
+        rows,
+    );
+}
+
+fn works_as_json(v: &[Work], bib: &BibMap, json: Json) {
+    let records = v.iter().map(|w| {
+        let (author, title) = author_title(&w.key, bib);
+        json!({
+            "key": w.key,
+            "author": author,
+            "title": title,
+            "status": w.status.name(),
+            "count": w.count,
+            "published": w.published
+        })
+    });
+    output::print_json(records, json, "preprints");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_classify() {
+        let entry = |pubtype: PubType, fields: &[(&str, &str)]| BibEntry {
+            pubtype,
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            ..BibEntry::empty()
+        };
+        let cases = [
+            (
+                entry(
+                    PubType::Article,
+                    &[("journal", "arXiv preprint arXiv:1706.03762")],
+                ),
+                Status::Preprint,
+            ),
+            (
+                entry(PubType::Other("online".into()), &[("eprint", "2101.00001")]),
+                Status::Preprint,
+            ),
+            (
+                entry(PubType::Misc, &[("doi", "10.1101/2020.03.04.976456")]),
+                Status::Preprint,
+            ),
+            (
+                entry(PubType::Article, &[("doi", "10.1101/gr.123.1")]),
+                Status::PeerReviewed,
+            ),
+            (
+                entry(PubType::Inproc, &[("booktitle", "Proc. of POPL")]),
+                Status::PeerReviewed,
+            ),
+            (entry(PubType::Book, &[]), Status::Other),
+        ];
+        for (e, status) in cases {
+            assert_eq!(classify(&e), status, "{:?}", e.fields);
+        }
+    }
+}
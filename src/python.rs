@@ -0,0 +1,147 @@
+use std::ffi::OsString;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::bib;
+use crate::docs;
+use crate::docs::{Citation, Documents};
+use crate::parser;
+use crate::parser::{BibEntry, TexConfig};
+use crate::resolve::SearchConfig;
+use crate::stats;
+
+// The Python module bibstats, built with maturin:
+//
+//   import bibstats
+//   entries = bibstats.parse_bib("refs.bib")
+//   cites = bibstats.scan_document("thesis.tex")
+//   stats = bibstats.compute_stats("refs.bib", ["thesis.tex"])
+//
+// Entries and citations are dicts with the fields of the JSON output;
+// the stats map authors to titles to the count and the citekeys.
+// Warnings are printed on stderr as by the command.
+#[pymodule]
+fn bibstats(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(parse_bib, m)?)?;
+    m.add_function(wrap_pyfunction!(scan_document, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_stats, m)?)?;
+    Ok(())
+}
+
+// The entries of the bib file and of the files it includes.
+#[pyfunction]
+#[pyo3(signature = (path, lenient = false))]
+fn parse_bib<'py>(py: Python<'py>, path: &str, lenient: bool) -> PyResult<Bound<'py, PyList>> {
+    let v = load(path, lenient)?;
+    let entries = PyList::empty(py);
+    for e in &v {
+        entries.append(entry(py, e)?)?;
+    }
+    Ok(entries)
+}
+
+// The citations of the document and of the files it includes,
+// with the commands in cite_cmds counting as citation commands.
+#[pyfunction]
+#[pyo3(signature = (path, cite_cmds = Vec::new()))]
+fn scan_document<'py>(
+    py: Python<'py>,
+    path: &str,
+    cite_cmds: Vec<String>,
+) -> PyResult<Bound<'py, PyList>> {
+    let v = scan(&[path.to_string()], cite_cmds)?;
+    let cites = PyList::empty(py);
+    for c in &v {
+        cites.append(citation(py, c)?)?;
+    }
+    Ok(cites)
+}
+
+// The citations of the documents per author and work, as in
+// the default report; with whole, per author field as written.
+#[pyfunction]
+#[pyo3(signature = (bib, documents, whole = false, cite_cmds = Vec::new()))]
+fn compute_stats<'py>(
+    py: Python<'py>,
+    bib: &str,
+    documents: Vec<String>,
+    whole: bool,
+    cite_cmds: Vec<String>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let (mut bibmap, _) = stats::bib_to_map(load(bib, false)?);
+    stats::editor_fallback(&mut bibmap);
+    let (cites, _) = docs::nocite_all(scan(&documents, cite_cmds)?, &[], false);
+    let authors = PyDict::new(py);
+    for (author, works) in stats::compute(&bibmap, &cites, None, whole) {
+        let titles = PyDict::new(py);
+        for (title, w) in works {
+            let work = PyDict::new(py);
+            work.set_item("count", w.count)?;
+            work.set_item("keys", w.keys)?;
+            titles.set_item(title, work)?;
+        }
+        authors.set_item(author, titles)?;
+    }
+    Ok(authors)
+}
+
+fn load(path: &str, lenient: bool) -> PyResult<Vec<BibEntry>> {
+    let opts = parser::Options {
+        lenient,
+        ..parser::Options::default()
+    };
+    bib::load(
+        &OsString::from(path),
+        &[],
+        &SearchConfig::new(&[], false),
+        opts,
+    )
+    .map(|b| b.entries)
+    .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn scan(paths: &[String], cite_cmds: Vec<String>) -> PyResult<Vec<Citation>> {
+    let docs = Documents {
+        roots: paths.iter().map(OsString::from).collect(),
+        files: Vec::new(),
+        stdin: false,
+        search: SearchConfig::new(&[], false),
+        cache: None,
+        tex: TexConfig {
+            cite_cmds,
+            ..TexConfig::default()
+        },
+        syntax: None,
+    };
+    docs::collect(&docs).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn entry<'py>(py: Python<'py>, e: &BibEntry) -> PyResult<Bound<'py, PyDict>> {
+    let d = PyDict::new(py);
+    d.set_item("key", &e.key)?;
+    d.set_item("type", e.pubtype.to_string())?;
+    d.set_item("author", &e.author)?;
+    d.set_item("title", &e.title)?;
+    d.set_item("date", &e.date)?;
+    d.set_item("fields", &e.fields)?;
+    d.set_item("file", e.file.to_string_lossy())?;
+    d.set_item("line", e.line)?;
+    Ok(d)
+}
+
+fn citation<'py>(py: Python<'py>, c: &Citation) -> PyResult<Bound<'py, PyDict>> {
+    let d = PyDict::new(py);
+    d.set_item("key", &c.key)?;
+    d.set_item("file", c.file.to_string_lossy())?;
+    d.set_item("line", c.line)?;
+    d.set_item("column", c.column)?;
+    d.set_item("section", &c.section)?;
+    d.set_item("chapter", &c.chapter)?;
+    d.set_item("prenote", &c.prenote)?;
+    d.set_item("postnote", &c.postnote)?;
+    d.set_item("quoted", c.quoted)?;
+    d.set_item("kind", c.kind.name())?;
+    Ok(d)
+}
@@ -0,0 +1,361 @@
+use rustc_hash::FxHashMap;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::{author_title, BibMap};
+
+// Citations of direct quotes, which usually need a page, without one.
+// Whether a citation belongs to a quote is a guess of the scanner:
+// it follows closing quotation marks, \enquote or a quote environment
+// or stands in one of them.
+pub fn compute(cites: &[Citation]) -> Vec<&Citation> {
+    cites
+        .iter()
+        .filter(|c| c.quoted && !c.postnote.as_deref().is_some_and(has_page))
+        .collect()
+}
+
+// Pages are numbers, as in 'p.~12' or 'S. 3f.',
+// or roman numbers, as in 'xii', for prefaces.
+fn has_page(note: &str) -> bool {
+    note.bytes().any(|b| b.is_ascii_digit())
+        || note
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|w| w.len() > 1 && is_roman(&w.to_lowercase()))
+}
+
+static ROMAN: &[(u32, &str)] = &[
+    (1000, "m"),
+    (900, "cm"),
+    (500, "d"),
+    (400, "cd"),
+    (100, "c"),
+    (90, "xc"),
+    (50, "l"),
+    (40, "xl"),
+    (10, "x"),
+    (9, "ix"),
+    (5, "v"),
+    (4, "iv"),
+    (1, "i"),
+];
+
+// Only numbers written the usual way, so that words
+// made of the same letters, like 'did', do not count.
+fn is_roman(w: &str) -> bool {
+    let mut rest = w;
+    let mut n = 0;
+    for (v, r) in ROMAN {
+        while let Some(t) = rest.strip_prefix(r) {
+            n += v;
+            rest = t;
+        }
+    }
+    if !rest.is_empty() || n == 0 {
+        return false;
+    }
+    let mut canonical = String::new();
+    for (v, r) in ROMAN {
+        while n >= *v {
+            canonical.push_str(r);
+            n -= v;
+        }
+    }
+    canonical == w
+}
+
+// How often a work is cited as the source of a quotation
+// and how often it is referenced otherwise.
+pub struct Usage {
+    pub key: String,
+    pub quoted: u32,
+    pub referenced: u32,
+}
+
+// The most quoted works come first.
+pub fn usage(cites: &[Citation]) -> Vec<Usage> {
+    let mut m: FxHashMap<&str, Usage> = FxHashMap::default();
+    for c in cites {
+        let u = m.entry(c.key.as_str()).or_insert_with(|| Usage {
+            key: c.key.clone(),
+            quoted: 0,
+            referenced: 0,
+        });
+        if c.quoted {
+            u.quoted += 1;
+        } else {
+            u.referenced += 1;
+        }
+    }
+    let mut v: Vec<Usage> = m.into_values().collect();
+    v.sort_unstable_by(|a, b| {
+        (b.quoted, a.referenced, &a.key).cmp(&(a.quoted, b.referenced, &b.key))
+    });
+    v
+}
+
+pub fn print_usage(v: &[Usage], bib: &BibMap, f: Format) {
+    match f {
+        Format::Json(j) => usage_as_json(v, bib, j),
+        Format::Tsv => usage_as_tsv(v, bib),
+        Format::Markdown => usage_as_markdown(v, bib),
+    }
+}
+
+fn usage_as_tsv(v: &[Usage], bib: &BibMap) {
+    for u in v {
+        let (author, title) = author_title(&u.key, bib);
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t{}\t{}",
+            u.key, author, title, u.quoted, u.referenced
+        );
+    }
+}
+
+fn usage_as_markdown(v: &[Usage], bib: &BibMap) {
+    let rows = v.iter().map(|u| {
+        let (author, title) = author_title(&u.key, bib);
+        vec![
+            u.key.clone(),
+            author.to_string(),
+            title.to_string(),
+            u.quoted.to_string(),
+            u.referenced.to_string(),
+        ]
+    });
+    output::print_md_table(&["Key", "Author", "Title", "Quoted", "Referenced"], rows);
+}
+
+fn usage_as_json(v: &[Usage], bib: &BibMap, json: Json) {
+    let records = v.iter().map(|u| {
+        let (author, title) = author_title(&u.key, bib);
+        json!({
+            "key": u.key,
+            "author": author,
+            "title": title,
+            "quoted": u.quoted,
+            "referenced": u.referenced
+        })
+    });
+    output::print_json(records, json, "quotations");
+}
+
+// How often a page, or a range of pages, of a work is cited
+// and how often as the source of a quotation.
+pub struct PageUsage {
+    pub key: String,
+    pub page: String,
+    pub count: u32,
+    pub quoted: u32,
+}
+
+// The pages cited in the postnotes; works in the order of their
+// first citation, their pages in the order of the page numbers.
+pub fn pages(cites: &[Citation]) -> Vec<PageUsage> {
+    let mut works: FxHashMap<&str, usize> = FxHashMap::default();
+    let mut m: FxHashMap<(&str, String), PageUsage> = FxHashMap::default();
+    for c in cites {
+        let n = works.len();
+        works.entry(c.key.as_str()).or_insert(n);
+        let Some(page) = c.postnote.as_deref().and_then(page) else {
+            continue;
+        };
+        let u = m
+            .entry((c.key.as_str(), page.clone()))
+            .or_insert_with(|| PageUsage {
+                key: c.key.clone(),
+                page,
+                count: 0,
+                quoted: 0,
+            });
+        u.count += 1;
+        if c.quoted {
+            u.quoted += 1;
+        }
+    }
+    let mut v: Vec<PageUsage> = m.into_values().collect();
+    v.sort_by_cached_key(|u| (works[u.key.as_str()], first_number(&u.page), u.page.clone()));
+    v
+}
+
+// The page of a postnote without the abbreviation for page,
+// so that 'p.~12', 'p. 12' and '12' are the same page.
+fn page(note: &str) -> Option<String> {
+    if !has_page(note) {
+        return None;
+    }
+    let note = note.replace(['~', '\u{a0}'], " ").replace("\\,", " ");
+    let mut words: Vec<&str> = note.split_whitespace().collect();
+    let abbreviated = words.first().is_some_and(|w| {
+        let w = w.to_lowercase();
+        ["p.", "pp.", "page", "pages", "s.", "seite", "seiten"].contains(&w.as_str())
+    });
+    if abbreviated && words.len() > 1 {
+        words.remove(0);
+    }
+    Some(words.join(" "))
+}
+
+fn first_number(page: &str) -> u64 {
+    page.split(|c: char| !c.is_ascii_digit())
+        .find(|n| !n.is_empty())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(u64::MAX)
+}
+
+pub fn print_pages(v: &[PageUsage], bib: &BibMap, f: Format) {
+    match f {
+        Format::Json(j) => pages_as_json(v, bib, j),
+        Format::Tsv => {
+            for u in v {
+                let (author, title) = author_title(&u.key, bib);
+                println!(
+                    "\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t{}\t{}",
+                    u.key, author, title, u.page, u.count, u.quoted
+                );
+            }
+        }
+        Format::Markdown => {
+            let rows = v.iter().map(|u| {
+                let (author, title) = author_title(&u.key, bib);
+                vec![
+                    u.key.clone(),
+                    author.to_string(),
+                    title.to_string(),
+                    u.page.clone(),
+                    u.count.to_string(),
+                    u.quoted.to_string(),
+                ]
+            });
+            output::print_md_table(&["Key", "Author", "Title", "Page", "Count", "Quoted"], rows);
+        }
+    }
+}
+
+fn pages_as_json(v: &[PageUsage], bib: &BibMap, json: Json) {
+    let records = v.iter().map(|u| {
+        let (author, title) = author_title(&u.key, bib);
+        json!({
+            "key": u.key,
+            "author": author,
+            "title": title,
+            "page": u.page,
+            "count": u.count,
+            "quoted": u.quoted
+        })
+    });
+    output::print_json(records, json, "cited-pages");
+}
+
+pub fn print_quotes(v: &[&Citation], bib: &BibMap, f: Format) {
+    match f {
+        Format::Json(j) => quotes_as_json(v, bib, j),
+        Format::Tsv => quotes_as_tsv(v, bib),
+        Format::Markdown => quotes_as_markdown(v, bib),
+    }
+}
+
+fn quotes_as_tsv(v: &[&Citation], bib: &BibMap) {
+    for c in v {
+        let (author, title) = author_title(&c.key, bib);
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"",
+            c.key,
+            author,
+            title,
+            c.location(),
+            c.postnote.as_deref().unwrap_or("")
+        );
+    }
+}
+
+fn quotes_as_markdown(v: &[&Citation], bib: &BibMap) {
+    let rows = v.iter().map(|c| {
+        let (author, title) = author_title(&c.key, bib);
+        vec![
+            c.key.clone(),
+            author.to_string(),
+            title.to_string(),
+            c.location(),
+            c.postnote.clone().unwrap_or_default(),
+        ]
+    });
+    output::print_md_table(&["Key", "Author", "Title", "Cited at", "Note"], rows);
+}
+
+fn quotes_as_json(v: &[&Citation], bib: &BibMap, json: Json) {
+    let records = v.iter().map(|c| {
+        let (author, title) = author_title(&c.key, bib);
+        json!({
+            "key": c.key,
+            "author": author,
+            "title": title,
+            "file": c.file.to_string_lossy(),
+            "line": c.line,
+            "section": c.section,
+            "prenote": c.prenote,
+            "postnote": c.postnote
+        })
+    });
+    output::print_json(records, json, "quotes");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_has_page() {
+        assert!(has_page("p.~12"));
+        assert!(has_page("S. 3f."));
+        assert!(has_page("pp. xii--xiv"));
+        assert!(!has_page("see also"));
+        assert!(!has_page("cf."));
+        assert!(!has_page("did"));
+        assert!(!has_page(""));
+    }
+
+    #[test]
+    fn test_pages() {
+        use crate::parser::CiteKind;
+        use std::ffi::OsString;
+
+        let cite = |key: &str, note: Option<&str>, quoted: bool| Citation {
+            key: key.to_string(),
+            file: OsString::new(),
+            line: 1,
+            column: 1,
+            section: None,
+            chapter: None,
+            prenote: None,
+            postnote: note.map(String::from),
+            quoted,
+            kind: CiteKind::Plain,
+        };
+        let cites = vec![
+            cite("marx", Some("p.~112"), true),
+            cite("engels", Some("S. 3f."), false),
+            cite("marx", Some("12--14"), false),
+            cite("marx", Some("p. 112"), false),
+            cite("marx", Some("see also"), false),
+            cite("marx", None, false),
+            cite("engels", Some("pp. xii"), true),
+        ];
+        let v = pages(&cites);
+        let v: Vec<(&str, &str, u32, u32)> = v
+            .iter()
+            .map(|u| (u.key.as_str(), u.page.as_str(), u.count, u.quoted))
+            .collect();
+        assert_eq!(
+            v,
+            vec![
+                ("marx", "12--14", 1, 0),
+                ("marx", "112", 2, 1),
+                ("engels", "3f.", 1, 0),
+                ("engels", "xii", 1, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The kind of file we are looking for.
+// It determines the default extension
+// and the environment variable with additional search paths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileKind {
+    Tex,
+    Bib,
+}
+
+impl FileKind {
+    fn extension(&self) -> &'static str {
+        match self {
+            FileKind::Tex => "tex",
+            FileKind::Bib => "bib",
+        }
+    }
+}
+
+// Where to look for files beyond the directory of the root document:
+// the directories given on the command line, the directories
+// in TEXINPUTS or BIBINPUTS and, optionally, whatever kpsewhich finds.
+#[derive(Debug, Clone, Default)]
+pub struct SearchConfig {
+    paths: Vec<SearchPath>,
+    texinputs: Vec<SearchPath>,
+    bibinputs: Vec<SearchPath>,
+    kpsewhich: bool,
+}
+
+impl SearchConfig {
+    pub fn new(paths: &[OsString], kpsewhich: bool) -> SearchConfig {
+        SearchConfig {
+            paths: paths.iter().map(search_path).collect(),
+            texinputs: env_paths("TEXINPUTS"),
+            bibinputs: env_paths("BIBINPUTS"),
+            kpsewhich,
+        }
+    }
+
+    fn env(&self, kind: FileKind) -> &[SearchPath] {
+        match kind {
+            FileKind::Tex => &self.texinputs,
+            FileKind::Bib => &self.bibinputs,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SearchPath {
+    dir: PathBuf,
+    recursive: bool,
+}
+
+// Resolves file names used in \input, \include and bib includes
+// similar to kpathsea: a name is first looked up relative to the
+// directory of the root document, then in the search paths
+// in the order in which they are given, then in the paths
+// of the environment variable and finally with kpsewhich.
+// A search path ending in '//' is searched recursively.
+#[derive(Debug, Clone)]
+pub struct Resolver<'a> {
+    root: PathBuf,
+    config: &'a SearchConfig,
+}
+
+impl<'a> Resolver<'a> {
+    // root is the directory of the root document
+    pub fn new(root: &Path, config: &'a SearchConfig) -> Resolver<'a> {
+        Resolver {
+            root: root.to_path_buf(),
+            config,
+        }
+    }
+
+    // Creates a resolver anchored at the directory of the given file.
+    pub fn for_file(file: &Path, config: &'a SearchConfig) -> Resolver<'a> {
+        let root = match file.parent() {
+            Some(d) => d.to_path_buf(),
+            None => PathBuf::new(),
+        };
+        Resolver::new(&root, config)
+    }
+
+    pub fn resolve(&self, name: &str, kind: FileKind) -> Option<PathBuf> {
+        let candidates = candidates(name, kind);
+
+        let p = Path::new(name);
+        if p.is_absolute() {
+            return candidates
+                .into_iter()
+                .map(PathBuf::from)
+                .find(|c| c.is_file());
+        }
+
+        for c in &candidates {
+            let p = self.root.join(c);
+            if p.is_file() {
+                return Some(p);
+            }
+        }
+
+        for sp in &self.config.paths {
+            for c in &candidates {
+                if let Some(p) = find_in(&sp.dir, c, sp.recursive) {
+                    return Some(p);
+                }
+            }
+        }
+
+        // TeX runs in the directory of the root document,
+        // so relative paths in the environment are relative to it.
+        for sp in self.config.env(kind) {
+            let dir = self.root.join(&sp.dir);
+            for c in &candidates {
+                if let Some(p) = find_in(&dir, c, sp.recursive) {
+                    return Some(p);
+                }
+            }
+        }
+
+        if self.config.kpsewhich {
+            return candidates.iter().find_map(|c| self.kpsewhich(c));
+        }
+
+        None
+    }
+
+    // Returns the path where a file would be expected
+    // if it cannot be found anywhere.
+    // This is useful to report errors with a meaningful path.
+    pub fn fallback(&self, name: &str, kind: FileKind) -> PathBuf {
+        self.root.join(&candidates(name, kind)[0])
+    }
+
+    // kpsewhich prints the path of the file, if it finds it,
+    // and fails otherwise; if it is not installed, we do not find anything.
+    fn kpsewhich(&self, name: &str) -> Option<PathBuf> {
+        let mut cmd = Command::new("kpsewhich");
+        cmd.arg(name);
+        if !self.root.as_os_str().is_empty() {
+            cmd.current_dir(&self.root);
+        }
+        let out = cmd.output().ok()?;
+        if !out.status.success() {
+            return None;
+        }
+        let found = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        if found.is_empty() {
+            return None;
+        }
+        Some(self.root.join(found))
+    }
+}
+
+fn search_path(p: &OsString) -> SearchPath {
+    let s = p.to_string_lossy();
+    // '!!' tells kpathsea to only consult the ls-R database;
+    // we have none and search the directory itself.
+    let s = s.strip_prefix("!!").unwrap_or(&s);
+    match s.strip_suffix("//") {
+        Some(d) => SearchPath {
+            dir: PathBuf::from(if d.is_empty() { "/" } else { d }),
+            recursive: true,
+        },
+        None => SearchPath {
+            dir: PathBuf::from(s),
+            recursive: false,
+        },
+    }
+}
+
+// Empty elements in the environment variable stand for the default path,
+// which, for us, is the directory of the root document; it is always
+// searched first anyway.
+fn env_paths(var: &str) -> Vec<SearchPath> {
+    match env::var_os(var) {
+        Some(v) => env::split_paths(&v)
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| search_path(&p.into_os_string()))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// Like TeX, we try the name with the default extension first
+// if the name has no extension or a different one.
+fn candidates(name: &str, kind: FileKind) -> Vec<String> {
+    let ext = kind.extension();
+    match Path::new(name).extension() {
+        Some(e) if e == ext => vec![name.to_string()],
+        _ => vec![format!("{}.{}", name, ext), name.to_string()],
+    }
+}
+
+fn find_in(dir: &Path, name: &str, recursive: bool) -> Option<PathBuf> {
+    let p = dir.join(name);
+    if p.is_file() {
+        return Some(p);
+    }
+    if !recursive {
+        return None;
+    }
+    let mut subdirs: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect(),
+        Err(_) => return None,
+    };
+    // make the search deterministic
+    subdirs.sort();
+    subdirs.into_iter().find_map(|d| find_in(&d, name, true))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("bibstats-resolve-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("chapters/deep")).unwrap();
+        for f in [
+            "main.tex",
+            "refs.bib",
+            "chapters/intro.tex",
+            "chapters/deep/notes.tex",
+            "notes.txt",
+        ] {
+            fs::write(dir.join(f), "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_candidates() {
+        assert_eq!(candidates("intro", FileKind::Tex), ["intro.tex", "intro"]);
+        assert_eq!(candidates("intro.tex", FileKind::Tex), ["intro.tex"]);
+        assert_eq!(
+            candidates("refs.tex", FileKind::Bib),
+            ["refs.tex.bib", "refs.tex"]
+        );
+        assert_eq!(
+            candidates("v1.2/refs", FileKind::Bib),
+            ["v1.2/refs.bib", "v1.2/refs"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_relative() {
+        let dir = dir("relative");
+        let config = SearchConfig::default();
+        let r = Resolver::for_file(&dir.join("main.tex"), &config);
+        assert_eq!(
+            r.resolve("chapters/intro", FileKind::Tex),
+            Some(dir.join("chapters/intro.tex"))
+        );
+        assert_eq!(r.resolve("refs", FileKind::Bib), Some(dir.join("refs.bib")));
+        // without the default extension, the name is taken as given
+        assert_eq!(
+            r.resolve("notes.txt", FileKind::Tex),
+            Some(dir.join("notes.txt"))
+        );
+        // absolute paths are not relative to the root
+        let abs = dir.join("refs.bib");
+        assert_eq!(r.resolve(&abs.to_string_lossy(), FileKind::Bib), Some(abs));
+        // search paths are only searched recursively with '//'
+        assert_eq!(r.resolve("notes", FileKind::Tex), None);
+        let config = SearchConfig {
+            paths: vec![search_path(&format!("{}//", dir.display()).into())],
+            ..Default::default()
+        };
+        let r = Resolver::new(Path::new("/nonexistent"), &config);
+        assert_eq!(
+            r.resolve("notes", FileKind::Tex),
+            Some(dir.join("chapters/deep/notes.tex"))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fallback() {
+        let dir = dir("fallback");
+        let config = SearchConfig::default();
+        let r = Resolver::new(&dir, &config);
+        assert_eq!(r.resolve("missing", FileKind::Bib), None);
+        assert_eq!(
+            r.fallback("missing", FileKind::Bib),
+            dir.join("missing.bib")
+        );
+        assert_eq!(
+            r.fallback("chapters/missing.tex", FileKind::Tex),
+            dir.join("chapters/missing.tex")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
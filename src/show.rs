@@ -0,0 +1,84 @@
+use std::fs;
+
+use crate::parser::BibEntry;
+
+// The text of the entry as it is written in its bib file.
+pub fn source(e: &BibEntry) -> Result<String, String> {
+    let file = e.file.to_string_lossy();
+    let text = fs::read(&e.file).map_err(|err| format!("{}: cannot read: {}", file, err))?;
+    match text.get(e.span.clone()) {
+        Some(bs) => Ok(String::from_utf8_lossy(bs).into_owned()),
+        None => Err(format!("{}: changed while reading", file)),
+    }
+}
+
+// The entry as bibstats reads it: author, title and date first,
+// then the other fields by name, with the values in braces.
+// A date taken from the year field is not repeated.
+pub fn format(e: &BibEntry) -> String {
+    let date = match e.field("year") {
+        Some(y) if y == e.date => "",
+        _ => e.date.as_str(),
+    };
+    let mut fields: Vec<(&str, &str)> = [
+        ("author", e.author.as_str()),
+        ("title", e.title.as_str()),
+        ("date", date),
+    ]
+    .into_iter()
+    .filter(|(_, v)| !v.is_empty())
+    .collect();
+    let mut rest: Vec<(&str, &str)> = e
+        .fields
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    rest.sort_unstable();
+    fields.extend(rest);
+    let width = fields.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+    let mut s = format!("@{}{{{},\n", e.pubtype, e.key);
+    for (k, v) in fields {
+        s.push_str(&format!("  {:width$} = {{{}}},\n", k, v, width = width));
+    }
+    s.push('}');
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bib;
+    use crate::parser;
+    use crate::resolve::SearchConfig;
+    use std::env;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_show() {
+        let dir = env::temp_dir().join(format!("bibstats-show-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("refs.bib");
+        let knuth = "@Article{knuth84,\n  author = \"Knuth, Donald E.\",\n  \
+                     title = {Literate {P}rogramming},\n  year = 1984, journal = {CJ}\n}";
+        fs::write(
+            &path,
+            format!("% refs\n@book{{a, title={{A}}}}\n\n{}\n", knuth),
+        )
+        .unwrap();
+        let b = bib::load(
+            &OsString::from(&path),
+            &[],
+            &SearchConfig::default(),
+            parser::Options::default(),
+        )
+        .unwrap();
+        let e = &b.entries[1];
+        assert_eq!(source(e).unwrap(), knuth);
+        assert_eq!(
+            format(e),
+            "@article{knuth84,\n  author  = {Knuth, Donald E.},\n  \
+             title   = {Literate Programming},\n  journal = {CJ},\n  year    = {1984},\n}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
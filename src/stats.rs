@@ -1,136 +1,260 @@
-use std::collections::HashMap;
-use std::ffi::OsString;
-
-use pacosso;
-use pacosso::{Opts, ParseResult};
+use rustc_hash::FxHashMap;
 use serde_json::json;
 
-use crate::parser;
+use crate::diagnostics;
+use crate::docs::Citation;
+use crate::keymatch;
+use crate::names;
+use crate::output;
+use crate::output::{Format, Json};
 use crate::parser::BibEntry;
+use crate::weights::Weights;
 
-// AuthorStats[author] -> map[title] -> count
-pub type AuthorStats = HashMap<String, HashMap<String, u32>>;
+// AuthorStats[author] -> map[title] -> work
+// The maps are hashed with FxHash, which is much cheaper than SipHash
+// for the short keys we have. Iteration order is therefore arbitrary
+// and output is sorted before printing.
+pub type AuthorStats = FxHashMap<String, FxHashMap<String, Work>>;
 
-pub enum Format {
-    Json(bool),
-    Tsv,
+// What we know about a cited work:
+// how often it is cited and under which citekeys.
+// With document weights, the weighted count is the sum
+// of the weights of the documents of the citations.
+#[derive(Debug, Default)]
+pub struct Work {
+    pub count: u32,
+    pub weighted: Option<f64>,
+    pub keys: Vec<String>,
 }
 
-pub fn compute(bib: OsString, files: Vec<OsString>, no_files: bool) -> ParseResult<AuthorStats> {
-    let bibmap = bib_to_map(parse_bib_file(&bib)?);
+// BibMap[citekey] -> entry
+pub type BibMap = FxHashMap<String, BibEntry>;
 
-    let mut authostats = HashMap::new();
+// Citations are counted for every author of the work;
+// with whole, for the author field as it is written.
+pub fn compute(
+    bibmap: &BibMap,
+    cites: &[Citation],
+    mut weights: Option<&mut Weights>,
+    whole: bool,
+) -> AuthorStats {
+    let mut authostats = AuthorStats::with_capacity_and_hasher(bibmap.len(), Default::default());
 
-    if no_files {
-        for quote in get_quotes_from_stdin()? {
-            match count_up(&quote, &bibmap, &mut authostats) {
-                Ok(()) => continue,
-                Err(()) => eprintln!("Citekey {} not in database", quote),
-            };
-        }
-    } else {
-        for file in files {
-            for quote in get_quotes_from_file(&file)? {
-                match count_up(&quote, &bibmap, &mut authostats) {
-                    Ok(()) => continue,
-                    Err(()) => eprintln!("Citekey {} not in database", quote),
-                };
-            }
-        }
+    for quote in cites {
+        let weight = weights.as_mut().map(|w| w.weight(&quote.file));
+        match count_up(&quote.key, weight, bibmap, whole, &mut authostats) {
+            Ok(()) => continue,
+            Err(()) => diagnostics::warn(
+                "B002",
+                &format!(
+                    "Citekey {} not in database{}",
+                    quote.key,
+                    keymatch::did_you_mean(&quote.key, bibmap)
+                ),
+            ),
+        };
     }
 
-    Ok(authostats)
+    authostats
 }
 
-pub fn print_stats(m: AuthorStats, f: Format) {
+// With full, every record also lists the bib entries of the work
+// with their type, date and the place where they are defined;
+// in JSON also with all their other fields.
+pub fn print_stats(m: AuthorStats, bib: &BibMap, f: Format, full: bool) {
+    let bib = if full { Some(bib) } else { None };
     match f {
-        Format::Json(a) => stats_as_json(m, a),
-        Format::Tsv => stats_as_tsv(m),
+        Format::Json(j) => stats_as_json(m, bib, j),
+        Format::Tsv => stats_as_tsv(m, bib),
+        Format::Markdown => stats_as_markdown(m, bib),
     }
 }
 
-fn stats_as_tsv(m: AuthorStats) {
-    let mut i = 0;
+// flatten the stats into (author, title, work) records
+// sorted by author and title
+fn sorted(m: AuthorStats) -> Vec<(String, String, Work)> {
+    let mut v = Vec::with_capacity(m.len());
     for (author, works) in m.into_iter() {
-        for (title, count) in works.into_iter() {
-            println!("{}\t\"{}\"\t\"{}\"\t{}", i, author, title, count);
-            i += 1;
+        for (title, mut work) in works.into_iter() {
+            work.keys.sort_unstable();
+            v.push((author.clone(), title, work));
         }
     }
+    v.sort_unstable_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    v
 }
 
-fn stats_as_json(m: AuthorStats, with_array: bool) {
-    let mut first = true;
-    if with_array {
-        println!("[");
+fn stats_as_tsv(m: AuthorStats, bib: Option<&BibMap>) {
+    for (i, (author, title, work)) in sorted(m).into_iter().enumerate() {
+        print!("{}\t\"{}\"\t\"{}\"\t{}", i, author, title, work.count);
+        if let Some(w) = work.weighted {
+            print!("\t{}", round(w));
+        }
+        if let Some(bib) = bib {
+            let locations: Vec<String> = work.keys.iter().map(|k| bib[k].location()).collect();
+            print!("\t\"{}\"\t\"{}\"", work.keys.join(","), locations.join(","));
+        }
+        println!();
     }
-    for (author, works) in m.into_iter() {
-        for (title, count) in works.into_iter() {
-            let js = json!({
-                "author": author,
-                "title": title,
-                "count": count
-            });
+}
 
-            // print comma if we are in an array
-            if !first {
-                if with_array {
-                    println!(",")
-                } else {
-                    println!("")
-                }
+fn stats_as_markdown(m: AuthorStats, bib: Option<&BibMap>) {
+    let works = sorted(m);
+    let weighted = works.iter().any(|(_, _, w)| w.weighted.is_some());
+    let mut header = vec!["", "Author", "Title", "Count"];
+    if weighted {
+        header.push("Weighted");
+    }
+    if bib.is_some() {
+        header.extend(["Keys", "Defined at"]);
+    }
+    let rows = works
+        .into_iter()
+        .enumerate()
+        .map(|(i, (author, title, work))| {
+            let mut row = vec![i.to_string(), author, title, work.count.to_string()];
+            if let Some(w) = work.weighted {
+                row.push(round(w).to_string());
+            }
+            if let Some(bib) = bib {
+                let locations: Vec<String> = work.keys.iter().map(|k| bib[k].location()).collect();
+                row.push(work.keys.join(", "));
+                row.push(locations.join(", "));
             }
+            row
+        });
+    output::print_md_table(&header, rows);
+}
 
-            print!("{}", js.to_string());
+fn stats_as_json(m: AuthorStats, bib: Option<&BibMap>, json: Json) {
+    output::print_json(records(m, bib), json, "stats");
+}
 
-            if first {
-                first = false;
+// The records of the JSON output, sorted like all output;
+// with bib, with the entries of the works.
+pub fn records(m: AuthorStats, bib: Option<&BibMap>) -> Vec<serde_json::Value> {
+    sorted(m)
+        .into_iter()
+        .map(|(author, title, work)| {
+            let mut js = json!({
+                "author": author,
+                "title": title,
+                "count": work.count
+            });
+            if let Some(w) = work.weighted {
+                js["weighted"] = round(w).into();
             }
-        }
-    }
-    println!("");
-    if with_array {
-        println!("]");
-    }
+            if let Some(bib) = bib {
+                let entries: Vec<serde_json::Value> = work
+                    .keys
+                    .iter()
+                    .map(|k| {
+                        let e = &bib[k];
+                        json!({
+                            "key": e.key,
+                            "type": e.pubtype.to_string(),
+                            "date": e.date,
+                            "year": e.year_number(),
+                            "file": e.file.to_string_lossy(),
+                            "line": e.line,
+                            "fields": e.fields
+                        })
+                    })
+                    .collect();
+                js["entries"] = entries.into();
+            }
+            js
+        })
+        .collect()
 }
 
-fn bib_to_map(works: Vec<BibEntry>) -> HashMap<String, BibEntry> {
-    let mut m = HashMap::new();
+// Weighted counts are sums of fractions like 0.3,
+// which are not exact in binary.
+fn round(w: f64) -> f64 {
+    (w * 1000.0).round() / 1000.0
+}
+
+// The first definition of a citekey wins;
+// later definitions are returned as duplicates.
+pub fn bib_to_map(works: Vec<BibEntry>) -> (BibMap, Vec<BibEntry>) {
+    let mut m = BibMap::with_capacity_and_hasher(works.len(), Default::default());
+    let mut duplicates = Vec::new();
     for work in works {
         if m.contains_key(&work.key) {
+            duplicates.push(work);
             continue;
         }
         m.insert(work.key.clone(), work);
     }
-    m
+    (m, duplicates)
 }
 
-fn count_up(
-    citekey: &str,
-    bib: &HashMap<String, BibEntry>,
-    authors: &mut AuthorStats,
-) -> Result<(), ()> {
-    if !bib.contains_key(citekey) {
-        return Err(());
+// Edited volumes often have no author but editors,
+// who are then credited with the work instead.
+pub fn editor_fallback(bib: &mut BibMap) {
+    for e in bib.values_mut() {
+        if !e.author.trim().is_empty() {
+            continue;
+        }
+        if let Some(editor) = e.field("editor") {
+            e.author = names::as_editors(editor);
+        }
     }
-    let b = &bib[citekey];
-    let author = authors.entry(b.author.clone()).or_insert(HashMap::new());
-    *author.entry(b.title.clone()).or_insert(0) += 1;
-    Ok(())
 }
 
-fn parse_bib_file(path: &OsString) -> ParseResult<Vec<BibEntry>> {
-    pacosso::parse_file(path.clone(), Opts::default(), parser::parse)
+pub fn warn_duplicates<'a>(bib: &BibMap, duplicates: impl IntoIterator<Item = &'a BibEntry>) {
+    for work in duplicates {
+        diagnostics::warn(
+            "B001",
+            &format!(
+                "Duplicate citekey {} at {}, first defined at {}",
+                work.key,
+                work.location(),
+                bib[&work.key].location()
+            ),
+        );
+    }
 }
 
-fn get_quotes_from_file(path: &OsString) -> ParseResult<Vec<String>> {
-    pacosso::parse_file(path.clone(), Opts::default(), parser::collect_cites)
+// Citekeys not in the database have empty author and title in reports.
+pub fn author_title<'a>(key: &str, bib: &'a BibMap) -> (&'a str, &'a str) {
+    match bib.get(key) {
+        Some(e) => (&e.author, &e.title),
+        None => ("", ""),
+    }
 }
 
-fn get_quotes_from_stdin() -> ParseResult<Vec<String>> {
-    let mut stdin = std::io::stdin();
-    let mut s = pacosso::Stream::new(Opts::default(), &mut stdin);
-    s.apply(parser::collect_cites)
+fn count_up(
+    citekey: &str,
+    weight: Option<f64>,
+    bib: &BibMap,
+    whole: bool,
+    authors: &mut AuthorStats,
+) -> Result<(), ()> {
+    let Some(b) = bib.get(citekey) else {
+        return Err(());
+    };
+    // works without author are still counted under the empty author
+    let mut names = if whole {
+        Vec::new()
+    } else {
+        names::split(&b.author)
+    };
+    if names.is_empty() {
+        names.push(b.author.clone());
+    }
+    for name in names {
+        let author = authors.entry(name).or_default();
+        let work = author.entry(b.title.clone()).or_default();
+        work.count += 1;
+        if let Some(w) = weight {
+            *work.weighted.get_or_insert(0.0) += w;
+        }
+        if !work.keys.iter().any(|k| k == citekey) {
+            work.keys.push(citekey.to_string());
+        }
+    }
+    Ok(())
 }
 
 #[allow(dead_code)]
@@ -0,0 +1,220 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::diagnostics;
+use crate::docs::Citation;
+use crate::parser::BibEntry;
+use crate::stats::BibMap;
+
+// Comments in bib and tex files that suppress diagnostics:
+//
+//   % bibstats: ignore-next-line
+//   % bibstats: disable=B002,page-range-dash
+//
+// ignore-next-line suppresses all diagnostics on the next line.
+// disable suppresses the given diagnostics (by code or name)
+// on the same line, if it follows other text, or else on the next line.
+// The files are read again to find the comments;
+// comments in documents read from stdin are not seen.
+// Suppressed diagnostics are counted per code.
+#[derive(Default)]
+pub struct Suppressions {
+    // file -> line -> codes; None suppresses all codes
+    files: FxHashMap<OsString, FxHashMap<u64, Option<Vec<&'static str>>>>,
+    counts: Vec<(&'static str, usize)>,
+}
+
+const MARKER: &str = "bibstats:";
+
+impl Suppressions {
+    pub fn is_suppressed(&mut self, file: &OsStr, line: u64, code: &'static str) -> bool {
+        let lines = self
+            .files
+            .entry(file.to_os_string())
+            .or_insert_with(|| scan(file));
+        let suppressed = match lines.get(&line) {
+            Some(None) => true,
+            Some(Some(codes)) => codes.contains(&code),
+            None => false,
+        };
+        if suppressed {
+            match self.counts.iter_mut().find(|(c, _)| *c == code) {
+                Some((_, n)) => *n += 1,
+                None => self.counts.push((code, 1)),
+            }
+        }
+        suppressed
+    }
+
+    // The entries defining a citekey again whose warning, B001,
+    // is not suppressed at their definition.
+    pub fn duplicates<'a>(&mut self, duplicates: &'a [BibEntry]) -> Vec<&'a BibEntry> {
+        duplicates
+            .iter()
+            .filter(|d| !self.is_suppressed(&d.file, d.line, "B001"))
+            .collect()
+    }
+
+    // The first citation of every key not in the database whose
+    // warning, B002, is not suppressed at the citation.
+    pub fn missing<'a>(&mut self, bib: &BibMap, cites: &'a [Citation]) -> Vec<&'a Citation> {
+        let mut seen = FxHashSet::default();
+        cites
+            .iter()
+            .filter(|c| !bib.contains_key(&c.key))
+            .filter(|c| !self.is_suppressed(&c.file, c.line, "B002"))
+            .filter(|c| seen.insert(c.key.as_str()))
+            .collect()
+    }
+
+    // How many diagnostics were suppressed per code, sorted by code.
+    pub fn counts(&self) -> Vec<(&'static str, usize)> {
+        let mut v = self.counts.clone();
+        v.sort_unstable();
+        v
+    }
+}
+
+// Tells how many findings or warnings were suppressed, if any.
+pub fn report(counts: &[(&'static str, usize)], finding: &str, findings: &str) {
+    if counts.is_empty() {
+        return;
+    }
+    let total: usize = counts.iter().map(|(_, n)| n).sum();
+    let per_code: Vec<String> = counts
+        .iter()
+        .map(|(code, n)| format!("{}: {}", code, n))
+        .collect();
+    eprintln!(
+        "{} {} suppressed ({})",
+        total,
+        if total == 1 { finding } else { findings },
+        per_code.join(", ")
+    );
+}
+
+fn scan(file: &OsStr) -> FxHashMap<u64, Option<Vec<&'static str>>> {
+    let mut m = FxHashMap::default();
+    let Ok(text) = fs::read_to_string(file) else {
+        return m;
+    };
+    for (i, l) in text.lines().enumerate() {
+        let Some((before, directive)) = directive(l) else {
+            continue;
+        };
+        let n = i as u64 + 1;
+        if directive == "ignore-next-line" {
+            m.insert(n + 1, None);
+        } else if let Some(ids) = directive.strip_prefix("disable=") {
+            let target = if before.trim().is_empty() { n + 1 } else { n };
+            let mut codes = Vec::new();
+            for id in ids.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+                match diagnostics::lookup(id) {
+                    Some(d) => codes.push(d.code),
+                    None => eprintln!(
+                        "unknown diagnostic '{}' in {}:{}",
+                        id,
+                        file.to_string_lossy(),
+                        n
+                    ),
+                }
+            }
+            // ignore-next-line already suppresses everything
+            if let Some(cs) = m.entry(target).or_insert_with(|| Some(Vec::new())) {
+                cs.extend(codes);
+            }
+        } else {
+            eprintln!(
+                "unknown directive '{}' in {}:{}",
+                directive,
+                file.to_string_lossy(),
+                n
+            );
+        }
+    }
+    m
+}
+
+// Finds a directive in a comment, i.e. after an unescaped '%',
+// and returns the text before the comment and the directive.
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' => escaped = !escaped,
+            '%' if !escaped => {
+                let comment = line[i + 1..].trim_start();
+                let d = comment.strip_prefix(MARKER)?;
+                return Some((&line[..i], d.trim()));
+            }
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_directive() {
+        assert_eq!(
+            directive("% bibstats: ignore-next-line"),
+            Some(("", "ignore-next-line"))
+        );
+        assert_eq!(
+            directive("\\cite{x} %bibstats: disable=B002 "),
+            Some(("\\cite{x} ", "disable=B002"))
+        );
+        assert_eq!(directive("50\\% bibstats: disable=B002"), None);
+        assert_eq!(directive("% just a comment"), None);
+    }
+
+    #[test]
+    fn test_suppressed_sites() {
+        let dir = std::env::temp_dir().join(format!("bibstats-suppress-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let doc = dir.join("doc.tex");
+        fs::write(
+            &doc,
+            "\\cite{x} % bibstats: disable=missing-key\n\
+             % bibstats: ignore-next-line\n\
+             \\cite{y}\n\
+             \\cite{y}\\cite{z}\n",
+        )
+        .unwrap();
+        let bib = dir.join("refs.bib");
+        fs::write(&bib, "% bibstats: disable=B001\n@book{a,\n}\n@book{a,\n}\n").unwrap();
+        let cite = |key: &str, line| Citation {
+            file: doc.clone().into_os_string(),
+            line,
+            ..crate::fixtures::citation(key)
+        };
+        let dup = |line| BibEntry {
+            key: "a".to_string(),
+            file: bib.clone().into_os_string(),
+            line,
+            ..BibEntry::empty()
+        };
+
+        let mut sup = Suppressions::default();
+        // y is still missing where it is not suppressed
+        let cites = vec![cite("x", 1), cite("y", 3), cite("y", 4), cite("z", 4)];
+        let missing: Vec<(&str, u64)> = sup
+            .missing(&BibMap::default(), &cites)
+            .iter()
+            .map(|c| (c.key.as_str(), c.line))
+            .collect();
+        assert_eq!(missing, [("y", 4), ("z", 4)]);
+        // with all missing keys suppressed, validate passes
+        assert!(sup.missing(&BibMap::default(), &cites[..2]).is_empty());
+        let duplicates = vec![dup(2), dup(4)];
+        let warned: Vec<u64> = sup.duplicates(&duplicates).iter().map(|d| d.line).collect();
+        assert_eq!(warned, [4]);
+        assert_eq!(sup.counts(), [("B001", 1), ("B002", 4)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
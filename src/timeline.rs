@@ -0,0 +1,135 @@
+use rustc_hash::FxHashMap;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::{author_title, BibMap};
+
+// A work in the order of its first mention:
+// where it is first cited and how often it is cited at all.
+// Progress is the position of the first citation among
+// all citations in percent; 0 is the very first citation.
+pub struct Mention<'a> {
+    pub first: &'a Citation,
+    pub count: u32,
+    pub progress: f64,
+}
+
+// Returns the citekeys in the order of their first citation.
+pub fn compute(cites: &[Citation]) -> Vec<Mention<'_>> {
+    let mut v: Vec<Mention> = Vec::new();
+    let mut idx: FxHashMap<&str, usize> = FxHashMap::default();
+    let total = cites.len() as f64;
+    for (i, c) in cites.iter().enumerate() {
+        match idx.get(c.key.as_str()) {
+            Some(&k) => v[k].count += 1,
+            None => {
+                idx.insert(&c.key, v.len());
+                v.push(Mention {
+                    first: c,
+                    count: 1,
+                    progress: (i as f64 * 1000.0 / total).round() / 10.0,
+                });
+            }
+        }
+    }
+    v
+}
+
+pub fn print_timeline(ms: Vec<Mention>, bib: &BibMap, f: Format) {
+    match f {
+        Format::Json(j) => timeline_as_json(ms, bib, j),
+        Format::Tsv => timeline_as_tsv(ms, bib),
+        Format::Markdown => timeline_as_markdown(ms, bib),
+    }
+}
+
+fn timeline_as_tsv(ms: Vec<Mention>, bib: &BibMap) {
+    for (i, m) in ms.into_iter().enumerate() {
+        let (author, title) = author_title(&m.first.key, bib);
+        println!(
+            "{}\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t{}\t{}",
+            i + 1,
+            m.first.key,
+            author,
+            title,
+            m.first.location(),
+            m.first.section.as_deref().unwrap_or(""),
+            m.count,
+            m.progress
+        );
+    }
+}
+
+fn timeline_as_markdown(ms: Vec<Mention>, bib: &BibMap) {
+    let rows = ms.into_iter().enumerate().map(|(i, m)| {
+        let (author, title) = author_title(&m.first.key, bib);
+        vec![
+            (i + 1).to_string(),
+            m.first.key.clone(),
+            author.to_string(),
+            title.to_string(),
+            m.first.location(),
+            m.first.section.clone().unwrap_or_default(),
+            m.count.to_string(),
+            format!("{}%", m.progress),
+        ]
+    });
+    output::print_md_table(
+        &[
+            "",
+            "Key",
+            "Author",
+            "Title",
+            "First cited",
+            "Section",
+            "Count",
+            "Progress",
+        ],
+        rows,
+    );
+}
+
+fn timeline_as_json(ms: Vec<Mention>, bib: &BibMap, json: Json) {
+    let records = ms.into_iter().enumerate().map(|(i, m)| {
+        let (author, title) = author_title(&m.first.key, bib);
+        json!({
+            "position": i + 1,
+            "key": m.first.key,
+            "author": author,
+            "title": title,
+            "file": m.first.file.to_string_lossy(),
+            "line": m.first.line,
+            "section": m.first.section,
+            "count": m.count,
+            "progress": m.progress
+        })
+    });
+    output::print_json(records, json, "timeline");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::citation;
+
+    #[test]
+    fn test_progress() {
+        assert!(compute(&[]).is_empty());
+        let cites: Vec<Citation> = ["a", "b", "a", "c", "b", "a"]
+            .iter()
+            .enumerate()
+            .map(|(i, k)| Citation {
+                line: i as u64 + 1,
+                ..citation(k)
+            })
+            .collect();
+        let ms: Vec<(&str, u64, u32, f64)> = compute(&cites)
+            .iter()
+            .map(|m| (m.first.key.as_str(), m.first.line, m.count, m.progress))
+            .collect();
+        // positions in percent, rounded to one decimal
+        assert_eq!(ms, [("a", 1, 3, 0.0), ("b", 2, 2, 16.7), ("c", 4, 1, 50.0)]);
+    }
+}
@@ -0,0 +1,396 @@
+// Citations in Typst documents: references like @knuth84 and
+// @knuth84[p. 7] and the cite function, like #cite(<knuth84>) and
+// #cite(<knuth84>, supplement: [p. 7], form: "prose").
+
+use std::ops::Range;
+
+use rustc_hash::FxHashSet;
+
+use crate::markdown;
+use crate::parser::{Cite, CiteKind, Section, TexItem};
+
+// Scans a Typst document for citations, headings, included files
+// and the bibliography files. Comments and raw text are not scanned.
+// Since references also refer to labels, like @fig:plot to a
+// figure labelled <fig:plot>, references to the labels of the
+// document are no citations. Headings = of level 1 are sections,
+// those of level 2 subsections and so on; citations in quotes,
+// #quote[...], or after a quotation mark are quoted.
+pub fn collect_cites(text: &str) -> Vec<TexItem> {
+    let s = masked(text);
+    let bs = s.as_bytes();
+    let starts: Vec<usize> = std::iter::once(0)
+        .chain(s.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let place = |pos: usize| {
+        let line = starts.partition_point(|s| *s <= pos);
+        (line as u64, (pos - starts[line - 1] + 1) as u64)
+    };
+
+    let mut items = Vec::new();
+    let mut refs = Vec::new();
+    let mut labels = FxHashSet::default();
+    let mut quotes: Vec<Range<usize>> = Vec::new();
+    let quoted = |quotes: &[Range<usize>], i: usize| {
+        quotes.iter().any(|q| q.contains(&i)) || markdown::after_quotation(&s[..i])
+    };
+    let mut i = 0;
+    while i < bs.len() {
+        if i == 0 || bs[i - 1] == b'\n' {
+            if let Some(h) = heading(&s[i..s[i..].find('\n').map_or(s.len(), |n| i + n)]) {
+                items.push(TexItem::Section(h));
+            }
+        }
+        let rest = &s[i..];
+        if bs[i] == b'\\' {
+            i += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+            continue;
+        }
+        if let Some(args) = rest.strip_prefix("#cite(") {
+            let end = closing(args, '(', ')').unwrap_or(args.len());
+            let (line, column) = place(i);
+            let quoted = quoted(&quotes, i);
+            let (keys, kind, postnote) = cite_args(&args[..end]);
+            items.extend(keys.into_iter().map(|key| {
+                TexItem::Cite(Cite {
+                    key,
+                    line,
+                    column,
+                    prenote: None,
+                    postnote: postnote.clone(),
+                    quoted,
+                    kind,
+                    span: None,
+                })
+            }));
+            i += "#cite(".len() + end;
+            continue;
+        }
+        if let Some(args) = rest.strip_prefix("#bibliography(") {
+            let end = closing(args, '(', ')').unwrap_or(args.len());
+            let first = split_args(&args[..end]).into_iter().next().unwrap_or("");
+            let files = first.trim_start_matches('(').trim_end_matches(')');
+            items.extend(
+                split_args(files)
+                    .into_iter()
+                    .filter_map(string)
+                    .map(TexItem::Bib),
+            );
+            i += "#bibliography(".len() + end;
+            continue;
+        }
+        if let Some(file) = rest.strip_prefix("#include") {
+            let file = file.trim_start_matches([' ', '\t']);
+            let end = file[1.min(file.len())..].find('"').map_or(0, |n| n + 2);
+            if let Some(f) = string(&file[..end]) {
+                items.push(TexItem::Input(f));
+            }
+            i += 1;
+            continue;
+        }
+        if rest.starts_with("#quote") {
+            // the attribution in parentheses and the quote in brackets
+            let mut end = i + "#quote".len();
+            for (open, close) in [('(', ')'), ('[', ']')] {
+                if let Some(args) = s[end..].strip_prefix(open) {
+                    end += 1 + closing(args, open, close).unwrap_or(args.len());
+                }
+            }
+            quotes.push(i..end);
+        }
+        if let Some(args) = rest.strip_prefix("label(") {
+            let end = closing(args, '(', ')').unwrap_or(args.len());
+            labels.extend(string(&args[..end]));
+        }
+        if bs[i] == b'<' {
+            let n = label_len(&rest[1..]);
+            if n > 0 && rest[1 + n..].starts_with('>') {
+                labels.insert(rest[1..1 + n].to_string());
+                i += n + 2;
+                continue;
+            }
+        }
+        // addresses like me@example.com, URLs and packages,
+        // like "@preview/cetz:0.3.0", are no references
+        let in_word = s[..i]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || "_\"/@".contains(c));
+        if bs[i] == b'@' && !in_word {
+            let n = label_len(&rest[1..]);
+            if n > 0 {
+                let (line, column) = place(i);
+                let mut end = i + 1 + n;
+                let postnote = s[end..].strip_prefix('[').map(|sup| {
+                    let close = closing(sup, '[', ']').unwrap_or(sup.len());
+                    end += close + 2;
+                    sup[..close].trim().to_string()
+                });
+                refs.push(items.len());
+                items.push(TexItem::Cite(Cite {
+                    key: rest[1..1 + n].to_string(),
+                    line,
+                    column,
+                    prenote: None,
+                    postnote,
+                    quoted: quoted(&quotes, i),
+                    kind: CiteKind::Parenthetical,
+                    span: None,
+                }));
+                i = end;
+                continue;
+            }
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+
+    let refs: FxHashSet<usize> = refs
+        .into_iter()
+        .filter(|r| matches!(&items[*r], TexItem::Cite(c) if labels.contains(&c.key)))
+        .collect();
+    items
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !refs.contains(i))
+        .map(|(_, item)| item)
+        .collect()
+}
+
+// The text with comments and raw text blanked out, keeping the positions.
+// // in URLs, like https://typst.app, starts no comment.
+fn masked(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let end = if rest.starts_with("//") && !text[..i].ends_with(':') {
+            i + rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            i + block_comment_len(rest)
+        } else if rest.starts_with('`') {
+            let run = rest.len() - rest.trim_start_matches('`').len();
+            // `` is empty raw text, longer runs open raw blocks
+            let close = if run >= 3 { &rest[..run] } else { "`" };
+            match rest[run..].find(close).filter(|_| run != 2) {
+                Some(n) => i + run + n + close.len(),
+                None => i + run,
+            }
+        } else {
+            // a character, escaped ones, like \@ or \/, with the backslash
+            let escaped = usize::from(rest.starts_with('\\'));
+            let c = rest[escaped..].chars().next().map_or(0, char::len_utf8);
+            out.push_str(&rest[..escaped + c]);
+            i += escaped + c;
+            continue;
+        };
+        out.push_str(&markdown::blank(&text[i..end]));
+        i = end;
+    }
+    out
+}
+
+// Block comments nest.
+fn block_comment_len(s: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < s.len() {
+        if s[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if s[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += s[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    s.len()
+}
+
+// A heading like == Methods <methods>.
+fn heading(l: &str) -> Option<Section> {
+    let l = l.trim_start();
+    let level = l.len() - l.trim_start_matches('=').len();
+    let rest = &l[level..];
+    if level == 0 || !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let mut title = rest.trim();
+    if let Some(open) = title.rfind('<').filter(|_| title.ends_with('>')) {
+        title = title[..open].trim_end();
+    }
+    Some(Section {
+        level: (level + 1).min(6),
+        starred: false,
+        title: title.to_string(),
+    })
+}
+
+// The length of a label name, made of letters, digits and _-:.,
+// which ends with a letter, a digit or _, like the key in @doe:99.
+fn label_len(s: &str) -> usize {
+    let mut end = 0;
+    for (k, c) in s.char_indices() {
+        if c.is_alphanumeric() || c == '_' {
+            end = k + c.len_utf8();
+        } else if !matches!(c, '-' | ':' | '.') {
+            break;
+        }
+    }
+    end
+}
+
+// Where the group opened before s closes, outside of strings.
+fn closing(s: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            c if c == open => depth += 1,
+            c if c == close && depth == 0 => return Some(i),
+            c if c == close => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+// The arguments of a function call, separated by commas
+// outside of strings and nested groups.
+fn split_args(s: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let (mut depth, mut in_string, mut start) = (0, false, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    args.push(s[start..].trim());
+    args.retain(|a| !a.is_empty());
+    args
+}
+
+fn string(s: &str) -> Option<String> {
+    let s = s.trim();
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    Some(inner.to_string())
+}
+
+// The keys, the kind and the supplement of #cite: the keys are
+// labels, like <knuth84> or label("knuth84"), or, in early versions
+// of Typst, strings; form: "prose" cites in the text, form: none
+// only adds the entry to the bibliography.
+fn cite_args(args: &str) -> (Vec<String>, CiteKind, Option<String>) {
+    let mut keys = Vec::new();
+    let mut kind = CiteKind::Parenthetical;
+    let mut supplement = None;
+    for a in split_args(args) {
+        if let Some(k) = a.strip_prefix('<').and_then(|a| a.strip_suffix('>')) {
+            keys.push(k.to_string());
+        } else if let Some(k) = a.strip_prefix("label(").and_then(|a| a.strip_suffix(')')) {
+            keys.extend(string(k));
+        } else if let Some(v) = a.strip_prefix("supplement:") {
+            let v = v.trim();
+            supplement = v
+                .strip_prefix('[')
+                .and_then(|v| v.strip_suffix(']'))
+                .map(|v| v.trim().to_string())
+                .or_else(|| string(v));
+        } else if let Some(v) = a.strip_prefix("form:") {
+            kind = match v.trim() {
+                "none" => CiteKind::Nocite,
+                "\"prose\"" => CiteKind::Narrative,
+                "\"normal\"" => CiteKind::Parenthetical,
+                _ => CiteKind::Plain,
+            };
+        } else if !a.contains(':') {
+            keys.extend(string(a));
+        }
+    }
+    (keys, kind, supplement)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_collect_cites() {
+        let text = "#import \"@preview/x:0.1.0\": *\n\
+                    = Introduction <intro>\n\
+                    As @knuth84[p. 7] shows, see #cite(<lamport86>, supplement: [ch. 2], form: \"prose\").\n\
+                    See @intro and @fig:plot, mail me@example.com, // @comment\n\
+                    /* @block /* nested */ @still */ `@raw` and ```@code``` #cite(label(\"a\"), <b>)\n\
+                    #figure(image(\"plot.png\")) <fig:plot>\n\
+                    #quote(attribution: @c)[Words] \"Said\" @d. #cite(<e>, form: none)\n\
+                    #include \"chapter.typ\"\n\
+                    #bibliography((\"refs.bib\", \"more.yml\"))\n";
+        let items = collect_cites(text);
+        let cites: Vec<(&str, CiteKind, bool)> = items
+            .iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some((c.key.as_str(), c.kind, c.quoted)),
+                _ => None,
+            })
+            .collect();
+        use CiteKind::*;
+        assert_eq!(
+            cites,
+            vec![
+                ("knuth84", Parenthetical, false),
+                ("lamport86", Narrative, false),
+                ("a", Parenthetical, false),
+                ("b", Parenthetical, false),
+                ("c", Parenthetical, true),
+                ("d", Parenthetical, true),
+                ("e", Nocite, false),
+            ]
+        );
+        let notes: Vec<Option<&str>> = items
+            .iter()
+            .filter_map(|i| match i {
+                TexItem::Cite(c) => Some(c.postnote.as_deref()),
+                _ => None,
+            })
+            .take(2)
+            .collect();
+        assert_eq!(notes, vec![Some("p. 7"), Some("ch. 2")]);
+        assert_eq!(
+            items[0],
+            TexItem::Section(Section {
+                level: 2,
+                starred: false,
+                title: "Introduction".to_string()
+            })
+        );
+        assert!(matches!(&items[1], TexItem::Cite(c) if (c.line, c.column) == (3, 4)));
+        let files: Vec<&TexItem> = items
+            .iter()
+            .filter(|i| matches!(i, TexItem::Input(_) | TexItem::Bib(_)))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                &TexItem::Input("chapter.typ".to_string()),
+                &TexItem::Bib("refs.bib".to_string()),
+                &TexItem::Bib("more.yml".to_string())
+            ]
+        );
+    }
+}
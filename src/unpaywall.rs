@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use rustc_hash::FxHashSet;
+use serde_json::json;
+
+use crate::docs::Citation;
+use crate::netcache;
+use crate::netcache::{NetCache, Response};
+use crate::output;
+use crate::output::{Format, Json};
+use crate::stats::{author_title, BibMap};
+
+const API: &str = "https://api.unpaywall.org/v2/";
+
+// Open-access copies come and go, but not daily.
+const TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+// Whether a cited work has an open-access version and where
+// its best copy is. Unpaywall knows works by DOI only, so works
+// without DOI, and works it does not know, have no status.
+// The status is gold, hybrid, bronze, green or closed.
+pub struct Access {
+    pub key: String,
+    pub doi: Option<String>,
+    pub status: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Access {
+    pub fn open(&self) -> Option<bool> {
+        self.status.as_deref().map(|s| s != "closed")
+    }
+}
+
+// Unpaywall asks for an email address with every request.
+fn url(doi: &str, email: &str) -> String {
+    format!(
+        "{}{}?email={}",
+        API,
+        netcache::encode(doi),
+        netcache::encode(email)
+    )
+}
+
+// Looks up the works cited in the order of their first citation;
+// failed lookups are reported, but do not stop the others.
+pub fn lookup(net: &NetCache, email: &str, bib: &BibMap, cites: &[Citation]) -> Vec<Access> {
+    let mut seen = FxHashSet::default();
+    let mut v: Vec<Access> = cites
+        .iter()
+        .filter(|c| seen.insert(c.key.as_str()))
+        .map(|c| Access {
+            key: c.key.clone(),
+            doi: bib.get(&c.key).and_then(|e| e.doi()),
+            status: None,
+            url: None,
+        })
+        .collect();
+    let urls: Vec<String> = v
+        .iter()
+        .filter_map(|a| a.doi.as_deref())
+        .map(|d| url(d, email))
+        .collect();
+    let answers = net.get_all(&urls, TTL);
+    let with_doi = v.iter_mut().filter(|a| a.doi.is_some());
+    for ((a, answer), url) in with_doi.zip(answers).zip(&urls) {
+        match answer.and_then(|r| parse(url, r)) {
+            Ok(Some((status, oa_url))) => {
+                a.status = Some(status);
+                a.url = oa_url;
+            }
+            Ok(None) => (),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+    v
+}
+
+// The status and the URL of the best open-access copy, or None
+// if Unpaywall does not know the DOI or we are offline.
+fn parse(url: &str, r: Option<Response>) -> Result<Option<(String, Option<String>)>, String> {
+    let Some(r) = r else {
+        return Ok(None);
+    };
+    match r.status {
+        200 => {
+            let js: serde_json::Value = serde_json::from_str(&r.body)
+                .map_err(|e| format!("invalid response for {}: {}", url, e))?;
+            Ok(Some(oa(&js)))
+        }
+        404 => Ok(None),
+        s => Err(format!("Unpaywall answered {} for {}", s, url)),
+    }
+}
+
+fn oa(js: &serde_json::Value) -> (String, Option<String>) {
+    let status = js["oa_status"].as_str().unwrap_or("closed").to_string();
+    let best = &js["best_oa_location"];
+    let url = best["url_for_pdf"]
+        .as_str()
+        .or_else(|| best["url"].as_str())
+        .map(String::from);
+    (status, url)
+}
+
+pub fn print_access(v: &[Access], bib: &BibMap, f: Format) {
+    let known = v.iter().filter(|a| a.status.is_some()).count();
+    let open = v.iter().filter(|a| a.open() == Some(true)).count();
+    eprintln!(
+        "{} of {} works cited known to Unpaywall are open access, {} works without DOI",
+        open,
+        known,
+        v.iter().filter(|a| a.doi.is_none()).count()
+    );
+    match f {
+        Format::Json(j) => access_as_json(v, bib, j),
+        Format::Tsv => access_as_tsv(v, bib),
+        Format::Markdown => access_as_markdown(v, bib),
+    }
+}
+
+fn yes_no(open: Option<bool>) -> &'static str {
+    match open {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "",
+    }
+}
+
+fn access_as_tsv(v: &[Access], bib: &BibMap) {
+    for a in v {
+        let (author, title) = author_title(&a.key, bib);
+        println!(
+            "\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"\t\"{}\"",
+            a.key,
+            author,
+            title,
+            a.doi.as_deref().unwrap_or(""),
+            yes_no(a.open()),
+            a.status.as_deref().unwrap_or(""),
+            a.url.as_deref().unwrap_or("")
+        );
+    }
+}
+
+fn access_as_markdown(v: &[Access], bib: &BibMap) {
+    let rows = v.iter().map(|a| {
+        let (author, title) = author_title(&a.key, bib);
+        vec![
+            a.key.clone(),
+            author.to_string(),
+            title.to_string(),
+            a.doi.clone().unwrap_or_default(),
+            yes_no(a.open()).to_string(),
+            a.status.clone().unwrap_or_default(),
+            a.url.clone().unwrap_or_default(),
+        ]
+    });
+    output::print_md_table(
+        &["Key", "Author", "Title", "DOI", "Open", "Status", "URL"],
+        rows,
+    );
+}
+
+fn access_as_json(v: &[Access], bib: &BibMap, json: Json) {
+    let records = v.iter().map(|a| {
+        let (author, title) = author_title(&a.key, bib);
+        json!({
+            "key": a.key,
+            "author": author,
+            "title": title,
+            "doi": a.doi,
+            "open": a.open(),
+            "status": a.status,
+            "url": a.url
+        })
+    });
+    output::print_json(records, json, "open-access");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_oa() {
+        let js = json!({
+            "is_oa": true,
+            "oa_status": "green",
+            "best_oa_location": {
+                "url": "https://arxiv.org/abs/1706.03762",
+                "url_for_pdf": "https://arxiv.org/pdf/1706.03762"
+            }
+        });
+        assert_eq!(
+            oa(&js),
+            (
+                "green".to_string(),
+                Some("https://arxiv.org/pdf/1706.03762".to_string())
+            )
+        );
+        let js = json!({ "is_oa": false, "oa_status": "closed", "best_oa_location": null });
+        assert_eq!(oa(&js), ("closed".to_string(), None));
+    }
+}
@@ -0,0 +1,36 @@
+use wasm_bindgen::prelude::*;
+
+use crate::api;
+
+// The API for the browser, for tools that compute the stats from
+// content pasted into a web page, without a filesystem:
+//
+//   import init, { citationStats } from "./bibstats.js";
+//   await init();
+//   const stats = JSON.parse(citationStats(bib, tex));
+//
+// Results are JSON text with the records of the JSON output;
+// errors are thrown with the message the command prints.
+
+// The entries of the bib text.
+#[wasm_bindgen(js_name = parseBib)]
+pub fn parse_bib(bib: &str) -> Result<String, JsError> {
+    json(api::entries(bib))
+}
+
+// The citations of the LaTeX text.
+#[wasm_bindgen(js_name = scanDocument)]
+pub fn scan_document(tex: &str) -> Result<String, JsError> {
+    json(api::citations(tex))
+}
+
+// The citations of the LaTeX text per author and work,
+// like the default report.
+#[wasm_bindgen(js_name = citationStats)]
+pub fn citation_stats(bib: &str, tex: &str) -> Result<String, JsError> {
+    json(api::stats(bib, tex))
+}
+
+fn json(r: Result<serde_json::Value, String>) -> Result<String, JsError> {
+    r.map(|v| v.to_string()).map_err(|e| JsError::new(&e))
+}
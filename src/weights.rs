@@ -0,0 +1,127 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustc_hash::FxHashMap;
+
+// The importance of documents for weighted citation counts.
+// The weights file has one document or directory per line
+// followed by its weight, e.g.
+//
+//   chapters/   1.0
+//   appendix.tex 0.3
+//
+// Paths are relative to the directory of the weights file.
+// A directory applies to all files below it; the longest match wins.
+// Documents without weight, and stdin, weigh 1.
+// Lines starting with '#' are comments.
+#[derive(Debug, Default)]
+pub struct Weights {
+    paths: Vec<(PathBuf, f64)>,
+    cache: FxHashMap<OsString, f64>,
+}
+
+impl Weights {
+    pub fn load(path: &OsStr) -> Result<Weights, String> {
+        let name = path.to_string_lossy();
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("cannot read weights {}: {}", name, e))?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut w = Weights::default();
+        for (i, l) in text.lines().enumerate() {
+            let l = l.trim();
+            if l.is_empty() || l.starts_with('#') {
+                continue;
+            }
+            let (p, weight) = match l.rsplit_once(char::is_whitespace) {
+                Some((p, weight)) => (p.trim(), weight.parse::<f64>().ok()),
+                None => (l, None),
+            };
+            let Some(weight) = weight.filter(|w| *w >= 0.0) else {
+                return Err(format!(
+                    "{}:{}: path and non-negative weight expected",
+                    name,
+                    i + 1
+                ));
+            };
+            let p = dir.join(p);
+            let p = p.canonicalize().unwrap_or(p);
+            w.paths.push((p, weight));
+        }
+        Ok(w)
+    }
+
+    pub fn weight(&mut self, file: &OsStr) -> f64 {
+        if let Some(w) = self.cache.get(file) {
+            return *w;
+        }
+        let p = Path::new(file);
+        let p = p.canonicalize().unwrap_or(p.to_path_buf());
+        let w = self
+            .paths
+            .iter()
+            .filter(|(d, _)| p.starts_with(d))
+            .max_by_key(|(d, _)| d.components().count())
+            .map_or(1.0, |(_, w)| *w);
+        self.cache.insert(file.to_os_string(), w);
+        w
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("bibstats-weights-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("chapters/intro")).unwrap();
+        for f in [
+            "chapters/a.tex",
+            "chapters/intro/b.tex",
+            "appendix.tex",
+            "main.tex",
+        ] {
+            fs::write(dir.join(f), "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = dir("load");
+        let file = dir.join("weights");
+        fs::write(&file, "# weights\n\nchapters/   2\nappendix.tex 0.5\n").unwrap();
+        assert!(match Weights::load(file.as_os_str()) {
+            Ok(w) => w.paths.len() == 2 && w.paths[1].1 == 0.5,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                false
+            }
+        });
+        for bad in ["chapters/\n", "chapters/ -1\n", "chapters/ heavy\n"] {
+            fs::write(&file, format!("# weights\n{}", bad)).unwrap();
+            assert!(match Weights::load(file.as_os_str()) {
+                Ok(_) => false,
+                Err(e) => e.ends_with(":2: path and non-negative weight expected"),
+            });
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_weight_of_directory() {
+        let dir = dir("match");
+        let file = dir.join("weights");
+        fs::write(&file, "chapters 2\nchapters/intro/ 3\nappendix.tex 0.5\n").unwrap();
+        let mut w = Weights::load(file.as_os_str()).unwrap();
+        let weight = |w: &mut Weights, f: &str| w.weight(dir.join(f).as_os_str());
+        assert_eq!(weight(&mut w, "chapters/a.tex"), 2.0);
+        // the longest match wins
+        assert_eq!(weight(&mut w, "chapters/intro/b.tex"), 3.0);
+        assert_eq!(weight(&mut w, "appendix.tex"), 0.5);
+        assert_eq!(weight(&mut w, "main.tex"), 1.0);
+        assert_eq!(w.weight(OsStr::new("<stdin>")), 1.0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}